@@ -22,6 +22,7 @@ __all__ = [
     "validate_pii_config",
     "convert_datascrubbing_config",
     "pii_strip_event",
+    "pii_decrypt",
     "pii_selector_suggestions_from_event",
     "VALID_PLATFORMS",
     "validate_sampling_condition",
@@ -183,16 +184,33 @@ def convert_datascrubbing_config(config):
     return json.loads(decode_str(raw_rv, free=True))
 
 
-def pii_strip_event(config, event):
+def pii_strip_event(config, event, dry_run=False):
     """
     Scrub an event using new PII stripping config.
+
+    With `dry_run`, the event is not modified. Instead, this returns a dict
+    with a list of `matches` describing every redaction the config would make.
     """
     raw_config = encode_str(json.dumps(config))
     raw_event = encode_str(json.dumps(event))
-    raw_rv = rustcall(lib.relay_pii_strip_event, raw_config, raw_event)
+    func = lib.relay_pii_strip_event_dry_run if dry_run else lib.relay_pii_strip_event
+    raw_rv = rustcall(func, raw_config, raw_event)
     return json.loads(decode_str(raw_rv, free=True))
 
 
+def pii_decrypt(keys, text):
+    """
+    Decrypt all tokens of the `encrypt` PII redaction in the given text.
+
+    `keys` maps key IDs to hex-encoded secret keys, whose public keys are
+    configured in `vars.encryptionKeys` of the PII config. Raises if a token
+    uses an unknown key or cannot be decrypted.
+    """
+    raw_keys = encode_str(json.dumps(keys))
+    raw_rv = rustcall(lib.relay_pii_decrypt, raw_keys, encode_str(text))
+    return decode_str(raw_rv, free=True)
+
+
 def pii_selector_suggestions_from_event(event):
     """
     Walk through the event and collect selectors that can be applied to it in a
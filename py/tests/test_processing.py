@@ -1,4 +1,6 @@
 # coding: utf-8
+import json
+
 import sentry_relay
 
 import pytest
@@ -141,6 +143,34 @@ def test_validate_pii_config():
     with pytest.raises(ValueError):
         sentry_relay.validate_pii_config('{"applications": true}')
 
+    with pytest.raises(ValueError):
+        sentry_relay.validate_pii_config(
+            '{"rules": {"r": {"type": "email", "redaction": {"method": "encrypt", "keyId": "x"}}}}'
+        )
+
+    with pytest.raises(ValueError):
+        sentry_relay.validate_pii_config('{"vars": {"encryptionKeys": {"x": "abcd"}}}')
+
+    # Encrypted values cannot be tagged without `hashKey`.
+    with pytest.raises(ValueError):
+        sentry_relay.validate_pii_config(
+            json.dumps(
+                {
+                    "rules": {
+                        "r": {
+                            "type": "email",
+                            "redaction": {"method": "encrypt", "keyId": "x"},
+                        }
+                    },
+                    "vars": {
+                        "encryptionKeys": {
+                            "x": "8f40c5adb68f25624ae5b214ea767a6ec94d829d3d7b5e1ad1ba6f3e2138285f"
+                        }
+                    },
+                }
+            )
+        )
+
 
 def test_convert_datascrubbing_config():
     cfg = sentry_relay.convert_datascrubbing_config(
@@ -174,6 +204,50 @@ def test_pii_strip_event():
     assert sentry_relay.pii_strip_event({}, event) == event
 
 
+def test_pii_strip_event_dry_run():
+    config = {"applications": {"$string": ["@email:replace"]}}
+    event = {"extra": {"contact": "mail jane@example.com"}}
+    assert sentry_relay.pii_strip_event(config, event, dry_run=True) == {
+        "matches": [
+            {
+                "rule_id": "@email:replace",
+                "path": "extra.contact",
+                "value_type": ["string"],
+                "remark": "s",
+            }
+        ]
+    }
+
+
+def test_pii_decrypt():
+    keys = {"2023": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"}
+    public_keys = {
+        "2023": "8f40c5adb68f25624ae5b214ea767a6ec94d829d3d7b5e1ad1ba6f3e2138285f"
+    }
+    config = {
+        "rules": {
+            "encrypt_user": {
+                "type": "anything",
+                "redaction": {"method": "encrypt", "keyId": "2023"},
+            }
+        },
+        "vars": {"hashKey": "correlation secret", "encryptionKeys": public_keys},
+        "applications": {"$user.email": ["encrypt_user"]},
+    }
+    event = {"user": {"email": "jane@example.com"}}
+
+    scrubbed = sentry_relay.pii_strip_event(config, event)
+    token = scrubbed["user"]["email"]
+    assert token.startswith("enc:2023:")
+    assert sentry_relay.pii_decrypt(keys, "user " + token) == "user jane@example.com"
+
+    with pytest.raises(sentry_relay.RelayError):
+        sentry_relay.pii_decrypt({}, token)
+
+    with pytest.raises(sentry_relay.RelayError):
+        sentry_relay.pii_decrypt(public_keys, token)
+
+
 def test_pii_selector_suggestions_from_event():
     event = {"logentry": {"formatted": "hi"}}
     assert sentry_relay.pii_selector_suggestions_from_event(event) == [
@@ -234,6 +308,47 @@ def test_invalid_sampling_condition():
         sentry_relay.validate_sampling_condition(condition)
 
 
+@pytest.mark.parametrize(
+    "condition",
+    [
+        '{"op": "regex", "name": "event.transaction", "value": "^/api/"}',
+        '{"op": "contains", "name": "event.transaction", "value": "api"}',
+        '{"op": "startsWith", "name": "event.transaction", "value": ["/api/"]}',
+        '{"op": "semverGte", "name": "event.release", "value": "2.3.0"}',
+        '{"op": "semverLte", "name": "event.release", "value": "2.3"}',
+        '{"op": "cidrMatch", "value": ["10.0.0.0/8"]}',
+    ],
+)
+def test_validate_extended_sampling_conditions(condition):
+    """
+    Tests that the extended condition operators pass validation
+    """
+    sentry_relay.validate_sampling_condition(condition)
+
+
+@pytest.mark.parametrize(
+    "condition,message",
+    [
+        (
+            '{"op": "regex", "name": "event.transaction", "value": "(unclosed"}',
+            "regex parse error",
+        ),
+        (
+            '{"op": "semverGte", "name": "event.release", "value": "latest"}',
+            "invalid version",
+        ),
+        ('{"op": "cidrMatch", "value": ["not-an-ip"]}', "invalid address"),
+        ('{"op": "eq", "value": "foo"}', "missing field `name`"),
+    ],
+)
+def test_invalid_extended_sampling_conditions(condition, message):
+    """
+    Tests that invalid values of the extended condition operators are caught
+    """
+    with pytest.raises(ValueError, match=message):
+        sentry_relay.validate_sampling_condition(condition)
+
+
 def test_validate_sampling_configuration():
     """
     Tests that a valid sampling rule configuration passes
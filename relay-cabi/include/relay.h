@@ -62,6 +62,18 @@ enum RelayDataCategory {
    * contrast, `transaction` only guarantees that metrics have been accepted for the transaction.
    */
   RELAY_DATA_CATEGORY_TRANSACTION_INDEXED = 9,
+  /**
+   * Monitor check-ins.
+   */
+  RELAY_DATA_CATEGORY_MONITOR = 10,
+  /**
+   * Metric buckets. Quantity is the number of buckets.
+   */
+  RELAY_DATA_CATEGORY_METRIC_BUCKET = 11,
+  /**
+   * Spans extracted from transactions. Quantity is the number of spans.
+   */
+  RELAY_DATA_CATEGORY_SPAN = 12,
   /**
    * Any other data category not known by this Relay.
    */
@@ -528,6 +540,24 @@ struct RelayStr relay_convert_datascrubbing_config(const struct RelayStr *config
 struct RelayStr relay_pii_strip_event(const struct RelayStr *config,
                                       const struct RelayStr *event);
 
+/**
+ * Report what a PII stripping config would scrub from an event without modifying it.
+ *
+ * Returns a JSON object with a list of `matches`, each containing the rule ID, the path and the
+ * value type of a would-be redaction.
+ */
+struct RelayStr relay_pii_strip_event_dry_run(const struct RelayStr *config,
+                                              const struct RelayStr *event);
+
+/**
+ * Decrypt all tokens of the `encrypt` PII redaction in the given text.
+ *
+ * The keys are a JSON object mapping key IDs to hex-encoded secret keys, whose public keys are
+ * configured in `vars.encryptionKeys`. Decryption is never part of event processing and is only
+ * meant for offline use.
+ */
+struct RelayStr relay_pii_decrypt(const struct RelayStr *keys, const struct RelayStr *text);
+
 /**
  * Walk through the event and collect selectors that can be applied to it in a PII config. This
  * function is used in the UI to provide auto-completion of selectors.
@@ -4,6 +4,7 @@
 #![allow(clippy::derive_partial_eq_without_eq)]
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::ffi::CStr;
 use std::os::raw::c_char;
 use std::slice;
@@ -12,7 +13,7 @@ use once_cell::sync::OnceCell;
 
 use relay_common::{glob_match_bytes, GlobOptions};
 use relay_general::pii::{
-    selector_suggestions_from_value, DataScrubbingConfig, PiiConfig, PiiProcessor,
+    decrypt_tokens, selector_suggestions_from_value, DataScrubbingConfig, PiiConfig, PiiProcessor,
 };
 use relay_general::processor::{process_value, split_chunks, ProcessingState};
 use relay_general::protocol::{Event, VALID_PLATFORMS};
@@ -121,6 +122,7 @@ pub unsafe extern "C" fn relay_store_normalizer_normalize_event(
         normalize_user_agent: config.normalize_user_agent,
         normalize_transaction_name: false, // only supported in relay
         tx_name_rules: &[],                // only supported in relay
+        replace_span_descriptions: false,  // only supported in relay
         is_renormalize: config.is_renormalize.unwrap_or(false),
     };
     light_normalize_event(&mut event, &light_normalization_config)?;
@@ -141,8 +143,11 @@ pub unsafe extern "C" fn relay_translate_legacy_python_json(event: *mut RelayStr
 #[no_mangle]
 #[relay_ffi::catch_unwind]
 pub unsafe extern "C" fn relay_validate_pii_config(value: *const RelayStr) -> RelayStr {
-    match serde_json::from_str((*value).as_str()) {
-        Ok(PiiConfig { .. }) => RelayStr::new(""),
+    match serde_json::from_str::<PiiConfig>((*value).as_str()) {
+        Ok(config) => match config.validate() {
+            Ok(()) => RelayStr::new(""),
+            Err(e) => RelayStr::from_string(e.to_string()),
+        },
         Err(e) => RelayStr::from_string(e.to_string()),
     }
 }
@@ -176,6 +181,42 @@ pub unsafe extern "C" fn relay_pii_strip_event(
     RelayStr::from_string(event.to_json()?)
 }
 
+/// Report what a PII stripping config would scrub from an event without modifying it.
+///
+/// Returns a JSON object with a list of `matches`, each containing the rule ID, the path and the
+/// value type of a would-be redaction.
+#[no_mangle]
+#[relay_ffi::catch_unwind]
+pub unsafe extern "C" fn relay_pii_strip_event_dry_run(
+    config: *const RelayStr,
+    event: *const RelayStr,
+) -> RelayStr {
+    let config = serde_json::from_str::<PiiConfig>((*config).as_str())?;
+    let mut processor = PiiProcessor::dry_run(config.compiled());
+
+    let mut event = Annotated::<Event>::from_json((*event).as_str())?;
+    process_value(&mut event, &mut processor, ProcessingState::root())?;
+
+    let diff = serde_json::json!({ "matches": processor.audit_matches() });
+    RelayStr::from_string(serde_json::to_string(&diff)?)
+}
+
+/// Decrypt all tokens of the `encrypt` PII redaction in the given text.
+///
+/// The keys are a JSON object mapping key IDs to hex-encoded secret keys, whose public keys are
+/// configured in `vars.encryptionKeys`. Decryption is never part of event processing and is only
+/// meant for offline use.
+#[no_mangle]
+#[relay_ffi::catch_unwind]
+pub unsafe extern "C" fn relay_pii_decrypt(
+    keys: *const RelayStr,
+    text: *const RelayStr,
+) -> RelayStr {
+    let keys: BTreeMap<String, String> = serde_json::from_str((*keys).as_str())?;
+    let decrypted = decrypt_tokens((*text).as_str(), &keys)?;
+    RelayStr::from_string(decrypted.into_owned())
+}
+
 /// Walk through the event and collect selectors that can be applied to it in a PII config. This
 /// function is used in the UI to provide auto-completion of selectors.
 #[no_mangle]
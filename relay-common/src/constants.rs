@@ -123,6 +123,12 @@ pub enum DataCategory {
     /// This is the category for transaction payloads that were accepted and stored in full. In
     /// contrast, `transaction` only guarantees that metrics have been accepted for the transaction.
     TransactionIndexed = 9,
+    /// Monitor check-ins.
+    Monitor = 10,
+    /// Metric buckets. Quantity is the number of buckets.
+    MetricBucket = 11,
+    /// Spans extracted from transactions. Quantity is the number of spans.
+    Span = 12,
     //
     // IMPORTANT: After adding a new entry to DataCategory, go to the `relay-cabi` subfolder and run
     // `make header` to regenerate the C-binding. This allows using the data category from Python.
@@ -148,6 +154,9 @@ impl DataCategory {
             "replay" => Self::Replay,
             "transaction_processed" => Self::TransactionProcessed,
             "transaction_indexed" => Self::TransactionIndexed,
+            "monitor" => Self::Monitor,
+            "metric_bucket" => Self::MetricBucket,
+            "span" => Self::Span,
             _ => Self::Unknown,
         }
     }
@@ -166,6 +175,9 @@ impl DataCategory {
             Self::Replay => "replay",
             Self::TransactionProcessed => "transaction_processed",
             Self::TransactionIndexed => "transaction_indexed",
+            Self::Monitor => "monitor",
+            Self::MetricBucket => "metric_bucket",
+            Self::Span => "span",
             Self::Unknown => "unknown",
         }
     }
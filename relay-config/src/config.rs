@@ -16,7 +16,7 @@ use serde::de::{Unexpected, Visitor};
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 
 use relay_auth::{generate_key_pair, generate_relay_id, PublicKey, RelayId, SecretKey};
-use relay_common::{Dsn, Uuid};
+use relay_common::{Dsn, ProjectKey, Uuid};
 use relay_kafka::{
     ConfigError as KafkaConfigError, KafkaConfig, KafkaConfigParam, KafkaTopic, TopicAssignments,
 };
@@ -489,6 +489,14 @@ struct Metrics {
     /// For example, a value of `0.3` means that only 30% of the emitted metrics will be sent.
     /// Defaults to `1.0` (100%).
     sample_rate: f32,
+    /// Record all metrics in process and expose them on the `/metrics` endpoint.
+    ///
+    /// Metrics are served in the Prometheus text exposition format. Defaults to `false`.
+    prometheus: bool,
+    /// Upper bounds of the histogram buckets for timers in milliseconds.
+    ///
+    /// Only used if `prometheus` is enabled.
+    prometheus_buckets: Vec<f64>,
 }
 
 impl Default for Metrics {
@@ -500,6 +508,11 @@ impl Default for Metrics {
             hostname_tag: None,
             buffering: true,
             sample_rate: 1.0,
+            prometheus: false,
+            prometheus_buckets: vec![
+                1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+                30000.0, 60000.0,
+            ],
         }
     }
 }
@@ -538,6 +551,8 @@ struct Limits {
     max_profile_size: ByteSize,
     /// The maximum payload size for a replay.
     max_replay_size: ByteSize,
+    /// The maximum payload size for a monitor check-in.
+    max_check_in_size: ByteSize,
     /// The maximum number of threads to spawn for CPU and web work, each.
     ///
     /// The total number of threads spawned will roughly be `2 * max_thread_count + 1`. Defaults to
@@ -578,6 +593,7 @@ impl Default for Limits {
             max_api_chunk_upload_size: ByteSize::mebibytes(100),
             max_profile_size: ByteSize::mebibytes(50),
             max_replay_size: ByteSize::mebibytes(100),
+            max_check_in_size: ByteSize::kibibytes(100),
             max_thread_count: num_cpus::get(),
             query_timeout: 30,
             max_connection_rate: 256,
@@ -629,6 +645,10 @@ pub enum HttpEncoding {
     Gzip,
     /// A format using the [Brotli](https://en.wikipedia.org/wiki/Brotli) algorithm.
     Br,
+    /// A format using the [Zstandard](https://en.wikipedia.org/wiki/Zstd) algorithm.
+    ///
+    /// The format is defined in [RFC 8878](https://datatracker.ietf.org/doc/html/rfc8878).
+    Zstd,
 }
 
 impl HttpEncoding {
@@ -641,6 +661,8 @@ impl HttpEncoding {
             Self::Gzip
         } else if str.eq_ignore_ascii_case("deflate") {
             Self::Deflate
+        } else if str.eq_ignore_ascii_case("zstd") {
+            Self::Zstd
         } else {
             Self::Identity
         }
@@ -655,6 +677,7 @@ impl HttpEncoding {
             Self::Deflate => Some("deflate"),
             Self::Gzip => Some("gzip"),
             Self::Br => Some("br"),
+            Self::Zstd => Some("zstd"),
         }
     }
 }
@@ -712,6 +735,7 @@ struct Http {
     ///  - `deflate`: Compression using a zlib header with deflate encoding.
     ///  - `gzip` (default): Compression using gzip.
     ///  - `br`: Compression using the brotli algorithm.
+    ///  - `zstd`: Compression using the zstandard algorithm.
     encoding: HttpEncoding,
 }
 
@@ -762,6 +786,12 @@ struct Cache {
     file_interval: u32,
     /// Interval for evicting outdated project configs from memory.
     eviction_interval: u32,
+    /// Directory in which fetched project configs are persisted across restarts.
+    ///
+    /// On startup, persisted project configs are loaded as stale configs and refreshed in the
+    /// background, subject to `project_grace_period`. Only used in managed mode. Defaults to
+    /// `None`, which disables persistence.
+    project_persist_path: Option<PathBuf>,
 }
 
 impl Default for Cache {
@@ -777,6 +807,7 @@ impl Default for Cache {
             batch_size: 500,
             file_interval: 10,     // 10 seconds
             eviction_interval: 60, // 60 seconds
+            project_persist_path: None,
         }
     }
 }
@@ -1118,6 +1149,94 @@ pub struct AwsConfig {
     pub runtime_api: Option<String>,
 }
 
+/// Persistent envelope spool configuration.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct EnvelopeSpool {
+    /// The directory in which spool segment files are stored.
+    ///
+    /// If not set, envelopes are only buffered in memory. A relative path is resolved against the
+    /// config directory.
+    path: Option<PathBuf>,
+    /// The maximum total size of all segment files on disk.
+    ///
+    /// If this size is exceeded, the oldest segments are evicted from the spool. Defaults to 500MB.
+    max_disk_size: ByteSize,
+    /// The maximum size of a single segment file. Defaults to 10MB.
+    max_segment_size: ByteSize,
+    /// The maximum age of spooled envelopes in seconds.
+    ///
+    /// Older envelopes are discarded instead of being sent upstream. Defaults to 1 day.
+    max_age: u64,
+    /// The interval in milliseconds at which Relay attempts to drain the spool. Defaults to 1s.
+    drain_interval: u64,
+}
+
+impl Default for EnvelopeSpool {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_disk_size: ByteSize::mebibytes(500),
+            max_segment_size: ByteSize::mebibytes(10),
+            max_age: 86400, // 1 day
+            drain_interval: 1000,
+        }
+    }
+}
+
+/// Configuration for ingesting statsd and DogStatsD packets as custom metrics.
+///
+/// Each line in a packet is parsed as a metric. The project is selected with the `sentry_key` tag
+/// of the metric, which is removed before aggregation. Lines without this tag are assigned to
+/// `default_project_key`, or dropped if it is not configured.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct StatsdListener {
+    /// The address of a UDP socket to bind, for example `"127.0.0.1:8125"`.
+    ///
+    /// Defaults to `None`, which disables the UDP listener.
+    udp: Option<String>,
+    /// The path of a Unix datagram socket to bind.
+    ///
+    /// A relative path is resolved against the config directory. Defaults to `None`, which disables
+    /// the Unix socket listener.
+    unix: Option<PathBuf>,
+    /// The project key for metrics without a `sentry_key` tag.
+    default_project_key: Option<ProjectKey>,
+}
+
+/// Configuration for resolving minified JavaScript stack traces with source maps.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Sourcemaps {
+    /// The directory containing source maps and minified files.
+    ///
+    /// Artifacts are stored in a subdirectory per release and optionally dist, followed by the
+    /// URL path of the minified file. A relative path is resolved against the config directory.
+    /// Defaults to `None`, which disables source map resolution.
+    path: Option<PathBuf>,
+}
+
+/// Configuration for deobfuscating Java and Android stack traces with ProGuard mapping files.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Proguard {
+    /// The directory containing ProGuard and R8 mapping files.
+    ///
+    /// Mapping files are named after the UUID of their ProGuard debug image with a `.txt`
+    /// extension. A relative path is resolved against the config directory. Defaults to `None`,
+    /// which disables deobfuscation.
+    path: Option<PathBuf>,
+}
+
+/// Spooling configuration.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Spool {
+    /// Configures spooling of envelopes to disk during upstream outages.
+    #[serde(default)]
+    envelopes: EnvelopeSpool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 struct ConfigValues {
     #[serde(default)]
@@ -1146,6 +1265,14 @@ struct ConfigValues {
     auth: AuthConfig,
     #[serde(default)]
     aws: AwsConfig,
+    #[serde(default)]
+    spool: Spool,
+    #[serde(default)]
+    statsd_listener: StatsdListener,
+    #[serde(default)]
+    sourcemaps: Sourcemaps,
+    #[serde(default)]
+    proguard: Proguard,
 }
 
 impl ConfigObject for ConfigValues {
@@ -1608,6 +1735,16 @@ impl Config {
         self.values.metrics.sample_rate
     }
 
+    /// Returns true if metrics should be exposed on the Prometheus `/metrics` endpoint.
+    pub fn metrics_prometheus(&self) -> bool {
+        self.values.metrics.prometheus
+    }
+
+    /// Returns the histogram buckets for timers exposed to Prometheus, in milliseconds.
+    pub fn metrics_prometheus_buckets(&self) -> &[f64] {
+        &self.values.metrics.prometheus_buckets
+    }
+
     /// Returns the default timeout for all upstream HTTP requests.
     pub fn http_timeout(&self) -> Duration {
         Duration::from_secs(self.values.http.timeout.into())
@@ -1628,6 +1765,12 @@ impl Config {
         Duration::from_secs(self.values.cache.project_expiry.into())
     }
 
+    /// Returns the directory for persisting project states, if enabled.
+    pub fn project_cache_persist_path(&self) -> Option<PathBuf> {
+        let path = self.values.cache.project_persist_path.as_ref()?;
+        Some(self.path.join(path))
+    }
+
     /// Returns the expiry timeout for cached relay infos (public keys).
     pub fn relay_cache_expiry(&self) -> Duration {
         Duration::from_secs(self.values.cache.relay_expiry.into())
@@ -1727,6 +1870,11 @@ impl Config {
         self.values.limits.max_replay_size.as_bytes()
     }
 
+    /// Returns the maximum payload size for a monitor check-in.
+    pub fn max_check_in_size(&self) -> usize {
+        self.values.limits.max_check_in_size.as_bytes()
+    }
+
     /// Returns the maximum number of active requests
     pub fn max_concurrent_requests(&self) -> usize {
         self.values.limits.max_concurrent_requests
@@ -1861,6 +2009,67 @@ impl Config {
     pub fn aws_runtime_api(&self) -> Option<&str> {
         self.values.aws.runtime_api.as_deref()
     }
+
+    /// Returns the directory of the envelope spool, if spooling to disk is enabled.
+    pub fn spool_envelopes_path(&self) -> Option<PathBuf> {
+        let path = self.values.spool.envelopes.path.as_ref()?;
+        Some(self.path.join(path))
+    }
+
+    /// Returns the socket addresses for receiving statsd packets via UDP, if enabled.
+    pub fn statsd_listener_udp_addrs(&self) -> anyhow::Result<Vec<SocketAddr>> {
+        match self.values.statsd_listener.udp {
+            Some(ref addr) => Ok(addr
+                .as_str()
+                .to_socket_addrs()
+                .with_context(|| ConfigError::file(ConfigErrorKind::InvalidValue, &self.path))?
+                .collect()),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns the path of the Unix datagram socket for receiving statsd packets, if enabled.
+    pub fn statsd_listener_unix_path(&self) -> Option<PathBuf> {
+        let path = self.values.statsd_listener.unix.as_ref()?;
+        Some(self.path.join(path))
+    }
+
+    /// Returns the directory of artifacts for source map resolution, if enabled.
+    pub fn sourcemaps_path(&self) -> Option<PathBuf> {
+        let path = self.values.sourcemaps.path.as_ref()?;
+        Some(self.path.join(path))
+    }
+
+    /// Returns the directory of ProGuard mapping files for deobfuscation, if enabled.
+    pub fn proguard_path(&self) -> Option<PathBuf> {
+        let path = self.values.proguard.path.as_ref()?;
+        Some(self.path.join(path))
+    }
+
+    /// Returns the project key for statsd metrics without a `sentry_key` tag.
+    pub fn statsd_listener_default_project_key(&self) -> Option<ProjectKey> {
+        self.values.statsd_listener.default_project_key
+    }
+
+    /// Returns the maximum total size of the envelope spool on disk in bytes.
+    pub fn spool_envelopes_max_disk_size(&self) -> usize {
+        self.values.spool.envelopes.max_disk_size.as_bytes()
+    }
+
+    /// Returns the maximum size of a single envelope spool segment in bytes.
+    pub fn spool_envelopes_max_segment_size(&self) -> usize {
+        self.values.spool.envelopes.max_segment_size.as_bytes()
+    }
+
+    /// Returns the maximum age of envelopes in the spool.
+    pub fn spool_envelopes_max_age(&self) -> Duration {
+        Duration::from_secs(self.values.spool.envelopes.max_age)
+    }
+
+    /// Returns the interval at which the envelope spool is drained.
+    pub fn spool_envelopes_drain_interval(&self) -> Duration {
+        Duration::from_millis(self.values.spool.envelopes.drain_interval)
+    }
 }
 
 impl Default for Config {
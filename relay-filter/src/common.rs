@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::{convert::TryFrom, fmt};
 
 use globset::GlobBuilder;
@@ -97,11 +98,14 @@ impl<'de> Deserialize<'de> for GlobPatterns {
     }
 }
 
+/// Prefix of the [`FilterStatKey`] names of generic filters.
+const GENERIC_FILTER_PREFIX: &str = "generic-";
+
 /// Identifies which filter dropped an event for which reason.
 ///
 /// Ported from Sentry's same-named "enum". The enum variants are fed into outcomes in kebap-case
 /// (e.g.  "browser-extensions")
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Hash)]
 pub enum FilterStatKey {
     /// Filtered by ip address.
     IpAddress,
@@ -126,6 +130,9 @@ pub enum FilterStatKey {
 
     /// Filtered due to invalid CSP policy.
     InvalidCsp,
+
+    /// Filtered by a generic filter with the given identifier.
+    Generic(String),
 }
 
 // An event grouped to a removed group.
@@ -142,8 +149,11 @@ pub enum FilterStatKey {
 
 impl FilterStatKey {
     /// Returns the string identifier of the filter stat key.
-    pub fn name(self) -> &'static str {
-        match self {
+    ///
+    /// For generic filters, this is the identifier of the filter prefixed with `generic-`, so that
+    /// it cannot collide with the built-in filters.
+    pub fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(match self {
             FilterStatKey::IpAddress => "ip-address",
             FilterStatKey::ReleaseVersion => "release-version",
             FilterStatKey::ErrorMessage => "error-message",
@@ -152,7 +162,10 @@ impl FilterStatKey {
             FilterStatKey::Localhost => "localhost",
             FilterStatKey::WebCrawlers => "web-crawlers",
             FilterStatKey::InvalidCsp => "invalid-csp",
-        }
+            FilterStatKey::Generic(id) => {
+                return Cow::Owned(format!("{}{}", GENERIC_FILTER_PREFIX, id))
+            }
+        })
     }
 }
 
@@ -175,9 +188,10 @@ impl<'a> TryFrom<&'a str> for FilterStatKey {
             "localhost" => FilterStatKey::Localhost,
             "web-crawlers" => FilterStatKey::WebCrawlers,
             "invalid-csp" => FilterStatKey::InvalidCsp,
-            other => {
-                return Err(other);
-            }
+            other => match other.strip_prefix(GENERIC_FILTER_PREFIX) {
+                Some(id) => FilterStatKey::Generic(id.to_owned()),
+                None => return Err(other),
+            },
         })
     }
 }
@@ -267,4 +281,18 @@ mod tests {
         assert!(!globs.is_match("1.18.4.2153-2aa83397b"));
         assert!(!globs.is_match("1.18.5.2153-2aa83397b"));
     }
+
+    #[test]
+    fn test_filter_stat_key_roundtrip() {
+        for key in [
+            FilterStatKey::BrowserExtensions,
+            FilterStatKey::Generic("browser-extensions".to_owned()),
+        ] {
+            let name = key.name();
+            assert_eq!(FilterStatKey::try_from(name.as_ref()), Ok(key.clone()));
+        }
+
+        let generic = FilterStatKey::Generic("browser-extensions".to_owned());
+        assert_eq!(generic.name(), "generic-browser-extensions");
+    }
 }
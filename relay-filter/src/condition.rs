@@ -0,0 +1,1167 @@
+//! Conditions that can be evaluated against events and other field value providers.
+//!
+//! Conditions are used by [generic inbound filters](crate::GenericFilterConfig) and by dynamic
+//! sampling rules.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+use regex::{Regex, RegexBuilder};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Number, Value};
+
+use relay_common::EventType;
+use relay_general::protocol::{Context, Event};
+use relay_general::store;
+
+use crate::GlobPatterns;
+
+/// A condition that checks the values using the equality operator.
+///
+/// For string values it supports case-insensitive comparison.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EqCondOptions {
+    /// Compares strings case-insensitively.
+    #[serde(default)]
+    pub ignore_case: bool,
+}
+
+/// A condition that checks for equality
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EqCondition {
+    /// The name of the field to compare.
+    pub name: String,
+    /// The value or list of values to compare against.
+    pub value: Value,
+    /// Options for the comparison.
+    #[serde(default)]
+    pub options: EqCondOptions,
+}
+
+impl EqCondition {
+    fn matches<T>(&self, value_provider: &T) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        let value = value_provider.get_value(self.name.as_str());
+
+        match value {
+            Value::Null => self.value == Value::Null,
+            Value::String(ref field) => match self.value {
+                Value::String(ref val) => {
+                    if self.options.ignore_case {
+                        unicase::eq(field.as_str(), val.as_str())
+                    } else {
+                        field == val
+                    }
+                }
+                Value::Array(ref val) => {
+                    if self.options.ignore_case {
+                        val.iter().any(|v| {
+                            if let Some(v) = v.as_str() {
+                                unicase::eq(v, field.as_str())
+                            } else {
+                                false
+                            }
+                        })
+                    } else {
+                        val.iter().any(|v| {
+                            if let Some(v) = v.as_str() {
+                                v == field.as_str()
+                            } else {
+                                false
+                            }
+                        })
+                    }
+                }
+                _ => false,
+            },
+            Value::Bool(field) => {
+                if let Value::Bool(val) = self.value {
+                    field == val
+                } else {
+                    false
+                }
+            }
+            _ => false, // unsupported types
+        }
+    }
+}
+
+macro_rules! impl_cmp_condition {
+    ($struct_name:ident, $operator:tt, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct $struct_name {
+            /// The name of the field to compare.
+            pub name: String,
+            /// The number to compare against.
+            pub value: Number,
+        }
+
+        impl $struct_name {
+            fn matches<T>(&self, value_provider: &T) -> bool where T: FieldValueProvider{
+                let value = match value_provider.get_value(self.name.as_str()) {
+                    Value::Number(x) => x,
+                    _ => return false
+                };
+
+                // Try various conversion functions in order of expensiveness and likelihood
+                // - as_i64 is not really fast, but most values in sampling rules can be i64, so we could
+                //   return early
+                // - f64 is more likely to succeed than u64, but we might lose precision
+                if let (Some(a), Some(b)) = (value.as_i64(), self.value.as_i64()) {
+                    a $operator b
+                } else if let (Some(a), Some(b)) = (value.as_u64(), self.value.as_u64()) {
+                    a $operator b
+                } else if let (Some(a), Some(b)) = (value.as_f64(), self.value.as_f64()) {
+                    a $operator b
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+impl_cmp_condition!(GteCondition, >=, "A condition that checks for greater or equal.");
+impl_cmp_condition!(LteCondition, <=, "A condition that checks for less or equal.");
+impl_cmp_condition!(LtCondition, <, "A condition that checks for less than.");
+impl_cmp_condition!(GtCondition, >, "A condition that checks for greater than.");
+
+/// A condition that uses glob matching.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobCondition {
+    /// The name of the field to match.
+    pub name: String,
+    /// The glob patterns to match the field against.
+    pub value: GlobPatterns,
+}
+
+impl GlobCondition {
+    fn matches<T>(&self, value_provider: &T) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        value_provider
+            .get_value(self.name.as_str())
+            .as_str()
+            .map_or(false, |fv| self.value.is_match(fv))
+    }
+}
+
+/// The maximum size of a compiled regular expression in a [`RegexCondition`].
+const REGEX_SIZE_LIMIT: usize = 262_144;
+
+/// A compiled regular expression that serializes to its pattern.
+///
+/// Deserialization fails if the pattern is not a valid regular expression.
+#[derive(Debug, Clone)]
+pub struct RegexPattern(Regex);
+
+impl RegexPattern {
+    /// Returns `true` if the regular expression matches anywhere in the given string.
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+}
+
+impl Serialize for RegexPattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RegexPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        RegexBuilder::new(&pattern)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .build()
+            .map(RegexPattern)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// A condition that matches string values against a regular expression.
+///
+/// The expression is not anchored, so it matches anywhere in the value unless it contains `^` or
+/// `$`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegexCondition {
+    /// The name of the field to match.
+    pub name: String,
+    /// The regular expression to match the field against.
+    pub value: RegexPattern,
+}
+
+impl RegexCondition {
+    fn matches<T>(&self, value_provider: &T) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        value_provider
+            .get_value(self.name.as_str())
+            .as_str()
+            .map_or(false, |fv| self.value.is_match(fv))
+    }
+}
+
+macro_rules! impl_str_condition {
+    ($struct_name:ident, $method:ident, $doc:literal) => {
+        #[doc = $doc]
+        ///
+        /// The value can be a single string or a list of strings, in which case the condition
+        /// matches if any of them matches.
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct $struct_name {
+            /// The name of the field to match.
+            pub name: String,
+            /// The string or list of strings to match against.
+            pub value: Value,
+            /// Options for the comparison.
+            #[serde(default)]
+            pub options: EqCondOptions,
+        }
+
+        impl $struct_name {
+            fn matches<T>(&self, value_provider: &T) -> bool
+            where
+                T: FieldValueProvider,
+            {
+                let value = value_provider.get_value(self.name.as_str());
+                let field = match value.as_str() {
+                    Some(field) => field,
+                    None => return false,
+                };
+
+                let field = if self.options.ignore_case {
+                    Cow::Owned(field.to_lowercase())
+                } else {
+                    Cow::Borrowed(field)
+                };
+
+                let check = |pattern: &Value| match pattern.as_str() {
+                    Some(pattern) if self.options.ignore_case => {
+                        field.$method(pattern.to_lowercase().as_str())
+                    }
+                    Some(pattern) => field.$method(pattern),
+                    None => false,
+                };
+
+                match self.value {
+                    Value::Array(ref patterns) => patterns.iter().any(check),
+                    ref pattern => check(pattern),
+                }
+            }
+        }
+    };
+}
+
+impl_str_condition!(
+    ContainsCondition,
+    contains,
+    "A condition that checks if a string field contains a substring."
+);
+impl_str_condition!(
+    StartsWithCondition,
+    starts_with,
+    "A condition that checks if a string field starts with a prefix."
+);
+
+/// Parses a version from a release or version string.
+///
+/// Releases in the format `package@version` are supported, as well as a leading `v` and versions
+/// with fewer than three components, such as `2.3`.
+fn parse_version(value: &str) -> Option<semver::Version> {
+    let version = value.rsplit_once('@').map_or(value, |(_, version)| version);
+    let version = version.strip_prefix('v').unwrap_or(version);
+
+    if let Ok(version) = semver::Version::parse(version) {
+        return Some(version);
+    }
+
+    // Pad missing minor and patch components, keeping pre-release and build metadata.
+    let split = version
+        .find(|c| c == '-' || c == '+')
+        .unwrap_or(version.len());
+    let (core, rest) = version.split_at(split);
+    let padding = match core.split('.').count() {
+        1 => ".0.0",
+        2 => ".0",
+        _ => return None,
+    };
+
+    semver::Version::parse(&format!("{}{}{}", core, padding, rest)).ok()
+}
+
+/// A semantic version that serializes to its string representation.
+///
+/// Deserialization fails if the string is not a valid version according to [`parse_version`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SemverValue(semver::Version);
+
+impl Serialize for SemverValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SemverValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        match parse_version(&value) {
+            Some(version) => Ok(SemverValue(version)),
+            None => Err(de::Error::custom(format!("invalid version: {}", value))),
+        }
+    }
+}
+
+macro_rules! impl_semver_condition {
+    ($struct_name:ident, $operator:tt, $doc:literal) => {
+        #[doc = $doc]
+        ///
+        /// Fields that cannot be parsed as version, such as releases without a version, do not
+        /// match.
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct $struct_name {
+            /// The name of the field to compare.
+            pub name: String,
+            /// The version to compare against.
+            pub value: SemverValue,
+        }
+
+        impl $struct_name {
+            fn matches<T>(&self, value_provider: &T) -> bool
+            where
+                T: FieldValueProvider,
+            {
+                value_provider
+                    .get_value(self.name.as_str())
+                    .as_str()
+                    .and_then(parse_version)
+                    .map_or(false, |version| version $operator self.value.0)
+            }
+        }
+    };
+}
+
+impl_semver_condition!(
+    SemverGteCondition,
+    >=,
+    "A condition that checks if a version is greater than or equal to the given version."
+);
+impl_semver_condition!(
+    SemverLteCondition,
+    <=,
+    "A condition that checks if a version is less than or equal to the given version."
+);
+
+/// A condition that checks if an IP address is contained in any of the given networks.
+///
+/// Networks are given in CIDR notation, such as `10.0.0.0/8` or `2001:db8::/32`. Plain IP
+/// addresses match only themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CidrMatchCondition {
+    /// The name of the field containing the IP address.
+    ///
+    /// If omitted, the client IP address of the request is checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The networks to check against.
+    pub value: Vec<IpNetwork>,
+}
+
+impl CidrMatchCondition {
+    fn matches<T>(&self, value_provider: &T, ip_addr: Option<IpAddr>) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        let ip_addr = match self.name {
+            Some(ref name) => value_provider
+                .get_value(name.as_str())
+                .as_str()
+                .and_then(|ip| ip.parse().ok()),
+            None => ip_addr,
+        };
+
+        match ip_addr {
+            Some(ip_addr) => self.value.iter().any(|network| network.contains(ip_addr)),
+            None => false,
+        }
+    }
+}
+
+/// Condition that cover custom operators which need
+/// special handling and have a custom implementation
+/// for each case.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomCondition {
+    /// The name of the custom operator.
+    pub name: String,
+    /// The value passed to the custom operator.
+    #[serde(default)]
+    pub value: Value,
+    /// Additional options for the custom operator.
+    #[serde(default)]
+    pub options: HashMap<String, Value>,
+}
+
+impl CustomCondition {
+    fn matches<T>(&self, value_provider: &T, ip_addr: Option<IpAddr>) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        T::get_custom_operator(&self.name)(self, value_provider, ip_addr)
+    }
+}
+
+/// Or condition combinator.
+///
+/// Creates a condition that is true when any
+/// of the inner conditions are true
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrCondition {
+    /// The inner conditions.
+    pub inner: Vec<RuleCondition>,
+}
+
+impl OrCondition {
+    fn supported(&self) -> bool {
+        self.inner.iter().all(RuleCondition::supported)
+    }
+
+    fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        self.inner.iter().any(|cond| cond.matches(value, ip_addr))
+    }
+}
+
+/// And condition combinator.
+///
+/// Creates a condition that is true when all
+/// inner conditions are true.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AndCondition {
+    /// The inner conditions.
+    pub inner: Vec<RuleCondition>,
+}
+
+impl AndCondition {
+    fn supported(&self) -> bool {
+        self.inner.iter().all(RuleCondition::supported)
+    }
+    fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        self.inner.iter().all(|cond| cond.matches(value, ip_addr))
+    }
+}
+
+/// Not condition combinator.
+///
+/// Creates a condition that is true when the wrapped
+/// condition si false.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotCondition {
+    /// The negated condition.
+    pub inner: Box<RuleCondition>,
+}
+
+impl NotCondition {
+    fn supported(&self) -> bool {
+        self.inner.supported()
+    }
+
+    fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        !self.inner.matches(value, ip_addr)
+    }
+}
+
+/// Describes how a condition was evaluated, returned by [`RuleCondition::explain`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionExplanation {
+    /// The operator of the condition, such as `eq` or `and`.
+    pub op: &'static str,
+    /// The name of the field checked by the condition, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The value of the checked field as returned by the [`FieldValueProvider`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_value: Option<Value>,
+    /// Whether the condition matched.
+    pub matched: bool,
+    /// Explanations of the inner conditions of `and`, `or` and `not`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inner: Vec<ConditionExplanation>,
+}
+
+/// A condition from a sampling rule or generic filter.
+///
+/// Conditions with an unknown `op` deserialize to [`RuleCondition::Unsupported`]. Known conditions
+/// with an invalid schema or value, such as a missing field or an invalid regular expression, fail
+/// to deserialize.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "op")]
+pub enum RuleCondition {
+    /// See [`EqCondition`].
+    Eq(EqCondition),
+    /// See [`GteCondition`].
+    Gte(GteCondition),
+    /// See [`LteCondition`].
+    Lte(LteCondition),
+    /// See [`LtCondition`].
+    Lt(LtCondition),
+    /// See [`GtCondition`].
+    Gt(GtCondition),
+    /// See [`GlobCondition`].
+    Glob(GlobCondition),
+    /// See [`RegexCondition`].
+    Regex(RegexCondition),
+    /// See [`ContainsCondition`].
+    Contains(ContainsCondition),
+    /// See [`StartsWithCondition`].
+    StartsWith(StartsWithCondition),
+    /// See [`SemverGteCondition`].
+    SemverGte(SemverGteCondition),
+    /// See [`SemverLteCondition`].
+    SemverLte(SemverLteCondition),
+    /// See [`CidrMatchCondition`].
+    CidrMatch(CidrMatchCondition),
+    /// See [`OrCondition`].
+    Or(OrCondition),
+    /// See [`AndCondition`].
+    And(AndCondition),
+    /// See [`NotCondition`].
+    Not(NotCondition),
+    /// See [`CustomCondition`].
+    Custom(CustomCondition),
+    /// An unknown condition for forward compatibility.
+    #[serde(other)]
+    Unsupported,
+}
+
+impl RuleCondition {
+    /// Returns a condition that matches everything.
+    pub fn all() -> Self {
+        Self::And(AndCondition { inner: Vec::new() })
+    }
+
+    /// Checks if Relay supports this condition (in other words if the condition had any unknown configuration
+    /// which was serialized as "Unsupported" (because the configuration is either faulty or was created for a
+    /// newer relay that supports some other condition types)
+    pub fn supported(&self) -> bool {
+        match self {
+            RuleCondition::Unsupported => false,
+            // we have a known condition
+            RuleCondition::Gte(_)
+            | RuleCondition::Lte(_)
+            | RuleCondition::Gt(_)
+            | RuleCondition::Lt(_)
+            | RuleCondition::Eq(_)
+            | RuleCondition::Glob(_)
+            | RuleCondition::Regex(_)
+            | RuleCondition::Contains(_)
+            | RuleCondition::StartsWith(_)
+            | RuleCondition::SemverGte(_)
+            | RuleCondition::SemverLte(_)
+            | RuleCondition::CidrMatch(_) => true,
+            // dig down for embedded conditions
+            RuleCondition::And(rules) => rules.supported(),
+            RuleCondition::Or(rules) => rules.supported(),
+            RuleCondition::Not(rule) => rule.supported(),
+            RuleCondition::Custom(_) => true,
+        }
+    }
+
+    /// Returns the name of the condition's operator as it appears in the `op` field.
+    pub fn op(&self) -> &'static str {
+        match self {
+            RuleCondition::Eq(_) => "eq",
+            RuleCondition::Gte(_) => "gte",
+            RuleCondition::Lte(_) => "lte",
+            RuleCondition::Lt(_) => "lt",
+            RuleCondition::Gt(_) => "gt",
+            RuleCondition::Glob(_) => "glob",
+            RuleCondition::Regex(_) => "regex",
+            RuleCondition::Contains(_) => "contains",
+            RuleCondition::StartsWith(_) => "startsWith",
+            RuleCondition::SemverGte(_) => "semverGte",
+            RuleCondition::SemverLte(_) => "semverLte",
+            RuleCondition::CidrMatch(_) => "cidrMatch",
+            RuleCondition::Or(_) => "or",
+            RuleCondition::And(_) => "and",
+            RuleCondition::Not(_) => "not",
+            RuleCondition::Custom(_) => "custom",
+            RuleCondition::Unsupported => "unsupported",
+        }
+    }
+
+    /// Evaluates the condition and explains the result, including the field values that were
+    /// checked.
+    ///
+    /// The values of custom conditions are not available, so only their name is reported.
+    pub fn explain<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> ConditionExplanation
+    where
+        T: FieldValueProvider,
+    {
+        let field = |name: &str| (Some(name.to_owned()), Some(value.get_value(name)));
+        let explain_all = |conditions: &[RuleCondition]| -> Vec<ConditionExplanation> {
+            conditions
+                .iter()
+                .map(|condition| condition.explain(value, ip_addr))
+                .collect()
+        };
+
+        let ((name, field_value), inner) = match self {
+            RuleCondition::Eq(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::Gte(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::Lte(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::Lt(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::Gt(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::Glob(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::Regex(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::Contains(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::StartsWith(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::SemverGte(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::SemverLte(condition) => (field(&condition.name), Vec::new()),
+            RuleCondition::CidrMatch(condition) => match condition.name {
+                Some(ref name) => (field(name), Vec::new()),
+                None => {
+                    let client_ip = ip_addr.map_or(Value::Null, |ip| ip.to_string().into());
+                    ((None, Some(client_ip)), Vec::new())
+                }
+            },
+            RuleCondition::Or(condition) => ((None, None), explain_all(&condition.inner)),
+            RuleCondition::And(condition) => ((None, None), explain_all(&condition.inner)),
+            RuleCondition::Not(condition) => {
+                ((None, None), vec![condition.inner.explain(value, ip_addr)])
+            }
+            RuleCondition::Custom(condition) => ((Some(condition.name.clone()), None), Vec::new()),
+            RuleCondition::Unsupported => ((None, None), Vec::new()),
+        };
+
+        ConditionExplanation {
+            op: self.op(),
+            name,
+            field_value,
+            matched: self.matches(value, ip_addr),
+            inner,
+        }
+    }
+
+    /// Returns `true` if the condition matches the given value provider.
+    pub fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
+    where
+        T: FieldValueProvider,
+    {
+        match self {
+            RuleCondition::Eq(condition) => condition.matches(value),
+            RuleCondition::Lte(condition) => condition.matches(value),
+            RuleCondition::Gte(condition) => condition.matches(value),
+            RuleCondition::Gt(condition) => condition.matches(value),
+            RuleCondition::Lt(condition) => condition.matches(value),
+            RuleCondition::Glob(condition) => condition.matches(value),
+            RuleCondition::Regex(condition) => condition.matches(value),
+            RuleCondition::Contains(condition) => condition.matches(value),
+            RuleCondition::StartsWith(condition) => condition.matches(value),
+            RuleCondition::SemverGte(condition) => condition.matches(value),
+            RuleCondition::SemverLte(condition) => condition.matches(value),
+            RuleCondition::CidrMatch(condition) => condition.matches(value, ip_addr),
+            RuleCondition::And(conditions) => conditions.matches(value, ip_addr),
+            RuleCondition::Or(conditions) => conditions.matches(value, ip_addr),
+            RuleCondition::Not(condition) => condition.matches(value, ip_addr),
+            RuleCondition::Unsupported => false,
+            RuleCondition::Custom(condition) => condition.matches(value, ip_addr),
+        }
+    }
+}
+
+/// Trait implemented by providers of fields (Events and Trace Contexts).
+///
+/// The fields will be used by rules to check if they apply.
+pub trait FieldValueProvider {
+    /// gets the value of a field
+    fn get_value(&self, path: &str) -> Value;
+    /// returns a filtering function for custom operators.
+    /// The function returned takes the provider and a condition definition and
+    /// returns a match result
+    fn get_custom_operator(
+        name: &str,
+    ) -> fn(condition: &CustomCondition, slf: &Self, ip_addr: Option<IpAddr>) -> bool;
+}
+
+fn no_match<T>(_condition: &CustomCondition, _slf: &T, _ip_addr: Option<IpAddr>) -> bool {
+    false
+}
+
+impl FieldValueProvider for Event {
+    fn get_value(&self, field_name: &str) -> Value {
+        let field_name = match field_name.strip_prefix("event.") {
+            Some(stripped) => stripped,
+            None => return Value::Null,
+        };
+
+        match field_name {
+            // Simple fields
+            "release" => match self.release.value() {
+                None => Value::Null,
+                Some(s) => s.as_str().into(),
+            },
+            "environment" => match self.environment.value() {
+                None => Value::Null,
+                Some(s) => s.as_str().into(),
+            },
+            "transaction" => match self.transaction.value() {
+                None => Value::Null,
+                Some(s) => s.as_str().into(),
+            },
+            "platform" => match self.platform.value() {
+                Some(platform) if store::is_valid_platform(platform) => {
+                    Value::String(platform.clone())
+                }
+                _ => Value::from("other"),
+            },
+            "user.id" => self.user.value().map_or(Value::Null, |user| {
+                user.id.value().map_or(Value::Null, |id| {
+                    if id.is_empty() {
+                        Value::Null // we don't serialize empty values but check it anyway
+                    } else {
+                        id.as_str().into()
+                    }
+                })
+            }),
+            "user.ip_address" => self
+                .user
+                .value()
+                .and_then(|user| user.ip_address.value())
+                .map_or(Value::Null, |ip| ip.as_str().into()),
+            "user.segment" => self.user.value().map_or(Value::Null, |user| {
+                user.segment.value().map_or(Value::Null, |segment| {
+                    if segment.is_empty() {
+                        Value::Null
+                    } else {
+                        segment.as_str().into()
+                    }
+                })
+            }),
+
+            // Partial implementation of contexts.
+            "contexts.device.name" => self
+                .contexts
+                .value()
+                .and_then(|contexts| contexts.get("device"))
+                .and_then(|annotated| annotated.value())
+                .and_then(|context| match context.0 {
+                    Context::Device(ref device) => device.name.as_str(),
+                    _ => None,
+                })
+                .map_or(Value::Null, Value::from),
+            "contexts.device.family" => self
+                .contexts
+                .value()
+                .and_then(|contexts| contexts.get("device"))
+                .and_then(|annotated| annotated.value())
+                .and_then(|context| match context.0 {
+                    Context::Device(ref device) => device.family.as_str(),
+                    _ => None,
+                })
+                .map_or(Value::Null, Value::from),
+            "contexts.os.name" => self
+                .contexts
+                .value()
+                .and_then(|contexts| contexts.get("os"))
+                .and_then(|annotated| annotated.value())
+                .and_then(|context| match context.0 {
+                    Context::Os(ref os) => os.name.as_str(),
+                    _ => None,
+                })
+                .map_or(Value::Null, Value::from),
+            "contexts.os.version" => self
+                .contexts
+                .value()
+                .and_then(|contexts| contexts.get("os"))
+                .and_then(|annotated| annotated.value())
+                .and_then(|context| match context.0 {
+                    Context::Os(ref os) => os.version.as_str(),
+                    _ => None,
+                })
+                .map_or(Value::Null, Value::from),
+            "contexts.trace.op" => match (self.ty.value(), store::get_transaction_op(self)) {
+                (Some(&EventType::Transaction), Some(op_name)) => Value::String(op_name.to_owned()),
+                _ => Value::Null,
+            },
+
+            // Computed fields (see Discover)
+            "duration" => match (self.ty.value(), store::validate_timestamps(self)) {
+                (Some(&EventType::Transaction), Ok((start, end))) => {
+                    match Number::from_f64(relay_common::chrono_to_positive_millis(end - start)) {
+                        Some(num) => Value::Number(num),
+                        None => Value::Null,
+                    }
+                }
+                _ => Value::Null,
+            },
+
+            // Inbound filter functions represented as fields
+            "is_local_ip" => Value::Bool(crate::localhost::matches(self)),
+            "has_bad_browser_extensions" => Value::Bool(crate::browser_extensions::matches(self)),
+            "web_crawlers" => Value::Bool(crate::web_crawlers::matches(self)),
+
+            // Dynamic access to certain data bags
+            _ => {
+                if let Some(rest) = field_name.strip_prefix("measurements.") {
+                    rest.strip_suffix(".value")
+                        .filter(|measurement_name| !measurement_name.is_empty())
+                        .and_then(|measurement_name| store::get_measurement(self, measurement_name))
+                        .map_or(Value::Null, Value::from)
+                } else if let Some(rest) = field_name.strip_prefix("tags.") {
+                    self.tags
+                        .value()
+                        .and_then(|tags| tags.get(rest))
+                        .map_or(Value::Null, Value::from)
+                } else {
+                    Value::Null
+                }
+            }
+        }
+    }
+
+    fn get_custom_operator(
+        name: &str,
+    ) -> fn(condition: &CustomCondition, slf: &Self, ip_addr: Option<IpAddr>) -> bool {
+        match name {
+            "event.client_ip" => client_ips_matcher,
+            "event.legacy_browser" => legacy_browsers_matcher,
+            "event.error_messages" => error_messages_matcher,
+            "event.csp" => csp_matcher,
+            _ => no_match,
+        }
+    }
+}
+
+fn client_ips_matcher(
+    condition: &CustomCondition,
+    _event: &Event,
+    ip_addr: Option<IpAddr>,
+) -> bool {
+    let ips = condition
+        .value
+        .as_array()
+        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("")));
+
+    if let Some(ips) = ips {
+        crate::client_ips::matches(ip_addr, ips)
+    } else {
+        false
+    }
+}
+
+fn legacy_browsers_matcher(
+    condition: &CustomCondition,
+    event: &Event,
+    _ip_addr: Option<IpAddr>,
+) -> bool {
+    let browsers = condition
+        .value
+        .as_array()
+        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("").parse().unwrap()));
+    if let Some(browsers) = browsers {
+        crate::legacy_browsers::matches(event, &browsers.collect())
+    } else {
+        false
+    }
+}
+
+fn error_messages_matcher(
+    condition: &CustomCondition,
+    event: &Event,
+    _ip_addr: Option<IpAddr>,
+) -> bool {
+    let patterns = condition
+        .value
+        .as_array()
+        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("").to_owned()));
+
+    if let Some(patterns) = patterns {
+        let globs = GlobPatterns::new(patterns.collect());
+        crate::error_messages::matches(event, &globs)
+    } else {
+        false
+    }
+}
+
+fn csp_matcher(condition: &CustomCondition, event: &Event, _ip_addr: Option<IpAddr>) -> bool {
+    let sources = condition
+        .value
+        .as_array()
+        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("")));
+
+    if let Some(sources) = sources {
+        crate::csp::matches(event, sources)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use relay_general::protocol::{IpAddr as EventIpAddr, LenientString, User};
+    use relay_general::types::Annotated;
+    use serde_json::json;
+
+    use super::*;
+
+    fn condition(value: Value) -> RuleCondition {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn event() -> Event {
+        Event {
+            release: Annotated::new(LenientString("myapp@2.3.1".to_owned())),
+            transaction: Annotated::new("/api/0/organizations/{org}/".to_owned()),
+            user: Annotated::new(User {
+                ip_address: Annotated::new(EventIpAddr("10.1.2.3".to_owned())),
+                ..User::default()
+            }),
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn test_regex() {
+        let cond = condition(json!({
+            "op": "regex",
+            "name": "event.transaction",
+            "value": "^/api/[0-9]+/organizations/",
+        }));
+        assert!(cond.supported());
+        assert!(cond.matches(&event(), None));
+
+        let cond = condition(json!({
+            "op": "regex",
+            "name": "event.transaction",
+            "value": "^/organizations/",
+        }));
+        assert!(!cond.matches(&event(), None));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let result = serde_json::from_value::<RuleCondition>(json!({
+            "op": "regex",
+            "name": "event.transaction",
+            "value": "(unclosed",
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invalid_nested_condition() {
+        let result = serde_json::from_value::<RuleCondition>(json!({
+            "op": "and",
+            "inner": [
+                {"op": "startsWith", "name": "event.transaction", "value": "/api/"},
+                {"op": "semverGte", "name": "event.release", "value": "latest"},
+            ],
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unknown_condition() {
+        let cond = condition(json!({"op": "fancyNewOp", "name": "event.transaction"}));
+        assert!(matches!(cond, RuleCondition::Unsupported));
+
+        // Known conditions with an invalid schema are not treated as unknown.
+        let result = serde_json::from_value::<RuleCondition>(json!({"op": "eq", "value": "foo"}));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_contains() {
+        let cond = condition(json!({
+            "op": "contains",
+            "name": "event.transaction",
+            "value": ["/projects/", "ORGANIZATIONS"],
+            "options": {"ignoreCase": true},
+        }));
+        assert!(cond.matches(&event(), None));
+
+        let cond = condition(json!({
+            "op": "contains",
+            "name": "event.transaction",
+            "value": "ORGANIZATIONS",
+        }));
+        assert!(!cond.matches(&event(), None));
+    }
+
+    #[test]
+    fn test_starts_with() {
+        let cond = condition(json!({
+            "op": "startsWith",
+            "name": "event.transaction",
+            "value": "/api/",
+        }));
+        assert!(cond.matches(&event(), None));
+
+        let cond = condition(json!({
+            "op": "startsWith",
+            "name": "event.transaction",
+            "value": "organizations",
+        }));
+        assert!(!cond.matches(&event(), None));
+    }
+
+    #[test]
+    fn test_semver() {
+        let gte = |version: &str| {
+            condition(json!({"op": "semverGte", "name": "event.release", "value": version}))
+        };
+        let lte = |version: &str| {
+            condition(json!({"op": "semverLte", "name": "event.release", "value": version}))
+        };
+
+        assert!(gte("2.3.0").matches(&event(), None));
+        assert!(gte("2.3.1").matches(&event(), None));
+        assert!(gte("v2.3").matches(&event(), None));
+        assert!(!gte("2.10.0").matches(&event(), None));
+        assert!(lte("2.10").matches(&event(), None));
+        assert!(!lte("2.3.1-beta.1").matches(&event(), None));
+    }
+
+    #[test]
+    fn test_semver_invalid() {
+        let result = serde_json::from_value::<RuleCondition>(json!({
+            "op": "semverGte",
+            "name": "event.release",
+            "value": "latest",
+        }));
+        assert!(result.is_err());
+
+        // Releases without a version never match.
+        let cond = condition(json!({"op": "semverGte", "name": "event.release", "value": "1"}));
+        let event = Event {
+            release: Annotated::new(LenientString("abcdef".to_owned())),
+            ..Event::default()
+        };
+        assert!(!cond.matches(&event, None));
+    }
+
+    #[test]
+    fn test_parse_version() {
+        let version = |s| parse_version(s).map(|v| v.to_string());
+        assert_eq!(version("1.2.3"), Some("1.2.3".to_owned()));
+        assert_eq!(version("pkg@1.2"), Some("1.2.0".to_owned()));
+        assert_eq!(version("v1-rc.1"), Some("1.0.0-rc.1".to_owned()));
+        assert_eq!(version("1.2.3.4"), None);
+        assert_eq!(version("abc"), None);
+    }
+
+    #[test]
+    fn test_cidr_match() {
+        let cond = condition(json!({"op": "cidrMatch", "value": ["192.168.0.0/16", "::1"]}));
+        assert!(cond.matches(&event(), Some("192.168.1.1".parse().unwrap())));
+        assert!(cond.matches(&event(), Some("::1".parse().unwrap())));
+        assert!(!cond.matches(&event(), Some("10.0.0.1".parse().unwrap())));
+        assert!(!cond.matches(&event(), None));
+
+        let cond = condition(json!({
+            "op": "cidrMatch",
+            "name": "event.user.ip_address",
+            "value": ["10.0.0.0/8"],
+        }));
+        assert!(cond.matches(&event(), None));
+    }
+
+    #[test]
+    fn test_cidr_match_invalid() {
+        let result = serde_json::from_value::<RuleCondition>(json!({
+            "op": "cidrMatch",
+            "value": ["not-an-ip"],
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let cond = condition(json!({
+            "op": "and",
+            "inner": [
+                {"op": "startsWith", "name": "event.transaction", "value": "/api/"},
+                {"op": "not", "inner": {"op": "eq", "name": "event.environment", "value": "dev"}},
+                {"op": "cidrMatch", "value": ["10.0.0.0/8"]},
+            ],
+        }));
+
+        let explanation = cond.explain(&event(), Some("192.168.0.1".parse().unwrap()));
+        assert_eq!(
+            serde_json::to_value(explanation).unwrap(),
+            json!({
+                "op": "and",
+                "matched": false,
+                "inner": [
+                    {
+                        "op": "startsWith",
+                        "name": "event.transaction",
+                        "fieldValue": "/api/0/organizations/{org}/",
+                        "matched": true,
+                    },
+                    {
+                        "op": "not",
+                        "matched": true,
+                        "inner": [{
+                            "op": "eq",
+                            "name": "event.environment",
+                            "fieldValue": null,
+                            "matched": false,
+                        }],
+                    },
+                    {
+                        "op": "cidrMatch",
+                        "fieldValue": "192.168.0.1",
+                        "matched": false,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let value = json!({
+            "op": "and",
+            "inner": [
+                {"op": "regex", "name": "event.transaction", "value": "^/api/"},
+                {"op": "semverGte", "name": "event.release", "value": "2.3.0"},
+                {"op": "cidrMatch", "value": ["10.0.0.0/8"]},
+            ],
+        });
+        let cond = condition(value.clone());
+        assert_eq!(serde_json::to_value(&cond).unwrap(), value);
+    }
+}
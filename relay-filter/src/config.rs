@@ -8,6 +8,7 @@ use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 
 use crate::common::GlobPatterns;
+use crate::condition::RuleCondition;
 
 /// Common configuration for event filters.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -175,6 +176,20 @@ impl LegacyBrowsersFilterConfig {
     }
 }
 
+/// Configuration for a generic filter.
+///
+/// Generic filters drop events that match an arbitrary [`RuleCondition`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenericFilterConfig {
+    /// Unique identifier of the filter, reported in the outcome of filtered events.
+    pub id: String,
+    /// Specifies whether this filter is enabled.
+    pub is_enabled: bool,
+    /// The condition that events must match to be filtered.
+    pub condition: RuleCondition,
+}
+
 /// Configuration for all event filters.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -210,6 +225,10 @@ pub struct FiltersConfig {
     /// Configuration for the releases filter.
     #[serde(default, skip_serializing_if = "ReleasesFilterConfig::is_empty")]
     pub releases: ReleasesFilterConfig,
+
+    /// Configuration for generic filters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub generic: Vec<GenericFilterConfig>,
 }
 
 impl FiltersConfig {
@@ -223,6 +242,7 @@ impl FiltersConfig {
             && self.legacy_browsers.is_empty()
             && self.localhost.is_empty()
             && self.releases.is_empty()
+            && self.generic.is_empty()
     }
 }
 
@@ -230,6 +250,8 @@ impl FiltersConfig {
 mod tests {
     use super::*;
 
+    use crate::condition::EqCondition;
+
     #[test]
     fn test_empty_config() -> Result<(), serde_json::Error> {
         let filters_config = serde_json::from_str::<FiltersConfig>("{}")?;
@@ -260,6 +282,7 @@ mod tests {
             releases: ReleasesFilterConfig {
                 releases: [],
             },
+            generic: [],
         }
         "###);
         Ok(())
@@ -293,6 +316,15 @@ mod tests {
             releases: ReleasesFilterConfig {
                 releases: GlobPatterns::new(vec!["1.2.3".to_string()]),
             },
+            generic: vec![GenericFilterConfig {
+                id: "hydration-error".to_string(),
+                is_enabled: true,
+                condition: RuleCondition::Eq(EqCondition {
+                    name: "event.environment".to_string(),
+                    value: "test".into(),
+                    options: Default::default(),
+                }),
+            }],
         };
 
         insta::assert_json_snapshot!(filters_config, @r###"
@@ -331,7 +363,21 @@ mod tests {
             "releases": [
               "1.2.3"
             ]
-          }
+          },
+          "generic": [
+            {
+              "id": "hydration-error",
+              "isEnabled": true,
+              "condition": {
+                "op": "eq",
+                "name": "event.environment",
+                "value": "test",
+                "options": {
+                  "ignoreCase": false
+                }
+              }
+            }
+          ]
         }
         "###);
     }
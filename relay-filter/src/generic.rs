@@ -0,0 +1,132 @@
+//! Implements generic filtering based on conditions.
+//!
+//! Each generic filter carries a [`RuleCondition`](crate::condition::RuleCondition) that is
+//! evaluated against the event. Events matching the condition of an enabled filter are dropped and
+//! reported with the filter's identifier.
+
+use std::net::IpAddr;
+
+use relay_general::protocol::Event;
+
+use crate::{FilterStatKey, GenericFilterConfig};
+
+/// Filters events matching the condition of any of the enabled generic filters.
+///
+/// Filters with conditions that are not supported by this version of Relay are skipped.
+pub fn should_filter(
+    event: &Event,
+    client_ip: Option<IpAddr>,
+    filters: &[GenericFilterConfig],
+) -> Result<(), FilterStatKey> {
+    for filter in filters {
+        if !filter.is_enabled || !filter.condition.supported() {
+            continue;
+        }
+
+        if filter.condition.matches(event, client_ip) {
+            return Err(FilterStatKey::Generic(filter.id.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use relay_general::protocol::LenientString;
+    use relay_general::types::Annotated;
+
+    fn get_event_for_release(release: &str) -> Event {
+        Event {
+            release: Annotated::from(LenientString::from(release.to_string())),
+            ..Event::default()
+        }
+    }
+
+    fn get_filter(id: &str, is_enabled: bool, condition: &str) -> GenericFilterConfig {
+        GenericFilterConfig {
+            id: id.to_owned(),
+            is_enabled,
+            condition: serde_json::from_str(condition).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_should_filter_match() {
+        let event = get_event_for_release("1.2.3");
+        let filters = [
+            get_filter(
+                "other-release",
+                true,
+                r#"{"op": "eq", "name": "event.release", "value": "2.0.0"}"#,
+            ),
+            get_filter(
+                "bad-release",
+                true,
+                r#"{"op": "glob", "name": "event.release", "value": ["1.2.*"]}"#,
+            ),
+        ];
+
+        assert_eq!(
+            should_filter(&event, None, &filters),
+            Err(FilterStatKey::Generic("bad-release".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_should_filter_no_match() {
+        let event = get_event_for_release("1.2.3");
+        let filters = [get_filter(
+            "other-release",
+            true,
+            r#"{"op": "eq", "name": "event.release", "value": "2.0.0"}"#,
+        )];
+
+        assert_eq!(should_filter(&event, None, &filters), Ok(()));
+    }
+
+    #[test]
+    fn test_should_filter_disabled() {
+        let event = get_event_for_release("1.2.3");
+        let filters = [get_filter(
+            "bad-release",
+            false,
+            r#"{"op": "eq", "name": "event.release", "value": "1.2.3"}"#,
+        )];
+
+        assert_eq!(should_filter(&event, None, &filters), Ok(()));
+    }
+
+    #[test]
+    fn test_should_filter_unsupported_condition() {
+        let event = get_event_for_release("1.2.3");
+        let filters = [get_filter(
+            "future-filter",
+            true,
+            r#"{"op": "not", "inner": {"op": "someFutureOp", "name": "event.release"}}"#,
+        )];
+
+        assert_eq!(should_filter(&event, None, &filters), Ok(()));
+    }
+
+    #[test]
+    fn test_should_filter_client_ip() {
+        let event = Event::default();
+        let filters = [get_filter(
+            "blocked-ip",
+            true,
+            r#"{"op": "custom", "name": "event.client_ip", "value": ["127.0.0.1"]}"#,
+        )];
+
+        assert_eq!(
+            should_filter(&event, Some("127.0.0.1".parse().unwrap()), &filters),
+            Err(FilterStatKey::Generic("blocked-ip".to_owned()))
+        );
+        assert_eq!(
+            should_filter(&event, Some("10.0.0.1".parse().unwrap()), &filters),
+            Ok(())
+        );
+    }
+}
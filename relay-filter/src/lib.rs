@@ -6,6 +6,7 @@
 //! * browser extensions (filter events caused by known problematic browser extensions)
 //! * web crawlers (filter events sent by user agents known to be web crawlers)
 //! * legacy browsers (filter events originating from legacy browsers, can be configured)
+//! * generic filters (filter events matching a configurable condition)
 #![warn(missing_docs)]
 #![doc(
     html_logo_url = "https://raw.githubusercontent.com/getsentry/relay/master/artwork/relay-icon.png",
@@ -19,8 +20,10 @@ use relay_general::protocol::Event;
 
 pub mod browser_extensions;
 pub mod client_ips;
+pub mod condition;
 pub mod csp;
 pub mod error_messages;
+pub mod generic;
 pub mod legacy_browsers;
 pub mod localhost;
 pub mod web_crawlers;
@@ -56,6 +59,7 @@ pub fn should_filter(
     browser_extensions::should_filter(event, &config.browser_extensions)?;
     legacy_browsers::should_filter(event, &config.legacy_browsers)?;
     web_crawlers::should_filter(event, &config.web_crawlers)?;
+    generic::should_filter(event, client_ip, &config.generic)?;
 
     Ok(())
 }
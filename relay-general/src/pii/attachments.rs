@@ -1,13 +1,16 @@
 use std::borrow::Cow;
 use std::iter::FusedIterator;
+use std::ops::Range;
 
 use regex::bytes::RegexBuilder as BytesRegexBuilder;
-use regex::{Match, Regex};
+use regex::Regex;
 use smallvec::SmallVec;
 use utf16string::{LittleEndian, WStr};
 
 use crate::pii::compiledconfig::RuleRef;
-use crate::pii::regexes::{get_regex_for_rule_type, ReplaceBehavior};
+use crate::pii::regexes::{
+    get_regex_for_rule_type, valid_bytes_match_groups, valid_match_groups, ReplaceBehavior,
+};
 use crate::pii::utils::hash_value;
 use crate::pii::{CompiledPiiConfig, Redaction};
 use crate::processor::{FieldAttrs, Pii, ProcessingState, ValueType};
@@ -43,17 +46,19 @@ fn apply_regex_to_utf8_bytes(
         }
     };
 
-    for captures in regex.captures_iter(data) {
-        for (idx, group) in captures.iter().enumerate() {
+    let captures_iter = valid_bytes_match_groups(&rule.ty, &regex, data);
+
+    for captures in captures_iter {
+        for (idx, group) in captures.into_iter().enumerate() {
             if let Some(group) = group {
-                if group.start() == group.end() {
+                if group.start == group.end {
                     continue;
                 }
 
                 match replace_behavior {
                     ReplaceBehavior::Groups(ref replace_groups) => {
                         if replace_groups.contains(&(idx as u8)) {
-                            matches.push((group.start(), group.end()));
+                            matches.push((group.start, group.end));
                         }
                     }
                     ReplaceBehavior::Value => {
@@ -81,16 +86,20 @@ fn apply_regex_to_utf16le_bytes(
     for segment in WStrSegmentIter::new(data) {
         match replace_behavior {
             ReplaceBehavior::Value => {
-                for re_match in regex.find_iter(&segment.decoded) {
+                let matches = valid_match_groups(&rule.ty, regex, &segment.decoded)
+                    .filter_map(|mut groups| groups.remove(0));
+                for re_match in matches {
                     changed = true;
                     let match_wstr = get_wstr_match(&segment.decoded, re_match, segment.encoded);
                     match_wstr.apply_redaction(&rule.redaction);
                 }
             }
             ReplaceBehavior::Groups(ref replace_groups) => {
-                for captures in regex.captures_iter(&segment.decoded) {
+                let captures_iter = valid_match_groups(&rule.ty, regex, &segment.decoded);
+                for captures in captures_iter {
                     for group_idx in replace_groups.iter() {
-                        if let Some(re_match) = captures.get(*group_idx as usize) {
+                        if let Some(re_match) = captures.get(*group_idx as usize).cloned().flatten()
+                        {
                             changed = true;
                             let match_wstr =
                                 get_wstr_match(&segment.decoded, re_match, segment.encoded);
@@ -107,7 +116,7 @@ fn apply_regex_to_utf16le_bytes(
 /// Extract the matching encoded slice from the encoded string.
 fn get_wstr_match<'a>(
     all_text: &str,
-    re_match: Match,
+    re_match: Range<usize>,
     all_encoded: &'a mut WStr<LittleEndian>,
 ) -> &'a mut WStr<LittleEndian> {
     let mut encoded_start = 0;
@@ -115,10 +124,10 @@ fn get_wstr_match<'a>(
 
     let offsets_iter = all_text.char_indices().zip(all_encoded.char_indices());
     for ((text_offset, _text_char), (encoded_offset, _encoded_char)) in offsets_iter {
-        if text_offset == re_match.start() {
+        if text_offset == re_match.start {
             encoded_start = encoded_offset;
         }
-        if text_offset == re_match.end() {
+        if text_offset == re_match.end {
             encoded_end = encoded_offset;
             break;
         }
@@ -171,6 +180,11 @@ trait StringMods: AsRef<[u8]> {
             Redaction::Replace(ref replace) => {
                 self.swap_content(replace.text.as_str(), PADDING);
             }
+            Redaction::Encrypt(_) => {
+                // Tokens are longer than the value and would be truncated, so they could never
+                // be decrypted. Mask the value instead.
+                self.fill_content(MASK);
+            }
         }
     }
 }
@@ -392,7 +406,8 @@ impl<'a> PiiAttachmentsProcessor<'a> {
 
         for (selector, rules) in &self.compiled_config.applications {
             if state.path().matches_selector(selector) {
-                for rule in rules {
+                // Audited rules only record matches, which is not supported for attachments.
+                for rule in rules.iter().filter(|rule| !rule.audit) {
                     // Note:
                     //
                     // - We ignore pattern_type and just treat every regex like a value regex (i.e.
@@ -953,13 +968,13 @@ mod tests {
         // Partial match
         let re = Regex::new("hello").unwrap();
         let re_match = re.find(s).unwrap();
-        let m = get_wstr_match(s, re_match, w);
+        let m = get_wstr_match(s, re_match.range(), w);
         assert_eq!(m.as_bytes(), b"h\x00e\x00l\x00l\x00o\x00");
 
         // Full match
         let re = Regex::new(".*").unwrap();
         let re_match = re.find(s).unwrap();
-        let m = get_wstr_match(s, re_match, w);
+        let m = get_wstr_match(s, re_match.range(), w);
         assert_eq!(
             m.as_bytes(),
             b"h\x00e\x00l\x00l\x00o\x00 \x00t\x00h\x00e\x00r\x00e\x00"
@@ -2,7 +2,7 @@ use std::cmp::Ordering;
 use std::collections::BTreeSet;
 
 use crate::pii::builtin::BUILTIN_RULES_MAP;
-use crate::pii::{PiiConfig, Redaction, RuleSpec, RuleType};
+use crate::pii::{EncryptionKey, PiiConfig, Redaction, RuleSpec, RuleType, Vars};
 use crate::processor::SelectorSpec;
 
 /// A representation of `PiiConfig` that is more (CPU-)efficient for use in `PiiProcessor`. It is
@@ -11,6 +11,7 @@ use crate::processor::SelectorSpec;
 #[derive(Debug, Clone)]
 pub struct CompiledPiiConfig {
     pub(super) applications: Vec<(SelectorSpec, BTreeSet<RuleRef>)>,
+    pub(super) scrub_embedded_data: bool,
 }
 
 impl CompiledPiiConfig {
@@ -20,12 +21,15 @@ impl CompiledPiiConfig {
             #[allow(clippy::mutable_key_type)]
             let mut rule_set = BTreeSet::default();
             for rule_id in rules {
-                collect_rules(config, &mut rule_set, rule_id, None);
+                collect_rules(config, &mut rule_set, rule_id, None, false);
             }
             applications.push((selector.clone(), rule_set));
         }
 
-        CompiledPiiConfig { applications }
+        CompiledPiiConfig {
+            applications,
+            scrub_embedded_data: config.scrub_embedded_data,
+        }
     }
 }
 
@@ -45,7 +49,10 @@ fn collect_rules(
     rules: &mut BTreeSet<RuleRef>,
     rule_id: &str,
     parent: Option<RuleRef>,
+    audit: bool,
 ) {
+    let audit = audit || config.audit_rules.contains(rule_id);
+
     let rule = match get_rule(config, rule_id) {
         Some(rule) => rule,
         None => return,
@@ -68,7 +75,7 @@ fn collect_rules(
                 None
             };
             for rule_id in &m.rules {
-                collect_rules(config, rules, rule_id, parent.clone());
+                collect_rules(config, rules, rule_id, parent.clone(), audit);
             }
         }
         RuleType::Alias(ref a) => {
@@ -77,9 +84,11 @@ fn collect_rules(
             } else {
                 None
             };
-            collect_rules(config, rules, &a.rule, parent);
+            collect_rules(config, rules, &a.rule, parent, audit);
         }
         _ => {
+            let mut rule = rule.with_encryption_key(&config.vars);
+            rule.audit = audit;
             rules.insert(rule);
         }
     }
@@ -92,6 +101,10 @@ pub(super) struct RuleRef {
     pub origin: String,
     pub ty: RuleType,
     pub redaction: Redaction,
+    /// The resolved key of an `encrypt` redaction, if the key exists and is valid.
+    pub encryption_key: Option<EncryptionKey>,
+    /// Whether the rule only records matches instead of modifying values.
+    pub audit: bool,
 }
 
 impl RuleRef {
@@ -101,6 +114,8 @@ impl RuleRef {
             id,
             ty: spec.ty.clone(),
             redaction: spec.redaction.clone(),
+            encryption_key: None,
+            audit: false,
         }
     }
 
@@ -113,7 +128,18 @@ impl RuleRef {
                 Redaction::Default => self.redaction,
                 _ => parent.redaction,
             },
+            encryption_key: None,
+            audit: false,
+        }
+    }
+
+    /// Resolves the key of an `encrypt` redaction from the config's variables.
+    fn with_encryption_key(mut self, vars: &Vars) -> Self {
+        if let Redaction::Encrypt(ref encrypt) = self.redaction {
+            // Invalid keys are reported by `PiiConfig::validate`.
+            self.encryption_key = EncryptionKey::from_vars(&encrypt.key_id, vars).ok();
         }
+        self
     }
 }
 
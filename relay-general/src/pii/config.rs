@@ -6,15 +6,21 @@ use once_cell::sync::OnceCell;
 use regex::{Regex, RegexBuilder};
 use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::pii::{CompiledPiiConfig, Redaction};
+use crate::pii::{CompiledPiiConfig, EncryptionError, EncryptionKey, Redaction};
 use crate::processor::SelectorSpec;
 
 const COMPILED_PATTERN_MAX_SIZE: usize = 262_144;
 
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum PiiConfigError {
     #[error("could not parse pattern")]
     RegexError(#[source] regex::Error),
+    #[error(transparent)]
+    EncryptionError(#[from] EncryptionError),
 }
 
 /// A regex pattern for text replacement.
@@ -143,6 +149,18 @@ pub enum RuleType {
     UsSsn,
     /// Keys that look like passwords
     Password,
+    /// International bank account number, validated with the mod-97 checksum.
+    Iban,
+    /// Phone number in international format.
+    Phone,
+    /// JSON Web Token.
+    Jwt,
+    /// AWS access key ID or secret access key.
+    AwsKey,
+    /// GitHub personal access, OAuth, app or refresh token.
+    GithubToken,
+    /// Slack bot, user, app or workspace token.
+    SlackToken,
     /// When a regex matches a key, a value is removed
     #[serde(alias = "redactPair")]
     RedactPair(RedactPairRule),
@@ -168,10 +186,17 @@ pub struct Vars {
     /// The default secret key for hashing operations.
     #[serde(default)]
     pub hash_key: Option<String>,
+    /// X25519 public keys for the `encrypt` redaction as 64 hex digits, by key ID.
+    ///
+    /// The matching secret keys must never be part of the config, since it is served to
+    /// downstream Relays. To rotate a key, add a new key ID and reference it from the rules.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub encryption_keys: BTreeMap<String, String>,
 }
 
 /// A set of named rule configurations.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct PiiConfig {
     /// A map of custom PII rules.
     #[serde(default)]
@@ -185,6 +210,23 @@ pub struct PiiConfig {
     #[serde(default)]
     pub applications: BTreeMap<SelectorSpec, Vec<String>>,
 
+    /// IDs of rules that only record their matches without modifying values.
+    ///
+    /// This allows to audit new rules before enforcing them. Matches are added to the event's meta
+    /// data as annotations and can be inspected with [`PiiProcessor::audit_matches`]. Audited
+    /// rules are not applied to attachments.
+    ///
+    /// [`PiiProcessor::audit_matches`]: crate::pii::PiiProcessor::audit_matches
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub audit_rules: BTreeSet<String>,
+
+    /// Parse JSON and URL-encoded data embedded in strings and apply rules to their contents.
+    ///
+    /// Nested values are visited with their keys appended to the path of the string, so selectors
+    /// and key-based rules apply to them. If a rule matches, the string is serialized again.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub scrub_embedded_data: bool,
+
     /// PII config derived from datascrubbing settings.
     ///
     /// Cached because the conversion process is expensive.
@@ -200,10 +242,16 @@ impl PartialEq for PiiConfig {
             rules,
             vars,
             applications,
+            audit_rules,
+            scrub_embedded_data,
             compiled: _compiled,
         } = &self;
 
-        rules == &other.rules && vars == &other.vars && applications == &other.applications
+        rules == &other.rules
+            && vars == &other.vars
+            && applications == &other.applications
+            && audit_rules == &other.audit_rules
+            && scrub_embedded_data == &other.scrub_embedded_data
     }
 }
 
@@ -223,6 +271,24 @@ impl PiiConfig {
         serde_json::to_string_pretty(&self)
     }
 
+    /// Checks that all keys of `encrypt` redactions exist and are valid, and that `vars.hashKey`
+    /// is set to tag encrypted values.
+    ///
+    /// Invalid keys do not fail processing. Instead, values of rules with such keys are removed.
+    pub fn validate(&self) -> Result<(), PiiConfigError> {
+        for (id, public_key) in &self.vars.encryption_keys {
+            EncryptionKey::new(id, public_key, &[])?;
+        }
+
+        for rule in self.rules.values() {
+            if let Redaction::Encrypt(ref encrypt) = rule.redaction {
+                EncryptionKey::from_vars(&encrypt.key_id, &self.vars)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Get a representation of this `PiiConfig` that is more (CPU-)efficient for processing.
     ///
     /// This can be computationally expensive when called for the first time. The result is cached
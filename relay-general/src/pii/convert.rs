@@ -38,6 +38,10 @@ pub fn to_pii_config(
         applied_rules.push("@common:filter".to_owned());
     }
 
+    if datascrubbing_config.scrub_data && datascrubbing_config.scrub_secrets {
+        applied_rules.push("@secrets:filter".to_owned());
+    }
+
     if datascrubbing_config.scrub_ip_addresses {
         applications.insert(KNOWN_IP_FIELDS.clone(), vec!["@anything:remove".to_owned()]);
     }
@@ -123,6 +127,7 @@ pub fn to_pii_config(
         rules: custom_rules,
         vars: Vars::default(),
         applications,
+        scrub_embedded_data: datascrubbing_config.scrub_embedded_data,
         ..Default::default()
     }))
 }
@@ -135,10 +140,10 @@ mod tests {
     /// has an equivalent testcase in Python.
     use crate::pii::{DataScrubbingConfig, PiiConfig, PiiProcessor};
     use crate::processor::{process_value, ProcessingState};
-    use crate::protocol::Event;
+    use crate::protocol::{Event, ExtraValue};
     use crate::store::{StoreConfig, StoreProcessor};
     use crate::testutils::assert_annotated_snapshot;
-    use crate::types::FromValue;
+    use crate::types::{Annotated, FromValue, Value};
 
     use super::to_pii_config as to_pii_config_impl;
 
@@ -227,6 +232,102 @@ THd+9FBxiHLGXNKhG/FRSyREXEt+NyYIf/0cyByc9tNksat794ddUqnLOg0vwSkv
         "###);
     }
 
+    #[test]
+    fn test_convert_scrub_secrets() {
+        let pii_config = to_pii_config(&DataScrubbingConfig {
+            scrub_secrets: true,
+            ..simple_enabled_config()
+        });
+
+        insta::assert_json_snapshot!(pii_config, @r###"
+        {
+          "rules": {},
+          "vars": {
+            "hashKey": null
+          },
+          "applications": {
+            "($string || $number || $array) && !(debug_meta.** || $frame.filename || $frame.abs_path || $logentry.formatted || $error.value)": [
+              "@common:filter",
+              "@secrets:filter"
+            ],
+            "$http.env.REMOTE_ADDR || $user.ip_address || $sdk.client_ip": [
+              "@anything:remove"
+            ]
+          }
+        }
+        "###);
+    }
+
+    #[test]
+    fn test_scrub_secrets() {
+        let mut data = Event::from_value(
+            serde_json::json!({
+                "extra": {
+                    "header": "Bearer eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxMjM0In0.abc123-_",
+                    "note": "Transfer to DE89 3704 0044 0532 0130 00"
+                }
+            })
+            .into(),
+        );
+
+        let pii_config = to_pii_config(&DataScrubbingConfig {
+            scrub_secrets: true,
+            ..simple_enabled_config()
+        })
+        .unwrap();
+
+        let mut pii_processor = PiiProcessor::new(pii_config.compiled());
+        process_value(&mut data, &mut pii_processor, ProcessingState::root()).unwrap();
+
+        let extra = data.value().unwrap().extra.value().unwrap();
+        assert_eq!(
+            extra.get("header").and_then(Annotated::value),
+            Some(&ExtraValue(Value::String("Bearer [Filtered]".to_owned())))
+        );
+        assert_eq!(
+            extra.get("note").and_then(Annotated::value),
+            Some(&ExtraValue(Value::String(
+                "Transfer to [Filtered]".to_owned()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_scrub_embedded_data() {
+        let mut data = Event::from_value(
+            serde_json::json!({
+                "extra": {
+                    "body": "{\"user\": \"jane\", \"password\": \"hunter2\"}",
+                    "query": "user=jane&password=hunter2"
+                }
+            })
+            .into(),
+        );
+
+        let pii_config = to_pii_config(&DataScrubbingConfig {
+            scrub_embedded_data: true,
+            ..simple_enabled_config()
+        })
+        .unwrap();
+
+        let mut pii_processor = PiiProcessor::new(pii_config.compiled());
+        process_value(&mut data, &mut pii_processor, ProcessingState::root()).unwrap();
+
+        let extra = data.value().unwrap().extra.value().unwrap();
+        assert_eq!(
+            extra.get("body").and_then(Annotated::value),
+            Some(&ExtraValue(Value::String(
+                r#"{"password":"[Filtered]","user":"jane"}"#.to_owned()
+            )))
+        );
+        assert_eq!(
+            extra.get("query").and_then(Annotated::value),
+            Some(&ExtraValue(Value::String(
+                "user=jane&password=%5BFiltered%5D".to_owned()
+            )))
+        );
+    }
+
     #[test]
     fn test_convert_empty_sensitive_field() {
         let pii_config = to_pii_config(&DataScrubbingConfig {
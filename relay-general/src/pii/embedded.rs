@@ -0,0 +1,233 @@
+//! Detection and re-serialization of structured data embedded in string values.
+//!
+//! With `scrubEmbeddedData` enabled in the PII config, strings containing JSON objects or arrays,
+//! URL query strings and `application/x-www-form-urlencoded` bodies are parsed so that PII rules
+//! can be applied to their contents with nested paths. For instance, the `password` key in a JSON
+//! body sent as string in `request.data` is visited at the path `request.data.password`.
+use std::collections::BTreeSet;
+
+use crate::types::{Annotated, Object, Remark, Value};
+
+/// The format of embedded data, along with what is needed to serialize it again.
+#[derive(Debug)]
+enum Format {
+    /// A JSON object or array.
+    Json,
+    /// URL-encoded pairs, optionally after a URL and before a fragment.
+    UrlEncoded {
+        prefix: String,
+        keys: Vec<String>,
+        suffix: String,
+    },
+}
+
+/// Structured data parsed from a string value.
+#[derive(Debug)]
+pub(super) struct EmbeddedData {
+    format: Format,
+    data: Annotated<Value>,
+}
+
+impl EmbeddedData {
+    /// Parses JSON or URL-encoded data from a string, if it contains any.
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::parse_json(value).or_else(|| Self::parse_urlencoded(value))
+    }
+
+    fn parse_json(value: &str) -> Option<Self> {
+        // Only objects and arrays have nested paths. This also avoids parsing every number.
+        if !value.starts_with('{') && !value.starts_with('[') {
+            return None;
+        }
+
+        let json = serde_json::from_str::<serde_json::Value>(value).ok()?;
+        Some(Self {
+            format: Format::Json,
+            data: Annotated::from(json),
+        })
+    }
+
+    fn parse_urlencoded(value: &str) -> Option<Self> {
+        // URL-encoded data is ASCII and never contains whitespace. This rules out most prose.
+        if !value.is_ascii() || value.contains(char::is_whitespace) || !value.contains('=') {
+            return None;
+        }
+
+        let (prefix, rest) = match value.find('?') {
+            Some(index) => value.split_at(index + 1),
+            None => ("", value),
+        };
+
+        let (query, suffix) = match rest.find('#') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query).ok()?;
+
+        // Avoid false positives such as base64 encoded strings, which decode with a single empty
+        // value or `=`. Duplicate keys cannot be represented as object.
+        let mut seen = BTreeSet::new();
+        let is_valid = match pairs.as_slice() {
+            [] => false,
+            [(key, value)] => !key.is_empty() && !matches!(value.as_str(), "" | "="),
+            _ => pairs.iter().all(|(key, _)| !key.is_empty()),
+        };
+
+        if !is_valid || !pairs.iter().all(|(key, _)| seen.insert(key.as_str())) {
+            return None;
+        }
+
+        let keys = pairs.iter().map(|(key, _)| key.clone()).collect();
+        let object = pairs
+            .into_iter()
+            .map(|(key, value)| (key, Annotated::new(Value::String(value))))
+            .collect::<Object<Value>>();
+
+        Some(Self {
+            format: Format::UrlEncoded {
+                prefix: prefix.to_owned(),
+                keys,
+                suffix: suffix.to_owned(),
+            },
+            data: Annotated::new(Value::Object(object)),
+        })
+    }
+
+    /// Returns the parsed data for processing.
+    pub fn data_mut(&mut self) -> &mut Annotated<Value> {
+        &mut self.data
+    }
+
+    /// Returns remarks of all modifications in the data, without ranges.
+    ///
+    /// Ranges refer to nested values and are meaningless for the serialized string.
+    pub fn remarks(&self) -> Vec<Remark> {
+        let mut remarks = Vec::new();
+        collect_remarks(&self.data, &mut remarks);
+        remarks
+    }
+
+    /// Serializes the data back into its original format.
+    pub fn serialize(&self) -> Option<String> {
+        match self.format {
+            // Removed values serialize as `null` to retain the structure.
+            Format::Json => serde_json::to_string(self.data.value()?).ok(),
+            Format::UrlEncoded {
+                ref prefix,
+                ref keys,
+                ref suffix,
+            } => {
+                let object = match self.data.value() {
+                    Some(Value::Object(object)) => object,
+                    _ => return None,
+                };
+
+                // Removed values are kept as empty values, and values that were replaced with
+                // other types are serialized as JSON.
+                let pairs = keys
+                    .iter()
+                    .map(|key| {
+                        let value = match object.get(key).and_then(Annotated::value) {
+                            Some(Value::String(value)) => value.clone(),
+                            Some(value) => serde_json::to_string(value).ok()?,
+                            None => String::new(),
+                        };
+                        Some((key.as_str(), value))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let query = serde_urlencoded::to_string(pairs).ok()?;
+                Some(format!("{}{}{}", prefix, query, suffix))
+            }
+        }
+    }
+}
+
+fn collect_remarks(annotated: &Annotated<Value>, remarks: &mut Vec<Remark>) {
+    for remark in annotated.meta().iter_remarks() {
+        remarks.push(Remark::new(remark.ty(), remark.rule_id()));
+    }
+
+    match annotated.value() {
+        Some(Value::Array(items)) => {
+            for item in items {
+                collect_remarks(item, remarks);
+            }
+        }
+        Some(Value::Object(items)) => {
+            for item in items.values() {
+                collect_remarks(item, remarks);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let mut embedded = EmbeddedData::parse(r#"{"user": "jane", "ids": [1, 2]}"#).unwrap();
+        assert!(matches!(embedded.format, Format::Json));
+
+        let data = embedded.data_mut().value_mut().as_mut().unwrap();
+        if let Value::Object(object) = data {
+            object.insert("user".to_owned(), Annotated::empty());
+        }
+
+        assert_eq!(
+            embedded.serialize().as_deref(),
+            Some(r#"{"ids":[1,2],"user":null}"#)
+        );
+    }
+
+    #[test]
+    fn test_parse_urlencoded() {
+        let mut embedded =
+            EmbeddedData::parse("https://example.com/login?user=jane&token=abc%201#top").unwrap();
+
+        let data = embedded.data_mut().value_mut().as_mut().unwrap();
+        if let Value::Object(object) = data {
+            assert_eq!(object["token"].as_str(), Some("abc 1"));
+            object.insert(
+                "token".to_owned(),
+                Annotated::new(Value::String("[Filtered]".to_owned())),
+            );
+        }
+
+        assert_eq!(
+            embedded.serialize().as_deref(),
+            Some("https://example.com/login?user=jane&token=%5BFiltered%5D#top")
+        );
+    }
+
+    #[test]
+    fn test_parse_form_body() {
+        let embedded = EmbeddedData::parse("password=hunter2&remember=1").unwrap();
+        assert_eq!(
+            embedded.serialize().as_deref(),
+            Some("password=hunter2&remember=1")
+        );
+    }
+
+    #[test]
+    fn test_parse_false_positives() {
+        for value in [
+            "hello world",
+            "dGU=",
+            "dA==",
+            "a=1 b=2",
+            "https://example.com/path",
+            "a=1&a=2",
+            "42",
+            "{not json",
+        ] {
+            assert!(EmbeddedData::parse(value).is_none(), "{}", value);
+        }
+    }
+}
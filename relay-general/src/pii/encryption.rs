@@ -0,0 +1,393 @@
+//! Reversible pseudonymization for the `encrypt` redaction.
+//!
+//! Values are encrypted to an X25519 public key, so that configs only ever contain public keys and
+//! Relays cannot decrypt the tokens they produce. Each value is sealed with a fresh ephemeral key
+//! and AES-SIV. To correlate scrubbed values across events without revealing them, every token
+//! carries a deterministic tag, an HMAC-SHA256 of the value keyed with `vars.hashKey`.
+//!
+//! Tokens have the form `enc:<key_id>:<tag>:<payload>`, so that the key can be rotated by switching
+//! to a new key ID while tokens of the old key remain decryptable.
+//!
+//! Decryption is not part of event processing. It is only exposed through [`decrypt_tokens`] for
+//! offline tools, which hold the secret keys.
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use aes_siv::siv::Aes128Siv;
+use aes_siv::KeyInit;
+use data_encoding::{BASE64URL_NOPAD, HEXLOWER, HEXLOWER_PERMISSIVE};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::pii::Vars;
+
+/// The length of public and secret keys in bytes.
+const KEY_LENGTH: usize = 32;
+
+/// The length of the correlation tag in bytes.
+const TAG_LENGTH: usize = 16;
+
+/// Matches encrypted tokens in text.
+static TOKEN_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\benc:([A-Za-z0-9_.-]+):([0-9a-f]{32}):([A-Za-z0-9_-]+)").unwrap());
+
+/// Matches valid key IDs.
+static KEY_ID_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z0-9_.-]+$").unwrap());
+
+/// An error raised when loading keys or decrypting tokens.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum EncryptionError {
+    #[error("invalid key id {0:?}, only alphanumeric characters, `_`, `.` and `-` are allowed")]
+    InvalidKeyId(String),
+
+    #[error("invalid encryption key {0:?}, expected 64 hex digits")]
+    InvalidKey(String),
+
+    #[error("unknown encryption key {0:?}")]
+    UnknownKey(String),
+
+    #[error("missing `vars.hashKey` to tag encrypted values")]
+    MissingHashKey,
+
+    #[error("invalid or tampered token for key {0:?}")]
+    InvalidToken(String),
+}
+
+/// Decodes 64 hex digits into key material.
+fn decode_key(id: &str, key: &str) -> Result<[u8; KEY_LENGTH], EncryptionError> {
+    if !KEY_ID_REGEX.is_match(id) {
+        return Err(EncryptionError::InvalidKeyId(id.to_owned()));
+    }
+
+    let bytes = HEXLOWER_PERMISSIVE
+        .decode(key.as_bytes())
+        .map_err(|_| EncryptionError::InvalidKey(id.to_owned()))?;
+
+    bytes
+        .try_into()
+        .map_err(|_| EncryptionError::InvalidKey(id.to_owned()))
+}
+
+/// Derives the AES-SIV cipher for a token from the Diffie-Hellman shared secret.
+///
+/// Both public keys are bound into the key, so a payload cannot be replayed for another recipient.
+fn derive_cipher(shared: &[u8], ephemeral: &PublicKey, recipient: &PublicKey) -> Aes128Siv {
+    let mut mac = Hmac::<Sha256>::new_varkey(shared).unwrap();
+    mac.input(ephemeral.as_bytes());
+    mac.input(recipient.as_bytes());
+
+    let mut key = [0; KEY_LENGTH];
+    key.copy_from_slice(mac.result().code().as_slice());
+    Aes128Siv::new(&key.into())
+}
+
+/// Derives the hex-encoded public key for a hex-encoded secret key.
+///
+/// Only the public key goes into `vars.encryptionKeys`. Keep the secret key outside of Sentry to
+/// decrypt tokens offline.
+pub fn encryption_public_key(secret_key: &str) -> Result<String, EncryptionError> {
+    let secret = StaticSecret::from(decode_key("secret", secret_key)?);
+    Ok(HEXLOWER.encode(PublicKey::from(&secret).as_bytes()))
+}
+
+/// A named public key for the `encrypt` redaction.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: String,
+    public_key: PublicKey,
+    tag_key: Vec<u8>,
+}
+
+impl EncryptionKey {
+    /// Creates a key from its ID, 64 hex digits of public key material, and the key for tags.
+    pub fn new(id: &str, public_key: &str, tag_key: &[u8]) -> Result<Self, EncryptionError> {
+        Ok(Self {
+            id: id.to_owned(),
+            public_key: PublicKey::from(decode_key(id, public_key)?),
+            tag_key: tag_key.to_owned(),
+        })
+    }
+
+    /// Looks up a key by its ID in `vars.encryptionKeys`, using `vars.hashKey` for tags.
+    ///
+    /// Fails if `vars.hashKey` is missing or empty, since tags without a secret key could be
+    /// computed by anyone to correlate or guess values.
+    pub fn from_vars(id: &str, vars: &Vars) -> Result<Self, EncryptionError> {
+        let public_key = vars
+            .encryption_keys
+            .get(id)
+            .ok_or_else(|| EncryptionError::UnknownKey(id.to_owned()))?;
+
+        match vars.hash_key.as_deref() {
+            Some(tag_key) if !tag_key.is_empty() => Self::new(id, public_key, tag_key.as_bytes()),
+            _ => Err(EncryptionError::MissingHashKey),
+        }
+    }
+
+    /// Returns the ID of this key.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Computes the deterministic correlation tag of a value.
+    fn tag(&self, value: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.tag_key).unwrap();
+        mac.input(self.id.as_bytes());
+        mac.input(&[0]);
+        mac.input(value.as_bytes());
+        HEXLOWER.encode(&mac.result().code().as_slice()[..TAG_LENGTH])
+    }
+
+    /// Encrypts a value into a token of the form `enc:<key_id>:<tag>:<payload>`.
+    ///
+    /// The key ID and tag are authenticated along with the value, so a token cannot be moved to
+    /// another key or tag.
+    pub fn encrypt(&self, value: &str) -> String {
+        let ephemeral = StaticSecret::from(rand::random::<[u8; KEY_LENGTH]>());
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&self.public_key);
+
+        let tag = self.tag(value);
+        let ciphertext = derive_cipher(shared.as_bytes(), &ephemeral_public, &self.public_key)
+            .encrypt([self.id.as_bytes(), tag.as_bytes()], value.as_bytes())
+            .expect("encryption with two headers does not fail");
+
+        let mut payload = ephemeral_public.as_bytes().to_vec();
+        payload.extend_from_slice(&ciphertext);
+
+        format!(
+            "enc:{}:{}:{}",
+            self.id,
+            tag,
+            BASE64URL_NOPAD.encode(&payload)
+        )
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the tag key.
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decrypts the payload of a token with the secret key for the given key ID.
+fn decrypt(
+    id: &str,
+    secret_key: &str,
+    tag: &str,
+    payload: &str,
+) -> Result<String, EncryptionError> {
+    let invalid = || EncryptionError::InvalidToken(id.to_owned());
+
+    let secret = StaticSecret::from(decode_key(id, secret_key)?);
+    let recipient = PublicKey::from(&secret);
+
+    let payload = BASE64URL_NOPAD
+        .decode(payload.as_bytes())
+        .map_err(|_| invalid())?;
+
+    if payload.len() < KEY_LENGTH {
+        return Err(invalid());
+    }
+
+    let (ephemeral, ciphertext) = payload.split_at(KEY_LENGTH);
+    let mut ephemeral_bytes = [0; KEY_LENGTH];
+    ephemeral_bytes.copy_from_slice(ephemeral);
+    let ephemeral = PublicKey::from(ephemeral_bytes);
+
+    let shared = secret.diffie_hellman(&ephemeral);
+    let plaintext = derive_cipher(shared.as_bytes(), &ephemeral, &recipient)
+        .decrypt([id.as_bytes(), tag.as_bytes()], ciphertext)
+        .map_err(|_| invalid())?;
+
+    String::from_utf8(plaintext).map_err(|_| invalid())
+}
+
+/// Replaces all tokens in the text with their decrypted values.
+///
+/// `keys` maps key IDs to hex-encoded secret keys, whose public keys are configured in
+/// `vars.encryptionKeys` of the PII config. Keep keys of previous rotations in this map to decrypt
+/// older tokens. Fails if a token refers to a key that is not in the map, or if it cannot be
+/// decrypted.
+pub fn decrypt_tokens<'a>(
+    text: &'a str,
+    keys: &BTreeMap<String, String>,
+) -> Result<Cow<'a, str>, EncryptionError> {
+    let mut output = String::new();
+    let mut pos = 0;
+
+    for captures in TOKEN_REGEX.captures_iter(text) {
+        let token = captures.get(0).unwrap();
+        let id = &captures[1];
+        let secret_key = keys
+            .get(id)
+            .ok_or_else(|| EncryptionError::UnknownKey(id.to_owned()))?;
+
+        output.push_str(&text[pos..token.start()]);
+        output.push_str(&decrypt(id, secret_key, &captures[2], &captures[3])?);
+        pos = token.end();
+    }
+
+    if pos == 0 {
+        return Ok(Cow::Borrowed(text));
+    }
+
+    output.push_str(&text[pos..]);
+    Ok(Cow::Owned(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+
+    const SECRET_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const PUBLIC_KEY: &str = "8f40c5adb68f25624ae5b214ea767a6ec94d829d3d7b5e1ad1ba6f3e2138285f";
+    const OTHER_SECRET_KEY: &str =
+        "1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100";
+
+    fn key(id: &str, secret_key: &str) -> EncryptionKey {
+        EncryptionKey::new(id, &encryption_public_key(secret_key).unwrap(), b"tag key").unwrap()
+    }
+
+    fn secret_keys() -> BTreeMap<String, String> {
+        let mut keys = BTreeMap::new();
+        keys.insert("2022".to_owned(), SECRET_KEY.to_owned());
+        keys.insert("2023".to_owned(), OTHER_SECRET_KEY.to_owned());
+        keys
+    }
+
+    /// Returns the correlation tag of a token.
+    fn tag(token: &str) -> &str {
+        token.split(':').nth(2).unwrap()
+    }
+
+    #[test]
+    fn test_encryption_public_key() {
+        assert_eq!(encryption_public_key(SECRET_KEY).unwrap(), PUBLIC_KEY);
+    }
+
+    #[test]
+    fn test_encrypt_tag_deterministic() {
+        let key = key("2022", SECRET_KEY);
+        let token = key.encrypt("jane@example.com");
+        assert!(token.starts_with("enc:2022:"));
+
+        // Ciphertexts are randomized, but tags correlate equal values.
+        let again = key.encrypt("jane@example.com");
+        assert_ne!(again, token);
+        assert_eq!(tag(&again), tag(&token));
+        assert_ne!(tag(&key.encrypt("john@example.com")), tag(&token));
+
+        let other_tag_key = EncryptionKey::new("2022", PUBLIC_KEY, b"other").unwrap();
+        assert_ne!(tag(&other_tag_key.encrypt("jane@example.com")), tag(&token));
+    }
+
+    #[test]
+    fn test_encrypt_from_vars() {
+        let mut vars = Vars::default();
+        vars.encryption_keys
+            .insert("2022".to_owned(), PUBLIC_KEY.to_owned());
+        assert_eq!(
+            EncryptionKey::from_vars("2022", &vars).unwrap_err(),
+            EncryptionError::MissingHashKey
+        );
+
+        vars.hash_key = Some("tag key".to_owned());
+
+        let token = EncryptionKey::from_vars("2022", &vars)
+            .unwrap()
+            .encrypt("42");
+        assert_eq!(decrypt_tokens(&token, &secret_keys()).unwrap(), "42");
+
+        assert_eq!(
+            EncryptionKey::from_vars("2023", &vars).unwrap_err(),
+            EncryptionError::UnknownKey("2023".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_decrypt_rotated_keys() {
+        let old = key("2022", SECRET_KEY).encrypt("42");
+        let new = key("2023", OTHER_SECRET_KEY).encrypt("42");
+        assert_ne!(tag(&old), tag(&new));
+
+        let text = format!("user {} is user {}.", old, new);
+        let decrypted = decrypt_tokens(&text, &secret_keys()).unwrap();
+        assert_eq!(decrypted, "user 42 is user 42.");
+    }
+
+    #[test]
+    fn test_decrypt_unknown_key() {
+        let token = key("2024", SECRET_KEY).encrypt("42");
+        assert_eq!(
+            decrypt_tokens(&token, &secret_keys()),
+            Err(EncryptionError::UnknownKey("2024".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_decrypt_public_key() {
+        // The public key from the config cannot decrypt tokens.
+        let token = key("2022", SECRET_KEY).encrypt("42");
+        let mut keys = BTreeMap::new();
+        keys.insert("2022".to_owned(), PUBLIC_KEY.to_owned());
+
+        assert_eq!(
+            decrypt_tokens(&token, &keys),
+            Err(EncryptionError::InvalidToken("2022".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_decrypt_wrong_key_id() {
+        // A token moved to another key ID must not decrypt, even if that key is the same.
+        let token = key("2022", SECRET_KEY).encrypt("42");
+        let mut keys = secret_keys();
+        keys.insert("copy".to_owned(), SECRET_KEY.to_owned());
+
+        let moved = token.replace("enc:2022:", "enc:copy:");
+        assert_eq!(
+            decrypt_tokens(&moved, &keys),
+            Err(EncryptionError::InvalidToken("copy".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_decrypt_wrong_tag() {
+        let token = key("2022", SECRET_KEY).encrypt("42");
+        let other = key("2022", SECRET_KEY).encrypt("43");
+
+        let swapped = token.replace(tag(&token), tag(&other));
+        assert_eq!(
+            decrypt_tokens(&swapped, &secret_keys()),
+            Err(EncryptionError::InvalidToken("2022".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_decrypt_without_tokens() {
+        let decrypted = decrypt_tokens("nothing to see here", &secret_keys()).unwrap();
+        assert!(matches!(decrypted, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert_eq!(
+            EncryptionKey::new("short", "abcd", b"").unwrap_err(),
+            EncryptionError::InvalidKey("short".to_owned())
+        );
+        assert_eq!(
+            EncryptionKey::new("a:b", PUBLIC_KEY, b"").unwrap_err(),
+            EncryptionError::InvalidKeyId("a:b".to_owned())
+        );
+    }
+}
@@ -33,6 +33,12 @@ pub struct DataScrubbingConfig {
     /// Controls whether default fields will be scrubbed.
     #[serde(skip_serializing_if = "is_flag_default")]
     pub scrub_defaults: bool,
+    /// Controls whether credentials, tokens and financial data are scrubbed with `@secrets`.
+    #[serde(skip_serializing_if = "is_flag_default")]
+    pub scrub_secrets: bool,
+    /// Controls whether JSON and URL-encoded data embedded in strings is scrubbed.
+    #[serde(skip_serializing_if = "is_flag_default")]
+    pub scrub_embedded_data: bool,
 
     /// PII config derived from datascrubbing settings.
     ///
@@ -54,6 +60,8 @@ impl DataScrubbingConfig {
             scrub_ip_addresses: false,
             sensitive_fields: vec![],
             scrub_defaults: false,
+            scrub_secrets: false,
+            scrub_embedded_data: false,
             pii_config: OnceCell::with_value(Ok(None)),
         }
     }
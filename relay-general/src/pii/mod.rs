@@ -5,6 +5,8 @@ mod builtin;
 mod compiledconfig;
 mod config;
 mod convert;
+mod embedded;
+mod encryption;
 mod generate_selectors;
 mod legacy;
 mod minidumps;
@@ -16,6 +18,7 @@ mod utils;
 pub use self::attachments::*;
 pub use self::compiledconfig::*;
 pub use self::config::*;
+pub use self::encryption::*;
 pub use self::generate_selectors::selector_suggestions_from_value;
 pub use self::legacy::*;
 pub use self::minidumps::*;
@@ -3,20 +3,40 @@ use std::mem;
 
 use once_cell::sync::OnceCell;
 use regex::Regex;
+use serde::Serialize;
 
 use crate::pii::compiledconfig::RuleRef;
-use crate::pii::regexes::{get_regex_for_rule_type, PatternType, ReplaceBehavior, ANYTHING_REGEX};
+use crate::pii::embedded::EmbeddedData;
+use crate::pii::regexes::{
+    get_regex_for_rule_type, valid_match_groups, PatternType, ReplaceBehavior, ANYTHING_REGEX,
+};
 use crate::pii::utils::{hash_value, process_pairlist};
 use crate::pii::{CompiledPiiConfig, Redaction, RuleType};
 use crate::processor::{
-    process_chunked_value, Chunk, Pii, ProcessValue, ProcessingState, Processor, ValueType,
+    process_chunked_value, process_value, Chunk, Pii, ProcessValue, ProcessingState, Processor,
+    ValueType,
 };
 use crate::protocol::{AsPair, IpAddr, NativeImagePath, PairList, User};
 use crate::types::{Meta, ProcessingAction, ProcessingResult, Remark, RemarkType};
 
+/// A match of an audited PII rule, which did not modify the value.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PiiAuditMatch {
+    /// The ID of the matching rule, or of its parent if the parent hides inner rules.
+    pub rule_id: String,
+    /// The path of the matched value.
+    pub path: String,
+    /// The types of the matched value, such as `string` or `message`.
+    pub value_type: Vec<String>,
+    /// The modification that the rule would have made.
+    pub remark: RemarkType,
+}
+
 /// A processor that performs PII stripping.
 pub struct PiiProcessor<'a> {
     compiled_config: &'a CompiledPiiConfig,
+    dry_run: bool,
+    audit_matches: Vec<PiiAuditMatch>,
 }
 
 impl<'a> PiiProcessor<'a> {
@@ -24,11 +44,32 @@ impl<'a> PiiProcessor<'a> {
     pub fn new(compiled_config: &'a CompiledPiiConfig) -> PiiProcessor<'a> {
         // this constructor needs to be cheap... a new PiiProcessor is created for each event. Move
         // any init logic into CompiledPiiConfig::new.
-        PiiProcessor { compiled_config }
+        PiiProcessor {
+            compiled_config,
+            dry_run: false,
+            audit_matches: Vec::new(),
+        }
+    }
+
+    /// Creates a processor that audits all rules of the config without modifying values.
+    ///
+    /// This is equivalent to listing all rules in [`PiiConfig::audit_rules`].
+    ///
+    /// [`PiiConfig::audit_rules`]: crate::pii::PiiConfig::audit_rules
+    pub fn dry_run(compiled_config: &'a CompiledPiiConfig) -> PiiProcessor<'a> {
+        PiiProcessor {
+            dry_run: true,
+            ..Self::new(compiled_config)
+        }
+    }
+
+    /// Returns the matches of audited rules recorded so far.
+    pub fn audit_matches(&self) -> &[PiiAuditMatch] {
+        &self.audit_matches
     }
 
     fn apply_all_rules(
-        &self,
+        &mut self,
         meta: &mut Meta,
         state: &ProcessingState<'_>,
         mut value: Option<&mut String>,
@@ -38,18 +79,110 @@ impl<'a> PiiProcessor<'a> {
             return Ok(());
         }
 
-        for (selector, rules) in self.compiled_config.applications.iter() {
+        // Annotations of audited rules are added after all rules have been applied, since redacting
+        // chunks of a string replaces all of its remarks.
+        let mut annotations = Vec::new();
+
+        let compiled_config = self.compiled_config;
+        for (selector, rules) in compiled_config.applications.iter() {
             if state.path().matches_selector(selector) {
                 #[allow(clippy::needless_option_as_deref)]
                 for rule in rules {
                     let reborrowed_value = value.as_deref_mut();
-                    apply_rule_to_value(meta, rule, state.path().key(), reborrowed_value)?;
+                    if self.dry_run || rule.audit {
+                        let value = reborrowed_value.as_deref();
+                        self.audit_rule(&mut annotations, state, rule, value);
+                    } else {
+                        apply_rule_to_value(meta, rule, state.path().key(), reborrowed_value)?;
+                    }
+                }
+            }
+        }
+
+        for remark in annotations {
+            meta.add_remark(remark);
+        }
+
+        Ok(())
+    }
+
+    /// Applies all rules to JSON or URL-encoded data embedded in a string.
+    ///
+    /// The string is treated like the container it embeds: Rules selecting the string itself are
+    /// applied as if it were an object, so they can only remove it. Value patterns, such as the
+    /// keys matched by `@password`, are applied to the nested values instead of the entire string.
+    ///
+    /// The embedded data is processed at the path of the string, so its keys extend the path. If
+    /// any rule modified the data, the string is replaced with the serialized data and the remarks
+    /// of all modifications are moved to the string. Audited matches only annotate the string.
+    fn process_embedded(
+        &mut self,
+        value: &mut String,
+        mut embedded: EmbeddedData,
+        meta: &mut Meta,
+        state: &ProcessingState<'_>,
+    ) -> ProcessingResult {
+        self.apply_all_rules(meta, state, None)?;
+        process_value(embedded.data_mut(), self, state)?;
+
+        // Audited rules and dry runs only annotate the data. Keep the original string in this
+        // case, since serializing may still change its formatting.
+        let remarks = embedded.remarks();
+        let modified = remarks
+            .iter()
+            .any(|remark| remark.ty() != RemarkType::Annotated);
+        if self.dry_run || !modified {
+            for remark in remarks {
+                if remark.ty() == RemarkType::Annotated {
+                    meta.add_remark(remark);
                 }
             }
+            return Ok(());
+        }
+
+        // Keep the original string if the data cannot be serialized, rather than losing it.
+        if let Some(serialized) = embedded.serialize() {
+            *value = serialized;
+            for remark in remarks {
+                meta.add_remark(remark);
+            }
         }
 
         Ok(())
     }
+
+    /// Applies a rule to a copy of the value and records what it would have changed.
+    ///
+    /// The value itself remains untouched. Every would-be modification is added to `annotations`
+    /// as a remark for the meta data of the value.
+    fn audit_rule(
+        &mut self,
+        annotations: &mut Vec<Remark>,
+        state: &ProcessingState<'_>,
+        rule: &RuleRef,
+        value: Option<&String>,
+    ) {
+        let mut audit_meta = Meta::default();
+        let mut audit_value = value.cloned();
+
+        // Removing the value is signaled through an error, which is already recorded as a remark.
+        let _ = apply_rule_to_value(
+            &mut audit_meta,
+            rule,
+            state.path().key(),
+            audit_value.as_mut(),
+        );
+
+        for remark in audit_meta.iter_remarks() {
+            annotations.push(Remark::new(RemarkType::Annotated, remark.rule_id()));
+            self.audit_matches.push(PiiAuditMatch {
+                rule_id: remark.rule_id().to_owned(),
+                path: state.path().to_string(),
+                value_type: state.value_type().iter().map(|ty| ty.to_string()).collect(),
+                remark: remark.ty(),
+            });
+        }
+    }
 }
 
 impl<'a> Processor for PiiProcessor<'a> {
@@ -84,6 +217,12 @@ impl<'a> Processor for PiiProcessor<'a> {
             return Ok(());
         }
 
+        if self.compiled_config.scrub_embedded_data {
+            if let Some(embedded) = EmbeddedData::parse(value) {
+                return self.process_embedded(value, embedded, meta, state);
+            }
+        }
+
         // same as before_process. duplicated here because we can only check for "true",
         // "false" etc in process_string.
         self.apply_all_rules(meta, state, Some(value))
@@ -238,7 +377,7 @@ fn apply_regex_to_chunks<'a>(
     }
 
     // Early exit if this regex does not match and return the original chunks.
-    let mut captures_iter = regex.captures_iter(&search_string).peekable();
+    let mut captures_iter = valid_match_groups(&rule.ty, regex, &search_string).peekable();
     if captures_iter.peek().is_none() {
         return chunks;
     }
@@ -286,16 +425,16 @@ fn apply_regex_to_chunks<'a>(
     match replace_behavior {
         ReplaceBehavior::Groups(ref groups) => {
             for m in captures_iter {
-                for (idx, g) in m.iter().enumerate() {
+                for (idx, g) in m.into_iter().enumerate() {
                     if let Some(g) = g {
                         if groups.contains(&(idx as u8)) {
                             process_text(
-                                &search_string[pos..g.start()],
+                                &search_string[pos..g.start],
                                 &mut rv,
                                 &mut replacement_chunks,
                             );
-                            insert_replacement_chunks(rule, g.as_str(), &mut rv);
-                            pos = g.end();
+                            insert_replacement_chunks(rule, &search_string[g.clone()], &mut rv);
+                            pos = g.end;
                         }
                     }
                 }
@@ -346,12 +485,29 @@ fn insert_replacement_chunks(rule: &RuleRef, text: &str, output: &mut Vec<Chunk<
                 text: Cow::Owned(replace.text.clone()),
             });
         }
+        Redaction::Encrypt(_) => match rule.encryption_key {
+            Some(ref key) => output.push(Chunk::Redaction {
+                ty: RemarkType::Encrypted,
+                rule_id: Cow::Owned(rule.origin.to_string()),
+                text: Cow::Owned(key.encrypt(text)),
+            }),
+            // Without a valid key, fail closed and remove the value.
+            None => output.push(Chunk::Redaction {
+                ty: RemarkType::Removed,
+                rule_id: Cow::Owned(rule.origin.to_string()),
+                text: Cow::Borrowed(""),
+            }),
+        },
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::pii::{PiiConfig, ReplaceRedaction};
+    use std::collections::BTreeMap;
+
+    use crate::pii::{
+        decrypt_tokens, EncryptionError, PiiConfig, PiiConfigError, ReplaceRedaction,
+    };
     use crate::processor::process_value;
     use crate::protocol::{
         Addr, DebugImage, DebugMeta, Event, ExtraValue, Headers, LogEntry, NativeDebugImage,
@@ -932,6 +1088,397 @@ mod tests {
         assert_eq!(user.id.value().unwrap().as_str(), "123");
     }
 
+    #[test]
+    fn test_audit_rules() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "applications": {
+                    "$string": ["@email:replace", "@ip:replace"]
+                },
+                "auditRules": ["@email:replace"]
+            }
+            "##,
+        )
+        .unwrap();
+
+        let mut event = Annotated::new(Event {
+            extra: {
+                let mut map = Object::new();
+                map.insert(
+                    "myvalue".to_string(),
+                    Annotated::new(ExtraValue(Value::String(
+                        "jane@example.com from 127.0.0.1".to_string(),
+                    ))),
+                );
+                Annotated::new(map)
+            },
+            ..Default::default()
+        });
+
+        let mut processor = PiiProcessor::new(config.compiled());
+        process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+        let extra = event.value().unwrap().extra.value().unwrap();
+        let value = extra.get("myvalue").unwrap();
+        assert_eq!(
+            value.value(),
+            Some(&ExtraValue(Value::String(
+                "jane@example.com from [ip]".to_string()
+            )))
+        );
+        assert!(value
+            .meta()
+            .iter_remarks()
+            .any(|remark| remark.ty() == RemarkType::Annotated
+                && remark.rule_id() == "@email:replace"));
+
+        assert_eq!(
+            processor.audit_matches(),
+            &[PiiAuditMatch {
+                rule_id: "@email:replace".to_owned(),
+                path: "extra.myvalue".to_owned(),
+                value_type: vec!["string".to_owned()],
+                remark: RemarkType::Substituted,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_scrub_embedded_data() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "applications": {
+                    "$string": ["@password:remove"],
+                    "extra.url.token": ["@anything:remove"]
+                },
+                "scrubEmbeddedData": true
+            }
+            "##,
+        )
+        .unwrap();
+
+        let mut event = Annotated::new(Event {
+            extra: {
+                let mut map = Object::new();
+                map.insert(
+                    "body".to_string(),
+                    Annotated::new(ExtraValue(Value::String(
+                        r#"{"user": "jane", "password": "hunter2"}"#.to_string(),
+                    ))),
+                );
+                map.insert(
+                    "url".to_string(),
+                    Annotated::new(ExtraValue(Value::String(
+                        "https://example.com/?token=abc&page=2".to_string(),
+                    ))),
+                );
+                Annotated::new(map)
+            },
+            ..Default::default()
+        });
+
+        let mut processor = PiiProcessor::new(config.compiled());
+        process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+        let extra = event.value().unwrap().extra.value().unwrap();
+        let body = extra.get("body").unwrap();
+        assert_eq!(
+            body.value(),
+            Some(&ExtraValue(Value::String(
+                r#"{"password":null,"user":"jane"}"#.to_string()
+            )))
+        );
+        assert!(body
+            .meta()
+            .iter_remarks()
+            .any(|remark| remark.ty() == RemarkType::Removed
+                && remark.rule_id() == "@password:remove"));
+
+        let url = extra.get("url").unwrap();
+        assert_eq!(
+            url.value(),
+            Some(&ExtraValue(Value::String(
+                "https://example.com/?token=&page=2".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_remove_embedded_data() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "applications": {
+                    "extra.body": ["@anything:remove"]
+                },
+                "scrubEmbeddedData": true
+            }
+            "##,
+        )
+        .unwrap();
+
+        let mut event = Annotated::new(Event {
+            extra: {
+                let mut map = Object::new();
+                map.insert(
+                    "body".to_string(),
+                    Annotated::new(ExtraValue(Value::String(r#"{"user": "jane"}"#.to_string()))),
+                );
+                Annotated::new(map)
+            },
+            ..Default::default()
+        });
+
+        let mut processor = PiiProcessor::new(config.compiled());
+        process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+        let extra = event.value().unwrap().extra.value().unwrap();
+        assert_eq!(extra.get("body").and_then(Annotated::value), None);
+    }
+
+    #[test]
+    fn test_audit_embedded_data() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "applications": {
+                    "extra.body.email": ["@email:replace"]
+                },
+                "auditRules": ["@email:replace"],
+                "scrubEmbeddedData": true
+            }
+            "##,
+        )
+        .unwrap();
+
+        let body = r#"{"user": "jane", "email": "jane@example.com"}"#;
+        let compiled = config.compiled();
+
+        for mut processor in [PiiProcessor::new(compiled), PiiProcessor::dry_run(compiled)] {
+            let mut event = Annotated::new(Event {
+                extra: {
+                    let mut map = Object::new();
+                    map.insert(
+                        "body".to_string(),
+                        Annotated::new(ExtraValue(Value::String(body.to_string()))),
+                    );
+                    Annotated::new(map)
+                },
+                ..Default::default()
+            });
+
+            process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+            let extra = event.value().unwrap().extra.value().unwrap();
+            let value = extra.get("body").unwrap();
+            assert_eq!(
+                value.value(),
+                Some(&ExtraValue(Value::String(body.to_string())))
+            );
+            assert!(value
+                .meta()
+                .iter_remarks()
+                .all(|remark| remark.ty() == RemarkType::Annotated
+                    && remark.rule_id() == "@email:replace"));
+            assert!(value.meta().iter_remarks().next().is_some());
+
+            assert_eq!(
+                processor.audit_matches(),
+                &[PiiAuditMatch {
+                    rule_id: "@email:replace".to_owned(),
+                    path: "extra.body.email".to_owned(),
+                    value_type: vec!["string".to_owned()],
+                    remark: RemarkType::Substituted,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_scrub_embedded_data_disabled() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "applications": {
+                    "extra.body.password": ["@anything:remove"]
+                }
+            }
+            "##,
+        )
+        .unwrap();
+
+        let body = r#"{"user": "jane", "password": "hunter2"}"#;
+        let mut event = Annotated::new(Event {
+            extra: {
+                let mut map = Object::new();
+                map.insert(
+                    "body".to_string(),
+                    Annotated::new(ExtraValue(Value::String(body.to_string()))),
+                );
+                Annotated::new(map)
+            },
+            ..Default::default()
+        });
+
+        let mut processor = PiiProcessor::new(config.compiled());
+        process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+        let extra = event.value().unwrap().extra.value().unwrap();
+        assert_eq!(
+            extra.get("body").and_then(Annotated::value),
+            Some(&ExtraValue(Value::String(body.to_string())))
+        );
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "applications": {
+                    "$object": ["@anything:remove"]
+                }
+            }
+            "##,
+        )
+        .unwrap();
+
+        let mut event = Annotated::new(Event {
+            extra: {
+                let mut map = Object::new();
+                map.insert(
+                    "myvalue".to_string(),
+                    Annotated::new(ExtraValue(Value::String("foobar".to_string()))),
+                );
+                Annotated::new(map)
+            },
+            ..Default::default()
+        });
+        let original = event.clone();
+
+        let mut processor = PiiProcessor::dry_run(config.compiled());
+        process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+        assert_eq!(
+            event.value().unwrap().extra.value(),
+            original.value().unwrap().extra.value()
+        );
+        assert_eq!(
+            processor.audit_matches(),
+            &[PiiAuditMatch {
+                rule_id: "@anything:remove".to_owned(),
+                path: "extra".to_owned(),
+                value_type: vec!["object".to_owned()],
+                remark: RemarkType::Removed,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_encrypt_user_fields() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "rules": {
+                    "encrypt_user": {
+                        "type": "anything",
+                        "redaction": {"method": "encrypt", "keyId": "2023"}
+                    }
+                },
+                "vars": {
+                    "hashKey": "correlation secret",
+                    "encryptionKeys": {
+                        "2023": "8f40c5adb68f25624ae5b214ea767a6ec94d829d3d7b5e1ad1ba6f3e2138285f"
+                    }
+                },
+                "applications": {
+                    "$user.id || $user.email": ["encrypt_user"]
+                }
+            }
+            "##,
+        )
+        .unwrap();
+
+        let mut event = Annotated::new(Event {
+            user: Annotated::new(User {
+                id: Annotated::new("42".to_string().into()),
+                email: Annotated::new("jane@example.com".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let mut processor = PiiProcessor::new(config.compiled());
+        process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+        let user = event.value().unwrap().user.value().unwrap();
+        let id = user.id.value().unwrap().as_str();
+        let email = user.email.as_str().unwrap();
+        assert!(id.starts_with("enc:2023:"));
+        assert!(email.starts_with("enc:2023:"));
+
+        // Decryption requires the secret key, which is not part of the config.
+        let mut keys = BTreeMap::new();
+        keys.insert(
+            "2023".to_owned(),
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f".to_owned(),
+        );
+        assert_eq!(decrypt_tokens(id, &keys).unwrap(), "42");
+        assert_eq!(decrypt_tokens(email, &keys).unwrap(), "jane@example.com");
+    }
+
+    #[test]
+    fn test_encrypt_missing_key() {
+        let config = PiiConfig::from_json(
+            r##"
+            {
+                "rules": {
+                    "encrypt_email": {
+                        "type": "email",
+                        "redaction": {"method": "encrypt", "keyId": "missing"}
+                    }
+                },
+                "applications": {
+                    "$string": ["encrypt_email"]
+                }
+            }
+            "##,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            config.validate(),
+            Err(PiiConfigError::EncryptionError(
+                EncryptionError::UnknownKey(_)
+            ))
+        ));
+
+        let mut event = Annotated::new(Event {
+            extra: {
+                let mut map = Object::new();
+                map.insert(
+                    "myvalue".to_string(),
+                    Annotated::new(ExtraValue(Value::String(
+                        "mail jane@example.com".to_string(),
+                    ))),
+                );
+                Annotated::new(map)
+            },
+            ..Default::default()
+        });
+
+        let mut processor = PiiProcessor::new(config.compiled());
+        process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+        let extra = event.value().unwrap().extra.value().unwrap();
+        assert_eq!(
+            extra.get("myvalue").and_then(Annotated::value),
+            Some(&ExtraValue(Value::String("mail ".to_string())))
+        );
+    }
+
     #[test]
     fn test_replace_replaced_text() {
         let chunks = vec![Chunk::Redaction {
@@ -946,6 +1493,8 @@ mod tests {
             redaction: Redaction::Replace(ReplaceRedaction {
                 text: "[ip]".into(),
             }),
+            encryption_key: None,
+            audit: false,
         };
         let res = apply_regex_to_chunks(
             chunks.clone(),
@@ -28,6 +28,14 @@ impl Default for ReplaceRedaction {
     }
 }
 
+/// Replaces a value with a reversible ciphertext token.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptRedaction {
+    /// The ID of the public key in `vars.encryptionKeys` that encrypts the value.
+    pub key_id: String,
+}
+
 /// Defines how replacements happen.
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 #[serde(tag = "method", rename_all = "snake_case")]
@@ -46,6 +54,12 @@ pub enum Redaction {
     Mask,
     /// Replaces the value with a hash
     Hash,
+    /// Replaces the value with a token that can only be decrypted offline with the secret key.
+    ///
+    /// The ciphertext is randomized. Only the token's tag is deterministic: an HMAC of the value
+    /// keyed with `vars.hashKey`, which correlates equal values. If `vars.hashKey` is not set, or
+    /// the key is missing from `vars.encryptionKeys` or invalid, the value is removed instead.
+    Encrypt(EncryptRedaction),
 }
 
 impl Default for Redaction {
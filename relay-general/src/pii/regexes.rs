@@ -1,4 +1,7 @@
+use std::ops::Range;
+
 use once_cell::sync::Lazy;
+use regex::bytes::Regex as BytesRegex;
 use regex::Regex;
 use smallvec::{smallvec, SmallVec};
 
@@ -87,12 +90,155 @@ pub fn get_regex_for_rule_type(
         RuleType::UrlAuth => smallvec![(v, &*URL_AUTH_REGEX, ReplaceBehavior::replace_group(1))],
         RuleType::UsSsn => smallvec![(v, &*US_SSN_REGEX, ReplaceBehavior::replace_match())],
         RuleType::Userpath => smallvec![(v, &*PATH_REGEX, ReplaceBehavior::replace_group(1))],
+        RuleType::Iban => smallvec![(v, &*IBAN_REGEX, ReplaceBehavior::replace_match())],
+        RuleType::Phone => smallvec![(v, &*PHONE_REGEX, ReplaceBehavior::replace_match())],
+        RuleType::Jwt => smallvec![(v, &*JWT_REGEX, ReplaceBehavior::replace_match())],
+        RuleType::AwsKey => smallvec![
+            (
+                v,
+                &*AWS_ACCESS_KEY_ID_REGEX,
+                ReplaceBehavior::replace_match()
+            ),
+            (v, &*AWS_SECRET_KEY_REGEX, ReplaceBehavior::replace_group(1)),
+        ],
+        RuleType::GithubToken => {
+            smallvec![(v, &*GITHUB_TOKEN_REGEX, ReplaceBehavior::replace_match())]
+        }
+        RuleType::SlackToken => {
+            smallvec![(v, &*SLACK_TOKEN_REGEX, ReplaceBehavior::replace_match())]
+        }
 
         // These ought to have been resolved in CompiledConfig
         RuleType::Alias(_) | RuleType::Multiple(_) => smallvec![],
     }
 }
 
+/// Returns whether a regex match for the given rule type passes its checksum or sanity check.
+///
+/// Rule types without a check accept every match.
+pub fn is_valid_match(ty: &RuleType, matched: &str) -> bool {
+    match ty {
+        RuleType::Iban => is_valid_iban(matched),
+        RuleType::Phone => is_valid_phone(matched),
+        _ => true,
+    }
+}
+
+/// The byte ranges of all groups of a regex match, indexed by group.
+///
+/// Groups that did not participate in the match are `None`.
+pub type MatchGroups = SmallVec<[Option<Range<usize>>; 4]>;
+
+/// Returns the groups of all matches in the text that pass [`is_valid_match`].
+///
+/// The IBAN pattern can run into a token that follows the IBAN, such as the currency in
+/// `BE68 5390 0754 7034 EUR`, which then fails the checksum. Such matches are retried with trailing
+/// space-separated groups removed. Retries only search the failed match, not the text before it.
+pub fn valid_match_groups<'a>(
+    ty: &'a RuleType,
+    regex: &'a Regex,
+    text: &'a str,
+) -> impl Iterator<Item = MatchGroups> + 'a {
+    let mut locations = regex.capture_locations();
+
+    regex.captures_iter(text).filter_map(move |captures| {
+        let matched = captures.get(0)?;
+        if is_valid_match(ty, matched.as_str()) {
+            let groups = captures.iter().map(|g| g.map(|m| m.range()));
+            return Some(groups.collect());
+        }
+
+        if !matches!(ty, RuleType::Iban) {
+            return None;
+        }
+
+        for (offset, _) in matched.as_str().rmatch_indices(' ') {
+            let end = matched.start() + offset;
+            let retry = regex.captures_read_at(&mut locations, &text[..end], matched.start());
+            if let Some(retry) = retry {
+                if retry.start() == matched.start() && is_valid_match(ty, retry.as_str()) {
+                    let groups = (0..locations.len()).map(|i| locations.get(i).map(|(s, e)| s..e));
+                    return Some(groups.collect());
+                }
+            }
+        }
+
+        None
+    })
+}
+
+/// Returns the groups of all matches in binary data that pass [`is_valid_match`].
+///
+/// See [`valid_match_groups`] for how IBAN matches are retried.
+pub fn valid_bytes_match_groups<'a>(
+    ty: &'a RuleType,
+    regex: &'a BytesRegex,
+    data: &'a [u8],
+) -> impl Iterator<Item = MatchGroups> + 'a {
+    let is_valid = move |bytes: &[u8]| is_valid_match(ty, &String::from_utf8_lossy(bytes));
+    let mut locations = regex.capture_locations();
+
+    regex.captures_iter(data).filter_map(move |captures| {
+        let matched = captures.get(0)?;
+        if is_valid(matched.as_bytes()) {
+            let groups = captures.iter().map(|g| g.map(|m| m.range()));
+            return Some(groups.collect());
+        }
+
+        if !matches!(ty, RuleType::Iban) {
+            return None;
+        }
+
+        let bytes = matched.as_bytes();
+        for offset in (0..bytes.len())
+            .rev()
+            .filter(|&offset| bytes[offset] == b' ')
+        {
+            let end = matched.start() + offset;
+            let retry = regex.captures_read_at(&mut locations, &data[..end], matched.start());
+            if let Some(retry) = retry {
+                if retry.start() == matched.start() && is_valid(retry.as_bytes()) {
+                    let groups = (0..locations.len()).map(|i| locations.get(i).map(|(s, e)| s..e));
+                    return Some(groups.collect());
+                }
+            }
+        }
+
+        None
+    })
+}
+
+/// Validates the ISO 7064 mod-97 checksum of an IBAN, ignoring spaces.
+fn is_valid_iban(iban: &str) -> bool {
+    let iban: Vec<char> = iban.chars().filter(|c| *c != ' ').collect();
+    if !(15..=34).contains(&iban.len()) {
+        return false;
+    }
+
+    // Move the country code and check digits to the end, then interpret letters as 10 to 35.
+    let mut remainder = 0;
+    for c in iban[4..].iter().chain(&iban[..4]) {
+        let value = match c.to_digit(36) {
+            Some(value) => value,
+            None => return false,
+        };
+
+        remainder = match value {
+            0..=9 => (remainder * 10 + value) % 97,
+            _ => (remainder * 100 + value) % 97,
+        };
+    }
+
+    remainder == 1
+}
+
+/// Checks that a phone number has the length of an E.164 number.
+fn is_valid_phone(phone: &str) -> bool {
+    let digits = phone.chars().filter(char::is_ascii_digit).count();
+    let prefix = if phone.starts_with("00") { 2 } else { 0 };
+    (7..=15).contains(&(digits - prefix))
+}
+
 #[rustfmt::skip]
 macro_rules! ip {
     (v4s) => { "(25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)" };
@@ -272,3 +418,82 @@ static PASSWORD_KEY_REGEX: Lazy<Regex> = Lazy::new(|| {
         r"(?i)(password|secret|passwd|api_key|apikey|auth|credentials|mysql_pwd|privatekey|private_key|token)"
     ).unwrap()
 });
+
+static IBAN_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+            \b
+            [A-Z]{2}\d{2}                # country code and check digits
+            (?:\ ?[A-Z0-9]{4}){2,7}      # account number, optionally grouped by four
+            (?:\ ?[A-Z0-9]{1,3})?
+            \b
+        "#,
+    )
+    .unwrap()
+});
+
+static PHONE_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+            (?:\+|\b00)[1-9]\d{0,2}      # international prefix and country code
+            (?:[\ .-]?\(\d{1,4}\))?      # optional area code in parentheses
+            (?:[\ .-]?\d{2,5}){1,5}
+            \b
+        "#,
+    )
+    .unwrap()
+});
+
+static JWT_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+            \beyJ[A-Za-z0-9_-]{2,}       # header, base64 of `{"`
+            \.eyJ[A-Za-z0-9_-]{2,}       # payload
+            \.[A-Za-z0-9_-]*             # signature, empty for unsecured tokens
+        "#,
+    )
+    .unwrap()
+});
+
+static AWS_ACCESS_KEY_ID_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+            \b(?:AKIA|ASIA|ABIA|ACCA)[A-Z0-9]{16}\b
+        "#,
+    )
+    .unwrap()
+});
+
+// Secret access keys have no distinct prefix, so they are only matched next to their usual key.
+static AWS_SECRET_KEY_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?ix)
+            \baws_?secret_?(?:access_?)?key["']?
+            \s*[:=]\s*["']?
+            ([A-Za-z0-9/+]{40})
+            (?:[^A-Za-z0-9/+=]|$)
+        "#,
+    )
+    .unwrap()
+});
+
+static GITHUB_TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+            \b(?:
+                gh[pousr]_[A-Za-z0-9]{36,251}     # classic, oauth, user, server and refresh tokens
+                | github_pat_[A-Za-z0-9_]{82}     # fine-grained personal access tokens
+            )\b
+        "#,
+    )
+    .unwrap()
+});
+
+static SLACK_TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+            \b(?:xox[abposr]|xapp)-[0-9]+-[A-Za-z0-9-]{10,}
+        "#,
+    )
+    .unwrap()
+});
@@ -10,13 +10,13 @@ pub struct OtelContext {
     ///
     /// <https://github.com/open-telemetry/opentelemetry-proto/blob/724e427879e3d2bae2edc0218fff06e37b9eb46e/opentelemetry/proto/trace/v1/trace.proto#L174-L186>
     #[metastructure(pii = "maybe", bag_size = "large")]
-    attributes: Annotated<Object<Value>>,
+    pub attributes: Annotated<Object<Value>>,
 
     /// Information about an OpenTelemetry resource.
     ///
     /// <https://github.com/open-telemetry/opentelemetry-proto/blob/724e427879e3d2bae2edc0218fff06e37b9eb46e/opentelemetry/proto/resource/v1/resource.proto>
     #[metastructure(pii = "maybe", bag_size = "large")]
-    resource: Annotated<Object<Value>>,
+    pub resource: Annotated<Object<Value>>,
 
     /// Additional arbitrary fields for forwards compatibility.
     #[metastructure(additional_properties, retain = "true", pii = "maybe")]
@@ -15,14 +15,18 @@ mod event_error;
 mod geo;
 mod legacy;
 mod normalize;
+mod proguard;
 mod regexes;
 mod remove_other;
 mod schema;
+mod sourcemaps;
 mod transactions;
 mod trimming;
 
 pub use self::clock_drift::*;
 pub use self::geo::*;
+pub use self::proguard::*;
+pub use self::sourcemaps::*;
 pub use normalize::breakdowns::*;
 pub use normalize::*;
 pub use transactions::*;
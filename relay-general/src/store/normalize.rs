@@ -36,6 +36,8 @@ mod stacktrace;
 
 mod user_agent;
 
+pub use spans::{normalize_sql_query, SPAN_DESCRIPTION_NORMALIZED};
+
 /// Defines a builtin measurement.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -665,6 +667,7 @@ pub struct LightNormalizationConfig<'a> {
     pub normalize_user_agent: Option<bool>,
     pub normalize_transaction_name: bool,
     pub tx_name_rules: &'a [TransactionNameRule],
+    pub replace_span_descriptions: bool,
     pub is_renormalize: bool,
 }
 
@@ -730,6 +733,7 @@ pub fn light_normalize_event(
         normalize_user_agent(event, config.normalize_user_agent); // Legacy browsers filter
         normalize_measurements(event, config.measurements_config); // Measurements are part of the metric extraction
         normalize_breakdowns(event, config.breakdowns_config); // Breakdowns are part of the metric extraction too
+        spans::normalize_span_descriptions(event, config.replace_span_descriptions); // Span metrics are tagged with the normalized description
 
         Ok(())
     })
@@ -1,9 +1,138 @@
+use std::borrow::Cow;
 use std::collections::{BTreeSet, HashMap};
 use std::time::Duration;
 
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
 use crate::protocol::{Context, ContextInner, Contexts, Event, Span, SpanId};
 use crate::store::normalize::breakdowns::TimeWindowSpan;
-use crate::types::{Annotated, SpanAttribute};
+use crate::types::{Annotated, SpanAttribute, Value};
+
+/// Key in the span data under which the normalized description of a span is stored.
+///
+/// The normalized description does not contain literal values, so it is stable across executions
+/// of the same query and can be used to group spans.
+pub const SPAN_DESCRIPTION_NORMALIZED: &str = "description.normalized";
+
+/// Database systems that use SQL as query language.
+const SQL_SYSTEMS: &[&str] = &[
+    "mariadb",
+    "mssql",
+    "mysql",
+    "oracle",
+    "postgresql",
+    "sqlite",
+];
+
+/// Tokenizes the parts of a SQL query that are removed or replaced during normalization.
+///
+/// The alternatives are matched in a single pass, so that comment markers inside of string literals
+/// and quotes inside of comments are handled correctly.
+static SQL_TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+        (?P<comment> --[^\n]* | /\*(?s:.*?)\*/ )
+        | (?P<string> '(?:[^'\\]|\\.|'')*' )
+        | (?P<identifier> "(?:[^"]|"")*" | `[^`]*` )
+        | (?P<cast> :: )
+        | (?P<placeholder> \? | \$\d+ | :[A-Za-z_]\w* | %\(\w+\)s | %s )
+        | (?P<number> \b(?:0[xX][0-9a-fA-F]+|\d+(?:\.\d+)?(?:[eE][+-]?\d+)?)\b )
+        | (?P<boolean> (?i:\btrue\b|\bfalse\b) )
+        "#,
+    )
+    .unwrap()
+});
+
+/// Matches parenthesized lists of placeholders, such as in `IN (%s, %s, %s)`.
+static SQL_LIST_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\(\s*%s(?:\s*,\s*%s)*\s*\)").unwrap());
+
+/// Matches repeated rows of placeholders, such as in `VALUES (%s), (%s)`.
+static SQL_ROWS_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\(%s\)(?:\s*,\s*\(%s\))+").unwrap());
+
+/// Matches sequences of whitespace.
+static WHITESPACE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
+
+/// Normalizes a SQL query into a stable form for grouping.
+///
+/// This strips comments, replaces string, numeric and boolean literals as well as bind parameters
+/// with `%s`, collapses lists of values such as in `IN (...)` and `VALUES (...), (...)` into a
+/// single `(%s)`, and collapses whitespace. Quoted identifiers are preserved.
+pub fn normalize_sql_query(query: &str) -> String {
+    let query = SQL_TOKEN_REGEX.replace_all(query, |captures: &Captures| {
+        if captures.name("comment").is_some() {
+            Cow::Borrowed(" ")
+        } else if captures.name("identifier").is_some() || captures.name("cast").is_some() {
+            Cow::Owned(captures[0].to_owned())
+        } else {
+            Cow::Borrowed("%s")
+        }
+    });
+
+    let query = SQL_LIST_REGEX.replace_all(&query, "(%s)");
+    let query = SQL_ROWS_REGEX.replace_all(&query, "(%s)");
+    let query = WHITESPACE_REGEX.replace_all(query.trim(), " ");
+    query
+        .trim_end_matches(|c: char| c == ';' || c.is_whitespace())
+        .to_owned()
+}
+
+/// Returns `true` if the span describes a SQL query.
+fn is_sql_span(span: &Span) -> bool {
+    let op = span.op.as_str().unwrap_or_default();
+    if op == "db" || op.starts_with("db.sql") {
+        return true;
+    }
+
+    if !op.starts_with("db.") {
+        return false;
+    }
+
+    let system = span
+        .data
+        .value()
+        .and_then(|data| data.get("db.system"))
+        .and_then(Annotated::value);
+
+    matches!(system, Some(Value::String(system)) if SQL_SYSTEMS.contains(&system.as_str()))
+}
+
+/// Normalizes the descriptions of database spans.
+///
+/// The normalized description is written to the span data under [`SPAN_DESCRIPTION_NORMALIZED`],
+/// overwriting any value sent by the client. If `replace_description` is set, the description
+/// itself is replaced with the normalized form, which removes all literal values from it. The raw
+/// description is not retained in this case.
+pub fn normalize_span_descriptions(event: &mut Event, replace_description: bool) {
+    let spans = match event.spans.value_mut() {
+        Some(spans) => spans,
+        None => return,
+    };
+
+    for span in spans
+        .iter_mut()
+        .filter_map(|span| span.value_mut().as_mut())
+    {
+        if !is_sql_span(span) {
+            continue;
+        }
+
+        let normalized = match span.description.as_str() {
+            Some(description) => normalize_sql_query(description),
+            None => continue,
+        };
+
+        span.data.get_or_insert_with(Default::default).insert(
+            SPAN_DESCRIPTION_NORMALIZED.to_owned(),
+            Annotated::new(Value::String(normalized.clone())),
+        );
+
+        if replace_description {
+            span.description.set_value(Some(normalized));
+        }
+    }
+}
 
 /// Computes the exclusive time of the source interval after subtracting the
 /// list of intervals.
@@ -152,6 +281,114 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_normalize_sql_query() {
+        let cases = [
+            (
+                "SELECT *  FROM users WHERE email = 'jane@example.com' AND id IN (1, 2, 3.5);",
+                "SELECT * FROM users WHERE email = %s AND id IN (%s)",
+            ),
+            (
+                "/* controller='users' */ SELECT \"t1\".\"id\" FROM t1 -- trailing\nWHERE t1.name = 'it''s'",
+                "SELECT \"t1\".\"id\" FROM t1 WHERE t1.name = %s",
+            ),
+            (
+                "SELECT col1 FROM table_2 WHERE a = :name AND b::int = $1 AND c = ? LIMIT 10",
+                "SELECT col1 FROM table_2 WHERE a = %s AND b::int = %s AND c = %s LIMIT %s",
+            ),
+            (
+                "INSERT INTO logs (a, b) VALUES (1, 'x'), (2, 'y')",
+                "INSERT INTO logs (a, b) VALUES (%s)",
+            ),
+            (
+                "UPDATE flags SET enabled = TRUE WHERE created > '2023-01-01 10:00:00'",
+                "UPDATE flags SET enabled = %s WHERE created > %s",
+            ),
+        ];
+
+        for (query, expected) in cases {
+            assert_eq!(normalize_sql_query(query), expected);
+        }
+    }
+
+    #[test]
+    fn test_normalize_span_descriptions() {
+        let json = r#"{
+            "type": "transaction",
+            "spans": [
+                {
+                    "op": "db.sql.query",
+                    "description": "SELECT * FROM users WHERE email = 'jane@example.com'"
+                },
+                {
+                    "op": "db.redis",
+                    "description": "GET user:42",
+                    "data": {"db.system": "redis"}
+                },
+                {
+                    "op": "db.query",
+                    "description": "SELECT 1",
+                    "data": {"db.system": "postgresql"}
+                }
+            ]
+        }"#;
+
+        let mut event = Annotated::<Event>::from_json(json).unwrap().0.unwrap();
+        normalize_span_descriptions(&mut event, false);
+
+        let spans = event.spans.value().unwrap();
+        let normalized = spans
+            .iter()
+            .map(|span| {
+                let span = span.value().unwrap();
+                let data = span.data.value()?;
+                match data.get(SPAN_DESCRIPTION_NORMALIZED)?.value()? {
+                    Value::String(normalized) => Some(normalized.as_str()),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            normalized,
+            vec![
+                Some("SELECT * FROM users WHERE email = %s"),
+                None,
+                Some("SELECT %s")
+            ]
+        );
+
+        // The raw description is kept unless replacement is requested.
+        let span = spans[0].value().unwrap();
+        assert_eq!(
+            span.description.as_str(),
+            Some("SELECT * FROM users WHERE email = 'jane@example.com'")
+        );
+    }
+
+    #[test]
+    fn test_replace_span_descriptions() {
+        let json = r#"{
+            "type": "transaction",
+            "spans": [
+                {
+                    "op": "db",
+                    "description": "SELECT * FROM users WHERE id IN (1, 2, 3)"
+                }
+            ]
+        }"#;
+
+        let mut event = Annotated::<Event>::from_json(json).unwrap().0.unwrap();
+        normalize_span_descriptions(&mut event, true);
+
+        let span = event.spans.value().unwrap()[0].value().unwrap();
+        assert_eq!(
+            span.description.as_str(),
+            Some("SELECT * FROM users WHERE id IN (%s)")
+        );
+        assert!(span.description.meta().original_value().is_none());
+    }
+
     fn make_event(
         start: Timestamp,
         end: Timestamp,
@@ -0,0 +1,476 @@
+//! Deobfuscation of Java and Android stack traces with ProGuard mapping files from a local
+//! directory.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use lru::LruCache;
+use proguard::{ProguardMapper, ProguardMapping, StackFrame};
+use symbolic_common::{AsSelf, SelfCell};
+use uuid::Uuid;
+
+use crate::processor::{process_value, ProcessValue, ProcessingState, Processor};
+use crate::protocol::{DebugImage, Event, EventProcessingError, Exception, Frame, RawStacktrace};
+use crate::types::{Annotated, IntoValue, Meta, Object, ProcessingResult, Value};
+
+/// Error type written to the event when no mapping file exists for a ProGuard debug image.
+const PROGUARD_MISSING_MAPPING: &str = "proguard_missing_mapping";
+
+/// Maximum number of parsed mapping files kept in memory by a [`ProguardLookup`].
+const CACHE_SIZE: usize = 20;
+
+/// Looks up ProGuard and R8 mapping files in a local directory.
+///
+/// Mapping files are named after the UUID of the ProGuard debug image with a `.txt` extension, for
+/// instance `<root>/3c6d4e5f-2b1a-4c8d-9e0f-1a2b3c4d5e6f.txt`. Since mapping files never change
+/// for a UUID, parsed mappers are cached by UUID.
+pub struct ProguardLookup {
+    root: PathBuf,
+    cache: Mutex<LruCache<Uuid, Arc<ProguardMappingFile>>>,
+}
+
+impl ProguardLookup {
+    /// Creates a lookup for the given mapping directory.
+    pub fn new<P>(root: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            root: root.into(),
+            cache: Mutex::new(LruCache::new(CACHE_SIZE)),
+        }
+    }
+
+    /// Loads and parses the mapping file with the given UUID.
+    ///
+    /// Returns `Ok(None)` if there is no mapping file with this UUID.
+    pub fn load(&self, uuid: Uuid) -> io::Result<Option<Arc<ProguardMappingFile>>> {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&uuid)
+            .cloned();
+
+        if let Some(file) = cached {
+            return Ok(Some(file));
+        }
+
+        let path = self.root.join(format!("{}.txt", uuid.to_hyphenated_ref()));
+        let file = match fs::read(path) {
+            Ok(bytes) => Arc::new(ProguardMappingFile::parse(bytes)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .put(uuid, file.clone());
+
+        Ok(Some(file))
+    }
+}
+
+impl std::fmt::Debug for ProguardLookup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProguardLookup")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+/// A parsed mapper that borrows from the contents of its mapping file.
+struct Mapper<'a>(Option<ProguardMapper<'a>>);
+
+impl<'slf, 'a: 'slf> AsSelf<'slf> for Mapper<'a> {
+    type Ref = Mapper<'slf>;
+
+    fn as_self(&'slf self) -> &Self::Ref {
+        self
+    }
+}
+
+/// A ProGuard mapping file loaded by [`ProguardLookup`], along with its parsed mapper.
+pub struct ProguardMappingFile(SelfCell<Vec<u8>, Mapper<'static>>);
+
+impl ProguardMappingFile {
+    /// Parses the contents of a mapping file.
+    fn parse(bytes: Vec<u8>) -> Self {
+        Self(SelfCell::new(bytes, |data| {
+            // SAFETY: `SelfCell` keeps the bytes at a stable address for the lifetime of the
+            // mapper.
+            let mapping = ProguardMapping::new(unsafe { &*data });
+            Mapper(mapping.is_valid().then(|| ProguardMapper::new(mapping)))
+        }))
+    }
+
+    /// Returns the mapper of this file.
+    ///
+    /// Returns `None` if the file is not a valid mapping file.
+    pub fn mapper(&self) -> Option<&ProguardMapper<'_>> {
+        self.0.get().0.as_ref()
+    }
+}
+
+impl std::fmt::Debug for ProguardMappingFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProguardMappingFile")
+            .field("valid", &self.mapper().is_some())
+            .finish()
+    }
+}
+
+/// Replaces the value and preserves the previous one as original value in the meta.
+fn replace<T>(annotated: &mut Annotated<T>, value: T)
+where
+    T: IntoValue + PartialEq,
+{
+    if annotated.value() != Some(&value) {
+        let original = annotated.0.replace(value);
+        annotated.1.set_original_value(original);
+    }
+}
+
+/// Returns `true` if the frame or exception belongs to the JVM and can be deobfuscated.
+fn is_java(platform: Option<&str>) -> bool {
+    matches!(platform, None | Some("java"))
+}
+
+/// Deobfuscates a single frame, which may expand to multiple frames for inlined methods.
+///
+/// The returned frames are ordered from caller to callee, as in Sentry stack traces.
+fn remap_frame(mappers: &[&ProguardMapper<'_>], frame: &Frame) -> Option<Vec<Frame>> {
+    if !is_java(frame.platform.as_str()) {
+        return None;
+    }
+
+    let class = frame.module.as_str()?;
+    let method = frame.function.as_str()?;
+    let line = frame.lineno.value().copied().unwrap_or(0) as usize;
+    let obfuscated = StackFrame::new(class, method, line);
+
+    for mapper in mappers {
+        let mut remapped = mapper.remap_frame(&obfuscated).collect::<Vec<_>>();
+        if remapped.is_empty() {
+            continue;
+        }
+
+        remapped.reverse();
+        let frames = remapped
+            .iter()
+            .map(|remapped| {
+                let mut frame = frame.clone();
+                replace(&mut frame.module, remapped.class().to_owned());
+                replace(&mut frame.function, remapped.method().to_owned());
+                if remapped.line() > 0 {
+                    replace(&mut frame.lineno, remapped.line() as u64);
+                }
+                if let Some(file) = remapped.file() {
+                    replace(&mut frame.filename, file.into());
+                }
+                frame
+            })
+            .collect();
+
+        return Some(frames);
+    }
+
+    // Fall back to the class name if the method could not be resolved.
+    let class = mappers
+        .iter()
+        .find_map(|mapper| mapper.remap_class(class))?;
+    let mut frame = frame.clone();
+    replace(&mut frame.module, class.to_owned());
+    Some(vec![frame])
+}
+
+/// Deobfuscates stack frames and exception types of an event.
+struct ProguardProcessor<'a> {
+    mappers: &'a [&'a ProguardMapper<'a>],
+}
+
+impl Processor for ProguardProcessor<'_> {
+    fn process_exception(
+        &mut self,
+        exception: &mut Exception,
+        _meta: &mut Meta,
+        state: &ProcessingState<'_>,
+    ) -> ProcessingResult {
+        exception.process_child_values(self, state)?;
+
+        let class = match (exception.module.as_str(), exception.ty.as_str()) {
+            (Some(module), Some(ty)) => format!("{}.{}", module, ty),
+            (None, Some(ty)) => ty.to_owned(),
+            _ => return Ok(()),
+        };
+
+        let remapped = self
+            .mappers
+            .iter()
+            .find_map(|mapper| mapper.remap_class(&class));
+
+        if let Some(remapped) = remapped {
+            match remapped.rsplit_once('.') {
+                Some((module, ty)) => {
+                    replace(&mut exception.module, module.to_owned());
+                    replace(&mut exception.ty, ty.to_owned());
+                }
+                None => replace(&mut exception.ty, remapped.to_owned()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_raw_stacktrace(
+        &mut self,
+        stacktrace: &mut RawStacktrace,
+        _meta: &mut Meta,
+        _state: &ProcessingState<'_>,
+    ) -> ProcessingResult {
+        let frames = match stacktrace.frames.value_mut() {
+            Some(frames) => frames,
+            None => return Ok(()),
+        };
+
+        let mut deobfuscated = Vec::with_capacity(frames.len());
+        for annotated in frames.drain(..) {
+            match annotated.value().and_then(|f| remap_frame(self.mappers, f)) {
+                Some(remapped) => deobfuscated.extend(remapped.into_iter().map(Annotated::new)),
+                None => deobfuscated.push(annotated),
+            }
+        }
+
+        *frames = deobfuscated;
+        Ok(())
+    }
+}
+
+/// Returns the UUIDs of all ProGuard debug images of the event.
+fn proguard_uuids(event: &Event) -> Vec<Uuid> {
+    let images = match event
+        .debug_meta
+        .value()
+        .and_then(|meta| meta.images.value())
+    {
+        Some(images) => images,
+        None => return Vec::new(),
+    };
+
+    images
+        .iter()
+        .filter_map(|image| match image.value() {
+            Some(DebugImage::Proguard(image)) => image.uuid.value().copied(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Deobfuscates a Java or Android event with ProGuard mapping files from a local directory.
+///
+/// Mapping files are looked up by the UUIDs of the event's ProGuard debug images. This rewrites
+/// class names, method names and line numbers of stack frames and the types of exceptions. Inlined
+/// methods expand into multiple frames. The obfuscated values are preserved as original values in
+/// the meta. For debug images without a mapping file, an error is added to the event.
+pub fn deobfuscate_event(event: &mut Annotated<Event>, lookup: &ProguardLookup) {
+    let uuids = match event.value() {
+        Some(event) if is_java(event.platform.as_str()) => proguard_uuids(event),
+        _ => return,
+    };
+
+    if uuids.is_empty() {
+        return;
+    }
+
+    let mut errors = Vec::new();
+    let mut files = Vec::new();
+
+    for uuid in uuids {
+        match lookup.load(uuid) {
+            Ok(Some(file)) => files.push(file),
+            Ok(None) => {
+                let mut other = Object::new();
+                other.insert(
+                    "mapping_uuid".to_owned(),
+                    Annotated::new(Value::String(uuid.to_string())),
+                );
+
+                errors.push(EventProcessingError {
+                    ty: Annotated::new(PROGUARD_MISSING_MAPPING.to_owned()),
+                    other,
+                    ..Default::default()
+                });
+            }
+            Err(error) => {
+                relay_log::debug!("failed to read proguard mapping {}: {}", uuid, error);
+            }
+        }
+    }
+
+    let mappers = files
+        .iter()
+        .filter_map(|file| file.mapper())
+        .collect::<Vec<_>>();
+
+    if !mappers.is_empty() {
+        let mut processor = ProguardProcessor { mappers: &mappers };
+        // The processor never returns errors or removes values.
+        process_value(event, &mut processor, ProcessingState::root()).ok();
+    }
+
+    if let Some(event) = event.value_mut() {
+        if !errors.is_empty() {
+            let event_errors = event.errors.get_or_insert_with(Vec::new);
+            event_errors.extend(errors.into_iter().map(Annotated::new));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+
+    const UUID: &str = "3c6d4e5f-2b1a-4c8d-9e0f-1a2b3c4d5e6f";
+
+    const MAPPING: &str = "\
+io.sentry.sample.MainActivity -> io.sentry.sample.a:
+    void <init>() -> <init>
+    1:1:void bar():54:54 -> a
+    1:1:void foo():44 -> a
+    1:1:void onClickHandler(android.view.View):40 -> a
+io.sentry.sample.BrokenException -> io.sentry.sample.b:
+";
+
+    fn create_event() -> Annotated<Event> {
+        let json = serde_json::json!({
+            "platform": "java",
+            "debug_meta": {
+                "images": [{"type": "proguard", "uuid": UUID}]
+            },
+            "exception": {
+                "values": [{
+                    "type": "b",
+                    "module": "io.sentry.sample",
+                    "stacktrace": {
+                        "frames": [{
+                            "module": "io.sentry.sample.a",
+                            "function": "a",
+                            "lineno": 1
+                        }]
+                    }
+                }]
+            }
+        });
+
+        Annotated::from_json(&json.to_string()).unwrap()
+    }
+
+    fn get_exception(event: &Annotated<Event>) -> &Exception {
+        event
+            .value()
+            .unwrap()
+            .exceptions
+            .value()
+            .unwrap()
+            .values
+            .value()
+            .unwrap()[0]
+            .value()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_deobfuscate_event() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(format!("{}.txt", UUID)), MAPPING).unwrap();
+
+        let lookup = ProguardLookup::new(dir.path());
+        let mut event = create_event();
+        deobfuscate_event(&mut event, &lookup);
+
+        let exception = get_exception(&event);
+        assert_eq!(exception.ty.as_str(), Some("BrokenException"));
+        assert_eq!(exception.module.as_str(), Some("io.sentry.sample"));
+
+        let frames = exception
+            .stacktrace
+            .value()
+            .unwrap()
+            .frames
+            .value()
+            .unwrap()
+            .iter()
+            .map(|frame| {
+                let frame = frame.value().unwrap();
+                (
+                    frame.function.as_str().unwrap(),
+                    frame.lineno.value().copied().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Inlined methods expand into multiple frames, ordered from caller to callee.
+        assert_eq!(
+            frames,
+            vec![("onClickHandler", 40), ("foo", 44), ("bar", 54)]
+        );
+
+        let frame = exception
+            .stacktrace
+            .value()
+            .unwrap()
+            .frames
+            .value()
+            .unwrap()[0]
+            .value()
+            .unwrap();
+        assert_eq!(
+            frame.module.value().map(String::as_str),
+            Some("io.sentry.sample.MainActivity")
+        );
+        assert_eq!(
+            frame.function.meta().original_value(),
+            Some(&Value::String("a".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_missing_mapping() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let lookup = ProguardLookup::new(dir.path());
+        let mut event = create_event();
+        deobfuscate_event(&mut event, &lookup);
+
+        let exception = get_exception(&event);
+        assert_eq!(exception.ty.as_str(), Some("b"));
+
+        let errors = event.value().unwrap().errors.value().unwrap();
+        assert_eq!(errors.len(), 1);
+
+        let error = errors[0].value().unwrap();
+        assert_eq!(error.ty.as_str(), Some(PROGUARD_MISSING_MAPPING));
+    }
+
+    #[test]
+    fn test_cache_mapper() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("{}.txt", UUID));
+        fs::write(&path, MAPPING).unwrap();
+
+        let lookup = ProguardLookup::new(dir.path());
+        let uuid = UUID.parse().unwrap();
+        let file = lookup.load(uuid).unwrap().unwrap();
+        assert!(file.mapper().is_some());
+
+        // The parsed mapper is served from the cache, even if the file is gone.
+        fs::remove_file(&path).unwrap();
+        let cached = lookup.load(uuid).unwrap().unwrap();
+        assert!(Arc::ptr_eq(&file, &cached));
+    }
+}
@@ -0,0 +1,631 @@
+//! Resolution of minified JavaScript stack frames with source maps from a local directory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use lru::LruCache;
+use sourcemap::{SourceMap, SourceView};
+use url::Url;
+
+use crate::processor::{process_value, ProcessingState, Processor};
+use crate::protocol::{Event, EventProcessingError, Frame, LenientString};
+use crate::types::{Annotated, IntoValue, Meta, Object, ProcessingResult, Value};
+
+/// Number of lines of source context added before and after the resolved line.
+const CONTEXT_LINES: u32 = 5;
+
+/// Error type written to the event when no source map exists for a frame.
+const JS_NO_SOURCE: &str = "js_no_source";
+
+/// Error type written to the event when a source map cannot be read or parsed.
+const JS_INVALID_SOURCE: &str = "js_invalid_source";
+
+/// Prefixes of the comment that references the source map of a minified file.
+const SOURCE_MAPPING_URL: &[&str] = &["//# sourceMappingURL=", "//@ sourceMappingURL="];
+
+/// Maximum size in bytes of a minified file or source map that is loaded.
+const MAX_ARTIFACT_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Maximum number of parsed source maps kept in memory by a [`SourceMapLookup`].
+const CACHE_SIZE: usize = 100;
+
+/// An error when loading a source map.
+#[derive(Debug, thiserror::Error)]
+enum SourceMapError {
+    #[error("failed to read artifact")]
+    Io(#[from] io::Error),
+
+    #[error("invalid source map")]
+    Parse(#[from] sourcemap::Error),
+
+    #[error("artifact exceeds the maximum size")]
+    TooLarge,
+}
+
+/// A source map along with the minified file it applies to.
+///
+/// Cached files are shared between threads behind a [`Mutex`], see [`SharedSourceFile`].
+struct SourceFile {
+    map: SourceMap,
+    minified: Option<SourceView<'static>>,
+}
+
+// SAFETY: `SourceView` caches line offsets as raw pointers into the source string it owns, which
+// makes it neither `Send` nor `Sync`. The pointers target the heap allocation of the owned source,
+// which does not move along with the view, so the file can be sent to other threads. Shared access
+// is serialized through the `Mutex` in `SharedSourceFile`, since the line cache is not `Sync`.
+unsafe impl Send for SourceFile {}
+
+/// A parsed source file that can be shared between threads.
+type SharedSourceFile = Arc<Mutex<SourceFile>>;
+
+/// A parsed source map in the cache of a [`SourceMapLookup`].
+///
+/// The entry is valid as long as the modification times of both files are unchanged.
+#[derive(Clone)]
+struct CachedFile {
+    minified_modified: Option<SystemTime>,
+    map_path: PathBuf,
+    map_modified: SystemTime,
+    file: SharedSourceFile,
+}
+
+/// Looks up JavaScript source maps in a local directory of artifacts.
+///
+/// Artifacts are stored in one directory per release, with an optional subdirectory per dist:
+///
+/// ```text
+/// <root>/<release>/[<dist>/]<url path>
+/// ```
+///
+/// The URL path is the path of a frame's `abs_path`, for instance `static/app.min.js` for
+/// `https://example.com/static/app.min.js` or `~/static/app.min.js`. The source map is located
+/// through the `sourceMappingURL` comment of the minified file if it exists, and otherwise next to
+/// it with an additional `.map` extension.
+///
+/// Parsed source maps are cached by the path of the minified file and reloaded when either file is
+/// modified. Artifacts larger than 50 MiB are not loaded.
+pub struct SourceMapLookup {
+    root: PathBuf,
+    cache: Mutex<LruCache<PathBuf, CachedFile>>,
+}
+
+impl SourceMapLookup {
+    /// Creates a lookup for the given artifact directory.
+    pub fn new<P>(root: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            root: root.into(),
+            cache: Mutex::new(LruCache::new(CACHE_SIZE)),
+        }
+    }
+
+    /// Returns the artifact directory of a release and dist.
+    fn release_dir(&self, release: &str, dist: Option<&str>) -> Option<PathBuf> {
+        let mut dir = self.root.join(path_component(release)?);
+        if let Some(dist) = dist {
+            dir.push(path_component(dist)?);
+        }
+        Some(dir)
+    }
+
+    /// Loads the source map for a minified file.
+    ///
+    /// Returns `Ok(None)` if there is no source map for the file.
+    fn load(
+        &self,
+        release: &str,
+        dist: Option<&str>,
+        abs_path: &str,
+    ) -> Result<Option<SharedSourceFile>, SourceMapError> {
+        let (dir, path) = match (self.release_dir(release, dist), artifact_path(abs_path)) {
+            (Some(dir), Some(path)) => (dir, path),
+            _ => return Ok(None),
+        };
+
+        let minified_path = dir.join(&path);
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&minified_path)
+            .cloned();
+
+        // Reuse the cached source map if neither file has been modified since it was loaded.
+        if let Some(cached) = cached {
+            if modified(&minified_path)? == cached.minified_modified
+                && modified(&cached.map_path)? == Some(cached.map_modified)
+            {
+                return Ok(Some(cached.file));
+            }
+        }
+
+        let (minified, minified_modified) = match read_artifact(&minified_path)? {
+            Some((bytes, modified)) => (
+                Some(String::from_utf8_lossy(&bytes).into_owned()),
+                Some(modified),
+            ),
+            None => (None, None),
+        };
+
+        let map_path = minified
+            .as_deref()
+            .and_then(source_mapping_url)
+            .and_then(|reference| resolve_reference(abs_path, reference))
+            .unwrap_or_else(|| {
+                let mut map_path = path.into_os_string();
+                map_path.push(".map");
+                map_path.into()
+            });
+
+        let map_path = dir.join(map_path);
+        let (bytes, map_modified) = match read_artifact(&map_path)? {
+            Some(artifact) => artifact,
+            None => return Ok(None),
+        };
+
+        let file = Arc::new(Mutex::new(SourceFile {
+            map: SourceMap::from_slice(&bytes)?,
+            minified: minified.map(SourceView::from_string),
+        }));
+
+        let cached = CachedFile {
+            minified_modified,
+            map_path,
+            map_modified,
+            file: file.clone(),
+        };
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .put(minified_path, cached);
+
+        Ok(Some(file))
+    }
+}
+
+impl std::fmt::Debug for SourceMapLookup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceMapLookup")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+/// Validates that a release or dist can be used as a single directory name.
+fn path_component(name: &str) -> Option<&str> {
+    match name {
+        "" | "." | ".." => None,
+        _ if name.contains(&['/', '\\'][..]) => None,
+        _ => Some(name),
+    }
+}
+
+/// Returns the relative path of an artifact from the `abs_path` of a frame.
+///
+/// Returns `None` for paths that do not refer to a file served by the application, for instance
+/// `<anonymous>` or `native`.
+fn artifact_path(abs_path: &str) -> Option<PathBuf> {
+    let path = match Url::parse(abs_path) {
+        Ok(url) if matches!(url.scheme(), "http" | "https" | "app") => url.path().to_owned(),
+        Ok(_) => return None,
+        Err(_) => abs_path.strip_prefix('~')?.to_owned(),
+    };
+
+    let mut artifact_path = PathBuf::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return None,
+            _ => artifact_path.push(path_component(segment)?),
+        }
+    }
+
+    if artifact_path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(artifact_path)
+    }
+}
+
+/// Resolves a `sourceMappingURL` relative to the minified file and returns its artifact path.
+fn resolve_reference(abs_path: &str, reference: &str) -> Option<PathBuf> {
+    let base = match Url::parse(abs_path) {
+        Ok(url) => url,
+        Err(_) => Url::parse("app:///")
+            .ok()?
+            .join(abs_path.strip_prefix('~')?)
+            .ok()?,
+    };
+
+    artifact_path(base.join(reference).ok()?.as_str())
+}
+
+/// Returns the `sourceMappingURL` referenced at the end of a minified file.
+fn source_mapping_url(source: &str) -> Option<&str> {
+    source
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find_map(|line| {
+            SOURCE_MAPPING_URL
+                .iter()
+                .find_map(|prefix| line.strip_prefix(prefix))
+        })
+        .map(str::trim)
+}
+
+/// Returns the modification time of an artifact, or `Ok(None)` if it does not exist.
+fn modified(path: &Path) -> io::Result<Option<SystemTime>> {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.modified().map(Some),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Reads an artifact along with its modification time, returning `Ok(None)` if it does not exist.
+///
+/// Artifacts larger than [`MAX_ARTIFACT_SIZE`] are rejected without reading them.
+fn read_artifact(path: &Path) -> Result<Option<(Vec<u8>, SystemTime)>, SourceMapError> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+
+    if metadata.len() > MAX_ARTIFACT_SIZE {
+        return Err(SourceMapError::TooLarge);
+    }
+
+    Ok(Some((fs::read(path)?, metadata.modified()?)))
+}
+
+/// Replaces the value and preserves the previous one as original value in the meta.
+fn replace<T>(annotated: &mut Annotated<T>, value: T)
+where
+    T: IntoValue,
+{
+    let original = annotated.0.replace(value);
+    annotated.1.set_original_value(original);
+}
+
+/// Returns up to [`CONTEXT_LINES`] lines of the source in the given range.
+fn context_lines<I>(view: &SourceView<'_>, lines: I) -> Annotated<Vec<Annotated<String>>>
+where
+    I: Iterator<Item = u32>,
+{
+    let lines = lines
+        .filter_map(|line| view.get_line(line))
+        .map(|line| Annotated::new(line.to_owned()))
+        .collect();
+
+    Annotated::new(lines)
+}
+
+/// Rewrites a minified frame with the original location from the source map.
+fn resolve_frame(frame: &mut Frame, file: &SourceFile, abs_path: &str, line: u32, col: u32) {
+    let token = match file.map.lookup_token(line, col) {
+        Some(token) => token,
+        None => return,
+    };
+
+    // Resolving function names requires the minified source to find the enclosing function.
+    let function = match (&file.minified, frame.function.as_str()) {
+        (Some(view), Some(function)) => file
+            .map
+            .get_original_function_name(line, col, function, view)
+            .map(str::to_owned),
+        _ => None,
+    };
+
+    if let Some(function) = function {
+        replace(&mut frame.function, function);
+    }
+
+    if let Some(source) = token.get_source() {
+        let resolved = Url::parse(abs_path)
+            .and_then(|base| base.join(source))
+            .map(String::from)
+            .unwrap_or_else(|_| source.to_owned());
+
+        replace(&mut frame.abs_path, resolved.into());
+    }
+
+    let src_line = token.get_src_line();
+    replace(&mut frame.lineno, u64::from(src_line) + 1);
+    replace(&mut frame.colno, u64::from(token.get_src_col()) + 1);
+
+    let view = match token.get_source_view() {
+        Some(view) => view,
+        None => return,
+    };
+
+    if let Some(context_line) = view.get_line(src_line) {
+        replace(&mut frame.context_line, context_line.to_owned());
+        frame.pre_context = context_lines(view, src_line.saturating_sub(CONTEXT_LINES)..src_line);
+        frame.post_context = context_lines(view, src_line + 1..=src_line + CONTEXT_LINES);
+    }
+}
+
+/// Resolves JavaScript frames of an event with source maps.
+struct SourceMapProcessor<'a> {
+    lookup: &'a SourceMapLookup,
+    release: &'a str,
+    dist: Option<&'a str>,
+    /// Source maps loaded for this event, keyed by the `abs_path` of the minified file.
+    files: BTreeMap<String, Option<SharedSourceFile>>,
+    errors: Vec<EventProcessingError>,
+}
+
+impl SourceMapProcessor<'_> {
+    /// Returns the source map for a minified file, recording an error if it cannot be loaded.
+    fn get_file(&mut self, abs_path: &str) -> Option<SharedSourceFile> {
+        if !self.files.contains_key(abs_path) {
+            let file = match self.lookup.load(self.release, self.dist, abs_path) {
+                Ok(Some(file)) => Some(file),
+                Ok(None) => {
+                    self.add_error(JS_NO_SOURCE, abs_path);
+                    None
+                }
+                Err(error) => {
+                    relay_log::debug!("failed to load source map for {}: {}", abs_path, error);
+                    self.add_error(JS_INVALID_SOURCE, abs_path);
+                    None
+                }
+            };
+
+            self.files.insert(abs_path.to_owned(), file);
+        }
+
+        self.files.get(abs_path)?.clone()
+    }
+
+    fn add_error(&mut self, ty: &str, url: &str) {
+        let mut other = Object::new();
+        other.insert(
+            "url".to_owned(),
+            Annotated::new(Value::String(url.to_owned())),
+        );
+
+        self.errors.push(EventProcessingError {
+            ty: Annotated::new(ty.to_owned()),
+            other,
+            ..Default::default()
+        });
+    }
+}
+
+impl Processor for SourceMapProcessor<'_> {
+    fn process_frame(
+        &mut self,
+        frame: &mut Frame,
+        _meta: &mut Meta,
+        _state: &ProcessingState<'_>,
+    ) -> ProcessingResult {
+        if !matches!(frame.platform.as_str(), None | Some("javascript")) {
+            return Ok(());
+        }
+
+        let (line, col) = match (frame.lineno.value(), frame.colno.value()) {
+            (Some(&line), Some(&col)) if line > 0 && col > 0 => (line - 1, col - 1),
+            _ => return Ok(()),
+        };
+
+        let (line, col) = match (u32::try_from(line), u32::try_from(col)) {
+            (Ok(line), Ok(col)) => (line, col),
+            _ => return Ok(()),
+        };
+
+        let abs_path = match frame.abs_path.value() {
+            Some(abs_path) => abs_path.as_str().to_owned(),
+            None => return Ok(()),
+        };
+
+        if let Some(file) = self.get_file(&abs_path) {
+            let file = file.lock().unwrap_or_else(PoisonError::into_inner);
+            resolve_frame(frame, &file, &abs_path, line, col);
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves minified frames of a JavaScript event with source maps from a local directory.
+///
+/// This rewrites `abs_path`, `lineno`, `colno`, `function` and the source context of all frames
+/// that have a source map in the artifacts of the event's release and dist. The minified values
+/// are preserved as original values in the meta. For frames without a source map, an error is
+/// added to the event.
+///
+/// Events without a release or with a platform other than `javascript` are not modified.
+pub fn resolve_sourcemaps(event: &mut Annotated<Event>, lookup: &SourceMapLookup) {
+    let (release, dist) = match event.value() {
+        Some(event) if event.platform.as_str() == Some("javascript") => {
+            match event.release.value().map(LenientString::as_str) {
+                Some(release) => (release.to_owned(), event.dist.value().cloned()),
+                None => return,
+            }
+        }
+        _ => return,
+    };
+
+    let mut processor = SourceMapProcessor {
+        lookup,
+        release: &release,
+        dist: dist.as_deref(),
+        files: BTreeMap::new(),
+        errors: Vec::new(),
+    };
+
+    // The processor never returns errors or removes values.
+    process_value(event, &mut processor, ProcessingState::root()).ok();
+
+    if let Some(event) = event.value_mut() {
+        let errors = event.errors.get_or_insert_with(Vec::new);
+        errors.extend(processor.errors.into_iter().map(Annotated::new));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+
+    const MINIFIED: &str = "function add(a,b){return a+b}function fail(){throw new Error(add(1,2))}\n//# sourceMappingURL=app.min.js.map\n";
+
+    const SOURCE: &str = "function add(first, second) {\n  return first + second;\n}\n\nfunction fail() {\n  throw new Error(add(1, 2));\n}\n";
+
+    fn write_artifacts(root: &Path) {
+        let dir = root.join("1.0").join("static");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("app.min.js"), MINIFIED).unwrap();
+
+        // Maps `throw new Error(...)` in the minified file to line 6, column 3 of the source.
+        let map = serde_json::json!({
+            "version": 3,
+            "file": "app.min.js",
+            "sources": ["../src/app.js"],
+            "sourcesContent": [SOURCE],
+            "names": [],
+            "mappings": "AAAA,6CAKE"
+        });
+        fs::write(dir.join("app.min.js.map"), map.to_string()).unwrap();
+    }
+
+    fn create_event(abs_path: &str) -> Annotated<Event> {
+        let json = serde_json::json!({
+            "platform": "javascript",
+            "release": "1.0",
+            "exception": {
+                "values": [{
+                    "type": "Error",
+                    "stacktrace": {
+                        "frames": [{
+                            "abs_path": abs_path,
+                            "function": "fail",
+                            "lineno": 1,
+                            "colno": 46
+                        }]
+                    }
+                }]
+            }
+        });
+
+        Annotated::from_json(&json.to_string()).unwrap()
+    }
+
+    fn get_frame(event: &Annotated<Event>) -> &Frame {
+        event
+            .value()
+            .unwrap()
+            .exceptions
+            .value()
+            .unwrap()
+            .values
+            .value()
+            .unwrap()[0]
+            .value()
+            .unwrap()
+            .stacktrace
+            .value()
+            .unwrap()
+            .frames
+            .value()
+            .unwrap()[0]
+            .value()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_artifact_path() {
+        let path = artifact_path("https://example.com/static/app.min.js?v=1");
+        assert_eq!(path, Some(PathBuf::from("static/app.min.js")));
+
+        let path = artifact_path("~/static/app.min.js");
+        assert_eq!(path, Some(PathBuf::from("static/app.min.js")));
+
+        // URLs resolve dot segments against the root, so they cannot leave the release directory.
+        let path = artifact_path("https://example.com/../../etc/passwd");
+        assert_eq!(path, Some(PathBuf::from("etc/passwd")));
+
+        assert_eq!(artifact_path("~/../../etc/passwd"), None);
+        assert_eq!(artifact_path("<anonymous>"), None);
+        assert_eq!(artifact_path("native"), None);
+    }
+
+    #[test]
+    fn test_source_mapping_url() {
+        assert_eq!(source_mapping_url(MINIFIED), Some("app.min.js.map"));
+        assert_eq!(source_mapping_url("function a(){}"), None);
+    }
+
+    #[test]
+    fn test_resolve_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        write_artifacts(dir.path());
+
+        let lookup = SourceMapLookup::new(dir.path());
+        let mut event = create_event("https://example.com/static/app.min.js");
+        resolve_sourcemaps(&mut event, &lookup);
+
+        let frame = get_frame(&event);
+        assert_eq!(
+            frame.abs_path.value().map(|p| p.as_str()),
+            Some("https://example.com/src/app.js")
+        );
+        assert_eq!(frame.lineno.value(), Some(&6));
+        assert_eq!(frame.colno.value(), Some(&3));
+        assert_eq!(
+            frame.context_line.as_str(),
+            Some("  throw new Error(add(1, 2));")
+        );
+        assert_eq!(frame.lineno.meta().original_value(), Some(&Value::U64(1)));
+
+        assert!(event.value().unwrap().errors.value().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_sourcemap() {
+        let dir = tempfile::tempdir().unwrap();
+        write_artifacts(dir.path());
+
+        let lookup = SourceMapLookup::new(dir.path());
+        let mut event = create_event("https://example.com/static/vendor.min.js");
+        resolve_sourcemaps(&mut event, &lookup);
+
+        let frame = get_frame(&event);
+        assert_eq!(frame.lineno.value(), Some(&1));
+
+        let errors = event.value().unwrap().errors.value().unwrap();
+        assert_eq!(errors.len(), 1);
+
+        let error = errors[0].value().unwrap();
+        assert_eq!(error.ty.as_str(), Some(JS_NO_SOURCE));
+    }
+
+    #[test]
+    fn test_cache_sourcemap() {
+        let dir = tempfile::tempdir().unwrap();
+        write_artifacts(dir.path());
+
+        let lookup = SourceMapLookup::new(dir.path());
+        let abs_path = "https://example.com/static/app.min.js";
+        let file = lookup.load("1.0", None, abs_path).unwrap().unwrap();
+
+        // Unchanged files are served from the cache.
+        let cached = lookup.load("1.0", None, abs_path).unwrap().unwrap();
+        assert!(Arc::ptr_eq(&file, &cached));
+
+        // Changes to the source map invalidate the cached entry.
+        fs::remove_file(dir.path().join("1.0/static/app.min.js.map")).unwrap();
+        assert!(lookup.load("1.0", None, abs_path).unwrap().is_none());
+    }
+}
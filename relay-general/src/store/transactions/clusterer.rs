@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::mem;
+
+use chrono::{DateTime, Utc};
+
+use super::TransactionNameRule;
+
+/// The segment that replaces high-cardinality segments in the tree and in rule patterns.
+const WILDCARD: &str = "*";
+
+/// A node in the path segment tree, holding the distinct segments that follow it.
+#[derive(Debug, Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    /// Inserts a sequence of segments below this node.
+    fn insert<'a, I>(&mut self, mut segments: I)
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        if let Some(segment) = segments.next() {
+            self.children
+                .entry(segment.to_owned())
+                .or_default()
+                .insert(segments);
+        }
+    }
+
+    /// Merges the subtree of another node into this node.
+    fn absorb(&mut self, other: Node) {
+        for (segment, child) in other.children {
+            self.children.entry(segment).or_default().absorb(child);
+        }
+    }
+
+    /// Replaces the children of every node that has more than `threshold` distinct children with a
+    /// single wildcard child, which contains the union of their subtrees.
+    fn merge(&mut self, threshold: usize) {
+        if self.children.len() > threshold {
+            let mut merged = Node::default();
+            for (_, child) in mem::take(&mut self.children) {
+                merged.absorb(child);
+            }
+            self.children.insert(WILDCARD.to_owned(), merged);
+        }
+
+        for child in self.children.values_mut() {
+            child.merge(threshold);
+        }
+    }
+
+    /// Collects a rule pattern for every wildcard in the subtree.
+    fn collect_patterns<'a>(&'a self, path: &mut Vec<&'a str>, patterns: &mut Vec<String>) {
+        for (segment, child) in &self.children {
+            path.push(segment);
+            if segment == WILDCARD {
+                patterns.push(format!("/{}/**", path.join("/")));
+            }
+            child.collect_patterns(path, patterns);
+            path.pop();
+        }
+    }
+}
+
+/// Learns [`TransactionNameRule`]s from observed URL transaction names.
+///
+/// The clusterer splits transaction names into path segments and builds a tree from them. Every
+/// position in the tree where more than `merge_threshold` distinct segments follow the same prefix
+/// is considered a high-cardinality parameter, such as an ID. For each such position, the clusterer
+/// produces a rule that replaces the segment with `*`.
+///
+/// Names that do not start with a slash are ignored.
+#[derive(Debug)]
+pub struct TransactionNameClusterer {
+    root: Node,
+    merge_threshold: usize,
+}
+
+impl TransactionNameClusterer {
+    /// The default number of distinct segments above which a segment is replaced.
+    pub const DEFAULT_MERGE_THRESHOLD: usize = 100;
+
+    /// Creates an empty clusterer with the given merge threshold.
+    pub fn new(merge_threshold: usize) -> Self {
+        Self {
+            root: Node::default(),
+            merge_threshold,
+        }
+    }
+
+    /// Adds an observed transaction name to the tree.
+    pub fn add_name(&mut self, name: &str) {
+        if let Some(path) = name.strip_prefix('/') {
+            let segments = path.split('/').filter(|segment| !segment.is_empty());
+            self.root.insert(segments);
+        }
+    }
+
+    /// Computes the rules for all high-cardinality segments of the observed names.
+    ///
+    /// Rules are ordered from the most to the least specific pattern, since only the first matching
+    /// rule is applied to a transaction name. All rules expire at the given time.
+    pub fn into_rules(mut self, expiry: DateTime<Utc>) -> Vec<TransactionNameRule> {
+        self.root.merge(self.merge_threshold);
+
+        let mut patterns = Vec::new();
+        self.root.collect_patterns(&mut Vec::new(), &mut patterns);
+        patterns.sort_by(|a, b| {
+            let depth = |pattern: &str| pattern.matches('/').count();
+            depth(b).cmp(&depth(a)).then_with(|| a.cmp(b))
+        });
+
+        patterns
+            .iter()
+            .map(|pattern| TransactionNameRule::new(pattern, expiry))
+            .collect()
+    }
+}
+
+impl Default for TransactionNameClusterer {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MERGE_THRESHOLD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use chrono::Duration;
+    use similar_asserts::assert_eq;
+
+    use crate::protocol::{TransactionInfo, TransactionSource};
+    use crate::types::Annotated;
+
+    use super::*;
+
+    fn patterns(rules: &[TransactionNameRule]) -> Vec<&str> {
+        rules.iter().map(|rule| rule.pattern.pattern()).collect()
+    }
+
+    #[test]
+    fn test_cluster_high_cardinality_segments() {
+        let mut clusterer = TransactionNameClusterer::new(2);
+        for name in [
+            "/users/1/",
+            "/users/2/",
+            "/users/3/posts/a",
+            "/users/4/posts/b",
+            "/users/5/posts/c",
+            "/users/6/posts/",
+            "/about/",
+            "/about/team",
+            "not-a-path",
+        ] {
+            clusterer.add_name(name);
+        }
+
+        let rules = clusterer.into_rules(Utc::now() + Duration::days(1));
+        assert_eq!(patterns(&rules), vec!["/users/*/posts/*/**", "/users/*/**"]);
+    }
+
+    #[test]
+    fn test_cluster_below_threshold() {
+        let mut clusterer = TransactionNameClusterer::new(2);
+        clusterer.add_name("/users/1/");
+        clusterer.add_name("/users/2/");
+
+        let rules = clusterer.into_rules(Utc::now() + Duration::days(1));
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn test_apply_clustered_rules() {
+        let mut clusterer = TransactionNameClusterer::new(1);
+        clusterer.add_name("/users/1/posts/8");
+        clusterer.add_name("/users/2/posts/9");
+
+        let rules = clusterer.into_rules(Utc::now() + Duration::days(1));
+        let info = TransactionInfo {
+            source: Annotated::new(TransactionSource::Url),
+            ..Default::default()
+        };
+
+        let applied = rules.iter().find_map(|rule| {
+            rule.match_and_apply(Cow::Owned("/users/3/posts/10".to_owned()), &info)
+        });
+        assert_eq!(applied.as_deref(), Some("/users/*/posts/*"));
+
+        let applied = rules
+            .iter()
+            .find_map(|rule| rule.match_and_apply(Cow::Owned("/users/3".to_owned()), &info));
+        assert_eq!(applied.as_deref(), Some("/users/*"));
+    }
+}
@@ -1,5 +1,7 @@
+mod clusterer;
 mod processor;
 mod rules;
 
+pub use clusterer::*;
 pub use processor::*;
 pub use rules::*;
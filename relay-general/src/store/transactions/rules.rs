@@ -12,12 +12,16 @@ where
     D: Deserializer<'de>,
 {
     let pattern = String::deserialize(deserializer)?;
-    let glob = Glob::builder(&pattern)
+    Ok(build_glob_pattern(&pattern))
+}
+
+/// Builds a [`relay_common::Glob`] that captures single `*` wildcards for replacement.
+fn build_glob_pattern(pattern: &str) -> Glob {
+    Glob::builder(pattern)
         .capture_star(true)
         .capture_double_star(false)
         .capture_question_mark(false)
-        .build();
-    Ok(glob)
+        .build()
 }
 
 /// Default value for substitution in [`RedactionRule`].
@@ -76,6 +80,16 @@ pub struct TransactionNameRule {
 }
 
 impl TransactionNameRule {
+    /// Creates a rule for URL transactions that replaces the `*` wildcards of the pattern with `*`.
+    pub fn new(pattern: &str, expiry: DateTime<Utc>) -> Self {
+        Self {
+            pattern: build_glob_pattern(pattern),
+            expiry,
+            scope: RuleScope::default(),
+            redaction: RedactionRule::default(),
+        }
+    }
+
     /// Checks is the current rule matches and tries to apply it.
     pub fn match_and_apply(
         &self,
@@ -28,7 +28,7 @@ pub enum RemarkType {
     /// The original value was replaced through pseudonymization.
     #[serde(rename = "p")]
     Pseudonymized,
-    /// The original value was encrypted (not implemented yet).
+    /// The original value was encrypted and can only be recovered with the key.
     #[serde(rename = "e")]
     Encrypted,
 }
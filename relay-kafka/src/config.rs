@@ -33,12 +33,20 @@ pub enum KafkaTopic {
     MetricsSessions,
     /// Any metric that is extracted from transactions.
     MetricsTransactions,
+    /// Any metric that is extracted from the spans of transactions.
+    MetricsSpans,
+    /// Custom metrics submitted by users or services.
+    MetricsCustom,
     /// Profiles
     Profiles,
     /// ReplayEvents, breadcrumb + session updates for replays
     ReplayEvents,
     /// ReplayRecordings, large blobs sent by the replay sdk
     ReplayRecordings,
+    /// Monitor check-ins.
+    Monitors,
+    /// Standalone spans extracted from transactions.
+    Spans,
 }
 
 impl KafkaTopic {
@@ -46,7 +54,7 @@ impl KafkaTopic {
     /// It will have to be adjusted if the new variants are added.
     pub fn iter() -> std::slice::Iter<'static, Self> {
         use KafkaTopic::*;
-        static TOPICS: [KafkaTopic; 11] = [
+        static TOPICS: [KafkaTopic; 15] = [
             Events,
             Attachments,
             Transactions,
@@ -55,9 +63,13 @@ impl KafkaTopic {
             Sessions,
             MetricsSessions,
             MetricsTransactions,
+            MetricsSpans,
+            MetricsCustom,
             Profiles,
             ReplayEvents,
             ReplayRecordings,
+            Monitors,
+            Spans,
         ];
         TOPICS.iter()
     }
@@ -81,18 +93,27 @@ pub struct TopicAssignments {
     pub sessions: TopicAssignment,
     /// Default topic name for all aggregate metrics. Specialized topics for session-based and
     /// transaction-based metrics can be configured via `metrics_sessions` and
-    /// `metrics_transactions` each.
+    /// `metrics_transactions` each. Span metrics can be configured via `metrics_spans` and custom
+    /// metrics via `metrics_custom`.
     pub metrics: TopicAssignment,
     /// Topic name for metrics extracted from sessions. Defaults to the assignment of `metrics`.
     pub metrics_sessions: Option<TopicAssignment>,
     /// Topic name for metrics extracted from transactions. Defaults to the assignment of `metrics`.
     pub metrics_transactions: Option<TopicAssignment>,
+    /// Topic name for metrics extracted from spans. Defaults to the assignment of `metrics`.
+    pub metrics_spans: Option<TopicAssignment>,
+    /// Topic name for custom metrics. Defaults to the assignment of `metrics`.
+    pub metrics_custom: Option<TopicAssignment>,
     /// Stacktrace topic name
     pub profiles: TopicAssignment,
     /// Replay Events topic name.
     pub replay_events: TopicAssignment,
     /// Recordings topic name.
     pub replay_recordings: TopicAssignment,
+    /// Monitor check-ins topic name.
+    pub monitors: TopicAssignment,
+    /// Standalone spans topic name.
+    pub spans: TopicAssignment,
 }
 
 impl TopicAssignments {
@@ -110,9 +131,13 @@ impl TopicAssignments {
             KafkaTopic::MetricsTransactions => {
                 self.metrics_transactions.as_ref().unwrap_or(&self.metrics)
             }
+            KafkaTopic::MetricsSpans => self.metrics_spans.as_ref().unwrap_or(&self.metrics),
+            KafkaTopic::MetricsCustom => self.metrics_custom.as_ref().unwrap_or(&self.metrics),
             KafkaTopic::Profiles => &self.profiles,
             KafkaTopic::ReplayEvents => &self.replay_events,
             KafkaTopic::ReplayRecordings => &self.replay_recordings,
+            KafkaTopic::Monitors => &self.monitors,
+            KafkaTopic::Spans => &self.spans,
         }
     }
 }
@@ -129,9 +154,13 @@ impl Default for TopicAssignments {
             metrics: "ingest-metrics".to_owned().into(),
             metrics_sessions: None,
             metrics_transactions: None,
+            metrics_spans: None,
+            metrics_custom: None,
             profiles: "profiles".to_owned().into(),
             replay_events: "ingest-replay-events".to_owned().into(),
             replay_recordings: "ingest-replay-recordings".to_owned().into(),
+            monitors: "ingest-monitors".to_owned().into(),
+            spans: "snuba-spans".to_owned().into(),
         }
     }
 }
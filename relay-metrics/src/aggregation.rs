@@ -20,8 +20,8 @@ use relay_system::{
 
 use crate::statsd::{MetricCounters, MetricGauges, MetricHistograms, MetricSets, MetricTimers};
 use crate::{
-    protocol, CounterType, DistributionType, GaugeType, Metric, MetricNamespace,
-    MetricResourceIdentifier, MetricType, MetricValue, MetricsContainer, SetType,
+    protocol, CardinalityLimiterConfig, CounterType, DistributionType, GaugeType, Metric,
+    MetricNamespace, MetricResourceIdentifier, MetricType, MetricValue, MetricsContainer, SetType,
 };
 
 /// Interval for the flush cycle of the [`AggregatorService`].
@@ -1000,6 +1000,11 @@ pub struct AggregatorConfig {
     ///
     /// Defaults to `None`, i.e. no limit.
     pub max_project_key_bucket_bytes: Option<usize>,
+
+    /// Limits the number of distinct buckets per project and metric name.
+    ///
+    /// The limit is enforced on flushed buckets before they are sent. Disabled by default.
+    pub cardinality_limiter: CardinalityLimiterConfig,
 }
 
 impl AggregatorConfig {
@@ -1109,6 +1114,7 @@ impl Default for AggregatorConfig {
             max_tag_value_length: 200,
             max_total_bucket_bytes: None,
             max_project_key_bucket_bytes: None,
+            cardinality_limiter: CardinalityLimiterConfig::default(),
         }
     }
 }
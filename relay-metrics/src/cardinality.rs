@@ -0,0 +1,596 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hasher;
+use std::sync::{Mutex, PoisonError};
+
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+
+use relay_common::ProjectKey;
+use relay_statsd::metric;
+
+use crate::statsd::MetricCounters;
+use crate::Bucket;
+
+#[cfg(feature = "redis")]
+use relay_redis::{redis::Script, RedisPool};
+
+/// Number of bits of the hash used to select a HyperLogLog register.
+const HLL_PRECISION: u32 = 10;
+
+/// Number of registers in a HyperLogLog, resulting in a standard error of about 3%.
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// Action taken for buckets that exceed the cardinality limit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardinalityLimitMode {
+    /// Drop the excess buckets entirely.
+    Reject,
+    /// Remove all tags from excess buckets, which merges them into a single bucket.
+    StripTags,
+}
+
+impl CardinalityLimitMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Reject => "reject",
+            Self::StripTags => "strip_tags",
+        }
+    }
+}
+
+impl Default for CardinalityLimitMode {
+    fn default() -> Self {
+        Self::Reject
+    }
+}
+
+/// Data structure used to count distinct buckets in memory.
+///
+/// When sharing state through Redis, buckets are always counted with an exact set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardinalityEstimator {
+    /// Stores a hash of every distinct bucket, which is exact but grows with the limit.
+    Exact,
+    /// Estimates the number of distinct buckets with a HyperLogLog of constant size.
+    ///
+    /// The estimator cannot tell whether a bucket has been seen before. Buckets that do not change
+    /// the estimate are accepted even after the limit has been reached, so more distinct buckets
+    /// than the limit can pass. Use this for large limits where memory matters more than precision.
+    HyperLogLog,
+}
+
+impl Default for CardinalityEstimator {
+    fn default() -> Self {
+        Self::Exact
+    }
+}
+
+/// Parameters used by the [`CardinalityLimiter`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CardinalityLimiterConfig {
+    /// Maximum number of distinct buckets per project, metric name and time window.
+    ///
+    /// Buckets are distinct if they have different tags. Defaults to `None`, which disables the
+    /// cardinality limiter.
+    pub limit: Option<usize>,
+
+    /// The length of the time window in seconds.
+    ///
+    /// Distinct buckets are counted separately in every window. Defaults to `3600` (1 hour).
+    pub window: u64,
+
+    /// Action taken for buckets that exceed the limit. Defaults to `reject`.
+    pub mode: CardinalityLimitMode,
+
+    /// Data structure used to count distinct buckets in memory. Defaults to `exact`.
+    pub estimator: CardinalityEstimator,
+}
+
+impl Default for CardinalityLimiterConfig {
+    fn default() -> Self {
+        Self {
+            limit: None,
+            window: 3600,
+            mode: CardinalityLimitMode::default(),
+            estimator: CardinalityEstimator::default(),
+        }
+    }
+}
+
+/// Buckets after enforcing cardinality limits, returned by [`CardinalityLimiter::enforce`].
+#[derive(Debug, Default)]
+pub struct CardinalityReport {
+    /// Buckets within the cardinality limit, including buckets with stripped tags.
+    pub accepted: Vec<Bucket>,
+    /// Buckets that exceeded the cardinality limit and were rejected.
+    pub rejected: Vec<Bucket>,
+}
+
+/// Computes a well-distributed hash of the bucket's metric name and tags.
+///
+/// Buckets with the same hash count as a single distinct bucket, regardless of their timestamp.
+fn bucket_hash(name: &str, tags: &BTreeMap<String, String>) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(name.as_bytes());
+    for (key, value) in tags {
+        hasher.write_u8(0);
+        hasher.write(key.as_bytes());
+        hasher.write_u8(0);
+        hasher.write(value.as_bytes());
+    }
+
+    // FNV does not distribute well into the high bits, so apply a finalizer from SplitMix64.
+    let mut hash = hasher.finish();
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// A HyperLogLog cardinality estimator.
+#[derive(Debug)]
+struct HyperLogLog {
+    registers: Box<[u8]>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the register index and rank for the given hash.
+    fn position(hash: u64) -> (usize, u8) {
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        (index, rank as u8)
+    }
+
+    /// Returns the estimated number of distinct hashes.
+    fn estimate(&self) -> f64 {
+        let m = HLL_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &register in self.registers.iter() {
+            sum += 1.0 / (1u64 << register) as f64;
+            if register == 0 {
+                zeros += 1;
+            }
+        }
+
+        let raw = alpha * m * m / sum;
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities.
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /// Adds the hash if the estimate stays within the limit. Returns `true` if accepted.
+    ///
+    /// Hashes that do not change the registers are likely known and always accepted.
+    fn insert(&mut self, hash: u64, limit: usize) -> bool {
+        let (index, rank) = Self::position(hash);
+        let previous = self.registers[index];
+        if previous >= rank {
+            return true;
+        }
+
+        self.registers[index] = rank;
+        if self.estimate().round() as usize > limit {
+            self.registers[index] = previous;
+            return false;
+        }
+
+        true
+    }
+}
+
+/// Counts distinct buckets within a single project, metric name and window.
+#[derive(Debug)]
+enum Estimator {
+    Exact(HashSet<u64>),
+    HyperLogLog(HyperLogLog),
+}
+
+impl Estimator {
+    fn new(kind: CardinalityEstimator) -> Self {
+        match kind {
+            CardinalityEstimator::Exact => Self::Exact(HashSet::new()),
+            CardinalityEstimator::HyperLogLog => Self::HyperLogLog(HyperLogLog::new()),
+        }
+    }
+
+    /// Records the hash if it is known or the limit has not been reached. Returns `true` if
+    /// accepted.
+    fn insert(&mut self, hash: u64, limit: usize) -> bool {
+        match self {
+            Self::Exact(set) => {
+                if set.contains(&hash) {
+                    true
+                } else if set.len() < limit {
+                    set.insert(hash);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::HyperLogLog(hll) => hll.insert(hash, limit),
+        }
+    }
+}
+
+/// Identifies the set of distinct buckets that is limited.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct SetKey {
+    project_key: ProjectKey,
+    metric_name: String,
+    window: u64,
+}
+
+/// In-memory state of the [`CardinalityLimiter`].
+#[derive(Debug, Default)]
+struct MemoryState {
+    sets: HashMap<SetKey, Estimator>,
+    last_purge: u64,
+}
+
+impl MemoryState {
+    /// Removes all sets of windows before the previous window.
+    ///
+    /// The current window is derived from bucket timestamps, the same clock that is used for the
+    /// keys of the sets. Purging only moves forward, so late buckets never purge newer sets.
+    fn purge(&mut self, current_window: u64) {
+        if self.last_purge >= current_window {
+            return;
+        }
+
+        let min_window = current_window.saturating_sub(1);
+        self.sets.retain(|key, _| key.window >= min_window);
+        self.last_purge = current_window;
+    }
+}
+
+/// Limits the number of distinct buckets per project, metric name and time window.
+///
+/// A single tag with unbounded values, such as a user identifier, creates a new bucket for every
+/// value. The cardinality limiter counts distinct tag combinations of every metric and rejects or
+/// strips tags from buckets that would exceed the configured limit. Buckets that have been accepted
+/// before in the same window are always accepted again.
+///
+/// By default, state is kept in memory. With the `redis` feature, state can be shared across
+/// Relay instances through Redis using [`with_redis`](Self::with_redis).
+pub struct CardinalityLimiter {
+    config: CardinalityLimiterConfig,
+    state: Mutex<MemoryState>,
+    #[cfg(feature = "redis")]
+    redis: Option<(RedisPool, Script)>,
+}
+
+impl CardinalityLimiter {
+    /// Creates a new in-memory cardinality limiter.
+    pub fn new(config: CardinalityLimiterConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(MemoryState::default()),
+            #[cfg(feature = "redis")]
+            redis: None,
+        }
+    }
+
+    /// Shares the state of this limiter across Relay instances through Redis.
+    ///
+    /// Redis always counts distinct buckets exactly, regardless of the configured estimator.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(mut self, pool: RedisPool) -> Self {
+        let script = Script::new(include_str!("is_cardinality_limited.lua"));
+        self.redis = Some((pool, script));
+        self
+    }
+
+    /// Returns `true` if a cardinality limit is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.config.limit.is_some()
+    }
+
+    /// Enforces the cardinality limit on buckets of a single project.
+    ///
+    /// Depending on the configured [mode](CardinalityLimitMode), buckets exceeding the limit are
+    /// either rejected or accepted with all tags removed.
+    pub fn enforce(&self, project_key: ProjectKey, buckets: Vec<Bucket>) -> CardinalityReport {
+        let limit = match self.config.limit {
+            Some(limit) => limit,
+            None => {
+                return CardinalityReport {
+                    accepted: buckets,
+                    rejected: Vec::new(),
+                }
+            }
+        };
+
+        let window = self.config.window.max(1);
+        let keyed = buckets
+            .iter()
+            .map(|bucket| {
+                let key = SetKey {
+                    project_key,
+                    metric_name: bucket.name.clone(),
+                    window: bucket.timestamp.as_secs() / window,
+                };
+                (key, bucket_hash(&bucket.name, &bucket.tags))
+            })
+            .collect::<Vec<_>>();
+
+        let decisions = self.check(&keyed, limit);
+
+        let mut report = CardinalityReport::default();
+        for (mut bucket, accepted) in buckets.into_iter().zip(decisions) {
+            if accepted {
+                report.accepted.push(bucket);
+                continue;
+            }
+
+            metric!(
+                counter(MetricCounters::CardinalityLimited) += 1,
+                mode = self.config.mode.as_str()
+            );
+
+            match self.config.mode {
+                CardinalityLimitMode::Reject => report.rejected.push(bucket),
+                CardinalityLimitMode::StripTags => {
+                    bucket.tags.clear();
+                    report.accepted.push(bucket);
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Returns for every bucket whether it is within the limit.
+    fn check(&self, keyed: &[(SetKey, u64)], limit: usize) -> Vec<bool> {
+        #[cfg(feature = "redis")]
+        if let Some((ref pool, ref script)) = self.redis {
+            let window = self.config.window.max(1);
+            match check_redis(pool, script, keyed, limit, window) {
+                Ok(decisions) => return decisions,
+                Err(error) => {
+                    // Fail open, since rejecting all buckets would lose data.
+                    relay_log::error!(
+                        "failed to check cardinality limits: {}",
+                        relay_log::LogError(&error)
+                    );
+                    return vec![true; keyed.len()];
+                }
+            }
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(current_window) = keyed.iter().map(|(key, _)| key.window).max() {
+            state.purge(current_window);
+        }
+
+        keyed
+            .iter()
+            .map(|(key, hash)| {
+                state
+                    .sets
+                    .entry(key.clone())
+                    .or_insert_with(|| Estimator::new(self.config.estimator))
+                    .insert(*hash, limit)
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for CardinalityLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CardinalityLimiter")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+/// Checks the cardinality limits in Redis with one script invocation per set.
+#[cfg(feature = "redis")]
+fn check_redis(
+    pool: &RedisPool,
+    script: &Script,
+    keyed: &[(SetKey, u64)],
+    limit: usize,
+    window: u64,
+) -> Result<Vec<bool>, relay_redis::RedisError> {
+    let mut groups = HashMap::<&SetKey, Vec<usize>>::new();
+    for (index, (key, _)) in keyed.iter().enumerate() {
+        groups.entry(key).or_default().push(index);
+    }
+
+    let mut client = pool.client()?;
+    let mut decisions = vec![true; keyed.len()];
+
+    for (key, indexes) in groups {
+        let mut name_hasher = FnvHasher::default();
+        name_hasher.write(key.metric_name.as_bytes());
+
+        let mut invocation = script.prepare_invoke();
+        invocation.key(format!(
+            "cardinality:{{{}}}:{}:{:x}",
+            key.project_key,
+            key.window,
+            name_hasher.finish()
+        ));
+        invocation.arg(limit);
+        invocation.arg((key.window + 2) * window);
+        for &index in &indexes {
+            invocation.arg(keyed[index].1);
+        }
+
+        let accepted: Vec<bool> = invocation
+            .invoke(&mut client.connection())
+            .map_err(relay_redis::RedisError::Redis)?;
+
+        for (index, accepted) in indexes.into_iter().zip(accepted) {
+            decisions[index] = accepted;
+        }
+    }
+
+    Ok(decisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use relay_common::UnixTimestamp;
+
+    use crate::BucketValue;
+
+    use super::*;
+
+    fn project_key() -> ProjectKey {
+        ProjectKey::parse("a94ae32be2584e0bbd7a4cbb95971fee").unwrap()
+    }
+
+    fn bucket(name: &str, user: &str) -> Bucket {
+        Bucket {
+            timestamp: UnixTimestamp::now(),
+            width: 10,
+            name: name.to_owned(),
+            value: BucketValue::Counter(1.0),
+            tags: BTreeMap::from([("user".to_owned(), user.to_owned())]),
+        }
+    }
+
+    fn limiter(mode: CardinalityLimitMode, estimator: CardinalityEstimator) -> CardinalityLimiter {
+        CardinalityLimiter::new(CardinalityLimiterConfig {
+            limit: Some(2),
+            mode,
+            estimator,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_disabled() {
+        let limiter = CardinalityLimiter::new(CardinalityLimiterConfig::default());
+        assert!(!limiter.is_enabled());
+
+        let buckets = (0..10).map(|i| bucket("c:custom/foo@none", &i.to_string()));
+        let report = limiter.enforce(project_key(), buckets.collect());
+        assert_eq!(report.accepted.len(), 10);
+        assert!(report.rejected.is_empty());
+    }
+
+    #[test]
+    fn test_reject() {
+        let limiter = limiter(CardinalityLimitMode::Reject, CardinalityEstimator::Exact);
+        let buckets = vec![
+            bucket("c:custom/foo@none", "a"),
+            bucket("c:custom/foo@none", "b"),
+            bucket("c:custom/foo@none", "c"),
+            bucket("c:custom/bar@none", "c"),
+        ];
+
+        let report = limiter.enforce(project_key(), buckets);
+        assert_eq!(report.accepted.len(), 3);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].tags["user"], "c");
+
+        // Known buckets are accepted again, new ones are still rejected.
+        let buckets = vec![
+            bucket("c:custom/foo@none", "a"),
+            bucket("c:custom/foo@none", "d"),
+        ];
+        let report = limiter.enforce(project_key(), buckets);
+        assert_eq!(report.accepted.len(), 1);
+        assert_eq!(report.accepted[0].tags["user"], "a");
+        assert_eq!(report.rejected.len(), 1);
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        let limiter = limiter(CardinalityLimitMode::StripTags, CardinalityEstimator::Exact);
+        let buckets = vec![
+            bucket("c:custom/foo@none", "a"),
+            bucket("c:custom/foo@none", "b"),
+            bucket("c:custom/foo@none", "c"),
+        ];
+
+        let report = limiter.enforce(project_key(), buckets);
+        assert_eq!(report.accepted.len(), 3);
+        assert!(report.rejected.is_empty());
+        assert!(report.accepted[2].tags.is_empty());
+    }
+
+    #[test]
+    fn test_separate_projects() {
+        let limiter = limiter(CardinalityLimitMode::Reject, CardinalityEstimator::Exact);
+        let other_key = ProjectKey::parse("b94ae32be2584e0bbd7a4cbb95971fee").unwrap();
+
+        let buckets = vec![
+            bucket("c:custom/foo@none", "a"),
+            bucket("c:custom/foo@none", "b"),
+        ];
+        let report = limiter.enforce(project_key(), buckets.clone());
+        assert!(report.rejected.is_empty());
+
+        let report = limiter.enforce(other_key, buckets);
+        assert!(report.rejected.is_empty());
+    }
+
+    #[test]
+    fn test_purge_by_bucket_timestamp() {
+        let limiter = limiter(CardinalityLimitMode::Reject, CardinalityEstimator::Exact);
+        let window = limiter.config.window;
+        let mut old = bucket("c:custom/foo@none", "a");
+        old.timestamp = UnixTimestamp::from_secs(window * 10);
+        limiter.enforce(project_key(), vec![old.clone()]);
+
+        // Late buckets from the same window do not purge their own set.
+        let report = limiter.enforce(project_key(), vec![old.clone()]);
+        assert_eq!(report.accepted.len(), 1);
+        assert_eq!(limiter.state.lock().unwrap().sets.len(), 1);
+
+        // Buckets two windows later purge the old set, regardless of the wall clock.
+        let mut new = old;
+        new.timestamp = UnixTimestamp::from_secs(window * 12);
+        limiter.enforce(project_key(), vec![new]);
+        let state = limiter.state.lock().unwrap();
+        assert_eq!(state.sets.len(), 1);
+        assert!(state.sets.keys().all(|key| key.window == 12));
+    }
+
+    #[test]
+    fn test_hyperloglog() {
+        let limiter = CardinalityLimiter::new(CardinalityLimiterConfig {
+            limit: Some(100),
+            estimator: CardinalityEstimator::HyperLogLog,
+            ..Default::default()
+        });
+
+        let buckets = (0..1000).map(|i| bucket("c:custom/foo@none", &i.to_string()));
+        let report = limiter.enforce(project_key(), buckets.collect());
+
+        // Buckets that do not change the estimate pass even after the limit has been reached.
+        let accepted = report.accepted.len();
+        assert!((95..300).contains(&accepted), "{}", accepted);
+    }
+
+    #[test]
+    fn test_hyperloglog_estimate() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..10_000 {
+            hll.insert(
+                bucket_hash("foo", &BTreeMap::from([(i.to_string(), "".to_owned())])),
+                usize::MAX,
+            );
+        }
+
+        let estimate = hll.estimate();
+        assert!((9_000.0..11_000.0).contains(&estimate), "{}", estimate);
+    }
+}
@@ -0,0 +1,36 @@
+-- Check a set of bucket hashes against a cardinality limit and add the hashes that fit into the
+-- limit to the set. Hashes that are already in the set are always accepted.
+--
+-- ``KEYS`` (1):
+--  * [string] Key of the set of distinct bucket hashes.
+--
+-- ``ARGV``:
+--  * [number] Maximum number of distinct hashes in the set.
+--  * [number] Absolute expiration time as Unix timestamp (secs since 1.1.1970) for the key.
+--  * [string] One or more bucket hashes to check.
+--
+-- Returns a table with ``1`` for every accepted and ``0`` for every rejected hash, in the order of
+-- the arguments.
+local key = KEYS[1]
+local limit = tonumber(ARGV[1])
+local expiry = tonumber(ARGV[2])
+
+local results = {}
+local cardinality = redis.call('SCARD', key)
+
+for i = 3, #ARGV do
+    local hash = ARGV[i]
+    if redis.call('SISMEMBER', key, hash) == 1 then
+        results[i - 2] = 1
+    elseif cardinality < limit then
+        redis.call('SADD', key, hash)
+        cardinality = cardinality + 1
+        results[i - 2] = 1
+    else
+        results[i - 2] = 0
+    end
+end
+
+redis.call('EXPIREAT', key, expiry)
+
+return results
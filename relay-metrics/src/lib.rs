@@ -101,8 +101,10 @@
 #![allow(clippy::derive_partial_eq_without_eq)]
 
 mod aggregation;
+mod cardinality;
 mod protocol;
 mod statsd;
 
 pub use aggregation::*;
+pub use cardinality::*;
 pub use protocol::*;
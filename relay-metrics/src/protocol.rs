@@ -171,14 +171,22 @@ fn is_valid_name(name: &str) -> bool {
 /// new metrics-based products.
 ///
 /// Right now this successfully deserializes any kind of string, but in reality only `"sessions"`
-/// (for release health) and `"transactions"` (for metrics-enhanced performance) is supported.
-/// Everything else is dropped both in the metrics aggregator and in the store actor.
+/// (for release health), `"transactions"` (for metrics-enhanced performance), `"spans"` (for
+/// span-level performance metrics), and `"custom"` are supported. Everything else is dropped both
+/// in the metrics aggregator and in the store actor.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MetricNamespace {
     /// Metrics extracted from sessions.
     Sessions,
     /// Metrics extracted from transaction events.
     Transactions,
+    /// Metrics extracted from the spans of transaction events.
+    Spans,
+    /// Custom metrics submitted directly by users or services.
+    ///
+    /// This is the default namespace for metrics submitted via the statsd protocol without an
+    /// explicit namespace.
+    Custom,
     /// Metrics that relay either doesn't know or recognize the namespace of, will be dropped before
     /// aggregating. For instance, an MRI of `c:something_new/foo@none` has the namespace
     /// `something_new`, but as Relay doesn't support that namespace, it gets deserialized into
@@ -196,6 +204,8 @@ impl std::str::FromStr for MetricNamespace {
         match ns {
             "sessions" => Ok(MetricNamespace::Sessions),
             "transactions" => Ok(MetricNamespace::Transactions),
+            "spans" => Ok(MetricNamespace::Spans),
+            "custom" => Ok(MetricNamespace::Custom),
             _ => Ok(MetricNamespace::Unsupported),
         }
     }
@@ -208,6 +218,8 @@ impl fmt::Display for MetricNamespace {
         match self {
             MetricNamespace::Sessions => write!(f, "sessions"),
             MetricNamespace::Transactions => write!(f, "transactions"),
+            MetricNamespace::Spans => write!(f, "spans"),
+            MetricNamespace::Custom => write!(f, "custom"),
             MetricNamespace::Unsupported => write!(f, "unsupported"),
         }
     }
@@ -509,34 +521,50 @@ impl Metric {
 
     /// Parse statsd-compatible payload of format
     /// ```text
-    /// [<ns>/]<name>[@<unit>]:<value>|<type>[|#<tags>]`
+    /// [<ns>/]<name>[@<unit>]:<value>|<type>[|@<rate>][|#<tags>]`
     /// ```
+    ///
+    /// Counter values are scaled by the inverse of the sample rate. The statsd timer type `ms`
+    /// parses as distribution with a default unit of milliseconds.
     fn parse_str(string: &str, timestamp: UnixTimestamp) -> Option<Self> {
         let mut components = string.split('|');
 
         let name_value_str = components.next()?;
-        let ty = components.next().and_then(|s| s.parse().ok())?;
-        let (name_and_namespace, unit, value) = parse_name_unit_value(name_value_str, ty)?;
+        let raw_ty = components.next()?;
+        let ty = raw_ty.parse().ok()?;
+        let (name_and_namespace, mut unit, mut value) = parse_name_unit_value(name_value_str, ty)?;
         let (raw_namespace, name) = name_and_namespace
             .split_once('/')
-            .unwrap_or(("custom", string));
+            .unwrap_or(("custom", name_and_namespace));
 
-        let mut metric = Self::new_mri(
-            raw_namespace.parse().ok()?,
-            name,
-            unit,
-            value,
-            timestamp,
-            BTreeMap::new(),
-        );
+        if raw_ty == "ms" && unit == MetricUnit::None {
+            unit = MetricUnit::Duration(DurationUnit::MilliSecond);
+        }
 
+        let mut tags = BTreeMap::new();
         for component in components {
-            if let Some('#') = component.chars().next() {
-                metric.tags = parse_tags(component.get(1..)?)?;
+            match component.chars().next() {
+                Some('#') => tags = parse_tags(component.get(1..)?)?,
+                Some('@') => {
+                    let rate: f64 = component.get(1..)?.parse().ok()?;
+                    if let MetricValue::Counter(ref mut count) = value {
+                        if rate > 0.0 && rate < 1.0 {
+                            *count /= rate;
+                        }
+                    }
+                }
+                _ => (),
             }
         }
 
-        Some(metric)
+        Some(Self::new_mri(
+            raw_namespace.parse().ok()?,
+            name,
+            unit,
+            value,
+            timestamp,
+            tags,
+        ))
     }
 
     /// Parses a single metric value from the raw protocol.
@@ -689,6 +717,28 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn test_parse_custom() {
+        let s = "endpoint.hits:1|c|@0.5|#route:user_index";
+        let timestamp = UnixTimestamp::from_secs(4711);
+        let metric = Metric::parse(s.as_bytes(), timestamp).unwrap();
+        insta::assert_debug_snapshot!(metric, @r###"
+        Metric {
+            name: "c:custom/endpoint.hits@none",
+            value: Counter(
+                2.0,
+            ),
+            timestamp: UnixTimestamp(4711),
+            tags: {
+                "route": "user_index",
+            },
+        }
+        "###);
+
+        let mri = MetricResourceIdentifier::parse(&metric.name).unwrap();
+        assert_eq!(mri.namespace, MetricNamespace::Custom);
+    }
+
     #[test]
     fn test_parse_distribution() {
         let s = "transactions/foo:17.5|d";
@@ -706,6 +756,23 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn test_parse_timer() {
+        let s = "transactions/foo:17.5|ms|@0.5";
+        let timestamp = UnixTimestamp::from_secs(4711);
+        let metric = Metric::parse(s.as_bytes(), timestamp).unwrap();
+        insta::assert_debug_snapshot!(metric, @r###"
+        Metric {
+            name: "d:transactions/foo@millisecond",
+            value: Distribution(
+                17.5,
+            ),
+            timestamp: UnixTimestamp(4711),
+            tags: {},
+        }
+        "###);
+    }
+
     #[test]
     fn test_parse_histogram() {
         let s = "transactions/foo:17.5|h"; // common alias for distribution
@@ -42,6 +42,11 @@ pub enum MetricCounters {
     ///
     /// This should only happen when a project state is invalid during graceful shutdown.
     BucketsDropped,
+
+    /// Incremented for every bucket that exceeds the cardinality limit.
+    ///
+    /// Tagged by `mode`, which is either `reject` or `strip_tags`.
+    CardinalityLimited,
 }
 
 impl CounterMetric for MetricCounters {
@@ -51,6 +56,7 @@ impl CounterMetric for MetricCounters {
             Self::MergeHit => "metrics.buckets.merge.hit",
             Self::MergeMiss => "metrics.buckets.merge.miss",
             Self::BucketsDropped => "metrics.buckets.dropped",
+            Self::CardinalityLimited => "metrics.buckets.cardinality_limited",
         }
     }
 }
@@ -0,0 +1,207 @@
+//! Monitors protocol and processing for Sentry.
+//!
+//! [Monitors] allow you to monitor the uptime and performance of any scheduled, recurring job in
+//! Sentry. Once implemented, it'll allow you to get alerts and metrics to help you solve errors,
+//! detect timeouts, and prevent disruptions to your service.
+//!
+//! # Protocol
+//!
+//! Each run of a job sends one or more check-ins to Relay. Check-ins are sent as envelope items of
+//! type `check_in` and contain a JSON object:
+//!
+//! ```json
+//! {
+//!     "check_in_id": "c75b7b2a0e8e4eb48b3d8d22d8b8bd3d",
+//!     "monitor_slug": "nightly-backup",
+//!     "status": "ok",
+//!     "environment": "production",
+//!     "duration": 21.0
+//! }
+//! ```
+//!
+//! Jobs usually send an `in_progress` check-in when they start, followed by either an `ok` or an
+//! `error` check-in with the same `check_in_id` when they finish. The `duration` is given in
+//! seconds.
+//!
+//! [monitors]: https://docs.sentry.io/product/crons/
+#![warn(missing_docs)]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/getsentry/relay/master/artwork/relay-icon.png",
+    html_favicon_url = "https://raw.githubusercontent.com/getsentry/relay/master/artwork/relay-icon.png"
+)]
+
+use relay_common::Uuid;
+use serde::{Deserialize, Serialize};
+
+/// Maximum length of monitor slugs.
+const SLUG_LENGTH: usize = 50;
+
+/// Error returned from [`process_check_in`].
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessCheckInError {
+    /// Failed to deserialize the payload.
+    #[error("failed to deserialize check in")]
+    Json(#[from] serde_json::Error),
+
+    /// The monitor slug is empty.
+    #[error("missing monitor slug")]
+    EmptySlug,
+
+    /// The monitor slug exceeds the maximum length.
+    #[error("monitor slug too long")]
+    SlugTooLong,
+
+    /// The status is not known to this Relay.
+    #[error("invalid check-in status")]
+    InvalidStatus,
+
+    /// The duration is negative or not a finite number.
+    #[error("invalid check-in duration")]
+    InvalidDuration,
+}
+
+/// The status of a check-in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckInStatus {
+    /// Check-in had no issues during execution.
+    Ok,
+    /// Check-in failed or otherwise had some issues.
+    Error,
+    /// Check-in is expected to complete.
+    InProgress,
+    /// No status was passed.
+    #[serde(other)]
+    Unknown,
+}
+
+fn uuid_simple<S>(uuid: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_str(&uuid.to_simple())
+}
+
+/// The monitor check-in payload.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CheckIn {
+    /// Unique identifier of this check-in.
+    ///
+    /// Follow-up check-ins of the same job run must send the same identifier. If omitted, a random
+    /// identifier is assigned.
+    #[serde(default = "Uuid::new_v4", serialize_with = "uuid_simple")]
+    pub check_in_id: Uuid,
+
+    /// Identifier of the monitor for this check-in.
+    pub monitor_slug: String,
+
+    /// Status of this check-in.
+    pub status: CheckInStatus,
+
+    /// The environment to associate the check-in with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+
+    /// Duration of this check-in since it has started in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+}
+
+impl CheckIn {
+    /// Validates and normalizes the check-in in place.
+    ///
+    /// The monitor slug is lowercased and trimmed. Empty environments are removed.
+    pub fn normalize(&mut self) -> Result<(), ProcessCheckInError> {
+        self.monitor_slug = self.monitor_slug.trim().to_lowercase();
+        if self.monitor_slug.is_empty() {
+            return Err(ProcessCheckInError::EmptySlug);
+        } else if self.monitor_slug.chars().count() > SLUG_LENGTH {
+            return Err(ProcessCheckInError::SlugTooLong);
+        }
+
+        if self.status == CheckInStatus::Unknown {
+            return Err(ProcessCheckInError::InvalidStatus);
+        }
+
+        if let Some(environment) = self.environment.take() {
+            let trimmed = environment.trim();
+            if !trimmed.is_empty() {
+                self.environment = Some(trimmed.to_owned());
+            }
+        }
+
+        if let Some(duration) = self.duration {
+            if !duration.is_finite() || duration < 0.0 {
+                return Err(ProcessCheckInError::InvalidDuration);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Normalizes a monitor check-in payload.
+pub fn process_check_in(payload: &[u8]) -> Result<Vec<u8>, ProcessCheckInError> {
+    let mut check_in = serde_json::from_slice::<CheckIn>(payload)?;
+    check_in.normalize()?;
+    Ok(serde_json::to_vec(&check_in)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_check_in() {
+        let json = r#"{
+            "check_in_id": "a460c25ff2554577b920fcfacae4e5eb",
+            "monitor_slug": " My-Monitor ",
+            "status": "in_progress",
+            "environment": "production",
+            "duration": 21.0
+        }"#;
+
+        let processed = process_check_in(json.as_bytes()).unwrap();
+        assert_eq!(
+            String::from_utf8(processed).unwrap(),
+            r#"{"check_in_id":"a460c25ff2554577b920fcfacae4e5eb","monitor_slug":"my-monitor","status":"in_progress","environment":"production","duration":21.0}"#
+        );
+    }
+
+    #[test]
+    fn test_process_check_in_minimal() {
+        let json = r#"{"monitor_slug": "my-monitor", "status": "ok", "environment": " "}"#;
+
+        let processed = process_check_in(json.as_bytes()).unwrap();
+        let check_in = serde_json::from_slice::<CheckIn>(&processed).unwrap();
+
+        assert!(!check_in.check_in_id.is_nil());
+        assert_eq!(check_in.monitor_slug, "my-monitor");
+        assert_eq!(check_in.status, CheckInStatus::Ok);
+        assert_eq!(check_in.environment, None);
+        assert_eq!(check_in.duration, None);
+    }
+
+    #[test]
+    fn test_process_check_in_invalid() {
+        let cases = [
+            r#"{"monitor_slug": "my-monitor"}"#,
+            r#"{"monitor_slug": "", "status": "ok"}"#,
+            r#"{"monitor_slug": "my-monitor", "status": "unknown"}"#,
+            r#"{"monitor_slug": "my-monitor", "status": "ok", "duration": -1.0}"#,
+        ];
+
+        for json in cases {
+            assert!(process_check_in(json.as_bytes()).is_err(), "{}", json);
+        }
+
+        let long_slug = format!(
+            r#"{{"monitor_slug": "{}", "status": "ok"}}"#,
+            "a".repeat(51)
+        );
+        assert!(matches!(
+            process_check_in(long_slug.as_bytes()),
+            Err(ProcessCheckInError::SlugTooLong)
+        ));
+    }
+}
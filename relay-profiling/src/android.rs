@@ -4,7 +4,9 @@ use android_trace_log::chrono::{DateTime, Utc};
 use android_trace_log::{AndroidTraceLog, Clock, Time, Vm};
 use serde::{Deserialize, Serialize};
 
+use relay_common::Uuid;
 use relay_general::protocol::EventId;
+use relay_general::store::ProguardLookup;
 
 use crate::measurements::Measurement;
 use crate::transaction_metadata::TransactionMetadata;
@@ -124,6 +126,35 @@ impl AndroidProfile {
         timestamps_per_thread_id.retain(|_, timestamps| timestamps.len() > 1);
         events.retain(|event| timestamps_per_thread_id.contains_key(&event.thread_id));
     }
+
+    /// Deobfuscates class and method names with the ProGuard mapping file of the build.
+    ///
+    /// Does nothing if the build ID is not a valid UUID or there is no mapping file for it.
+    fn deobfuscate(&mut self, lookup: &ProguardLookup) {
+        let uuid = match self.build_id.parse::<Uuid>() {
+            Ok(uuid) => uuid,
+            Err(_) => return,
+        };
+
+        let file = match lookup.load(uuid) {
+            Ok(Some(file)) => file,
+            _ => return,
+        };
+
+        let mapper = match file.mapper() {
+            Some(mapper) => mapper,
+            None => return,
+        };
+
+        for method in &mut self.profile.methods {
+            if let Some((class, name)) = mapper.remap_method(&method.class_name, &method.name) {
+                method.class_name = class.to_owned();
+                method.name = name.to_owned();
+            } else if let Some(class) = mapper.remap_class(&method.class_name) {
+                method.class_name = class.to_owned();
+            }
+        }
+    }
 }
 
 fn parse_profile(payload: &[u8]) -> Result<AndroidProfile, ProfileError> {
@@ -160,8 +191,16 @@ fn parse_profile(payload: &[u8]) -> Result<AndroidProfile, ProfileError> {
     Ok(profile)
 }
 
-pub fn parse_android_profile(payload: &[u8]) -> Result<Vec<u8>, ProfileError> {
-    let profile = parse_profile(payload)?;
+pub fn parse_android_profile(
+    payload: &[u8],
+    proguard: Option<&ProguardLookup>,
+) -> Result<Vec<u8>, ProfileError> {
+    let mut profile = parse_profile(payload)?;
+
+    if let Some(lookup) = proguard {
+        profile.deobfuscate(lookup);
+    }
+
     serde_json::to_vec(&profile).map_err(|_| ProfileError::CannotSerializePayload)
 }
 
@@ -211,13 +250,13 @@ mod tests {
         let profile = parse_profile(payload);
         assert!(profile.is_ok());
         let data = serde_json::to_vec(&profile.unwrap());
-        assert!(parse_android_profile(&(data.unwrap())[..]).is_ok());
+        assert!(parse_android_profile(&(data.unwrap())[..], None).is_ok());
     }
 
     #[test]
     fn test_no_transaction() {
         let payload = include_bytes!("../tests/fixtures/profiles/android/no_transaction.json");
-        let data = parse_android_profile(payload);
+        let data = parse_android_profile(payload, None);
         assert!(data.is_err());
     }
 
@@ -225,7 +264,7 @@ mod tests {
     fn test_remove_invalid_events() {
         let payload =
             include_bytes!("../tests/fixtures/profiles/android/remove_invalid_events.json");
-        let data = parse_android_profile(payload);
+        let data = parse_android_profile(payload, None);
         assert!(data.is_err());
     }
 
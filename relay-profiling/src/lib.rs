@@ -95,6 +95,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use relay_general::store::ProguardLookup;
+
 mod android;
 mod cocoa;
 mod error;
@@ -133,12 +135,15 @@ fn minimal_profile_from_json(data: &[u8]) -> Result<MinimalProfile, ProfileError
     serde_json::from_slice(data).map_err(ProfileError::InvalidJson)
 }
 
-pub fn expand_profile(payload: &[u8]) -> Result<Vec<u8>, ProfileError> {
+pub fn expand_profile(
+    payload: &[u8],
+    proguard: Option<&ProguardLookup>,
+) -> Result<Vec<u8>, ProfileError> {
     let profile: MinimalProfile = minimal_profile_from_json(payload)?;
     match profile.version {
         Version::V1 => parse_sample_profile(payload),
         Version::Unknown => match profile.platform {
-            Platform::Android => parse_android_profile(payload),
+            Platform::Android => parse_android_profile(payload, proguard),
             Platform::Cocoa => parse_cocoa_profile(payload),
             _ => Err(ProfileError::PlatformNotSupported),
         },
@@ -168,14 +173,14 @@ mod tests {
     #[test]
     fn test_expand_profile_with_version() {
         let payload = include_bytes!("../tests/fixtures/profiles/sample/roundtrip.json");
-        let profile = expand_profile(payload);
+        let profile = expand_profile(payload, None);
         assert!(profile.is_ok());
     }
 
     #[test]
     fn test_expand_profile_without_version() {
         let payload = include_bytes!("../tests/fixtures/profiles/cocoa/roundtrip.json");
-        let profile = expand_profile(payload);
+        let profile = expand_profile(payload, None);
         assert!(profile.is_ok());
     }
 }
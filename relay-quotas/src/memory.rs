@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use relay_common::UnixTimestamp;
+
+use crate::quota::{ItemScoping, Quota, QuotaScope};
+use crate::rate_limit::{RateLimit, RateLimits, RetryAfter};
+use crate::REJECT_ALL_SECS;
+
+/// Interval in seconds after which counters of elapsed windows are removed.
+const PURGE_INTERVAL: u64 = 60;
+
+/// Identifies the counter of a quota within a single time window.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CounterKey {
+    /// The quota id.
+    id: String,
+    /// The organization id, which is always part of the key.
+    organization_id: u64,
+    /// The id of the quota's scope, unless the quota is organization-scoped.
+    subscope: Option<u64>,
+    /// The index of the time window.
+    slot: u64,
+}
+
+/// The consumed quantity of a quota within a single time window.
+#[derive(Debug)]
+struct Counter {
+    /// The quantity consumed so far.
+    value: u64,
+    /// The end of the time window, after which this counter can be removed.
+    expiry: UnixTimestamp,
+}
+
+/// Reference to information required for tracking quotas in memory.
+///
+/// Windows are computed the same way as for the Redis rate limiter, so that both implementations
+/// agree on the boundaries of a window.
+#[derive(Debug)]
+struct MemoryQuota<'a> {
+    /// The original quota.
+    quota: &'a Quota,
+    /// Scopes of the item being tracked.
+    scoping: ItemScoping<'a>,
+    /// The quota id.
+    id: &'a str,
+    /// The window in seconds mapped from the quota.
+    window: u64,
+    /// The ingestion timestamp determining the rate limiting bucket.
+    timestamp: UnixTimestamp,
+}
+
+impl<'a> MemoryQuota<'a> {
+    fn new(quota: &'a Quota, scoping: ItemScoping<'a>, timestamp: UnixTimestamp) -> Option<Self> {
+        // These fields indicate that we *can* track this quota.
+        let id = quota.id.as_deref()?;
+        let window = quota.window.filter(|window| *window > 0)?;
+
+        Some(Self {
+            quota,
+            scoping,
+            id,
+            window,
+            timestamp,
+        })
+    }
+
+    fn shift(&self) -> u64 {
+        self.scoping.organization_id % self.window
+    }
+
+    fn slot(&self) -> u64 {
+        (self.timestamp.as_secs() - self.shift()) / self.window
+    }
+
+    fn expiry(&self) -> UnixTimestamp {
+        let next_slot = self.slot() + 1;
+        let next_start = next_slot * self.window + self.shift();
+        UnixTimestamp::from_secs(next_start)
+    }
+
+    fn key(&self) -> CounterKey {
+        let subscope = match self.quota.scope {
+            QuotaScope::Organization => None,
+            scope => self.scoping.scope_id(scope),
+        };
+
+        CounterKey {
+            id: self.id.to_owned(),
+            organization_id: self.scoping.organization_id,
+            subscope,
+            slot: self.slot(),
+        }
+    }
+}
+
+/// Counters of all tracked quotas.
+#[derive(Debug)]
+struct Counters {
+    counters: HashMap<CounterKey, Counter>,
+    last_purge: UnixTimestamp,
+}
+
+impl Counters {
+    fn consumed(&self, key: &CounterKey) -> u64 {
+        self.counters.get(key).map_or(0, |counter| counter.value)
+    }
+
+    /// Removes all counters of time windows that have elapsed.
+    fn purge(&mut self, timestamp: UnixTimestamp) {
+        if timestamp.as_secs() < self.last_purge.as_secs() + PURGE_INTERVAL {
+            return;
+        }
+
+        self.counters
+            .retain(|_, counter| counter.expiry > timestamp);
+        self.last_purge = timestamp;
+    }
+}
+
+/// A rate limiter that executes quotas in memory of the current process.
+///
+/// This implements the same semantics as `RedisRateLimiter`, including quota scopes, data
+/// categories, windows and reason codes. Since counters are not shared, every Relay instance
+/// enforces its quotas independently. This is intended for Relays that cannot access the shared
+/// Redis instance, for example Relays in static mode.
+///
+/// The rate limiter can be cloned cheaply. Clones share the same counters.
+#[derive(Clone, Debug)]
+pub struct MemoryRateLimiter {
+    counters: Arc<Mutex<Counters>>,
+    max_limit: Option<u64>,
+}
+
+impl MemoryRateLimiter {
+    /// Creates a new `MemoryRateLimiter` instance.
+    pub fn new() -> Self {
+        MemoryRateLimiter {
+            counters: Arc::new(Mutex::new(Counters {
+                counters: HashMap::new(),
+                last_purge: UnixTimestamp::from_secs(0),
+            })),
+            max_limit: None,
+        }
+    }
+
+    /// Sets the maximum rate limit in seconds.
+    ///
+    /// By default, this rate limiter will return rate limits based on the quotas' `window` fields.
+    /// If a maximum rate limit is set, this limit is bounded.
+    pub fn max_limit(mut self, max_limit: Option<u64>) -> Self {
+        self.max_limit = max_limit;
+        self
+    }
+
+    /// Checks whether any of the quotas in effect for the given project and project key has been
+    /// exceeded and records consumption of the quota.
+    ///
+    /// See `RedisRateLimiter::is_rate_limited` for a description of the parameters. Quantities are
+    /// only counted against the quotas if none of them has been exceeded.
+    pub fn is_rate_limited(
+        &self,
+        quotas: &[Quota],
+        item_scoping: ItemScoping<'_>,
+        quantity: usize,
+        over_accept_once: bool,
+    ) -> RateLimits {
+        self.is_rate_limited_at(
+            UnixTimestamp::now(),
+            quotas,
+            item_scoping,
+            quantity,
+            over_accept_once,
+        )
+    }
+
+    fn is_rate_limited_at(
+        &self,
+        timestamp: UnixTimestamp,
+        quotas: &[Quota],
+        item_scoping: ItemScoping<'_>,
+        quantity: usize,
+        over_accept_once: bool,
+    ) -> RateLimits {
+        let mut tracked_quotas = Vec::new();
+        let mut rate_limits = RateLimits::new();
+
+        for quota in quotas {
+            if !quota.matches(item_scoping) {
+                // Silently skip all quotas that do not apply to this item.
+            } else if quota.limit == Some(0) {
+                // A zero-sized quota is strongest. Do not increment any counters, as one quota has
+                // reached capacity.
+                let retry_after = self.retry_after(REJECT_ALL_SECS);
+                rate_limits.add(RateLimit::from_quota(quota, &item_scoping, retry_after));
+            } else if let Some(quota) = MemoryQuota::new(quota, item_scoping, timestamp) {
+                tracked_quotas.push(quota);
+            }
+            // Quotas that can neither be tracked nor reject all are skipped for
+            // forward-compatibility.
+        }
+
+        if tracked_quotas.is_empty() || rate_limits.is_limited() {
+            return rate_limits;
+        }
+
+        let quantity = quantity as u64;
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        counters.purge(timestamp);
+
+        let keys: Vec<_> = tracked_quotas.iter().map(MemoryQuota::key).collect();
+
+        for (quota, key) in tracked_quotas.iter().zip(&keys) {
+            let limit = match quota.quota.limit {
+                Some(limit) => u64::from(limit),
+                None => continue,
+            };
+
+            let consumed = counters.consumed(key);
+            let rejected = if quantity == 0 || over_accept_once {
+                consumed >= limit
+            } else {
+                consumed + quantity > limit
+            };
+
+            if rejected {
+                let retry_after = self.retry_after((quota.expiry() - timestamp).as_secs());
+                rate_limits.add(RateLimit::from_quota(
+                    quota.quota,
+                    &item_scoping,
+                    retry_after,
+                ));
+            }
+        }
+
+        if rate_limits.is_ok() && quantity > 0 {
+            for (quota, key) in tracked_quotas.iter().zip(keys) {
+                let expiry = quota.expiry();
+                counters
+                    .counters
+                    .entry(key)
+                    .or_insert(Counter { value: 0, expiry })
+                    .value += quantity;
+            }
+        }
+
+        rate_limits
+    }
+
+    /// Creates a rate limit bounded by `max_limit`.
+    fn retry_after(&self, mut seconds: u64) -> RetryAfter {
+        if let Some(max_limit) = self.max_limit {
+            seconds = std::cmp::min(seconds, max_limit);
+        }
+
+        RetryAfter::from_secs(seconds)
+    }
+}
+
+impl Default for MemoryRateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use relay_common::{ProjectId, ProjectKey};
+
+    use crate::quota::{DataCategories, DataCategory, ReasonCode, Scoping};
+    use crate::rate_limit::RateLimitScope;
+
+    use super::*;
+
+    fn scoping() -> Scoping {
+        Scoping {
+            organization_id: 42,
+            project_id: ProjectId::new(43),
+            project_key: ProjectKey::parse("a94ae32be2584e0bbd7a4cbb95971fee").unwrap(),
+            key_id: Some(44),
+        }
+    }
+
+    fn quota(id: &str, scope: QuotaScope, limit: u32) -> Quota {
+        Quota {
+            id: Some(id.to_owned()),
+            categories: DataCategories::new(),
+            scope,
+            scope_id: None,
+            limit: Some(limit),
+            window: Some(60),
+            reason_code: Some(ReasonCode::new(id)),
+        }
+    }
+
+    #[test]
+    fn test_zero_size_quotas() {
+        let quotas = &[Quota {
+            id: None,
+            categories: DataCategories::new(),
+            scope: QuotaScope::Organization,
+            scope_id: None,
+            limit: Some(0),
+            window: None,
+            reason_code: Some(ReasonCode::new("get_lost")),
+        }];
+
+        let scoping = scoping();
+        let rate_limits: Vec<RateLimit> = MemoryRateLimiter::new()
+            .is_rate_limited(quotas, scoping.item(DataCategory::Error), 1, false)
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            rate_limits,
+            vec![RateLimit {
+                categories: DataCategories::new(),
+                scope: RateLimitScope::Organization(42),
+                reason_code: Some(ReasonCode::new("get_lost")),
+                retry_after: rate_limits[0].retry_after,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_simple_quota() {
+        let quotas = &[quota("foo", QuotaScope::Organization, 5)];
+        let scoping = scoping();
+        let item_scoping = scoping.item(DataCategory::Error);
+        let timestamp = UnixTimestamp::from_secs(120);
+
+        let rate_limiter = MemoryRateLimiter::new();
+
+        for i in 0..10 {
+            let rate_limits =
+                rate_limiter.is_rate_limited_at(timestamp, quotas, item_scoping, 1, false);
+
+            if i >= 5 {
+                let limit = rate_limits.longest().expect("expected a rate limit");
+                assert_eq!(limit.reason_code, Some(ReasonCode::new("foo")));
+                assert_eq!(limit.scope, RateLimitScope::Organization(42));
+            } else {
+                assert!(rate_limits.is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_quota_window() {
+        let quotas = &[quota("foo", QuotaScope::Organization, 1)];
+        let scoping = scoping();
+        let item_scoping = scoping.item(DataCategory::Error);
+
+        let rate_limiter = MemoryRateLimiter::new();
+
+        // Organization 42 shifts windows by 42 seconds, so [102, 162) is a single window.
+        let start = UnixTimestamp::from_secs(102);
+        let end = UnixTimestamp::from_secs(162);
+
+        assert!(rate_limiter
+            .is_rate_limited_at(start, quotas, item_scoping, 1, false)
+            .is_ok());
+
+        let limits = rate_limiter.is_rate_limited_at(start, quotas, item_scoping, 1, false);
+        let limit = limits.longest().expect("expected a rate limit");
+        assert!(limit.retry_after.remaining_seconds() <= 60);
+
+        assert!(rate_limiter
+            .is_rate_limited_at(end, quotas, item_scoping, 1, false)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_quota_scopes() {
+        let quotas = &[quota("foo", QuotaScope::Key, 1)];
+        let scoping = scoping();
+        let other_scoping = Scoping {
+            key_id: Some(45),
+            ..scoping
+        };
+        let timestamp = UnixTimestamp::from_secs(120);
+
+        let rate_limiter = MemoryRateLimiter::new();
+
+        let check = |scoping: &Scoping| {
+            rate_limiter.is_rate_limited_at(
+                timestamp,
+                quotas,
+                scoping.item(DataCategory::Error),
+                1,
+                false,
+            )
+        };
+
+        assert!(check(&scoping).is_ok());
+        assert!(check(&other_scoping).is_ok());
+
+        let limits = check(&scoping);
+        let limit = limits.longest().expect("expected a rate limit");
+        assert_eq!(limit.scope, RateLimitScope::Key(scoping.project_key));
+    }
+
+    #[test]
+    fn test_quota_categories() {
+        let quotas = &[Quota {
+            categories: DataCategories::from_slice(&[DataCategory::Transaction]),
+            ..quota("foo", QuotaScope::Project, 1)
+        }];
+        let scoping = scoping();
+        let timestamp = UnixTimestamp::from_secs(120);
+
+        let rate_limiter = MemoryRateLimiter::new();
+
+        for _ in 0..2 {
+            assert!(rate_limiter
+                .is_rate_limited_at(
+                    timestamp,
+                    quotas,
+                    scoping.item(DataCategory::Error),
+                    1,
+                    false
+                )
+                .is_ok());
+        }
+
+        assert!(rate_limiter
+            .is_rate_limited_at(
+                timestamp,
+                quotas,
+                scoping.item(DataCategory::Transaction),
+                1,
+                false
+            )
+            .is_ok());
+
+        assert!(rate_limiter
+            .is_rate_limited_at(
+                timestamp,
+                quotas,
+                scoping.item(DataCategory::Transaction),
+                1,
+                false
+            )
+            .is_limited());
+    }
+
+    #[test]
+    fn test_rejected_quantity_not_counted() {
+        let quotas = &[
+            quota("foo", QuotaScope::Organization, 10),
+            quota("bar", QuotaScope::Project, 2),
+        ];
+        let scoping = scoping();
+        let item_scoping = scoping.item(DataCategory::Error);
+        let timestamp = UnixTimestamp::from_secs(120);
+
+        let rate_limiter = MemoryRateLimiter::new();
+
+        // Rejected by "bar", so neither counter is incremented.
+        let limits = rate_limiter.is_rate_limited_at(timestamp, quotas, item_scoping, 3, false);
+        assert!(limits.is_limited());
+
+        let limits = rate_limiter.is_rate_limited_at(timestamp, quotas, item_scoping, 2, false);
+        assert!(limits.is_ok());
+
+        let limits = rate_limiter.is_rate_limited_at(timestamp, quotas, item_scoping, 0, false);
+        let limit = limits.longest().expect("expected a rate limit");
+        assert_eq!(limit.reason_code, Some(ReasonCode::new("bar")));
+    }
+
+    #[test]
+    fn test_over_accept_once() {
+        let quotas = &[quota("foo", QuotaScope::Organization, 1)];
+        let scoping = scoping();
+        let item_scoping = scoping.item(DataCategory::Error);
+        let timestamp = UnixTimestamp::from_secs(120);
+
+        let rate_limiter = MemoryRateLimiter::new();
+
+        let limits = rate_limiter.is_rate_limited_at(timestamp, quotas, item_scoping, 5, true);
+        assert!(limits.is_ok());
+
+        let limits = rate_limiter.is_rate_limited_at(timestamp, quotas, item_scoping, 0, false);
+        assert!(limits.is_limited());
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let quotas = &[quota("foo", QuotaScope::Organization, 1)];
+        let scoping = scoping();
+        let item_scoping = scoping.item(DataCategory::Error);
+
+        let rate_limiter = MemoryRateLimiter::new();
+        let count = || rate_limiter.counters.lock().unwrap().counters.len();
+
+        rate_limiter.is_rate_limited_at(
+            UnixTimestamp::from_secs(120),
+            quotas,
+            item_scoping,
+            1,
+            false,
+        );
+        assert_eq!(count(), 1);
+
+        rate_limiter.is_rate_limited_at(
+            UnixTimestamp::from_secs(600),
+            quotas,
+            item_scoping,
+            1,
+            false,
+        );
+        assert_eq!(count(), 1);
+    }
+}
@@ -107,7 +107,10 @@ impl CategoryUnit {
             | DataCategory::Security
             | DataCategory::Profile
             | DataCategory::TransactionProcessed
-            | DataCategory::TransactionIndexed => Some(Self::Count),
+            | DataCategory::TransactionIndexed
+            | DataCategory::Monitor
+            | DataCategory::MetricBucket
+            | DataCategory::Span => Some(Self::Count),
             DataCategory::Attachment => Some(Self::Bytes),
             DataCategory::Session => Some(Self::Batched),
             DataCategory::Unknown => None,
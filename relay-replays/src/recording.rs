@@ -3,7 +3,7 @@ use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 use std::io::{Read, Write};
 
-use relay_general::pii::{PiiConfig, PiiProcessor};
+use relay_general::pii::{CompiledPiiConfig, PiiConfig, PiiProcessor};
 use relay_general::processor::{
     FieldAttrs, Pii, ProcessingState, Processor, SelectorSpec, ValueType,
 };
@@ -13,11 +13,29 @@ use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 use serde::{de::Error as DError, Deserialize, Serialize};
 use serde_json::Value;
 
+/// Field attributes of all values in a recording that are subject to data scrubbing.
+static PII_ATTRS: FieldAttrs = FieldAttrs::new().pii(Pii::True);
+
 /// Parses compressed replay recording payloads and applies data scrubbers.
 ///
 /// `limit` controls the maximum size in bytes during decompression. This function returns an `Err`
 /// if decompressed contents exceed the limit.
-pub fn process_recording(bytes: &[u8], limit: usize) -> Result<Vec<u8>, RecordingParseError> {
+///
+/// The default `@common` rules are always applied. Additionally, `pii_configs` are applied in the
+/// given order, which are usually the project's PII config and the PII config converted from its
+/// data scrubbing settings. Rules can select values in the recording with these selectors:
+///
+///  - `textContent`: Text nodes of the DOM and values of input fields. The legacy `text` selector
+///    matches all strings instead.
+///  - `attributes.<name>`: Attributes of DOM elements, for example `attributes.title`.
+///  - `$breadcrumb.message`: Messages of custom breadcrumb events.
+///
+/// Values that are removed by a rule are replaced with an empty string.
+pub fn process_recording(
+    bytes: &[u8],
+    limit: usize,
+    pii_configs: &[&CompiledPiiConfig],
+) -> Result<Vec<u8>, RecordingParseError> {
     // Check for null byte condition.
     if bytes.is_empty() {
         return Err(RecordingParseError::Message("no data found"));
@@ -34,7 +52,7 @@ pub fn process_recording(bytes: &[u8], limit: usize) -> Result<Vec<u8>, Recordin
     };
 
     let mut events = deserialize_compressed(body, limit)?;
-    strip_pii(&mut events).map_err(RecordingParseError::ProcessingAction)?;
+    strip_pii(&mut events, pii_configs).map_err(RecordingParseError::ProcessingAction)?;
     let out_bytes = serialize_compressed(events)?;
     Ok([header.into(), vec![b'\n'], out_bytes].concat())
 }
@@ -60,13 +78,20 @@ fn serialize_compressed(rrweb: Vec<Event>) -> Result<Vec<u8>, RecordingParseErro
     Ok(result)
 }
 
-fn strip_pii(events: &mut Vec<Event>) -> Result<(), ProcessingAction> {
+fn strip_pii(
+    events: &mut Vec<Event>,
+    pii_configs: &[&CompiledPiiConfig],
+) -> Result<(), ProcessingAction> {
     let mut pii_config = PiiConfig::default();
     pii_config.applications =
         BTreeMap::from([(SelectorSpec::And(vec![]), vec!["@common".to_string()])]);
 
-    let pii_processor = PiiProcessor::new(pii_config.compiled());
-    let mut processor = RecordingProcessor::new(pii_processor);
+    let pii_processors = std::iter::once(pii_config.compiled())
+        .chain(pii_configs.iter().copied())
+        .map(PiiProcessor::new)
+        .collect();
+
+    let mut processor = RecordingProcessor::new(pii_processors);
     processor.mask_pii(events)?;
 
     Ok(())
@@ -110,12 +135,12 @@ impl From<std::io::Error> for RecordingParseError {
 // Recording Processor
 
 struct RecordingProcessor<'a> {
-    pii_processor: PiiProcessor<'a>,
+    pii_processors: Vec<PiiProcessor<'a>>,
 }
 
-impl RecordingProcessor<'_> {
-    fn new(pii_processor: PiiProcessor) -> RecordingProcessor {
-        RecordingProcessor { pii_processor }
+impl<'a> RecordingProcessor<'a> {
+    fn new(pii_processors: Vec<PiiProcessor<'a>>) -> Self {
+        RecordingProcessor { pii_processors }
     }
 
     fn mask_pii(&mut self, events: &mut Vec<Event>) -> Result<(), ProcessingAction> {
@@ -143,7 +168,7 @@ impl RecordingProcessor<'_> {
                     }
                 }
             }
-            IncrementalSourceDataVariant::Input(input) => self.strip_pii(&mut input.text)?,
+            IncrementalSourceDataVariant::Input(input) => self.strip_text(&mut input.text)?,
             _ => {}
         }
 
@@ -159,7 +184,7 @@ impl RecordingProcessor<'_> {
             }
             NodeVariant::T2(element) => self.recurse_element(element)?,
             NodeVariant::Rest(text) => {
-                self.strip_pii(&mut text.text_content)?;
+                self.strip_text(&mut text.text_content)?;
             }
             _ => {}
         }
@@ -171,7 +196,19 @@ impl RecordingProcessor<'_> {
         match &mut event.data {
             CustomEventDataVariant::Breadcrumb(breadcrumb) => match &mut breadcrumb.payload.message
             {
-                Some(message) => self.strip_pii(message)?,
+                Some(message) => {
+                    let state = ProcessingState::root().enter_static(
+                        "breadcrumb",
+                        Some(Cow::Borrowed(&PII_ATTRS)),
+                        Some(ValueType::Breadcrumb),
+                    );
+                    let state = state.enter_static(
+                        "message",
+                        Some(Cow::Borrowed(&PII_ATTRS)),
+                        Some(ValueType::String),
+                    );
+                    self.strip_pii(message, &state)?
+                }
                 None => {}
             },
             CustomEventDataVariant::PerformanceSpan(_) => {}
@@ -186,9 +223,32 @@ impl RecordingProcessor<'_> {
             "img" | "source" => {
                 let attrs = &mut element.attributes;
                 attrs.insert("src".to_string(), "#".to_string());
+                self.strip_attributes(element)?;
+                self.recurse_element_children(element)?
+            }
+            _ => {
+                self.strip_attributes(element)?;
                 self.recurse_element_children(element)?
             }
-            _ => self.recurse_element_children(element)?,
+        }
+
+        Ok(())
+    }
+
+    fn strip_attributes(&mut self, element: &mut ElementNode) -> Result<(), ProcessingAction> {
+        let state = ProcessingState::root().enter_static(
+            "attributes",
+            Some(Cow::Borrowed(&PII_ATTRS)),
+            Some(ValueType::Object),
+        );
+
+        for (name, value) in &mut element.attributes {
+            let state = state.enter_borrowed(
+                name,
+                Some(Cow::Borrowed(&PII_ATTRS)),
+                Some(ValueType::String),
+            );
+            self.strip_pii(value, &state)?;
         }
 
         Ok(())
@@ -205,12 +265,30 @@ impl RecordingProcessor<'_> {
         Ok(())
     }
 
-    fn strip_pii(&mut self, value: &mut String) -> Result<(), ProcessingAction> {
-        let field_attrs = Cow::Owned(FieldAttrs::new().pii(Pii::True));
-        let processing_state =
-            ProcessingState::root().enter_static("", Some(field_attrs), Some(ValueType::String));
-        self.pii_processor
-            .process_string(value, &mut Meta::default(), &processing_state)?;
+    fn strip_text(&mut self, value: &mut String) -> Result<(), ProcessingAction> {
+        let state = ProcessingState::root().enter_static(
+            "textContent",
+            Some(Cow::Borrowed(&PII_ATTRS)),
+            Some(ValueType::String),
+        );
+        self.strip_pii(value, &state)
+    }
+
+    fn strip_pii(
+        &mut self,
+        value: &mut String,
+        state: &ProcessingState<'_>,
+    ) -> Result<(), ProcessingAction> {
+        for pii_processor in &mut self.pii_processors {
+            match pii_processor.process_string(value, &mut Meta::default(), state) {
+                Ok(()) => (),
+                Err(ProcessingAction::DeleteValueHard | ProcessingAction::DeleteValueSoft) => {
+                    value.clear();
+                    break;
+                }
+                Err(action) => return Err(action),
+            }
+        }
 
         Ok(())
     }
@@ -563,6 +641,7 @@ mod tests {
     use crate::recording;
     use crate::recording::Event;
     use assert_json_diff::assert_json_eq;
+    use relay_general::pii::PiiConfig;
     use serde_json::{Error, Value};
 
     fn loads(bytes: &[u8]) -> Result<Vec<Event>, Error> {
@@ -591,7 +670,7 @@ mod tests {
             146, 59, 13, 115, 10, 144, 115, 190, 126, 0, 2, 68, 180, 16,
         ];
 
-        let result = recording::process_recording(payload, 1000);
+        let result = recording::process_recording(payload, 1000, &[]);
         assert!(!result.unwrap().is_empty());
     }
 
@@ -602,7 +681,7 @@ mod tests {
             123, 34, 115, 101, 103, 109, 101, 110, 116, 95, 105, 100, 34, 58, 51, 125, 10,
         ];
 
-        let result = recording::process_recording(payload, 1000);
+        let result = recording::process_recording(payload, 1000, &[]);
         assert!(matches!(
             result.unwrap_err(),
             recording::RecordingParseError::Message("no body found"),
@@ -616,7 +695,7 @@ mod tests {
             123, 34, 115, 101, 103, 109, 101, 110, 116, 95, 105, 100, 34, 58, 51, 125, 10, 22,
         ];
 
-        let result = recording::process_recording(payload, 1000);
+        let result = recording::process_recording(payload, 1000, &[]);
         assert!(matches!(
             result.unwrap_err(),
             recording::RecordingParseError::Compression(_),
@@ -630,7 +709,7 @@ mod tests {
             123, 34, 115, 101, 103, 109, 101, 110, 116, 95, 105, 100, 34, 58, 51, 125,
         ];
 
-        let result = recording::process_recording(payload, 1000);
+        let result = recording::process_recording(payload, 1000, &[]);
         assert!(matches!(
             result.unwrap_err(),
             recording::RecordingParseError::Message("no body found"),
@@ -642,7 +721,7 @@ mod tests {
         // Empty payload can not be decompressed.  Header check never fails.
         let payload: &[u8] = &[];
 
-        let result = recording::process_recording(payload, 1000);
+        let result = recording::process_recording(payload, 1000, &[]);
         assert!(matches!(
             result.unwrap_err(),
             recording::RecordingParseError::Message("no data found"),
@@ -656,7 +735,7 @@ mod tests {
         let payload = include_bytes!("../tests/fixtures/rrweb-pii.json");
         let mut events: Vec<Event> = serde_json::from_slice(payload).unwrap();
 
-        recording::strip_pii(&mut events).unwrap();
+        recording::strip_pii(&mut events, &[]).unwrap();
 
         let aa = events.pop().unwrap();
         if let recording::Event::T3(bb) = aa {
@@ -679,7 +758,7 @@ mod tests {
         let payload = include_bytes!("../tests/fixtures/rrweb-pii-ip-address.json");
         let mut events: Vec<Event> = serde_json::from_slice(payload).unwrap();
 
-        recording::strip_pii(&mut events).unwrap();
+        recording::strip_pii(&mut events, &[]).unwrap();
 
         let aa = events.pop().unwrap();
         if let recording::Event::T3(bb) = aa {
@@ -697,6 +776,76 @@ mod tests {
         unreachable!();
     }
 
+    #[test]
+    fn test_pii_project_config() {
+        let pii_config = PiiConfig::from_json(
+            r#"{
+                "rules": {
+                    "ticket": {
+                        "type": "pattern",
+                        "pattern": "TICKET-\\d+",
+                        "redaction": {
+                            "method": "replace",
+                            "text": "[ticket]"
+                        }
+                    }
+                },
+                "applications": {
+                    "textContent": ["ticket"],
+                    "attributes.title": ["ticket"],
+                    "$breadcrumb.message": ["ticket"],
+                    "attributes.data-account": ["@anything:remove"]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let payload = br#"[
+            {
+                "type": 2,
+                "timestamp": 1,
+                "data": {
+                    "node": {
+                        "type": 2,
+                        "id": 1,
+                        "tagName": "h1",
+                        "attributes": {
+                            "id": "TICKET-1",
+                            "title": "TICKET-2",
+                            "data-account": "12345"
+                        },
+                        "childNodes": [{"type": 3, "id": 2, "textContent": "see TICKET-3"}]
+                    },
+                    "initialOffset": {"top": 0, "left": 0}
+                }
+            },
+            {
+                "type": 5,
+                "timestamp": 1.0,
+                "data": {
+                    "tag": "breadcrumb",
+                    "payload": {
+                        "type": "default",
+                        "timestamp": 1.0,
+                        "category": "console",
+                        "message": "TICKET-4"
+                    }
+                }
+            }
+        ]"#;
+
+        let mut events = loads(payload).unwrap();
+        recording::strip_pii(&mut events, &[pii_config.compiled()]).unwrap();
+
+        let value = serde_json::to_value(&events).unwrap();
+        let node = &value[0]["data"]["node"];
+        assert_eq!(node["attributes"]["id"], "TICKET-1");
+        assert_eq!(node["attributes"]["title"], "[ticket]");
+        assert_eq!(node["attributes"]["data-account"], "");
+        assert_eq!(node["childNodes"][0]["textContent"], "see [ticket]");
+        assert_eq!(value[1]["data"]["payload"]["message"], "[ticket]");
+    }
+
     #[test]
     fn test_rrweb_snapshot_parsing() {
         let payload = include_bytes!("../tests/fixtures/rrweb.json");
@@ -5,7 +5,7 @@
 )]
 
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 use std::fmt::{self, Display, Formatter};
 use std::net::IpAddr;
 
@@ -13,12 +13,19 @@ use chrono::{DateTime, Utc};
 use rand::{distributions::Uniform, Rng};
 use rand_pcg::Pcg32;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use serde_json::{Number, Value};
+use serde_json::Value;
 
 use relay_common::{EventType, ProjectKey, Uuid};
-use relay_filter::GlobPatterns;
 use relay_general::protocol::{Context, Event, TraceContext};
-use relay_general::store;
+
+pub use relay_filter::condition::{
+    AndCondition, ConditionExplanation, CustomCondition, EqCondOptions, EqCondition,
+    FieldValueProvider, GlobCondition, NotCondition, OrCondition, RuleCondition,
+};
+
+mod reservoir;
+
+pub use reservoir::*;
 
 /// Defines the type of dynamic rule, i.e. to which type of events it will be applied and how.
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -37,283 +44,6 @@ pub enum RuleType {
     Unsupported,
 }
 
-/// A condition that checks the values using the equality operator.
-///
-/// For string values it supports case-insensitive comparison.
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
-pub struct EqCondOptions {
-    #[serde(default)]
-    pub ignore_case: bool,
-}
-
-/// A condition that checks for equality
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct EqCondition {
-    pub name: String,
-    pub value: Value,
-    #[serde(default)]
-    pub options: EqCondOptions,
-}
-
-impl EqCondition {
-    fn matches<T>(&self, value_provider: &T) -> bool
-    where
-        T: FieldValueProvider,
-    {
-        let value = value_provider.get_value(self.name.as_str());
-
-        match value {
-            Value::Null => self.value == Value::Null,
-            Value::String(ref field) => match self.value {
-                Value::String(ref val) => {
-                    if self.options.ignore_case {
-                        unicase::eq(field.as_str(), val.as_str())
-                    } else {
-                        field == val
-                    }
-                }
-                Value::Array(ref val) => {
-                    if self.options.ignore_case {
-                        val.iter().any(|v| {
-                            if let Some(v) = v.as_str() {
-                                unicase::eq(v, field.as_str())
-                            } else {
-                                false
-                            }
-                        })
-                    } else {
-                        val.iter().any(|v| {
-                            if let Some(v) = v.as_str() {
-                                v == field.as_str()
-                            } else {
-                                false
-                            }
-                        })
-                    }
-                }
-                _ => false,
-            },
-            Value::Bool(field) => {
-                if let Value::Bool(val) = self.value {
-                    field == val
-                } else {
-                    false
-                }
-            }
-            _ => false, // unsupported types
-        }
-    }
-}
-
-macro_rules! impl_cmp_condition {
-    ($struct_name:ident, $operator:tt) => {
-        #[derive(Debug, Clone, Serialize, Deserialize)]
-        pub struct $struct_name {
-            pub name: String,
-            pub value: Number,
-        }
-
-        impl $struct_name {
-            fn matches<T>(&self, value_provider: &T) -> bool where T: FieldValueProvider{
-                let value = match value_provider.get_value(self.name.as_str()) {
-                    Value::Number(x) => x,
-                    _ => return false
-                };
-
-                // Try various conversion functions in order of expensiveness and likelihood
-                // - as_i64 is not really fast, but most values in sampling rules can be i64, so we could
-                //   return early
-                // - f64 is more likely to succeed than u64, but we might lose precision
-                if let (Some(a), Some(b)) = (value.as_i64(), self.value.as_i64()) {
-                    a $operator b
-                } else if let (Some(a), Some(b)) = (value.as_u64(), self.value.as_u64()) {
-                    a $operator b
-                } else if let (Some(a), Some(b)) = (value.as_f64(), self.value.as_f64()) {
-                    a $operator b
-                } else {
-                    false
-                }
-            }
-        }
-    }
-}
-
-impl_cmp_condition!(GteCondition, >=);
-impl_cmp_condition!(LteCondition, <=);
-impl_cmp_condition!(LtCondition, <);
-impl_cmp_condition!(GtCondition, >);
-
-/// A condition that uses glob matching.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GlobCondition {
-    pub name: String,
-    pub value: GlobPatterns,
-}
-
-impl GlobCondition {
-    fn matches<T>(&self, value_provider: &T) -> bool
-    where
-        T: FieldValueProvider,
-    {
-        value_provider
-            .get_value(self.name.as_str())
-            .as_str()
-            .map_or(false, |fv| self.value.is_match(fv))
-    }
-}
-
-/// Condition that cover custom operators which need
-/// special handling and have a custom implementation
-/// for each case.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CustomCondition {
-    pub name: String,
-    #[serde(default)]
-    pub value: Value,
-    #[serde(default)]
-    pub options: HashMap<String, Value>,
-}
-
-impl CustomCondition {
-    fn matches<T>(&self, value_provider: &T, ip_addr: Option<IpAddr>) -> bool
-    where
-        T: FieldValueProvider,
-    {
-        T::get_custom_operator(&self.name)(self, value_provider, ip_addr)
-    }
-}
-
-/// Or condition combinator.
-///
-/// Creates a condition that is true when any
-/// of the inner conditions are true
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct OrCondition {
-    inner: Vec<RuleCondition>,
-}
-
-impl OrCondition {
-    fn supported(&self) -> bool {
-        self.inner.iter().all(RuleCondition::supported)
-    }
-
-    fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
-    where
-        T: FieldValueProvider,
-    {
-        self.inner.iter().any(|cond| cond.matches(value, ip_addr))
-    }
-}
-
-/// And condition combinator.
-///
-/// Creates a condition that is true when all
-/// inner conditions are true.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AndCondition {
-    inner: Vec<RuleCondition>,
-}
-
-impl AndCondition {
-    fn supported(&self) -> bool {
-        self.inner.iter().all(RuleCondition::supported)
-    }
-    fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
-    where
-        T: FieldValueProvider,
-    {
-        self.inner.iter().all(|cond| cond.matches(value, ip_addr))
-    }
-}
-
-/// Not condition combinator.
-///
-/// Creates a condition that is true when the wrapped
-/// condition si false.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NotCondition {
-    inner: Box<RuleCondition>,
-}
-
-impl NotCondition {
-    fn supported(&self) -> bool {
-        self.inner.supported()
-    }
-
-    fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
-    where
-        T: FieldValueProvider,
-    {
-        !self.inner.matches(value, ip_addr)
-    }
-}
-
-/// A condition from a sampling rule.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase", tag = "op")]
-pub enum RuleCondition {
-    Eq(EqCondition),
-    Gte(GteCondition),
-    Lte(LteCondition),
-    Lt(LtCondition),
-    Gt(GtCondition),
-    Glob(GlobCondition),
-    Or(OrCondition),
-    And(AndCondition),
-    Not(NotCondition),
-    Custom(CustomCondition),
-    #[serde(other)]
-    Unsupported,
-}
-
-impl RuleCondition {
-    /// Returns a condition that matches everything.
-    pub fn all() -> Self {
-        Self::And(AndCondition { inner: Vec::new() })
-    }
-
-    /// Checks if Relay supports this condition (in other words if the condition had any unknown configuration
-    /// which was serialized as "Unsupported" (because the configuration is either faulty or was created for a
-    /// newer relay that supports some other condition types)
-    pub fn supported(&self) -> bool {
-        match self {
-            RuleCondition::Unsupported => false,
-            // we have a known condition
-            RuleCondition::Gte(_)
-            | RuleCondition::Lte(_)
-            | RuleCondition::Gt(_)
-            | RuleCondition::Lt(_)
-            | RuleCondition::Eq(_)
-            | RuleCondition::Glob(_) => true,
-            // dig down for embedded conditions
-            RuleCondition::And(rules) => rules.supported(),
-            RuleCondition::Or(rules) => rules.supported(),
-            RuleCondition::Not(rule) => rule.supported(),
-            RuleCondition::Custom(_) => true,
-        }
-    }
-    pub fn matches<T>(&self, value: &T, ip_addr: Option<IpAddr>) -> bool
-    where
-        T: FieldValueProvider,
-    {
-        match self {
-            RuleCondition::Eq(condition) => condition.matches(value),
-            RuleCondition::Lte(condition) => condition.matches(value),
-            RuleCondition::Gte(condition) => condition.matches(value),
-            RuleCondition::Gt(condition) => condition.matches(value),
-            RuleCondition::Lt(condition) => condition.matches(value),
-            RuleCondition::Glob(condition) => condition.matches(value),
-            RuleCondition::And(conditions) => conditions.matches(value, ip_addr),
-            RuleCondition::Or(conditions) => conditions.matches(value, ip_addr),
-            RuleCondition::Not(condition) => condition.matches(value, ip_addr),
-            RuleCondition::Unsupported => false,
-            RuleCondition::Custom(condition) => condition.matches(value, ip_addr),
-        }
-    }
-}
-
 /// Sampling rule Id
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct RuleId(pub u32);
@@ -386,238 +116,44 @@ impl SamplingRule {
     }
 }
 
-/// Trait implemented by providers of fields (Events and Trace Contexts).
+/// A rule that keeps a minimum number of matching events per time window.
 ///
-/// The fields will be used by rules to check if they apply.
-pub trait FieldValueProvider {
-    /// gets the value of a field
-    fn get_value(&self, path: &str) -> Value;
-    /// what type of rule can be applied to this provider
-    fn get_rule_type(&self) -> RuleType;
-    /// returns a filtering function for custom operators.
-    /// The function returned takes the provider and a condition definition and
-    /// returns a match result
-    fn get_custom_operator(
-        name: &str,
-    ) -> fn(condition: &CustomCondition, slf: &Self, ip_addr: Option<IpAddr>) -> bool;
-}
-
-fn no_match<T>(_condition: &CustomCondition, _slf: &T, _ip_addr: Option<IpAddr>) -> bool {
-    false
-}
-
-impl FieldValueProvider for Event {
-    fn get_value(&self, field_name: &str) -> Value {
-        let field_name = match field_name.strip_prefix("event.") {
-            Some(stripped) => stripped,
-            None => return Value::Null,
-        };
-
-        match field_name {
-            // Simple fields
-            "release" => match self.release.value() {
-                None => Value::Null,
-                Some(s) => s.as_str().into(),
-            },
-            "environment" => match self.environment.value() {
-                None => Value::Null,
-                Some(s) => s.as_str().into(),
-            },
-            "transaction" => match self.transaction.value() {
-                None => Value::Null,
-                Some(s) => s.as_str().into(),
-            },
-            "platform" => match self.platform.value() {
-                Some(platform) if store::is_valid_platform(platform) => {
-                    Value::String(platform.clone())
-                }
-                _ => Value::from("other"),
-            },
-            "user.id" => self.user.value().map_or(Value::Null, |user| {
-                user.id.value().map_or(Value::Null, |id| {
-                    if id.is_empty() {
-                        Value::Null // we don't serialize empty values but check it anyway
-                    } else {
-                        id.as_str().into()
-                    }
-                })
-            }),
-            "user.segment" => self.user.value().map_or(Value::Null, |user| {
-                user.segment.value().map_or(Value::Null, |segment| {
-                    if segment.is_empty() {
-                        Value::Null
-                    } else {
-                        segment.as_str().into()
-                    }
-                })
-            }),
-
-            // Partial implementation of contexts.
-            "contexts.device.name" => self
-                .contexts
-                .value()
-                .and_then(|contexts| contexts.get("device"))
-                .and_then(|annotated| annotated.value())
-                .and_then(|context| match context.0 {
-                    Context::Device(ref device) => device.name.as_str(),
-                    _ => None,
-                })
-                .map_or(Value::Null, Value::from),
-            "contexts.device.family" => self
-                .contexts
-                .value()
-                .and_then(|contexts| contexts.get("device"))
-                .and_then(|annotated| annotated.value())
-                .and_then(|context| match context.0 {
-                    Context::Device(ref device) => device.family.as_str(),
-                    _ => None,
-                })
-                .map_or(Value::Null, Value::from),
-            "contexts.os.name" => self
-                .contexts
-                .value()
-                .and_then(|contexts| contexts.get("os"))
-                .and_then(|annotated| annotated.value())
-                .and_then(|context| match context.0 {
-                    Context::Os(ref os) => os.name.as_str(),
-                    _ => None,
-                })
-                .map_or(Value::Null, Value::from),
-            "contexts.os.version" => self
-                .contexts
-                .value()
-                .and_then(|contexts| contexts.get("os"))
-                .and_then(|annotated| annotated.value())
-                .and_then(|context| match context.0 {
-                    Context::Os(ref os) => os.version.as_str(),
-                    _ => None,
-                })
-                .map_or(Value::Null, Value::from),
-            "contexts.trace.op" => match (self.ty.value(), store::get_transaction_op(self)) {
-                (Some(&EventType::Transaction), Some(op_name)) => Value::String(op_name.to_owned()),
-                _ => Value::Null,
-            },
-
-            // Computed fields (see Discover)
-            "duration" => match (self.ty.value(), store::validate_timestamps(self)) {
-                (Some(&EventType::Transaction), Ok((start, end))) => {
-                    match Number::from_f64(relay_common::chrono_to_positive_millis(end - start)) {
-                        Some(num) => Value::Number(num),
-                        None => Value::Null,
-                    }
-                }
-                _ => Value::Null,
-            },
-
-            // Inbound filter functions represented as fields
-            "is_local_ip" => Value::Bool(relay_filter::localhost::matches(self)),
-            "has_bad_browser_extensions" => {
-                Value::Bool(relay_filter::browser_extensions::matches(self))
-            }
-            "web_crawlers" => Value::Bool(relay_filter::web_crawlers::matches(self)),
-
-            // Dynamic access to certain data bags
-            _ => {
-                if let Some(rest) = field_name.strip_prefix("measurements.") {
-                    rest.strip_suffix(".value")
-                        .filter(|measurement_name| !measurement_name.is_empty())
-                        .and_then(|measurement_name| store::get_measurement(self, measurement_name))
-                        .map_or(Value::Null, Value::from)
-                } else if let Some(rest) = field_name.strip_prefix("tags.") {
-                    self.tags
-                        .value()
-                        .and_then(|tags| tags.get(rest))
-                        .map_or(Value::Null, Value::from)
-                } else {
-                    Value::Null
-                }
-            }
-        }
-    }
-
-    fn get_rule_type(&self) -> RuleType {
-        if let Some(ty) = self.ty.value() {
-            if *ty == EventType::Transaction {
-                return RuleType::Transaction;
-            }
-        }
-        RuleType::Error
-    }
-
-    fn get_custom_operator(
-        name: &str,
-    ) -> fn(condition: &CustomCondition, slf: &Self, ip_addr: Option<IpAddr>) -> bool {
-        match name {
-            "event.client_ip" => client_ips_matcher,
-            "event.legacy_browser" => legacy_browsers_matcher,
-            "event.error_messages" => error_messages_matcher,
-            "event.csp" => csp_matcher,
-            _ => no_match,
-        }
-    }
-}
-
-fn client_ips_matcher(
-    condition: &CustomCondition,
-    _event: &Event,
-    ip_addr: Option<IpAddr>,
-) -> bool {
-    let ips = condition
-        .value
-        .as_array()
-        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("")));
-
-    if let Some(ips) = ips {
-        relay_filter::client_ips::matches(ip_addr, ips)
-    } else {
-        false
-    }
-}
-
-fn legacy_browsers_matcher(
-    condition: &CustomCondition,
-    event: &Event,
-    _ip_addr: Option<IpAddr>,
-) -> bool {
-    let browsers = condition
-        .value
-        .as_array()
-        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("").parse().unwrap()));
-    if let Some(browsers) = browsers {
-        relay_filter::legacy_browsers::matches(event, &browsers.collect())
-    } else {
-        false
-    }
+/// Reservoir rules are evaluated before regular [`SamplingRule`]s. Until the reservoir of a rule
+/// is filled, every matching event is kept regardless of sample rates. Once the limit has been
+/// reached in the current window, events fall through to the regular sampling rules. The counters
+/// are kept in [`ReservoirCounters`].
+///
+/// Every matching event is counted, including events that the regular sampling rules would keep
+/// anyway. The reservoir guarantees that at least `limit` matching events are kept per window, it
+/// does not keep `limit` events on top of the sampled ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReservoirRule {
+    /// The condition that events or traces must match to be kept by the reservoir.
+    pub condition: RuleCondition,
+    /// The minimum number of matching events to keep per window.
+    pub limit: u64,
+    /// The length of the window in seconds.
+    ///
+    /// Counters reset at every multiple of the window since the UNIX epoch.
+    pub window: u64,
+    /// Whether the condition is checked against the trace or the event.
+    #[serde(rename = "type")]
+    pub ty: RuleType,
+    /// The identifier of the rule, which also keys its counter.
+    pub id: RuleId,
+    /// The time range the rule should be applicable in.
+    #[serde(default, skip_serializing_if = "TimeRange::is_empty")]
+    pub time_range: TimeRange,
 }
 
-fn error_messages_matcher(
-    condition: &CustomCondition,
-    event: &Event,
-    _ip_addr: Option<IpAddr>,
-) -> bool {
-    let patterns = condition
-        .value
-        .as_array()
-        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("").to_owned()));
-
-    if let Some(patterns) = patterns {
-        let globs = GlobPatterns::new(patterns.collect());
-        relay_filter::error_messages::matches(event, &globs)
-    } else {
-        false
+impl ReservoirRule {
+    fn supported(&self) -> bool {
+        self.condition.supported() && self.ty != RuleType::Unsupported && self.window > 0
     }
-}
 
-fn csp_matcher(condition: &CustomCondition, event: &Event, _ip_addr: Option<IpAddr>) -> bool {
-    let sources = condition
-        .value
-        .as_array()
-        .map(|v| v.iter().map(|s| s.as_str().unwrap_or("")));
-
-    if let Some(sources) = sources {
-        relay_filter::csp::matches(event, sources)
-    } else {
-        false
+    fn is_active(&self) -> bool {
+        self.time_range.contains(Utc::now())
     }
 }
 
@@ -654,15 +190,11 @@ impl FieldValueProvider for DynamicSamplingContext {
         }
     }
 
-    fn get_rule_type(&self) -> RuleType {
-        RuleType::Trace
-    }
-
     fn get_custom_operator(
         _name: &str,
     ) -> fn(condition: &CustomCondition, slf: &Self, ip_addr: Option<IpAddr>) -> bool {
         // no custom operators for trace
-        no_match
+        |_, _, _| false
     }
 }
 
@@ -672,7 +204,7 @@ impl FieldValueProvider for DynamicSamplingContext {
 /// occurs. The sampling mode controlls whether the sample rate is relative to the original
 /// population of items before client-side sampling, or relative to the number received by Relay
 /// after client-side sampling.
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum SamplingMode {
     /// The sample rate is based on the number of events received by Relay.
@@ -702,6 +234,45 @@ impl Default for SamplingMode {
     }
 }
 
+/// Describes how a sampling rule was evaluated, returned by [`SamplingConfig::explain_trace_rules`]
+/// and [`SamplingConfig::explain_event_rules`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleExplanation {
+    /// The identifier of the rule.
+    pub id: RuleId,
+    /// The type of the rule.
+    #[serde(rename = "type")]
+    pub ty: RuleType,
+    /// The sample rate configured on the rule, before any adjustments.
+    pub sample_rate: f64,
+    /// Whether the rule is within its time range.
+    pub active: bool,
+    /// Whether the rule is active and its condition matched.
+    pub matched: bool,
+    /// Explanation of the rule's condition.
+    pub condition: ConditionExplanation,
+}
+
+impl RuleExplanation {
+    fn new<T>(rule: &SamplingRule, value: &T, ip_addr: Option<IpAddr>) -> Self
+    where
+        T: FieldValueProvider,
+    {
+        let active = rule.is_active();
+        let condition = rule.condition.explain(value, ip_addr);
+
+        Self {
+            id: rule.id,
+            ty: rule.ty,
+            sample_rate: rule.sample_rate,
+            active,
+            matched: active && condition.matched,
+            condition,
+        }
+    }
+}
+
 /// Represents the dynamic sampling configuration available to a project.
 ///
 /// Note: This comes from the organization data
@@ -716,11 +287,83 @@ pub struct SamplingConfig {
     /// The unique identifier for the next new rule to be added.
     #[serde(default)]
     pub next_id: Option<u32>,
+    /// Rules that keep a minimum number of matching events before `rules` are applied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reservoir_rules: Vec<ReservoirRule>,
 }
 
 impl SamplingConfig {
     pub fn has_unsupported_rules(&self) -> bool {
         !self.rules.iter().all(SamplingRule::supported)
+            || !self.reservoir_rules.iter().all(ReservoirRule::supported)
+    }
+
+    /// Returns all active reservoir rules of type [`RuleType::Trace`] whose conditions match on
+    /// the given sampling context, in order of priority.
+    pub fn get_matching_trace_reservoirs<'a>(
+        &'a self,
+        sampling_context: &'a DynamicSamplingContext,
+        ip_addr: Option<IpAddr>,
+    ) -> impl Iterator<Item = &'a ReservoirRule> + 'a {
+        self.reservoir_rules.iter().filter(move |rule| {
+            rule.ty == RuleType::Trace
+                && rule.is_active()
+                && rule.condition.matches(sampling_context, ip_addr)
+        })
+    }
+
+    /// Returns all active reservoir rules of type [`RuleType::Transaction`] or
+    /// [`RuleType::Error`] whose conditions match the given event, in order of priority.
+    pub fn get_matching_event_reservoirs<'a>(
+        &'a self,
+        event: &'a Event,
+        ip_addr: Option<IpAddr>,
+    ) -> impl Iterator<Item = &'a ReservoirRule> + 'a {
+        let ty = if let Some(EventType::Transaction) = &event.ty.0 {
+            RuleType::Transaction
+        } else {
+            RuleType::Error
+        };
+
+        self.reservoir_rules.iter().filter(move |rule| {
+            rule.ty == ty && rule.is_active() && rule.condition.matches(event, ip_addr)
+        })
+    }
+
+    /// Explains the evaluation of all rules of type [`RuleType::Trace`] in order of priority.
+    ///
+    /// The first matching rule is applied, see [`Self::get_matching_trace_rule`].
+    pub fn explain_trace_rules(
+        &self,
+        sampling_context: &DynamicSamplingContext,
+        ip_addr: Option<IpAddr>,
+    ) -> Vec<RuleExplanation> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.ty == RuleType::Trace)
+            .map(|rule| RuleExplanation::new(rule, sampling_context, ip_addr))
+            .collect()
+    }
+
+    /// Explains the evaluation of all rules that apply to the given event in order of priority.
+    ///
+    /// The first matching rule is applied, see [`Self::get_matching_event_rule`].
+    pub fn explain_event_rules(
+        &self,
+        event: &Event,
+        ip_addr: Option<IpAddr>,
+    ) -> Vec<RuleExplanation> {
+        let ty = if let Some(EventType::Transaction) = &event.ty.0 {
+            RuleType::Transaction
+        } else {
+            RuleType::Error
+        };
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.ty == ty)
+            .map(|rule| RuleExplanation::new(rule, event, ip_addr))
+            .collect()
     }
 
     /// Get the first rule of type [`RuleType::Trace`] whose conditions match on the given sampling
@@ -770,6 +413,220 @@ impl SamplingConfig {
             rule.condition.matches(event, ip_addr)
         })
     }
+
+    /// Returns the first matching trace rule along with its effective sample rate.
+    ///
+    /// In [`SamplingMode::Total`], the sample rate is adjusted by the client sample rate from the
+    /// sampling context. The trace id seeds the random number.
+    pub fn match_trace_rule(
+        &self,
+        sampling_context: &DynamicSamplingContext,
+        ip_addr: Option<IpAddr>,
+    ) -> Option<SamplingMatch> {
+        let rule = self.get_matching_trace_rule(sampling_context, ip_addr)?;
+        let sample_rate = match self.mode {
+            SamplingMode::Total => sampling_context.adjusted_sample_rate(rule.sample_rate),
+            SamplingMode::Received | SamplingMode::Unsupported => rule.sample_rate,
+        };
+
+        Some(SamplingMatch {
+            rule_id: rule.id,
+            mode: self.mode,
+            sample_rate,
+            random_number: pseudo_random_from_uuid(sampling_context.trace_id),
+        })
+    }
+
+    /// Returns the first matching event rule along with its effective sample rate.
+    ///
+    /// In [`SamplingMode::Total`], the sample rate is adjusted by the client sample rate from the
+    /// sampling context, if there is one. The event id seeds the random number, so events without
+    /// an id never match.
+    pub fn match_event_rule(
+        &self,
+        event: &Event,
+        sampling_context: Option<&DynamicSamplingContext>,
+        ip_addr: Option<IpAddr>,
+    ) -> Option<SamplingMatch> {
+        let event_id = event.id.value()?;
+        let rule = self.get_matching_event_rule(event, ip_addr)?;
+        let sample_rate = match (sampling_context, self.mode) {
+            (Some(dsc), SamplingMode::Total) => dsc.adjusted_sample_rate(rule.sample_rate),
+            _ => rule.sample_rate,
+        };
+
+        Some(SamplingMatch {
+            rule_id: rule.id,
+            mode: self.mode,
+            sample_rate,
+            random_number: pseudo_random_from_uuid(event_id.0),
+        })
+    }
+}
+
+/// A matching sampling rule along with its effective sample rate.
+///
+/// Returned by [`SamplingConfig::match_trace_rule`] and [`SamplingConfig::match_event_rule`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplingMatch {
+    /// The identifier of the matching rule.
+    pub rule_id: RuleId,
+    /// The sampling mode of the configuration that contains the rule.
+    pub mode: SamplingMode,
+    /// The sample rate of the rule after adjusting it for the sampling mode.
+    pub sample_rate: f64,
+    /// The random number derived from the trace or event id, compared to the sample rate.
+    pub random_number: f64,
+}
+
+impl SamplingMatch {
+    /// Returns `true` if the event is kept according to the sample rate of the rule.
+    pub fn should_keep(&self) -> bool {
+        self.random_number < self.sample_rate
+    }
+}
+
+/// Describes a reservoir rule that matched an event or its trace, see [`SamplingExplanation`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReservoirExplanation {
+    /// The identifier of the rule.
+    pub id: RuleId,
+    /// The minimum number of matching events to keep per window.
+    pub limit: u64,
+    /// The length of the window in seconds.
+    pub window: u64,
+    /// Whether the reservoir has reached its limit in the current window.
+    pub filled: bool,
+}
+
+impl ReservoirExplanation {
+    /// Creates the explanation of a matching reservoir rule.
+    pub fn new(rule: &ReservoirRule, filled: bool) -> Self {
+        Self {
+            id: rule.id,
+            limit: rule.limit,
+            window: rule.window,
+            filled,
+        }
+    }
+}
+
+/// The final decision in a [`SamplingExplanation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SamplingDecision {
+    /// The event is kept.
+    Keep,
+    /// The event is dropped.
+    Drop,
+}
+
+/// The reason for the decision in a [`SamplingExplanation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SamplingReason {
+    /// The sampling configuration contains rules or a mode this Relay does not support.
+    UnsupportedConfig,
+    /// No rule matched the event or its trace.
+    NoMatchingRule,
+    /// A matching reservoir rule had not reached its limit yet.
+    Reservoir,
+    /// The effective sample rate of the applied rule was compared to the random number.
+    SampleRate,
+}
+
+/// Describes how dynamic sampling evaluated an event.
+///
+/// The decision is derived in the same order as Relay samples events: Matching reservoir rules
+/// that are not yet filled keep the event before the sample rate of the matching rule applies.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplingExplanation {
+    /// The sampling mode of the project that owns the applied rule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<SamplingMode>,
+    /// The client sample rate from the dynamic sampling context.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_sample_rate: Option<f64>,
+    /// All trace rules of the sampling project in order of priority.
+    pub trace_rules: Vec<RuleExplanation>,
+    /// All event rules of the project that apply to the event type in order of priority.
+    pub event_rules: Vec<RuleExplanation>,
+    /// Matching reservoir rules, event reservoirs before trace reservoirs.
+    pub reservoir_rules: Vec<ReservoirExplanation>,
+    /// The identifier of the rule that determined the decision.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub applied_rule: Option<RuleId>,
+    /// The sample rate of the applied rule after adjusting it for the sampling mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_sample_rate: Option<f64>,
+    /// The random number derived from the trace or event id, compared to the sample rate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random_number: Option<f64>,
+    /// Whether the event is kept or dropped.
+    pub decision: SamplingDecision,
+    /// The reason for the decision.
+    pub reason: SamplingReason,
+}
+
+impl SamplingExplanation {
+    /// Creates an explanation of the evaluated rules that keeps the event.
+    ///
+    /// Call [`decide`](Self::decide) or [`unsupported`](Self::unsupported) to derive the
+    /// decision.
+    pub fn new(
+        client_sample_rate: Option<f64>,
+        trace_rules: Vec<RuleExplanation>,
+        event_rules: Vec<RuleExplanation>,
+        reservoir_rules: Vec<ReservoirExplanation>,
+    ) -> Self {
+        Self {
+            mode: None,
+            client_sample_rate,
+            trace_rules,
+            event_rules,
+            reservoir_rules,
+            applied_rule: None,
+            effective_sample_rate: None,
+            random_number: None,
+            decision: SamplingDecision::Keep,
+            reason: SamplingReason::NoMatchingRule,
+        }
+    }
+
+    /// Derives the decision from the reservoir rules and the matching sampling rule.
+    ///
+    /// The first reservoir rule that is not yet filled keeps the event. Otherwise, the sample
+    /// rate of the matching rule decides. Without a matching rule, the event is kept.
+    pub fn decide(&mut self, sampling_match: Option<SamplingMatch>) {
+        if let Some(reservoir) = self.reservoir_rules.iter().find(|rule| !rule.filled) {
+            self.applied_rule = Some(reservoir.id);
+            self.decision = SamplingDecision::Keep;
+            self.reason = SamplingReason::Reservoir;
+            return;
+        }
+
+        let sampling_match = match sampling_match {
+            Some(sampling_match) => sampling_match,
+            None => return,
+        };
+
+        self.mode = Some(sampling_match.mode);
+        self.applied_rule = Some(sampling_match.rule_id);
+        self.effective_sample_rate = Some(sampling_match.sample_rate);
+        self.random_number = Some(sampling_match.random_number);
+        self.reason = SamplingReason::SampleRate;
+        if !sampling_match.should_keep() {
+            self.decision = SamplingDecision::Drop;
+        }
+    }
+
+    /// Keeps the event since sampling cannot be applied with an unsupported configuration.
+    pub fn unsupported(&mut self) {
+        self.decision = SamplingDecision::Keep;
+        self.reason = SamplingReason::UnsupportedConfig;
+    }
 }
 
 /// The User related information in the trace context
@@ -916,7 +773,9 @@ impl DynamicSamplingContext {
 
         let contexts = event.contexts.value()?;
         let context = contexts.get(TraceContext::default_key())?.value()?;
-        let Context::Trace(ref trace) = context.0 else { return None };
+        let Context::Trace(ref trace) = context.0 else {
+            return None;
+        };
         let trace_id = trace.trace_id.value()?;
         let trace_id = trace_id.0.parse().ok()?;
 
@@ -982,11 +841,14 @@ pub fn pseudo_random_from_uuid(id: Uuid) -> f64 {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::net::{IpAddr as NetIpAddr, Ipv4Addr};
     use std::str::FromStr;
 
     use chrono::{TimeZone, Utc};
 
+    use relay_filter::GlobPatterns;
+
     use relay_general::protocol::{
         Contexts, Csp, DeviceContext, Exception, Headers, IpAddr, JsonLenientString, LenientString,
         LogEntry, OsContext, PairList, Request, TagEntry, Tags, User, Values,
@@ -1926,6 +1788,127 @@ mod tests {
         assert_eq!(time_range.end, Some(Utc.ymd(2022, 10, 20).and_hms(0, 0, 0)));
     }
 
+    #[test]
+    fn test_reservoir_rule_deserialization() {
+        let serialized_config = r#"{
+            "rules": [],
+            "reservoirRules": [
+                {
+                    "condition": {"op": "eq", "name": "event.transaction", "value": "/health"},
+                    "limit": 10,
+                    "window": 3600,
+                    "type": "transaction",
+                    "id": 1
+                }
+            ]
+        }"#;
+        let config: SamplingConfig = serde_json::from_str(serialized_config).unwrap();
+        assert!(!config.has_unsupported_rules());
+
+        let rule = &config.reservoir_rules[0];
+        assert_eq!(rule.limit, 10);
+        assert_eq!(rule.window, 3600);
+        assert_eq!(rule.ty, RuleType::Transaction);
+    }
+
+    #[test]
+    fn test_matching_event_reservoirs() {
+        let config = SamplingConfig {
+            rules: vec![],
+            mode: SamplingMode::Received,
+            next_id: None,
+            reservoir_rules: vec![
+                ReservoirRule {
+                    condition: eq("event.release", &["1.1.1"], true),
+                    limit: 10,
+                    window: 60,
+                    ty: RuleType::Transaction,
+                    id: RuleId(1),
+                    time_range: Default::default(),
+                },
+                ReservoirRule {
+                    condition: RuleCondition::all(),
+                    limit: 10,
+                    window: 60,
+                    ty: RuleType::Error,
+                    id: RuleId(2),
+                    time_range: Default::default(),
+                },
+                ReservoirRule {
+                    condition: RuleCondition::all(),
+                    limit: 10,
+                    window: 60,
+                    ty: RuleType::Transaction,
+                    id: RuleId(3),
+                    time_range: Default::default(),
+                },
+            ],
+        };
+
+        let event = Event {
+            ty: Annotated::new(EventType::Transaction),
+            release: Annotated::new(LenientString("1.1.1".to_owned())),
+            ..Event::default()
+        };
+
+        let ids = config
+            .get_matching_event_reservoirs(&event, None)
+            .map(|rule| rule.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![RuleId(1), RuleId(3)]);
+    }
+
+    #[test]
+    fn test_explain_event_rules() {
+        let config = SamplingConfig {
+            rules: vec![
+                SamplingRule {
+                    condition: eq("event.release", &["1.1.1"], true),
+                    sample_rate: 0.5,
+                    ty: RuleType::Transaction,
+                    id: RuleId(1),
+                    time_range: Default::default(),
+                },
+                SamplingRule {
+                    condition: RuleCondition::all(),
+                    sample_rate: 0.2,
+                    ty: RuleType::Trace,
+                    id: RuleId(2),
+                    time_range: Default::default(),
+                },
+                SamplingRule {
+                    condition: RuleCondition::all(),
+                    sample_rate: 0.1,
+                    ty: RuleType::Transaction,
+                    id: RuleId(3),
+                    time_range: Default::default(),
+                },
+            ],
+            mode: SamplingMode::Received,
+            next_id: None,
+            reservoir_rules: Vec::new(),
+        };
+
+        let event = Event {
+            ty: Annotated::new(EventType::Transaction),
+            release: Annotated::new(LenientString("1.1.2".to_owned())),
+            ..Event::default()
+        };
+
+        let explanations = config.explain_event_rules(&event, None);
+        assert_eq!(explanations.len(), 2);
+
+        assert_eq!(explanations[0].id, RuleId(1));
+        assert!(!explanations[0].matched);
+        assert_eq!(
+            explanations[0].condition.field_value,
+            Some(Value::from("1.1.2"))
+        );
+
+        assert_eq!(explanations[1].id, RuleId(3));
+        assert!(explanations[1].matched);
+    }
+
     #[test]
     fn test_partial_trace_matches() {
         let condition = and(vec![
@@ -2100,6 +2083,7 @@ mod tests {
             ],
             mode: SamplingMode::Received,
             next_id: None,
+            reservoir_rules: Vec::new(),
         };
 
         let trace_context = DynamicSamplingContext {
@@ -2321,6 +2305,7 @@ mod tests {
             ],
             mode: SamplingMode::Received,
             next_id: None,
+            reservoir_rules: Vec::new(),
         }
     }
 
@@ -2394,6 +2379,7 @@ mod tests {
                 }],
                 mode: SamplingMode::Received,
                 next_id: None,
+                reservoir_rules: Vec::new(),
             };
             assert_eq!(
                 config.get_matching_trace_rule(&trace, None).unwrap().id,
@@ -2411,6 +2397,7 @@ mod tests {
                 }],
                 mode: SamplingMode::Received,
                 next_id: None,
+                reservoir_rules: Vec::new(),
             };
             assert_eq!(
                 config
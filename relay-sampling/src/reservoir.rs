@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use chrono::Utc;
+
+use relay_common::ProjectId;
+
+use crate::{ReservoirRule, RuleId};
+
+#[cfg(feature = "redis")]
+use relay_redis::RedisPool;
+
+/// Interval in seconds at which expired reservoirs are removed from memory.
+const PURGE_INTERVAL: u64 = 60;
+
+/// The state of a single reservoir in memory.
+#[derive(Debug, Default)]
+struct Reservoir {
+    /// Index of the window since the UNIX epoch.
+    window: u64,
+    /// UNIX timestamp in seconds at which the window ends.
+    expiry: u64,
+    /// Number of events kept in this window.
+    count: u64,
+}
+
+/// In-memory state of [`ReservoirCounters`].
+#[derive(Debug, Default)]
+struct MemoryState {
+    reservoirs: HashMap<(ProjectId, RuleId), Reservoir>,
+    /// UNIX timestamp in seconds of the next purge.
+    next_purge: u64,
+}
+
+impl MemoryState {
+    /// Removes reservoirs whose window has ended.
+    ///
+    /// Reservoirs of rules that have been removed or whose time range has ended are no longer
+    /// updated, so they are removed once their last window ends.
+    fn purge(&mut self, now: u64) {
+        if now < self.next_purge {
+            return;
+        }
+
+        self.reservoirs
+            .retain(|_, reservoir| reservoir.expiry > now);
+        self.next_purge = now + PURGE_INTERVAL;
+    }
+}
+
+/// Counts the events kept by [`ReservoirRule`]s.
+///
+/// Counters are tracked per project and rule, and reset at the start of every window. By default,
+/// counters are kept in memory, so every Relay instance fills its own reservoirs. Counters are
+/// removed from memory once their window ends. With the `redis` feature, counters can be shared
+/// across Relay instances through Redis using [`with_redis`](Self::with_redis).
+#[derive(Default)]
+pub struct ReservoirCounters {
+    state: Mutex<MemoryState>,
+    #[cfg(feature = "redis")]
+    redis: Option<RedisPool>,
+}
+
+impl ReservoirCounters {
+    /// Creates empty in-memory reservoir counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shares the counters across Relay instances through Redis.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(mut self, pool: RedisPool) -> Self {
+        self.redis = Some(pool);
+        self
+    }
+
+    /// Counts an event for the rule if its reservoir is not yet filled.
+    ///
+    /// Returns `true` if the event should be kept by the reservoir. If the counter cannot be
+    /// updated, this returns `false` so that regular sampling rules apply.
+    pub fn try_acquire(&self, project_id: ProjectId, rule: &ReservoirRule) -> bool {
+        if rule.window == 0 {
+            return false;
+        }
+
+        let now = Utc::now().timestamp().max(0) as u64;
+        let window = now / rule.window;
+
+        #[cfg(feature = "redis")]
+        if let Some(ref pool) = self.redis {
+            return match acquire_redis(pool, project_id, rule, window) {
+                Ok(acquired) => acquired,
+                Err(error) => {
+                    relay_log::error!(
+                        "failed to update reservoir counter: {}",
+                        relay_log::LogError(&error)
+                    );
+                    false
+                }
+            };
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.purge(now);
+
+        let reservoir = state.reservoirs.entry((project_id, rule.id)).or_default();
+        if reservoir.window != window {
+            reservoir.window = window;
+            reservoir.count = 0;
+        }
+        reservoir.expiry = (window + 1) * rule.window;
+
+        if reservoir.count >= rule.limit {
+            return false;
+        }
+
+        reservoir.count += 1;
+        true
+    }
+
+    /// Returns `true` if the reservoir of the rule is not yet filled, without counting an event.
+    ///
+    /// This is the result [`try_acquire`](Self::try_acquire) would return for the next event. If
+    /// the counter cannot be read, this returns `false`.
+    pub fn peek(&self, project_id: ProjectId, rule: &ReservoirRule) -> bool {
+        if rule.window == 0 {
+            return false;
+        }
+
+        let now = Utc::now().timestamp().max(0) as u64;
+        let window = now / rule.window;
+
+        #[cfg(feature = "redis")]
+        if let Some(ref pool) = self.redis {
+            return match peek_redis(pool, project_id, rule, window) {
+                Ok(open) => open,
+                Err(error) => {
+                    relay_log::error!(
+                        "failed to read reservoir counter: {}",
+                        relay_log::LogError(&error)
+                    );
+                    false
+                }
+            };
+        }
+
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = match state.reservoirs.get(&(project_id, rule.id)) {
+            Some(reservoir) if reservoir.window == window => reservoir.count,
+            _ => 0,
+        };
+
+        count < rule.limit
+    }
+}
+
+impl std::fmt::Debug for ReservoirCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReservoirCounters").finish()
+    }
+}
+
+/// Returns the Redis key of the reservoir counter for a rule in the given window.
+#[cfg(feature = "redis")]
+fn redis_key(project_id: ProjectId, rule: &ReservoirRule, window: u64) -> String {
+    format!("reservoir:{{{}}}:{}:{}", project_id, rule.id, window)
+}
+
+/// Increments the reservoir counter in Redis and checks it against the rule's limit.
+///
+/// The counter expires at the end of its window.
+#[cfg(feature = "redis")]
+fn acquire_redis(
+    pool: &RedisPool,
+    project_id: ProjectId,
+    rule: &ReservoirRule,
+    window: u64,
+) -> Result<bool, relay_redis::RedisError> {
+    let key = redis_key(project_id, rule, window);
+    let expiry = (window + 1) * rule.window;
+
+    let mut client = pool.client()?;
+    let (count,): (u64,) = relay_redis::redis::pipe()
+        .atomic()
+        .incr(&key, 1)
+        .expire_at(&key, expiry as usize)
+        .ignore()
+        .query(&mut client.connection())
+        .map_err(relay_redis::RedisError::Redis)?;
+
+    Ok(count <= rule.limit)
+}
+
+/// Reads the reservoir counter from Redis and checks it against the rule's limit.
+#[cfg(feature = "redis")]
+fn peek_redis(
+    pool: &RedisPool,
+    project_id: ProjectId,
+    rule: &ReservoirRule,
+    window: u64,
+) -> Result<bool, relay_redis::RedisError> {
+    let mut client = pool.client()?;
+    let count: Option<u64> = relay_redis::redis::cmd("GET")
+        .arg(redis_key(project_id, rule, window))
+        .query(&mut client.connection())
+        .map_err(relay_redis::RedisError::Redis)?;
+
+    Ok(count.unwrap_or(0) < rule.limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RuleCondition, RuleType, TimeRange};
+
+    use super::*;
+
+    fn reservoir_rule(id: u32, limit: u64) -> ReservoirRule {
+        ReservoirRule {
+            condition: RuleCondition::all(),
+            limit,
+            window: 3600,
+            ty: RuleType::Transaction,
+            id: RuleId(id),
+            time_range: TimeRange::default(),
+        }
+    }
+
+    #[test]
+    fn test_fill_reservoir() {
+        let counters = ReservoirCounters::new();
+        let project_id = ProjectId::new(42);
+        let rule = reservoir_rule(1, 2);
+
+        assert!(counters.try_acquire(project_id, &rule));
+        assert!(counters.try_acquire(project_id, &rule));
+        assert!(!counters.try_acquire(project_id, &rule));
+    }
+
+    #[test]
+    fn test_peek_reservoir() {
+        let counters = ReservoirCounters::new();
+        let project_id = ProjectId::new(42);
+        let rule = reservoir_rule(1, 1);
+
+        assert!(counters.peek(project_id, &rule));
+        assert!(counters.peek(project_id, &rule));
+
+        assert!(counters.try_acquire(project_id, &rule));
+        assert!(!counters.peek(project_id, &rule));
+    }
+
+    #[test]
+    fn test_separate_reservoirs() {
+        let counters = ReservoirCounters::new();
+        let rule = reservoir_rule(1, 1);
+        let other_rule = reservoir_rule(2, 1);
+
+        assert!(counters.try_acquire(ProjectId::new(42), &rule));
+        assert!(counters.try_acquire(ProjectId::new(42), &other_rule));
+        assert!(counters.try_acquire(ProjectId::new(43), &rule));
+        assert!(!counters.try_acquire(ProjectId::new(42), &rule));
+    }
+
+    #[test]
+    fn test_reset_window() {
+        let counters = ReservoirCounters::new();
+        let project_id = ProjectId::new(42);
+        let rule = reservoir_rule(1, 1);
+
+        assert!(counters.try_acquire(project_id, &rule));
+        assert!(!counters.try_acquire(project_id, &rule));
+
+        // Simulate the start of a new window.
+        counters
+            .state
+            .lock()
+            .unwrap()
+            .reservoirs
+            .get_mut(&(project_id, rule.id))
+            .unwrap()
+            .window -= 1;
+
+        assert!(counters.try_acquire(project_id, &rule));
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let counters = ReservoirCounters::new();
+        let rule = reservoir_rule(1, 1);
+        let other_rule = reservoir_rule(2, 1);
+
+        assert!(counters.try_acquire(ProjectId::new(42), &rule));
+        assert!(counters.try_acquire(ProjectId::new(43), &other_rule));
+
+        // Simulate the end of the window of the first reservoir, after the purge interval.
+        {
+            let mut state = counters.state.lock().unwrap();
+            state.next_purge = 0;
+            state
+                .reservoirs
+                .get_mut(&(ProjectId::new(42), rule.id))
+                .unwrap()
+                .expiry = 1;
+        }
+
+        assert!(!counters.try_acquire(ProjectId::new(43), &other_rule));
+
+        let state = counters.state.lock().unwrap();
+        assert_eq!(state.reservoirs.len(), 1);
+        assert!(state
+            .reservoirs
+            .contains_key(&(ProjectId::new(43), other_rule.id)));
+    }
+}
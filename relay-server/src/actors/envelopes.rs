@@ -7,26 +7,28 @@ use chrono::Utc;
 use futures::compat::Future01CompatExt;
 use futures01::{future, sync::oneshot, Future as _};
 
-use relay_common::ProjectKey;
+use relay_common::{DataCategory, ProjectKey, UnixTimestamp};
 use relay_config::{Config, HttpEncoding};
 use relay_general::protocol::ClientReport;
 use relay_log::LogError;
-use relay_metrics::{Bucket, MergeBuckets};
-use relay_quotas::Scoping;
+use relay_metrics::{Bucket, CardinalityLimiter, MergeBuckets};
+use relay_quotas::{ReasonCode, Scoping};
 use relay_statsd::metric;
-use relay_system::{Addr, FromMessage, NoResponse};
+use relay_system::{compat, Addr, FromMessage, NoResponse};
 
-use crate::actors::outcome::{DiscardReason, Outcome};
+use crate::actors::outcome::{DiscardReason, Outcome, TrackOutcome};
 use crate::actors::processor::{EncodeEnvelope, EnvelopeProcessor};
 use crate::actors::project_cache::{ProjectCache, UpdateRateLimits};
 use crate::actors::test_store::{Capture, TestStore};
-use crate::actors::upstream::{SendRequest, UpstreamRelay, UpstreamRequest, UpstreamRequestError};
+use crate::actors::upstream::{
+    IsNetworkOutage, SendRequest, UpstreamRelay, UpstreamRequest, UpstreamRequestError,
+};
 use crate::envelope::{self, ContentType, Envelope, EnvelopeError, Item, ItemType};
 use crate::extractors::{PartialDsn, RequestMeta};
 use crate::http::{HttpError, Request, RequestBuilder, Response};
 use crate::service::{Registry, REGISTRY};
-use crate::statsd::RelayHistograms;
-use crate::utils::EnvelopeContext;
+use crate::statsd::{RelayCounters, RelayHistograms};
+use crate::utils::{self, BufferGuard, EnvelopeContext, EnvelopeSpool};
 
 #[cfg(feature = "processing")]
 use crate::actors::store::{Store, StoreEnvelope, StoreError};
@@ -163,6 +165,13 @@ pub struct SendMetrics {
     pub partition_key: Option<u64>,
 }
 
+/// Returns `true` if the upstream is currently unreachable.
+async fn is_network_outage() -> bool {
+    compat::send(UpstreamRelay::from_registry(), IsNetworkOutage)
+        .await
+        .unwrap_or(false)
+}
+
 /// Dispatch service for generating and submitting Envelopes.
 #[derive(Debug)]
 pub enum EnvelopeManager {
@@ -211,9 +220,15 @@ impl FromMessage<SendMetrics> for EnvelopeManager {
 ///  2. The in-memory [`TestStore`] if capture mode is enabled. This is meant for integration
 ///     testing and should not be used in production.
 ///  3. The [`UpstreamRelay`] via HTTP by default.
+///
+/// If an [`EnvelopeSpool`] is configured, envelopes are written to disk while the upstream is
+/// unavailable and sent in order once the network outage is over.
 #[derive(Debug)]
 pub struct EnvelopeManagerService {
     config: Arc<Config>,
+    spool: Option<Arc<EnvelopeSpool>>,
+    buffer_guard: Option<Arc<BufferGuard>>,
+    cardinality_limiter: Arc<CardinalityLimiter>,
     #[cfg(feature = "processing")]
     store_forwarder: Option<Addr<Store>>,
 }
@@ -221,19 +236,77 @@ pub struct EnvelopeManagerService {
 impl EnvelopeManagerService {
     /// Creates a new instance of the [`EnvelopeManager`] service.
     pub fn new(config: Arc<Config>) -> Self {
+        let limiter_config = config.aggregator_config().cardinality_limiter.clone();
         Self {
             config,
+            spool: None,
+            buffer_guard: None,
+            cardinality_limiter: Arc::new(CardinalityLimiter::new(limiter_config)),
             #[cfg(feature = "processing")]
             store_forwarder: None,
         }
     }
 
+    /// Configures an on-disk spool for envelopes that cannot be sent during network outages.
+    ///
+    /// The buffer guard is used to queue envelopes for processing that were spooled because the
+    /// envelope buffer was full.
+    pub fn set_spool(&mut self, spool: Arc<EnvelopeSpool>, buffer_guard: Arc<BufferGuard>) {
+        self.spool = Some(spool);
+        self.buffer_guard = Some(buffer_guard);
+    }
+
     /// Configures a store forwarder to produce Envelopes to Kafka.
     #[cfg(feature = "processing")]
     pub fn set_store_forwarder(&mut self, addr: Addr<Store>) {
         self.store_forwarder = Some(addr);
     }
 
+    /// Shares the state of the cardinality limiter across Relay instances through Redis.
+    #[cfg(feature = "processing")]
+    pub fn set_redis(&mut self, pool: relay_redis::RedisPool) {
+        let limiter_config = self.config.aggregator_config().cardinality_limiter.clone();
+        let limiter = CardinalityLimiter::new(limiter_config).with_redis(pool);
+        self.cardinality_limiter = Arc::new(limiter);
+    }
+
+    /// Enforces the cardinality limit on buckets of a project and tracks outcomes for rejections.
+    ///
+    /// Returns the buckets that should be sent.
+    async fn limit_cardinality(&self, scoping: Scoping, buckets: Vec<Bucket>) -> Vec<Bucket> {
+        if !self.cardinality_limiter.is_enabled() {
+            return buckets;
+        }
+
+        // Keep the original buckets to fail open if the limiter fails, since dropping all buckets
+        // would lose data.
+        let limiter = self.cardinality_limiter.clone();
+        let project_key = scoping.project_key;
+        let limited = buckets.clone();
+        let task = tokio::task::spawn_blocking(move || limiter.enforce(project_key, limited));
+        let report = match task.await {
+            Ok(report) => report,
+            Err(error) => {
+                relay_log::error!("cardinality limiter panicked: {}", LogError(&error));
+                return buckets;
+            }
+        };
+
+        if !report.rejected.is_empty() {
+            TrackOutcome::from_registry().send(TrackOutcome {
+                timestamp: UnixTimestamp::now().as_datetime(),
+                scoping,
+                outcome: Outcome::RateLimited(Some(ReasonCode::new("cardinality_limited"))),
+                event_id: None,
+                remote_addr: None,
+                category: DataCategory::MetricBucket,
+                quantity: report.rejected.len() as u32,
+            });
+        }
+
+        report.accepted
+    }
+
     /// Sends an envelope to the upstream or Kafka.
     async fn submit_envelope(
         &self,
@@ -299,9 +372,36 @@ impl EnvelopeManagerService {
     async fn handle_submit(&self, message: SubmitEnvelope) {
         let SubmitEnvelope {
             envelope,
-            mut envelope_context,
+            envelope_context,
         } = message;
 
+        if let Some(ref spool) = self.spool {
+            if is_network_outage().await {
+                match spool
+                    .push(&envelope, envelope_context.scoping(), true)
+                    .await
+                {
+                    Ok(()) => {
+                        metric!(
+                            counter(RelayCounters::EnvelopeSpooled) += 1,
+                            reason = "outage"
+                        );
+                        envelope_context.accept();
+                        return;
+                    }
+                    Err(error) => {
+                        // Fall back to the upstream's retry queue.
+                        relay_log::error!("failed to spool envelope: {}", LogError(&error));
+                    }
+                }
+            }
+        }
+
+        self.send_envelope(envelope, envelope_context).await;
+    }
+
+    /// Submits an envelope and records outcomes if it cannot be sent.
+    async fn send_envelope(&self, envelope: Box<Envelope>, mut envelope_context: EnvelopeContext) {
         let scoping = envelope_context.scoping();
         match self.submit_envelope(envelope, scoping, None).await {
             Ok(_) => {
@@ -322,6 +422,94 @@ impl EnvelopeManagerService {
         }
     }
 
+    /// Checks an envelope that was spooled before processing and queues it for processing.
+    ///
+    /// Project configs, rate limits and size limits may have changed while the envelope was on
+    /// disk, so it goes through the same checks as envelopes received by the endpoints.
+    async fn queue_spooled(
+        &self,
+        envelope: Box<Envelope>,
+        envelope_context: EnvelopeContext,
+        buffer_guard: &BufferGuard,
+    ) {
+        let checked = match utils::check_envelope(&self.config, envelope, envelope_context).await {
+            Ok(checked) => checked,
+            Err(error) => {
+                relay_log::debug!("dropped spooled envelope: {}", LogError(&error));
+                return;
+            }
+        };
+
+        if let Some((envelope, envelope_context)) = checked.envelope {
+            if let Err(error) = utils::queue_envelope(envelope, envelope_context, buffer_guard) {
+                relay_log::error!("failed to queue spooled envelope: {}", LogError(&error));
+            }
+        }
+    }
+
+    /// Drains envelopes from the spool in order until it is empty or the upstream is unavailable.
+    ///
+    /// Processed envelopes are sent to the upstream directly. Envelopes that were spooled before
+    /// processing are checked again and queued into the processing pipeline as long as the
+    /// envelope buffer has capacity.
+    async fn drain_spool(&self, spool: &EnvelopeSpool, buffer_guard: &BufferGuard) {
+        spool.evict_expired().await;
+
+        while !spool.is_empty().await {
+            let mut batch = match spool.pop_batch().await {
+                Some(batch) => batch,
+                None => return,
+            };
+
+            let mut interrupted = false;
+            while let Some(spooled) = batch.envelopes.pop_front() {
+                if is_network_outage().await {
+                    batch.envelopes.push_front(spooled);
+                    interrupted = true;
+                    break;
+                }
+
+                let envelope = match spooled.to_envelope() {
+                    Ok(envelope) => envelope,
+                    Err(error) => {
+                        relay_log::error!("failed to parse spooled envelope: {}", LogError(&error));
+                        spooled.reject_unparsed(Outcome::Invalid(DiscardReason::InvalidEnvelope));
+                        continue;
+                    }
+                };
+
+                if spooled.is_processed() {
+                    let mut envelope_context = EnvelopeContext::standalone(&envelope);
+                    envelope_context.scope(spooled.scoping());
+                    self.send_envelope(envelope, envelope_context).await;
+                } else {
+                    let envelope_context = match buffer_guard.enter(&envelope) {
+                        Ok(envelope_context) => envelope_context,
+                        Err(_) => {
+                            batch.envelopes.push_front(spooled);
+                            interrupted = true;
+                            break;
+                        }
+                    };
+
+                    self.queue_spooled(envelope, envelope_context, buffer_guard)
+                        .await;
+                }
+
+                metric!(counter(RelayCounters::EnvelopeUnspooled) += 1);
+            }
+
+            if let Err(error) = spool.commit(batch).await {
+                relay_log::error!("failed to update envelope spool: {}", LogError(&error));
+                return;
+            }
+
+            if interrupted {
+                return;
+            }
+        }
+    }
+
     async fn handle_send_metrics(&self, message: SendMetrics) {
         let SendMetrics {
             buckets,
@@ -329,6 +517,11 @@ impl EnvelopeManagerService {
             partition_key,
         } = message;
 
+        let buckets = self.limit_cardinality(scoping, buckets).await;
+        if buckets.is_empty() {
+            return;
+        }
+
         let upstream = self.config.upstream_descriptor();
         let dsn = PartialDsn {
             scheme: upstream.scheme(),
@@ -406,6 +599,21 @@ impl relay_system::Service for EnvelopeManagerService {
             relay_log::info!("envelope manager started");
 
             let service = Arc::new(self);
+
+            if let (Some(spool), Some(buffer_guard)) =
+                (service.spool.clone(), service.buffer_guard.clone())
+            {
+                let service = Arc::clone(&service);
+                tokio::spawn(async move {
+                    let interval = service.config.spool_envelopes_drain_interval();
+                    let mut ticker = tokio::time::interval(interval);
+                    loop {
+                        ticker.tick().await;
+                        service.drain_spool(&spool, &buffer_guard).await;
+                    }
+                });
+            }
+
             while let Some(message) = rx.recv().await {
                 let service = Arc::clone(&service);
                 tokio::spawn(async move {
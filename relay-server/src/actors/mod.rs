@@ -35,10 +35,12 @@ pub mod outcome_aggregator;
 pub mod processor;
 pub mod project;
 pub mod project_cache;
+pub mod project_disk;
 pub mod project_local;
 pub mod project_upstream;
 pub mod relays;
 pub mod server;
+pub mod statsd_listener;
 pub mod test_store;
 pub mod upstream;
 
@@ -198,7 +198,7 @@ impl Outcome {
     fn to_reason(&self) -> Option<Cow<str>> {
         match self {
             Outcome::Invalid(discard_reason) => Some(Cow::Borrowed(discard_reason.name())),
-            Outcome::Filtered(filter_key) => Some(Cow::Borrowed(filter_key.name())),
+            Outcome::Filtered(filter_key) => Some(filter_key.name()),
             Outcome::FilteredSampling(rule_id) => Some(Cow::Owned(format!("Sampled:{}", rule_id))),
             //TODO can we do better ? (not re copying the string )
             Outcome::RateLimited(code_opt) => code_opt
@@ -360,6 +360,15 @@ pub enum DiscardReason {
     InvalidReplayEvent,
     InvalidReplayRecordingEvent,
 
+    /// (Relay) The check-in could not be parsed or failed validation.
+    InvalidCheckIn,
+
+    /// (Relay) The on-disk envelope spool exceeded its maximum size and the envelope was evicted.
+    SpoolFull,
+
+    /// (Relay) The envelope was held in the on-disk spool for longer than the maximum age.
+    SpoolExpired,
+
     /// (Relay) Profiling related discard reasons
     Profiling(&'static str),
 }
@@ -401,6 +410,9 @@ impl DiscardReason {
             DiscardReason::EmptyEnvelope => "empty_envelope",
             DiscardReason::InvalidReplayEvent => "invalid_replay",
             DiscardReason::InvalidReplayRecordingEvent => "invalid_replay_recording",
+            DiscardReason::InvalidCheckIn => "invalid_check_in",
+            DiscardReason::SpoolFull => "spool_full",
+            DiscardReason::SpoolExpired => "spool_expired",
             DiscardReason::Profiling(reason) => reason,
         }
     }
@@ -13,52 +13,57 @@ use flate2::Compression;
 use once_cell::sync::OnceCell;
 use serde_json::Value as SerdeValue;
 use tokio::sync::Semaphore;
+use zstd::stream::write::Encoder as ZstdEncoder;
 
 use relay_auth::RelayVersion;
 use relay_common::{ProjectId, ProjectKey, UnixTimestamp};
 use relay_config::{Config, HttpEncoding};
 use relay_filter::FilterStatKey;
-use relay_general::pii::PiiConfigError;
-use relay_general::pii::{PiiAttachmentsProcessor, PiiProcessor};
+use relay_general::pii::{
+    PiiAttachmentsProcessor, PiiAuditMatch, PiiConfig, PiiConfigError, PiiProcessor,
+};
 use relay_general::processor::{process_value, ProcessingState};
 use relay_general::protocol::{
     self, Breadcrumb, ClientReport, Csp, Event, EventType, ExpectCt, ExpectStaple, Hpkp, IpAddr,
     LenientString, Metrics, RelayInfo, SecurityReportType, SessionAggregates, SessionAttributes,
     SessionUpdate, Timestamp, UserReport, Values,
 };
-use relay_general::store::{ClockDriftProcessor, LightNormalizationConfig};
+use relay_general::store::{
+    ClockDriftProcessor, LightNormalizationConfig, ProguardLookup, SourceMapLookup,
+};
 use relay_general::types::{Annotated, Array, FromValue, Object, ProcessingAction, Value};
 use relay_log::LogError;
 use relay_metrics::{Bucket, InsertMetrics, MergeBuckets, Metric};
-use relay_quotas::{DataCategory, ReasonCode};
+use relay_quotas::{DataCategory, ItemScoping, MemoryRateLimiter, RateLimits, ReasonCode};
 use relay_redis::RedisPool;
-use relay_sampling::{DynamicSamplingContext, RuleId};
+use relay_sampling::{DynamicSamplingContext, ReservoirCounters, RuleId, SamplingExplanation};
 use relay_statsd::metric;
-use relay_system::{Addr, FromMessage, NoResponse, Service};
+use relay_system::{Addr, AsyncResponse, FromMessage, NoResponse, Sender, Service};
 
 use crate::actors::envelopes::{EnvelopeManager, SendEnvelope, SendEnvelopeError, SubmitEnvelope};
 use crate::actors::outcome::{DiscardReason, Outcome, TrackOutcome};
 use crate::actors::project::{Feature, ProjectState};
-use crate::actors::project_cache::ProjectCache;
+use crate::actors::project_cache::{ProjectCache, UpdateRateLimits};
 use crate::actors::upstream::{SendRequest, UpstreamRelay};
 use crate::envelope::{AttachmentType, ContentType, Envelope, Item, ItemType};
 use crate::metrics_extraction::sessions::{extract_session_metrics, SessionMetricsConfig};
+use crate::metrics_extraction::spans::extract_span_metrics;
 use crate::metrics_extraction::transactions::{extract_transaction_metrics, ExtractMetricsError};
 use crate::service::REGISTRY;
 use crate::statsd::{RelayCounters, RelayTimers};
 use crate::utils::{
-    self, ChunkedFormDataAggregator, EnvelopeContext, ErrorBoundary, FormDataIter, SamplingResult,
+    self, ChunkedFormDataAggregator, EnvelopeContext, EnvelopeLimiter, ErrorBoundary, FormDataIter,
+    SamplingResult,
 };
 
 #[cfg(feature = "processing")]
 use {
     crate::actors::envelopes::SendMetrics,
-    crate::actors::project_cache::UpdateRateLimits,
     crate::service::ServerError,
-    crate::utils::{EnvelopeLimiter, MetricsLimiter},
+    crate::utils::{MetricsLimiter, TransactionNameStore},
     anyhow::Context,
+    relay_general::protocol::TransactionSource,
     relay_general::store::{GeoIpLookup, StoreConfig, StoreProcessor},
-    relay_quotas::ItemScoping,
     relay_quotas::{RateLimitingError, RedisRateLimiter},
     symbolic_unreal::{Unreal4Error, Unreal4ErrorKind},
 };
@@ -212,6 +217,17 @@ fn has_unprintable_fields(event: &Annotated<Event>) -> bool {
     }
 }
 
+/// Emits metrics for matches of audited PII rules.
+fn report_pii_audit(matches: &[PiiAuditMatch]) {
+    for audit_match in matches {
+        metric!(
+            counter(RelayCounters::PiiAuditMatches) += 1,
+            rule = &audit_match.rule_id,
+            value_type = &audit_match.value_type.join(","),
+        );
+    }
+}
+
 /// A state container for envelope processing.
 #[derive(Debug)]
 struct ProcessEnvelopeState {
@@ -304,7 +320,6 @@ impl ProcessEnvelopeState {
     }
 
     /// Removes the event payload from this processing state.
-    #[cfg(feature = "processing")]
     fn remove_event(&mut self) {
         self.event = Annotated::empty();
     }
@@ -419,6 +434,19 @@ impl EncodeEnvelope {
     }
 }
 
+/// Explains how dynamic sampling evaluates the event of an envelope, without sampling it.
+///
+/// The processor owns the reservoir counters, so the explanation reflects how far reservoirs are
+/// filled. See [`utils::explain_sampling`] for more information.
+#[derive(Debug)]
+pub struct ExplainSampling {
+    pub envelope: Box<Envelope>,
+    pub event: Option<Event>,
+    pub client_ip: Option<net::IpAddr>,
+    pub project_state: Arc<ProjectState>,
+    pub sampling_project_state: Option<Arc<ProjectState>>,
+}
+
 /// Applies rate limits to metrics buckets and forwards them to the envelope manager.
 #[cfg(feature = "processing")]
 #[derive(Debug)]
@@ -433,6 +461,7 @@ pub enum EnvelopeProcessor {
     ProcessEnvelope(Box<ProcessEnvelope>),
     ProcessMetrics(Box<ProcessMetrics>),
     EncodeEnvelope(Box<EncodeEnvelope>),
+    ExplainSampling(Box<ExplainSampling>, Sender<SamplingExplanation>),
     #[cfg(feature = "processing")]
     RateLimitFlushBuckets(RateLimitFlushBuckets),
 }
@@ -469,6 +498,14 @@ impl FromMessage<EncodeEnvelope> for EnvelopeProcessor {
     }
 }
 
+impl FromMessage<ExplainSampling> for EnvelopeProcessor {
+    type Response = AsyncResponse<SamplingExplanation>;
+
+    fn from_message(message: ExplainSampling, sender: Sender<SamplingExplanation>) -> Self {
+        Self::ExplainSampling(Box::new(message), sender)
+    }
+}
+
 #[cfg(feature = "processing")]
 impl FromMessage<RateLimitFlushBuckets> for EnvelopeProcessor {
     type Response = NoResponse;
@@ -485,13 +522,24 @@ pub struct EnvelopeProcessorService {
     config: Arc<Config>,
     #[cfg(feature = "processing")]
     rate_limiter: Option<RedisRateLimiter>,
+    memory_rate_limiter: MemoryRateLimiter,
+    reservoir_counters: ReservoirCounters,
+    sourcemap_lookup: Option<SourceMapLookup>,
+    proguard_lookup: Option<ProguardLookup>,
     #[cfg(feature = "processing")]
     geoip_lookup: Option<GeoIpLookup>,
+    #[cfg(feature = "processing")]
+    transaction_names: Option<TransactionNameStore>,
 }
 
 impl EnvelopeProcessorService {
     /// Creates a multi-threaded envelope processor.
-    pub fn new(config: Arc<Config>, _redis: Option<RedisPool>) -> anyhow::Result<Self> {
+    #[cfg_attr(not(feature = "processing"), allow(unused_variables))]
+    pub fn new(config: Arc<Config>, redis: Option<RedisPool>) -> anyhow::Result<Self> {
+        let memory_rate_limiter = MemoryRateLimiter::new().max_limit(config.max_rate_limit());
+        let sourcemap_lookup = config.sourcemaps_path().map(SourceMapLookup::new);
+        let proguard_lookup = config.proguard_path().map(ProguardLookup::new);
+
         #[cfg(feature = "processing")]
         {
             let geoip_lookup = match config.geoip_path() {
@@ -499,18 +547,36 @@ impl EnvelopeProcessorService {
                 None => None,
             };
 
+            // Processing Relays share reservoir counters so that guarantees hold across instances.
+            let reservoir_counters = match redis {
+                Some(ref pool) => ReservoirCounters::new().with_redis(pool.clone()),
+                None => ReservoirCounters::new(),
+            };
+
+            let transaction_names = redis.clone().map(TransactionNameStore::new);
             let rate_limiter =
-                _redis.map(|pool| RedisRateLimiter::new(pool).max_limit(config.max_rate_limit()));
+                redis.map(|pool| RedisRateLimiter::new(pool).max_limit(config.max_rate_limit()));
 
             Ok(Self {
                 config,
                 rate_limiter,
+                memory_rate_limiter,
+                reservoir_counters,
+                sourcemap_lookup,
+                proguard_lookup,
                 geoip_lookup,
+                transaction_names,
             })
         }
 
         #[cfg(not(feature = "processing"))]
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            memory_rate_limiter,
+            reservoir_counters: ReservoirCounters::new(),
+            sourcemap_lookup,
+            proguard_lookup,
+        })
     }
 
     /// Returns Ok(true) if attributes were modified.
@@ -968,7 +1034,10 @@ impl EnvelopeProcessorService {
         }
 
         envelope.retain_items(|item| match item.ty() {
-            ItemType::Profile => match relay_profiling::expand_profile(&item.payload()) {
+            ItemType::Profile => match relay_profiling::expand_profile(
+                &item.payload(),
+                self.proguard_lookup.as_ref(),
+            ) {
                 Ok(payload) => {
                     if payload.len() <= self.config.max_profile_size() {
                         item.set_payload(ContentType::Json, payload);
@@ -1011,6 +1080,7 @@ impl EnvelopeProcessorService {
     /// Remove replays if the feature flag is not enabled
     fn process_replays(&self, state: &mut ProcessEnvelopeState) {
         let replays_enabled = state.project_state.has_feature(Feature::Replays);
+        let project_config = &state.project_state.config;
         let context = &state.envelope_context;
         let envelope = &mut state.envelope;
         let client_addr = envelope.meta().client_addr();
@@ -1040,16 +1110,34 @@ impl EnvelopeProcessorService {
                 }
             }
             ItemType::ReplayRecording => {
-                // XXX: Temporarily, only the Sentry org will be allowed to parse replays while
-                // we measure the impact of this change.
-                if replays_enabled && state.project_state.organization_id == Some(1) {
+                if replays_enabled {
                     // Limit expansion of recordings to the max replay size. The payload is
                     // decompressed temporarily and then immediately re-compressed. However, to
                     // limit memory pressure, we use the replay limit as a good overall limit for
                     // allocations.
                     let limit = self.config.max_replay_size();
-                    let parsed_recording =
-                        relay_replays::recording::process_recording(&item.payload(), limit);
+
+                    // Apply the same PII configs as to events. If the data scrubbing settings
+                    // cannot be converted, the recording is treated as invalid.
+                    let parsed_recording = match project_config.datascrubbing_settings.pii_config()
+                    {
+                        Ok(datascrubbing_config) => {
+                            let pii_configs: Vec<_> = project_config
+                                .pii_config
+                                .iter()
+                                .chain(datascrubbing_config)
+                                .map(PiiConfig::compiled)
+                                .collect();
+
+                            relay_replays::recording::process_recording(
+                                &item.payload(),
+                                limit,
+                                &pii_configs,
+                            )
+                            .map_err(|e| e.to_string())
+                        }
+                        Err(e) => Err(e.to_string()),
+                    };
 
                     match parsed_recording {
                         Ok(recording) => {
@@ -1074,6 +1162,33 @@ impl EnvelopeProcessorService {
         });
     }
 
+    /// Normalizes monitor check-ins and removes invalid ones.
+    fn process_check_ins(&self, state: &mut ProcessEnvelopeState) {
+        let context = &state.envelope_context;
+
+        state.envelope.retain_items(|item| {
+            if item.ty() != &ItemType::CheckIn {
+                return true;
+            }
+
+            match relay_monitors::process_check_in(&item.payload()) {
+                Ok(processed) => {
+                    item.set_payload(ContentType::Json, processed);
+                    true
+                }
+                Err(error) => {
+                    relay_log::debug!("dropped invalid monitor check-in: {}", LogError(&error));
+                    context.track_outcome(
+                        Outcome::Invalid(DiscardReason::InvalidCheckIn),
+                        DataCategory::Monitor,
+                        1,
+                    );
+                    false
+                }
+            }
+        });
+    }
+
     /// Creates and initializes the processing state.
     ///
     /// This applies defaults to the envelope and initializes empty rate limits.
@@ -1384,6 +1499,8 @@ impl EnvelopeProcessorService {
             ItemType::Profile => false,
             ItemType::ReplayEvent => false,
             ItemType::ReplayRecording => false,
+            ItemType::CheckIn => false,
+            ItemType::Span => false,
             // Without knowing more, `Unknown` items are allowed to be repeated
             ItemType::Unknown(_) => false,
         }
@@ -1679,6 +1796,53 @@ impl EnvelopeProcessorService {
         Ok(())
     }
 
+    /// Extracts the spans of an accepted transaction into individual span items.
+    ///
+    /// Span items are internal to processing Relays, so span items sent by clients are removed.
+    /// Extraction requires the span ingestion feature. Every span is scrubbed individually with the
+    /// project's data scrubbing settings and PII config.
+    #[cfg(feature = "processing")]
+    fn extract_spans(&self, state: &mut ProcessEnvelopeState) -> Result<(), ProcessingError> {
+        state
+            .envelope
+            .retain_items(|item| item.ty() != &ItemType::Span);
+
+        if !state.project_state.has_feature(Feature::SpanIngestion) {
+            return Ok(());
+        }
+
+        let event = match state.event.value() {
+            Some(event) => event,
+            None => return Ok(()),
+        };
+
+        let config = &state.project_state.config;
+        let mut processors = Vec::new();
+        if let Some(ref config) = config.pii_config {
+            processors.push(PiiProcessor::new(config.compiled()));
+        }
+        let pii_config = config
+            .datascrubbing_settings
+            .pii_config()
+            .map_err(|e| ProcessingError::PiiConfigError(e.clone()))?;
+        if let Some(config) = pii_config {
+            processors.push(PiiProcessor::new(config.compiled()));
+        }
+
+        for mut record in utils::extract_spans(event) {
+            for processor in &mut processors {
+                process_value(&mut record.span, processor, ProcessingState::root())?;
+            }
+
+            let payload = serde_json::to_vec(&record).map_err(ProcessingError::SerializeFailed)?;
+            let mut item = Item::new(ItemType::Span);
+            item.set_payload(ContentType::Json, payload);
+            state.envelope.add_item(item);
+        }
+
+        Ok(())
+    }
+
     /// Ensures there is a valid dynamic sampling context and corresponding project state.
     ///
     /// The dynamic sampling context (DSC) specifies the project_key of the project that initiated
@@ -1728,33 +1892,55 @@ impl EnvelopeProcessorService {
 
         metric!(timer(RelayTimers::EventProcessingFiltering), {
             relay_filter::should_filter(event, client_ip, filter_settings).map_err(|err| {
-                state.envelope_context.reject(Outcome::Filtered(err));
+                state
+                    .envelope_context
+                    .reject(Outcome::Filtered(err.clone()));
                 ProcessingError::EventFiltered(err)
             })
         })
     }
 
-    #[cfg(feature = "processing")]
+    /// Enforces the project's quotas on the envelope.
+    ///
+    /// Processing Relays track quotas in Redis. All other Relays track quotas in memory, so that
+    /// every instance enforces the project's quotas independently.
     fn enforce_quotas(&self, state: &mut ProcessEnvelopeState) -> Result<(), ProcessingError> {
-        let rate_limiter = match self.rate_limiter.as_ref() {
-            Some(rate_limiter) => rate_limiter,
-            None => return Ok(()),
-        };
-
-        let project_state = &state.project_state;
+        let project_state = state.project_state.clone();
         let quotas = project_state.config.quotas.as_slice();
         if quotas.is_empty() {
             return Ok(());
         }
 
+        #[cfg(feature = "processing")]
+        if let Some(rate_limiter) = self.rate_limiter.as_ref() {
+            return self.enforce_quotas_with(state, |item_scope, quantity| {
+                rate_limiter
+                    .is_rate_limited(quotas, item_scope, quantity, false)
+                    .map_err(ProcessingError::QuotasFailed)
+            });
+        }
+
+        self.enforce_quotas_with(state, |item_scope, quantity| {
+            let rate_limiter = &self.memory_rate_limiter;
+            Ok(rate_limiter.is_rate_limited(quotas, item_scope, quantity, false))
+        })
+    }
+
+    /// Enforces quotas on the envelope using the given rate limiter `check` function.
+    fn enforce_quotas_with<F>(
+        &self,
+        state: &mut ProcessEnvelopeState,
+        check: F,
+    ) -> Result<(), ProcessingError>
+    where
+        F: FnMut(ItemScoping<'_>, usize) -> Result<RateLimits, ProcessingError>,
+    {
+        let project_state = state.project_state.clone();
         let event_category = state.event_category();
 
         // When invoking the rate limiter, capture if the event item has been rate limited to also
         // remove it from the processing state eventually.
-        let mut envelope_limiter =
-            EnvelopeLimiter::new(Some(&project_state.config), |item_scope, quantity| {
-                rate_limiter.is_rate_limited(quotas, item_scope, quantity, false)
-            });
+        let mut envelope_limiter = EnvelopeLimiter::new(Some(&project_state.config), check);
 
         // Tell the envelope limiter about the event, since it has been removed from the Envelope at
         // this stage in processing.
@@ -1820,6 +2006,25 @@ impl EnvelopeProcessorService {
 
             result?;
 
+            if state
+                .project_state
+                .has_feature(Feature::SpanMetricsExtraction)
+            {
+                let extracted;
+                metric!(
+                    timer(RelayTimers::SpanMetricsExtraction),
+                    extracted_anything = &extracted.to_string(),
+                    {
+                        extracted = extract_span_metrics(
+                            self.config.aggregator_config(),
+                            extraction_config,
+                            event,
+                            &mut state.extracted_metrics,
+                        );
+                    }
+                );
+            }
+
             state.transaction_metrics_extracted = true;
             state.envelope_context.set_event_metrics_extracted();
         }
@@ -1827,6 +2032,41 @@ impl EnvelopeProcessorService {
         Ok(())
     }
 
+    /// Records the names of URL transactions for transaction name clustering.
+    ///
+    /// This runs after light normalization, so names that are already covered by transaction name
+    /// rules are not recorded again. It also runs after data scrubbing, so that names are only
+    /// stored with the project's PII rules applied.
+    #[cfg(feature = "processing")]
+    fn record_transaction_name(&self, state: &ProcessEnvelopeState) {
+        let store = match self.transaction_names {
+            Some(ref store) => store,
+            None => return,
+        };
+
+        if !state
+            .project_state
+            .has_feature(Feature::TransactionNameClustering)
+        {
+            return;
+        }
+
+        let event = match state.event.value() {
+            Some(event) if event.ty.value() == Some(&EventType::Transaction) => event,
+            _ => return,
+        };
+
+        if event.get_transaction_source() != &TransactionSource::Url {
+            return;
+        }
+
+        if let Some(name) = event.transaction.as_str() {
+            if let Err(error) = store.record(state.project_id, name) {
+                relay_log::error!("failed to record transaction name: {}", LogError(&error));
+            }
+        }
+    }
+
     /// Apply data privacy rules to the event payload.
     ///
     /// This uses both the general `datascrubbing_settings`, as well as the the PII rules.
@@ -1838,6 +2078,7 @@ impl EnvelopeProcessorService {
             if let Some(ref config) = config.pii_config {
                 let mut processor = PiiProcessor::new(config.compiled());
                 process_value(event, &mut processor, ProcessingState::root())?;
+                report_pii_audit(processor.audit_matches());
             }
             let pii_config = config
                 .datascrubbing_settings
@@ -1846,6 +2087,7 @@ impl EnvelopeProcessorService {
             if let Some(config) = pii_config {
                 let mut processor = PiiProcessor::new(config.compiled());
                 process_value(event, &mut processor, ProcessingState::root())?;
+                report_pii_audit(processor.audit_matches());
             }
         });
 
@@ -1939,6 +2181,7 @@ impl EnvelopeProcessorService {
             &state.project_state,
             state.sampling_project_state.as_deref(),
             self.config.processing_enabled(),
+            &self.reservoir_counters,
         ) {
             SamplingResult::Drop(rule_id) => {
                 state
@@ -1969,6 +2212,9 @@ impl EnvelopeProcessorService {
                 .project_state
                 .has_feature(Feature::TransactionNameNormalize),
             tx_name_rules: &state.project_state.config.tx_name_rules,
+            replace_span_descriptions: state
+                .project_state
+                .has_feature(Feature::ReplaceSpanDescriptions),
 
             is_renormalize: false,
         };
@@ -1981,6 +2227,32 @@ impl EnvelopeProcessorService {
         Ok(())
     }
 
+    /// Resolves minified JavaScript frames with source maps, if an artifact directory is
+    /// configured.
+    fn resolve_sourcemaps(&self, state: &mut ProcessEnvelopeState) {
+        let lookup = match self.sourcemap_lookup {
+            Some(ref lookup) => lookup,
+            None => return,
+        };
+
+        metric!(timer(RelayTimers::EventProcessingSourcemaps), {
+            relay_general::store::resolve_sourcemaps(&mut state.event, lookup);
+        });
+    }
+
+    /// Deobfuscates Java and Android frames with ProGuard mappings, if a mapping directory is
+    /// configured.
+    fn deobfuscate_event(&self, state: &mut ProcessEnvelopeState) {
+        let lookup = match self.proguard_lookup {
+            Some(ref lookup) => lookup,
+            None => return,
+        };
+
+        metric!(timer(RelayTimers::EventProcessingProguard), {
+            relay_general::store::deobfuscate_event(&mut state.event, lookup);
+        });
+    }
+
     fn process_state(&self, state: &mut ProcessEnvelopeState) -> Result<(), ProcessingError> {
         macro_rules! if_processing {
             ($if_true:block) => {
@@ -1996,6 +2268,8 @@ impl EnvelopeProcessorService {
         self.process_profiles(state);
         self.process_replays(state);
 
+        self.process_check_ins(state);
+
         if state.creates_event() {
             // Some envelopes only create events in processing relays; for example, unreal events.
             // This makes it possible to get in this code block while not really having an event in
@@ -2014,6 +2288,8 @@ impl EnvelopeProcessorService {
 
             self.finalize_event(state)?;
             self.light_normalize_event(state)?;
+            self.resolve_sourcemaps(state);
+            self.deobfuscate_event(state);
             self.normalize_dsc(state);
             self.filter_event(state)?;
             self.extract_transaction_metrics(state)?;
@@ -2025,11 +2301,18 @@ impl EnvelopeProcessorService {
         }
 
         if_processing!({
-            self.enforce_quotas(state)?;
+            self.extract_spans(state)?;
         });
 
+        self.enforce_quotas(state)?;
+
         if state.has_event() {
             self.scrub_event(state)?;
+
+            if_processing!({
+                self.record_transaction_name(state);
+            });
+
             self.serialize_event(state)?;
         }
 
@@ -2256,6 +2539,11 @@ impl EnvelopeProcessorService {
                 encoder.write_all(body.as_ref())?;
                 encoder.finish()?
             }
+            HttpEncoding::Zstd => {
+                let mut encoder = ZstdEncoder::new(Vec::new(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+                encoder.write_all(body.as_ref())?;
+                encoder.finish()?
+            }
         };
         Ok(envelope_body)
     }
@@ -2277,11 +2565,26 @@ impl EnvelopeProcessorService {
         }
     }
 
+    fn handle_explain_sampling(&self, message: ExplainSampling) -> SamplingExplanation {
+        utils::explain_sampling(
+            message.envelope.dsc(),
+            message.event.as_ref(),
+            message.client_ip,
+            &message.project_state,
+            message.sampling_project_state.as_deref(),
+            self.config.processing_enabled(),
+            &self.reservoir_counters,
+        )
+    }
+
     fn handle_message(&self, message: EnvelopeProcessor) {
         match message {
             EnvelopeProcessor::ProcessEnvelope(message) => self.handle_process_envelope(*message),
             EnvelopeProcessor::ProcessMetrics(message) => self.handle_process_metrics(*message),
             EnvelopeProcessor::EncodeEnvelope(message) => self.handle_encode_envelope(*message),
+            EnvelopeProcessor::ExplainSampling(message, sender) => {
+                sender.send(self.handle_explain_sampling(*message));
+            }
             #[cfg(feature = "processing")]
             EnvelopeProcessor::RateLimitFlushBuckets(message) => {
                 self.handle_rate_limit_flush_buckets(message);
@@ -2478,8 +2781,14 @@ mod tests {
             config: Arc::new(config),
             #[cfg(feature = "processing")]
             rate_limiter: None,
+            memory_rate_limiter: MemoryRateLimiter::new(),
+            reservoir_counters: ReservoirCounters::new(),
+            sourcemap_lookup: None,
+            proguard_lookup: None,
             #[cfg(feature = "processing")]
             geoip_lookup: None,
+            #[cfg(feature = "processing")]
+            transaction_names: None,
         }
     }
 
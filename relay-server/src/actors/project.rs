@@ -80,6 +80,19 @@ pub enum Feature {
     /// Replacing UUIDs, SHAs and numerical IDs by placeholders.
     #[serde(rename = "organizations:transaction-name-normalize")]
     TransactionNameNormalize,
+    /// Enables extraction of standalone spans from transactions into a dedicated Kafka topic.
+    #[serde(rename = "organizations:standalone-span-ingestion")]
+    SpanIngestion,
+    /// Enables extraction of duration metrics from the spans of transactions.
+    #[serde(rename = "projects:span-metrics-extraction")]
+    SpanMetricsExtraction,
+    /// Replaces the descriptions of database spans with their normalized form, which removes
+    /// literal values from SQL queries.
+    #[serde(rename = "projects:replace-span-descriptions")]
+    ReplaceSpanDescriptions,
+    /// Records URL transaction names in Redis to derive transaction name rules from them.
+    #[serde(rename = "organizations:transaction-name-clusterer")]
+    TransactionNameClustering,
 
     /// Unused.
     ///
@@ -306,12 +319,19 @@ impl ProjectState {
         self.invalid
     }
 
-    /// Returns whether this state is outdated and needs to be refetched.
-    fn check_expiry(&self, config: &Config) -> Expiry {
-        let expiry = match self.project_id {
+    /// Returns the duration after which this state becomes stale.
+    ///
+    /// Missing projects use a separate expiry from regular projects.
+    pub fn cache_expiry(&self, config: &Config) -> Duration {
+        match self.project_id {
             None => config.cache_miss_expiry(),
             Some(_) => config.project_cache_expiry(),
-        };
+        }
+    }
+
+    /// Returns whether this state is outdated and needs to be refetched.
+    fn check_expiry(&self, config: &Config) -> Expiry {
+        let expiry = self.cache_expiry(config);
 
         let elapsed = self.last_fetch.elapsed();
         if elapsed >= expiry + config.project_grace_period() {
@@ -606,6 +626,16 @@ impl Project {
         self.last_updated_at
     }
 
+    /// Sets a project state restored from a persistent cache.
+    ///
+    /// The state is only set if no state has been fetched yet. Unlike
+    /// [`update_state`](Self::update_state), this does not notify pending receivers.
+    pub fn restore_state(&mut self, state: Arc<ProjectState>) {
+        if self.state.is_none() {
+            self.state = Some(state);
+        }
+    }
+
     /// Refresh the update time of the project in order to delay eviction.
     ///
     /// Called by the project cache when the project state is refreshed.
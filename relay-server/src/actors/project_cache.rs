@@ -16,6 +16,7 @@ use relay_system::{compat, Addr, FromMessage, Interface, Sender, Service};
 use crate::actors::outcome::DiscardReason;
 use crate::actors::processor::ProcessEnvelope;
 use crate::actors::project::{Project, ProjectSender, ProjectState};
+use crate::actors::project_disk::DiskProjectCache;
 use crate::actors::project_local::{LocalProjectSource, LocalProjectSourceService};
 use crate::actors::project_upstream::UpstreamProjectSource;
 use crate::envelope::Envelope;
@@ -418,6 +419,7 @@ pub struct ProjectCacheService {
     projects: hashbrown::HashMap<ProjectKey, Project>,
     garbage_disposal: GarbageDisposal<Project>,
     source: ProjectSource,
+    disk_cache: Option<DiskProjectCache>,
     state_tx: mpsc::UnboundedSender<UpdateProjectState>,
     state_rx: mpsc::UnboundedReceiver<UpdateProjectState>,
 }
@@ -429,6 +431,7 @@ impl ProjectCacheService {
             config: config.clone(),
             projects: hashbrown::HashMap::new(),
             garbage_disposal: GarbageDisposal::new(),
+            disk_cache: DiskProjectCache::new(config.clone()),
             source: ProjectSource::new(config, redis),
             state_tx,
             state_rx,
@@ -451,7 +454,10 @@ impl ProjectCacheService {
 
         // Defer dropping the projects to a dedicated thread:
         let mut count = 0;
-        for (_, project) in expired {
+        for (project_key, project) in expired {
+            if let Some(ref disk_cache) = self.disk_cache {
+                disk_cache.remove(project_key);
+            }
             self.garbage_disposal.dispose(project);
             count += 1;
         }
@@ -487,10 +493,36 @@ impl ProjectCacheService {
             no_cache,
         } = message;
 
+        if let Some(ref disk_cache) = self.disk_cache {
+            if !state.invalid() {
+                disk_cache.store(project_key, state.clone());
+            }
+        }
+
         self.get_or_create_project(project_key)
             .update_state(state, no_cache);
     }
 
+    /// Restores project states persisted by a previous run of Relay.
+    ///
+    /// This must be called before handling any message, so that restored states never replace
+    /// states fetched in this run.
+    async fn restore_persisted_states(&mut self) {
+        let disk_cache = match self.disk_cache {
+            Some(ref disk_cache) => disk_cache.clone(),
+            None => return,
+        };
+
+        let states = match tokio::task::spawn_blocking(move || disk_cache.load()).await {
+            Ok(states) => states,
+            Err(_) => return,
+        };
+
+        for (project_key, state) in states {
+            self.get_or_create_project(project_key).restore_state(state);
+        }
+    }
+
     fn handle_request_update(&mut self, message: RequestUpdate) {
         let RequestUpdate {
             project_key,
@@ -610,6 +642,8 @@ impl Service for ProjectCacheService {
             let mut ticker = tokio::time::interval(self.config.cache_eviction_interval());
             relay_log::info!("project cache started");
 
+            self.restore_persisted_states().await;
+
             loop {
                 tokio::select! {
                     biased;
@@ -0,0 +1,312 @@
+//! Persistent on-disk cache for project states.
+//!
+//! Managed Relays write every valid project state they fetch into a cache directory. When Relay
+//! restarts, the persisted states are loaded back into the [`ProjectCache`] before any message is
+//! handled. This allows Relay to process envelopes right away, even if the upstream is not yet
+//! reachable.
+//!
+//! [`ProjectCache`]: crate::actors::project_cache::ProjectCache
+
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use relay_common::ProjectKey;
+use relay_config::Config;
+use relay_log::LogError;
+
+use crate::actors::project::ProjectState;
+
+/// The contents of a single project state file.
+///
+/// The state is generic so that it can be serialized from a reference.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedState<S> {
+    /// The time at which the state was fetched from the upstream.
+    fetched_at: DateTime<Utc>,
+    /// The full project state.
+    state: S,
+}
+
+/// Operations executed on the background thread of [`DiskProjectCache`].
+enum DiskOperation {
+    Store(ProjectKey, Arc<ProjectState>),
+    Remove(ProjectKey),
+}
+
+/// Returns the path of the state file for the given project key.
+fn state_path(directory: &Path, project_key: ProjectKey) -> PathBuf {
+    directory.join(format!("{}.json", project_key))
+}
+
+/// Atomically writes a project state by replacing the file after writing it completely.
+fn write_state(directory: &Path, project_key: ProjectKey, state: &ProjectState) -> io::Result<()> {
+    let age = chrono::Duration::from_std(state.last_fetch.elapsed())
+        .unwrap_or_else(|_| chrono::Duration::zero());
+    let persisted = PersistedState {
+        fetched_at: Utc::now() - age,
+        state,
+    };
+
+    let path = state_path(directory, project_key);
+    let tmp_path = path.with_extension("json.tmp");
+
+    let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+    serde_json::to_writer(&mut writer, &persisted)?;
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(tmp_path, path)
+}
+
+/// Removes the state file of the given project key if it exists.
+fn remove_state(directory: &Path, project_key: ProjectKey) -> io::Result<()> {
+    match fs::remove_file(state_path(directory, project_key)) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Reads a single project state file.
+fn read_state(path: &Path) -> io::Result<PersistedState<ProjectState>> {
+    let reader = BufReader::new(fs::File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+/// Persists project states to a directory on disk.
+///
+/// Writes and removals are executed in order on a dedicated background thread, so that they never
+/// block the project cache. When the instance is dropped, the background thread stops
+/// automatically.
+#[derive(Clone, Debug)]
+pub struct DiskProjectCache {
+    config: Arc<Config>,
+    directory: PathBuf,
+    tx: mpsc::Sender<DiskOperation>,
+}
+
+impl DiskProjectCache {
+    /// Creates the disk cache if it is enabled in the config.
+    ///
+    /// Returns `None` if `cache.project_persist_path` is not configured or if Relay is not running
+    /// in managed mode. The cache directory is created if it does not exist.
+    pub fn new(config: Arc<Config>) -> Option<Self> {
+        if config.relay_mode() != relay_config::RelayMode::Managed {
+            return None;
+        }
+
+        let directory = config.project_cache_persist_path()?;
+        if let Err(error) = fs::create_dir_all(&directory) {
+            relay_log::error!(
+                "failed to create project cache directory {:?}: {}",
+                directory,
+                LogError(&error)
+            );
+            return None;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let thread_directory = directory.clone();
+        std::thread::spawn(move || {
+            relay_log::debug!("start project disk cache thread");
+            while let Ok(operation) = rx.recv() {
+                let result = match operation {
+                    DiskOperation::Store(key, ref state) => {
+                        write_state(&thread_directory, key, state)
+                    }
+                    DiskOperation::Remove(key) => remove_state(&thread_directory, key),
+                };
+
+                if let Err(error) = result {
+                    relay_log::error!(
+                        "failed to update project cache on disk: {}",
+                        LogError(&error)
+                    );
+                }
+            }
+            relay_log::debug!("stop project disk cache thread");
+        });
+
+        Some(Self {
+            config,
+            directory,
+            tx,
+        })
+    }
+
+    /// Writes the project state to disk in the background.
+    pub fn store(&self, project_key: ProjectKey, state: Arc<ProjectState>) {
+        self.tx.send(DiskOperation::Store(project_key, state)).ok();
+    }
+
+    /// Removes the project state from disk in the background.
+    pub fn remove(&self, project_key: ProjectKey) {
+        self.tx.send(DiskOperation::Remove(project_key)).ok();
+    }
+
+    /// Loads all persisted project states from disk.
+    ///
+    /// Loaded states are at most as old as the configured project cache expiry. Older states are
+    /// considered stale but usable, and are refreshed in the background on first use, while the
+    /// [grace period](Config::project_grace_period) applies as usual. Files that cannot be read are
+    /// skipped.
+    ///
+    /// This performs blocking I/O and should be run on a blocking thread.
+    pub fn load(&self) -> Vec<(ProjectKey, Arc<ProjectState>)> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                relay_log::error!(
+                    "failed to read project cache directory: {}",
+                    LogError(&error)
+                );
+                return Vec::new();
+            }
+        };
+
+        let mut states = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+
+            let project_key = match path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| ProjectKey::parse(stem).ok())
+            {
+                Some(project_key) => project_key,
+                None => continue,
+            };
+
+            let PersistedState {
+                fetched_at,
+                mut state,
+            } = match read_state(&path) {
+                Ok(persisted) => persisted,
+                Err(error) => {
+                    relay_log::warn!(
+                        "skipping persisted project state {:?}: {}",
+                        path,
+                        LogError(&error)
+                    );
+                    continue;
+                }
+            };
+
+            // Backdate the state by its real age, so that states persisted long ago are treated as
+            // outdated. If the monotonic clock cannot represent that age, fall back to the shortest
+            // age that still marks the state as outdated.
+            let age = (Utc::now() - fetched_at).to_std().unwrap_or_default();
+            let outdated_age =
+                state.cache_expiry(&self.config) + self.config.project_grace_period();
+            let now = Instant::now();
+            state.last_fetch = now
+                .checked_sub(age)
+                .or_else(|| now.checked_sub(age.min(outdated_age)))
+                .unwrap_or(now);
+
+            states.push((project_key, Arc::new(state)));
+        }
+
+        relay_log::info!("loaded {} project states from disk", states.len());
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn create_cache(directory: &Path) -> DiskProjectCache {
+        let config = Config::from_json_value(json!({
+            "relay": {
+                "mode": "managed",
+            },
+            "cache": {
+                "project_expiry": 10,
+                "project_persist_path": directory,
+            }
+        }))
+        .unwrap();
+
+        DiskProjectCache::new(Arc::new(config)).unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let directory = tempfile::tempdir().unwrap();
+        let project_key = ProjectKey::parse("a94ae32be2584e0bbd7a4cbb95971fee").unwrap();
+
+        let mut state = ProjectState::allowed();
+        state.project_id = Some("42".parse().unwrap());
+        write_state(directory.path(), project_key, &state).unwrap();
+
+        let cache = create_cache(directory.path());
+        let states = cache.load();
+        assert_eq!(states.len(), 1);
+
+        let (loaded_key, loaded_state) = &states[0];
+        assert_eq!(*loaded_key, project_key);
+        assert_eq!(loaded_state.project_id, state.project_id);
+        assert!(!loaded_state.disabled());
+        assert!(loaded_state.last_fetch.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_old_state_is_stale() {
+        let directory = tempfile::tempdir().unwrap();
+        let project_key = ProjectKey::parse("a94ae32be2584e0bbd7a4cbb95971fee").unwrap();
+
+        let mut state = ProjectState::allowed();
+        state.project_id = Some("42".parse().unwrap());
+        let persisted = PersistedState {
+            fetched_at: Utc::now() - chrono::Duration::days(1),
+            state,
+        };
+        let file = fs::File::create(state_path(directory.path(), project_key)).unwrap();
+        serde_json::to_writer(file, &persisted).unwrap();
+
+        let cache = create_cache(directory.path());
+        let states = cache.load();
+        assert_eq!(states.len(), 1);
+
+        // The state keeps its real age and is outdated beyond the expiry and grace period.
+        let state = &states[0].1;
+        let outdated_age = state.cache_expiry(&cache.config) + cache.config.project_grace_period();
+        assert!(state.last_fetch.elapsed() >= outdated_age);
+    }
+
+    #[test]
+    fn test_skip_invalid_files() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("invalid.json"), "{}").unwrap();
+        fs::write(
+            directory
+                .path()
+                .join("a94ae32be2584e0bbd7a4cbb95971fee.json"),
+            "not json",
+        )
+        .unwrap();
+
+        assert!(create_cache(directory.path()).load().is_empty());
+    }
+
+    #[test]
+    fn test_remove_missing() {
+        let directory = tempfile::tempdir().unwrap();
+        let project_key = ProjectKey::parse("a94ae32be2584e0bbd7a4cbb95971fee").unwrap();
+        assert!(remove_state(directory.path(), project_key).is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+//! Ingestion of statsd and DogStatsD packets as custom metrics.
+//!
+//! The listener binds a UDP socket and a Unix datagram socket, if configured, and parses every
+//! received packet with [`Metric::parse_all`]. Metrics without an explicit namespace are assigned
+//! to the [custom](relay_metrics::MetricNamespace::Custom) namespace. The parsed metrics are sent
+//! to the [`ProjectCache`], which checks the project and inserts them into the metrics aggregator,
+//! just like metrics submitted in envelopes.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::UdpSocket;
+
+use relay_common::{ProjectKey, UnixTimestamp};
+use relay_config::Config;
+use relay_log::LogError;
+use relay_metrics::{InsertMetrics, Metric};
+use relay_statsd::metric;
+use relay_system::Addr;
+
+use crate::actors::project_cache::ProjectCache;
+use crate::statsd::RelayCounters;
+
+/// The tag that contains the project key of a metric.
+///
+/// This tag is removed from the metric before it is aggregated.
+const PROJECT_KEY_TAG: &str = "sentry_key";
+
+/// The maximum size of a datagram.
+const MAX_PACKET_SIZE: usize = 65_535;
+
+/// Parses all metrics in a packet and groups them by project key.
+///
+/// Lines that cannot be parsed, and metrics without a valid project key are skipped.
+fn parse_packet(
+    payload: &[u8],
+    timestamp: UnixTimestamp,
+    default_project_key: Option<ProjectKey>,
+) -> BTreeMap<ProjectKey, Vec<Metric>> {
+    let mut metrics = BTreeMap::<_, Vec<_>>::new();
+
+    for result in Metric::parse_all(payload, timestamp) {
+        let mut metric = match result {
+            Ok(metric) => metric,
+            Err(_) => {
+                metric!(
+                    counter(RelayCounters::StatsdListenerLines) += 1,
+                    outcome = "invalid"
+                );
+                continue;
+            }
+        };
+
+        let project_key = match metric.tags.remove(PROJECT_KEY_TAG) {
+            Some(key) => ProjectKey::parse(&key).ok(),
+            None => default_project_key,
+        };
+
+        match project_key {
+            Some(project_key) => {
+                metric!(
+                    counter(RelayCounters::StatsdListenerLines) += 1,
+                    outcome = "accepted"
+                );
+                metrics.entry(project_key).or_default().push(metric);
+            }
+            None => {
+                metric!(
+                    counter(RelayCounters::StatsdListenerLines) += 1,
+                    outcome = "no_project"
+                );
+            }
+        }
+    }
+
+    metrics
+}
+
+/// Receives statsd packets and forwards the contained metrics to the project cache.
+#[derive(Clone, Debug)]
+pub struct StatsdListener {
+    project_cache: Addr<ProjectCache>,
+    default_project_key: Option<ProjectKey>,
+}
+
+impl StatsdListener {
+    /// Binds all configured sockets and starts a background thread for each of them.
+    ///
+    /// Returns an error if one of the sockets cannot be bound.
+    pub fn start(config: &Config, project_cache: Addr<ProjectCache>) -> anyhow::Result<()> {
+        let listener = Self {
+            project_cache,
+            default_project_key: config.statsd_listener_default_project_key(),
+        };
+
+        let udp_addrs = config.statsd_listener_udp_addrs()?;
+        if !udp_addrs.is_empty() {
+            let socket = UdpSocket::bind(&udp_addrs[..])?;
+            relay_log::info!(
+                "listening for statsd metrics on udp://{}",
+                socket.local_addr()?
+            );
+
+            let listener = listener.clone();
+            std::thread::Builder::new()
+                .name("statsd-udp".to_owned())
+                .spawn(move || listener.receive_loop(|buf| socket.recv(buf)))?;
+        }
+
+        #[cfg(unix)]
+        if let Some(path) = config.statsd_listener_unix_path() {
+            // Remove the socket file of a previous run, otherwise binding fails.
+            match std::fs::remove_file(&path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => (),
+            }
+
+            let socket = std::os::unix::net::UnixDatagram::bind(&path)?;
+            relay_log::info!("listening for statsd metrics on unix://{}", path.display());
+
+            std::thread::Builder::new()
+                .name("statsd-unix".to_owned())
+                .spawn(move || listener.receive_loop(|buf| socket.recv(buf)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Receives packets until the socket fails.
+    fn receive_loop<F>(&self, mut recv: F)
+    where
+        F: FnMut(&mut [u8]) -> io::Result<usize>,
+    {
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+
+        loop {
+            match recv(&mut buf) {
+                Ok(len) => self.handle_packet(&buf[..len]),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    relay_log::error!("failed to receive statsd packet: {}", LogError(&error));
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle_packet(&self, payload: &[u8]) {
+        let timestamp = UnixTimestamp::now();
+        for (project_key, metrics) in parse_packet(payload, timestamp, self.default_project_key) {
+            relay_log::trace!("inserting statsd metrics into project cache");
+            self.project_cache
+                .send(InsertMetrics::new(project_key, metrics));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_packet() {
+        let key_a = ProjectKey::parse("a94ae32be2584e0bbd7a4cbb95971fee").unwrap();
+        let key_b = ProjectKey::parse("b94ae32be2584e0bbd7a4cbb95971fee").unwrap();
+
+        let payload = b"page.views:1|c|#sentry_key:b94ae32be2584e0bbd7a4cbb95971fee,route:home
+request.duration:42|ms|@0.5|#route:home
+_sc|my.service|0
+page.views:1|c|#sentry_key:invalid";
+
+        let timestamp = UnixTimestamp::from_secs(4711);
+        let metrics = parse_packet(payload, timestamp, Some(key_a));
+
+        assert_eq!(metrics.len(), 2);
+
+        let metrics_a = &metrics[&key_a];
+        assert_eq!(metrics_a.len(), 1);
+        assert_eq!(metrics_a[0].name, "d:custom/request.duration@millisecond");
+        assert_eq!(metrics_a[0].tags["route"], "home");
+
+        let metrics_b = &metrics[&key_b];
+        assert_eq!(metrics_b.len(), 1);
+        assert_eq!(metrics_b[0].name, "c:custom/page.views@none");
+        assert!(!metrics_b[0].tags.contains_key(PROJECT_KEY_TAG));
+    }
+
+    #[test]
+    fn test_parse_packet_without_default_key() {
+        let timestamp = UnixTimestamp::from_secs(4711);
+        let metrics = parse_packet(b"page.views:1|c", timestamp, None);
+        assert!(metrics.is_empty());
+    }
+}
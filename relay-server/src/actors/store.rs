@@ -15,6 +15,7 @@ use relay_general::protocol::{self, EventId, SessionAggregates, SessionStatus, S
 use relay_kafka::{ClientError, KafkaClient, KafkaTopic, Message};
 use relay_log::LogError;
 use relay_metrics::{Bucket, BucketValue, MetricNamespace, MetricResourceIdentifier};
+use relay_monitors::CheckIn;
 use relay_quotas::Scoping;
 use relay_statsd::metric;
 use relay_system::{AsyncResponse, FromMessage, Interface, Sender, Service};
@@ -192,6 +193,20 @@ impl StoreService {
                     retention,
                     item,
                 )?,
+                ItemType::CheckIn => self.produce_check_in(
+                    scoping.organization_id,
+                    scoping.project_id,
+                    start_time,
+                    retention,
+                    item,
+                )?,
+                ItemType::Span => self.produce_span(
+                    scoping.organization_id,
+                    scoping.project_id,
+                    start_time,
+                    retention,
+                    item,
+                )?,
                 _ => {}
             }
         }
@@ -471,6 +486,8 @@ impl StoreService {
         let topic = match mri.map(|mri| mri.namespace) {
             Ok(MetricNamespace::Transactions) => KafkaTopic::MetricsTransactions,
             Ok(MetricNamespace::Sessions) => KafkaTopic::MetricsSessions,
+            Ok(MetricNamespace::Spans) => KafkaTopic::MetricsSpans,
+            Ok(MetricNamespace::Custom) => KafkaTopic::MetricsCustom,
             Ok(MetricNamespace::Unsupported) | Err(_) => {
                 relay_log::with_scope(
                     |scope| {
@@ -564,6 +581,71 @@ impl StoreService {
         Ok(())
     }
 
+    fn produce_check_in(
+        &self,
+        organization_id: u64,
+        project_id: ProjectId,
+        start_time: Instant,
+        retention_days: u16,
+        item: &Item,
+    ) -> Result<(), StoreError> {
+        let payload = item.payload();
+
+        // Check-ins have been validated during processing. Check-ins of the same job run share an
+        // identifier, which is used as partition key to keep their order.
+        let check_in_id = serde_json::from_slice::<CheckIn>(&payload)
+            .map_or_else(|_| Uuid::nil(), |check_in| check_in.check_in_id);
+
+        let message = KafkaMessage::CheckIn(CheckInKafkaMessage {
+            check_in_id,
+            project_id,
+            start_time: UnixTimestamp::from_instant(start_time).as_secs(),
+            payload,
+            retention_days,
+        });
+
+        relay_log::trace!("Sending check-in to Kafka");
+        self.produce(KafkaTopic::Monitors, organization_id, message)?;
+        metric!(
+            counter(RelayCounters::ProcessingMessageProduced) += 1,
+            event_type = "check_in"
+        );
+        Ok(())
+    }
+
+    fn produce_span(
+        &self,
+        organization_id: u64,
+        project_id: ProjectId,
+        start_time: Instant,
+        retention_days: u16,
+        item: &Item,
+    ) -> Result<(), StoreError> {
+        let span = match serde_json::from_slice(&item.payload()) {
+            Ok(span) => span,
+            Err(error) => {
+                relay_log::error!("failed to parse span: {}", LogError(&error));
+                return Ok(());
+            }
+        };
+
+        let message = KafkaMessage::Span(SpanKafkaMessage {
+            organization_id,
+            project_id,
+            start_time: UnixTimestamp::from_instant(start_time).as_secs(),
+            retention_days,
+            span,
+        });
+
+        relay_log::trace!("Sending span to Kafka");
+        self.produce(KafkaTopic::Spans, organization_id, message)?;
+        metric!(
+            counter(RelayCounters::ProcessingMessageProduced) += 1,
+            event_type = "span"
+        );
+        Ok(())
+    }
+
     fn produce_replay_event(
         &self,
         replay_id: EventId,
@@ -954,6 +1036,20 @@ struct MetricKafkaMessage {
     tags: BTreeMap<String, String>,
 }
 
+#[derive(Clone, Debug, Serialize)]
+struct CheckInKafkaMessage {
+    /// The identifier of the check-in, used as partition key.
+    #[serde(skip)]
+    check_in_id: Uuid,
+    /// Raw check-in payload.
+    payload: Bytes,
+    /// Time at which the check-in was received by Relay.
+    start_time: u64,
+    /// The project id for the current check-in.
+    project_id: ProjectId,
+    retention_days: u16,
+}
+
 #[derive(Clone, Debug, Serialize)]
 struct ProfileKafkaMessage {
     organization_id: u64,
@@ -963,6 +1059,17 @@ struct ProfileKafkaMessage {
     payload: Bytes,
 }
 
+#[derive(Clone, Debug, Serialize)]
+struct SpanKafkaMessage {
+    organization_id: u64,
+    project_id: ProjectId,
+    /// Time at which the transaction was received by Relay.
+    start_time: u64,
+    retention_days: u16,
+    /// The extracted span record, including trace and segment identifiers.
+    span: serde_json::Value,
+}
+
 /// An enum over all possible ingest messages.
 #[derive(Debug, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -979,6 +1086,8 @@ enum KafkaMessage {
     ReplayRecordingNotChunked(ReplayRecordingNotChunkedKafkaMessage),
     ReplayRecording(ReplayRecordingKafkaMessage),
     ReplayRecordingChunk(ReplayRecordingChunkKafkaMessage),
+    CheckIn(CheckInKafkaMessage),
+    Span(SpanKafkaMessage),
 }
 
 impl Message for KafkaMessage {
@@ -995,6 +1104,8 @@ impl Message for KafkaMessage {
             KafkaMessage::ReplayRecording(_) => "replay_recording",
             KafkaMessage::ReplayRecordingChunk(_) => "replay_recording_chunk",
             KafkaMessage::ReplayRecordingNotChunked(_) => "replay_recording_not_chunked",
+            KafkaMessage::CheckIn(_) => "check_in",
+            KafkaMessage::Span(_) => "span",
         }
     }
 
@@ -1012,6 +1123,8 @@ impl Message for KafkaMessage {
             Self::ReplayRecording(message) => message.replay_id.0,
             Self::ReplayRecordingChunk(message) => message.replay_id.0,
             Self::ReplayRecordingNotChunked(_message) => Uuid::nil(), // Ensure random partitioning.
+            Self::CheckIn(message) => message.check_in_id,
+            Self::Span(_message) => Uuid::nil(),
         };
 
         if uuid.is_nil() {
@@ -1033,6 +1146,9 @@ impl Message for KafkaMessage {
             KafkaMessage::ReplayEvent(message) => {
                 serde_json::to_vec(message).map_err(ClientError::InvalidJson)
             }
+            KafkaMessage::Span(message) => {
+                serde_json::to_vec(message).map_err(ClientError::InvalidJson)
+            }
             _ => rmp_serde::to_vec_named(&self).map_err(ClientError::InvalidMsgPack),
         }
     }
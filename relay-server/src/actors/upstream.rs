@@ -70,7 +70,7 @@ pub enum UpstreamRequestError {
 
 impl UpstreamRequestError {
     /// Returns `true` if the error indicates a network downtime.
-    fn is_network_error(&self) -> bool {
+    pub fn is_network_error(&self) -> bool {
         match self {
             Self::SendFailed(_) => true,
             Self::ResponseError(code, _) => matches!(code.as_u16(), 502 | 503 | 504),
@@ -13,20 +13,22 @@ use futures01::prelude::*;
 use serde::Deserialize;
 
 use relay_common::{clone, tryf};
+use relay_config::RelayInfo;
 use relay_general::protocol::{EventId, EventType};
 use relay_log::LogError;
+use relay_monitors::ProcessCheckInError;
 use relay_quotas::RateLimits;
 use relay_statsd::metric;
 
 use crate::actors::outcome::{DiscardReason, Outcome};
-use crate::actors::processor::{EnvelopeProcessor, ProcessMetrics};
-use crate::actors::project_cache::{CheckEnvelope, ProjectCache, ValidateEnvelope};
-use crate::envelope::{AttachmentType, Envelope, EnvelopeError, Item, ItemType, Items};
+use crate::actors::project::ProjectState;
+use crate::envelope::{AttachmentType, Envelope, EnvelopeError, ItemType, Items};
 use crate::extractors::RequestMeta;
 use crate::service::{ServiceApp, ServiceState};
 use crate::statsd::RelayCounters;
 use crate::utils::{
-    self, ApiErrorResponse, BufferError, BufferGuard, EnvelopeContext, FormDataIter, MultipartError,
+    self, ApiErrorResponse, BufferError, CheckEnvelopeError, EnvelopeContext, EnvelopeSpool,
+    FormDataIter, MultipartError, OtlpError,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -64,6 +66,12 @@ pub enum BadStoreRequest {
     #[error("invalid event id")]
     InvalidEventId,
 
+    #[error("invalid opentelemetry payload")]
+    InvalidOtlp(#[source] OtlpError),
+
+    #[error("invalid monitor check-in")]
+    InvalidCheckIn(#[source] ProcessCheckInError),
+
     #[error("failed to queue envelope")]
     QueueFailed(#[from] BufferError),
 
@@ -85,6 +93,17 @@ impl From<PayloadError> for BadStoreRequest {
     }
 }
 
+impl From<CheckEnvelopeError> for BadStoreRequest {
+    fn from(error: CheckEnvelopeError) -> Self {
+        match error {
+            CheckEnvelopeError::EmptyEnvelope => Self::EmptyEnvelope,
+            CheckEnvelopeError::ScheduleFailed => Self::ScheduleFailed,
+            CheckEnvelopeError::Rejected(reason) => Self::EventRejected(reason),
+            CheckEnvelopeError::TooLarge => PayloadError::Overflow.into(),
+        }
+    }
+}
+
 impl ResponseError for BadStoreRequest {
     fn error_response(&self) -> HttpResponse {
         let body = ApiErrorResponse::from_error(self);
@@ -227,6 +246,17 @@ pub fn event_id_from_items(items: &Items) -> Result<Option<EventId>, BadStoreReq
     Ok(None)
 }
 
+/// Returns `true` if the Relay may read the configuration of the project.
+///
+/// Internal Relays have access to all projects, other Relays must be trusted by the project.
+pub fn has_project_access(relay: &RelayInfo, project_state: &ProjectState) -> bool {
+    relay.internal
+        || project_state
+            .config
+            .trusted_relays
+            .contains(&relay.public_key)
+}
+
 /// Creates a preconfigured CORS middleware builder for store requests.
 ///
 /// To configure CORS, register endpoints using `resource()` and finalize by calling `register()`, which
@@ -260,67 +290,82 @@ pub fn cors(app: ServiceApp) -> CorsBuilder<ServiceState> {
     builder
 }
 
-/// Queues an envelope for processing.
-///
-/// Depending on the items in the envelope, there are multiple outcomes:
+/// Writes a checked envelope to the on-disk spool.
 ///
-/// - Events and event related items, such as attachments, are always queued together. See the
-///   [crate-level documentation](crate) for a full description of how envelopes are
-///   queued and processed.
-/// - Sessions and Session batches are always queued separately. If they occur in the same envelope
-///   as an event, they are split off. Their path is the same as other Envelopes.
-/// - Metrics are directly sent to the [`EnvelopeProcessor`], bypassing the manager's queue and
-///   going straight into metrics aggregation. See [`ProcessMetrics`] for a full description.
-///
-/// Queueing can fail if the queue exceeds `envelope_buffer_size`. In this case, `Err` is
-/// returned and the envelope is not queued.
-fn queue_envelope(
-    mut envelope: Box<Envelope>,
+/// Returns `Err` if the envelope could not be written. In this case, the envelope is rejected.
+async fn spool_envelope(
+    spool: &EnvelopeSpool,
+    envelope: Box<Envelope>,
     mut envelope_context: EnvelopeContext,
-    buffer_guard: &BufferGuard,
-) -> Result<(), BadStoreRequest> {
-    // Remove metrics from the envelope and queue them directly on the project's `Aggregator`.
-    let mut metric_items = Vec::new();
-    let is_metric = |i: &Item| matches!(i.ty(), ItemType::Metrics | ItemType::MetricBuckets);
-    while let Some(item) = envelope.take_item_by(is_metric) {
-        metric_items.push(item);
-    }
-
-    if !metric_items.is_empty() {
-        relay_log::trace!("sending metrics into processing queue");
-        EnvelopeProcessor::from_registry().send(ProcessMetrics {
-            items: metric_items,
-            project_key: envelope.meta().public_key(),
-            start_time: envelope.meta().start_time(),
-            sent_at: envelope.sent_at(),
-        });
+) -> Result<(), ()> {
+    if let Err(error) = spool
+        .push(&envelope, envelope_context.scoping(), false)
+        .await
+    {
+        relay_log::error!("failed to spool envelope: {}", LogError(&error));
+        envelope_context.reject(Outcome::Invalid(DiscardReason::Internal));
+        return Err(());
     }
 
-    // Split the envelope into event-related items and other items. This allows to fast-track:
-    //  1. Envelopes with only session items. They only require rate limiting.
-    //  2. Event envelope processing can bail out if the event is filtered or rate limited,
-    //     since all items depend on this event.
-    if let Some(event_envelope) = envelope.split_by(Item::requires_event) {
-        relay_log::trace!("queueing separate envelope for non-event items");
+    metric!(
+        counter(RelayCounters::EnvelopeSpooled) += 1,
+        reason = "buffer_full"
+    );
+    envelope_context.accept();
+    Ok(())
+}
 
-        // The envelope has been split, so we need to fork the context.
-        let event_context = buffer_guard.enter(&event_envelope)?;
+/// Checks an envelope extracted from a store-like request and queues it for processing.
+///
+/// This removes unknown items if configured, reserves a slot in the envelope buffer, checks the
+/// envelope against cached project state and rate limits, and finally queues it. If the envelope
+/// buffer is full and the envelope spool is enabled, the checked envelope is written to disk
+/// instead. Resolves to `Ok` if the envelope was queued without any rate limits applying to it.
+/// Otherwise, the future resolves to the respective [`BadStoreRequest`]; this includes
+/// [`BadStoreRequest::EmptyEnvelope`] as a control-flow error for envelopes without items.
+pub fn check_and_queue_envelope(
+    state: &ServiceState,
+    mut envelope: Box<Envelope>,
+) -> ResponseFuture<(), BadStoreRequest> {
+    let config = state.config();
+    let buffer_guard = state.buffer_guard();
+    let spool = state.envelope_spool();
+
+    // If configured, remove unknown items at the very beginning. If the envelope is empty, we fail
+    // the request with a special control flow error to skip checks and queueing, that still
+    // results in a `200 OK` response.
+    utils::remove_unknown_items(&config, &mut envelope);
+
+    let future = async move {
+        // If the buffer is at capacity, the envelope is checked without a slot and then written to
+        // disk. It will be queued for processing once the buffer has capacity again.
+        let (envelope_context, buffer_error) = match buffer_guard.enter(&envelope) {
+            Ok(envelope_context) => (envelope_context, None),
+            Err(error) if spool.is_some() => (EnvelopeContext::standalone(&envelope), Some(error)),
+            Err(error) => return Err(BadStoreRequest::QueueFailed(error)),
+        };
+
+        let checked = utils::check_envelope(&config, envelope, envelope_context).await?;
+
+        if let Some((envelope, envelope_context)) = checked.envelope {
+            match (buffer_error, spool) {
+                (Some(error), Some(spool)) => {
+                    spool_envelope(&spool, envelope, envelope_context)
+                        .await
+                        .map_err(|_| BadStoreRequest::QueueFailed(error))?;
+                }
+                _ => utils::queue_envelope(envelope, envelope_context, &buffer_guard)?,
+            }
+        }
 
-        // Update the old context after successful forking.
-        envelope_context.update(&envelope);
-        ProjectCache::from_registry().send(ValidateEnvelope::new(event_envelope, event_context));
-    }
+        if checked.rate_limits.is_limited() {
+            return Err(BadStoreRequest::RateLimited(checked.rate_limits));
+        }
 
-    if envelope.is_empty() {
-        // The envelope can be empty here if it contained only metrics items which were removed
-        // above. In this case, the envelope was accepted and needs no further queueing.
-        envelope_context.accept();
-    } else {
-        relay_log::trace!("queueing envelope");
-        ProjectCache::from_registry().send(ValidateEnvelope::new(envelope, envelope_context));
-    }
+        Ok(())
+    };
 
-    Ok(())
+    Box::new(future.boxed_local().compat())
 }
 
 /// Handles Sentry events.
@@ -355,59 +400,18 @@ where
         version = &format!("{}", version)
     );
 
-    let buffer_guard = request.state().buffer_guard();
-    let config = request.state().config();
+    let state = request.state().clone();
     let event_id = Rc::new(RefCell::new(None));
 
     let future = extract_envelope(&request, meta)
         .into_future()
-        .and_then(clone!(config, event_id, |mut envelope| {
+        .and_then(clone!(event_id, |envelope| {
             *event_id.borrow_mut() = envelope.event_id();
-
-            // If configured, remove unknown items at the very beginning. If the envelope is
-            // empty, we fail the request with a special control flow error to skip checks and
-            // queueing, that still results in a `200 OK` response.
-            utils::remove_unknown_items(&config, &mut envelope);
-
-            let mut envelope_context = request
-                .state()
-                .buffer_guard()
-                .enter(&envelope)
-                .map_err(BadStoreRequest::QueueFailed)?;
-
-            if envelope.is_empty() {
-                envelope_context.reject(Outcome::Invalid(DiscardReason::EmptyEnvelope));
-                Err(BadStoreRequest::EmptyEnvelope)
-            } else {
-                Ok((envelope, envelope_context))
-            }
+            check_and_queue_envelope(&state, envelope)
         }))
-        .and_then(move |(envelope, envelope_context)| {
-            ProjectCache::from_registry()
-                .send(CheckEnvelope::new(envelope, envelope_context))
-                .boxed_local()
-                .compat()
-                .map_err(|_| BadStoreRequest::ScheduleFailed)
-        })
-        .and_then(move |response| {
-            let checked = response.map_err(BadStoreRequest::EventRejected)?;
-
-            if let Some((envelope, mut envelope_context)) = checked.envelope {
-                if !utils::check_envelope_size_limits(&config, &envelope) {
-                    envelope_context.reject(Outcome::Invalid(DiscardReason::TooLarge));
-                    return Err(PayloadError::Overflow.into());
-                }
-
-                let event_id = envelope.event_id();
-                queue_envelope(envelope, envelope_context, &buffer_guard)?;
-
-                if !checked.rate_limits.is_limited() {
-                    return Ok(create_response(event_id));
-                }
-            }
-
-            Err(BadStoreRequest::RateLimited(checked.rate_limits))
-        })
+        .and_then(clone!(event_id, |()| Ok(create_response(
+            *event_id.borrow()
+        ))))
         .or_else(move |error: BadStoreRequest| {
             metric!(counter(RelayCounters::EnvelopeRejected) += 1);
             let event_id = *event_id.borrow();
@@ -0,0 +1,27 @@
+//! Exposes internal metrics in the Prometheus text exposition format.
+
+use actix_web::{HttpRequest, HttpResponse};
+
+use crate::service::{ServiceApp, ServiceState};
+
+/// The content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Renders all metrics recorded through `relay_statsd`.
+///
+/// Responds with `404 Not Found` if Prometheus is not enabled in the metrics configuration.
+fn metrics(_: &HttpRequest<ServiceState>) -> HttpResponse {
+    match relay_statsd::prometheus_registry() {
+        Some(registry) => HttpResponse::Ok()
+            .content_type(CONTENT_TYPE)
+            .body(registry.render()),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+pub fn configure_app(app: ServiceApp) -> ServiceApp {
+    app.resource("/metrics", |r| {
+        r.name("internal-metrics");
+        r.get().f(metrics);
+    })
+}
@@ -11,32 +11,46 @@ mod envelope;
 mod events;
 mod forward;
 mod health_check;
+mod metrics;
 mod minidump;
+mod monitors;
+mod otlp;
 mod outcomes;
 mod project_configs;
 mod public_keys;
+mod sampling_debug;
 mod security_report;
 mod statics;
 mod store;
+#[cfg(feature = "processing")]
+mod transaction_names;
 mod unreal;
 
 pub fn configure_app(app: ServiceApp) -> ServiceApp {
+    // Transaction names are only recorded by processing Relays.
+    #[cfg(feature = "processing")]
+    let app = app.configure(transaction_names::configure_app);
+
     app
         // Internal routes pointing to /api/relay
         .configure(health_check::configure_app)
+        .configure(metrics::configure_app)
         .configure(events::configure_app)
         .handler("/api/relay", statics::not_found)
         // Web API routes pointing to /api/0
         .configure(project_configs::configure_app)
         .configure(public_keys::configure_app)
         .configure(outcomes::configure_app)
+        .configure(sampling_debug::configure_app)
         // Ingestion routes pointing to /api/<project_id>/
         .configure(store::configure_app)
         .configure(envelope::configure_app)
+        .configure(otlp::configure_app)
         .configure(security_report::configure_app)
         .configure(minidump::configure_app)
         .configure(attachments::configure_app)
         .configure(unreal::configure_app)
+        .configure(monitors::configure_app)
         // `forward` must be last as it creates a wildcard proxy
         .configure(forward::configure_app)
 }
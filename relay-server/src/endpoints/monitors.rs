@@ -0,0 +1,97 @@
+//! Lightweight endpoint for monitor check-ins.
+//!
+//! This allows scheduled jobs to check in with a single HTTP request, for instance using `curl`,
+//! without constructing an envelope. The check-in is passed in query parameters.
+
+use actix_web::actix::ResponseFuture;
+use actix_web::{HttpRequest, HttpResponse, Query};
+use serde::{Deserialize, Serialize};
+
+use relay_common::{tryf, Uuid};
+use relay_monitors::{CheckIn, CheckInStatus};
+
+use crate::endpoints::common::{self, BadStoreRequest};
+use crate::envelope::{ContentType, Envelope, Item, ItemType};
+use crate::extractors::RequestMeta;
+use crate::service::{ServiceApp, ServiceState};
+
+#[derive(Debug, Deserialize)]
+struct CheckInParams {
+    status: CheckInStatus,
+    check_in_id: Option<Uuid>,
+    environment: Option<String>,
+    duration: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct CheckInResponse {
+    id: String,
+}
+
+fn extract_envelope(
+    meta: RequestMeta,
+    check_in: CheckIn,
+) -> Result<Box<Envelope>, BadStoreRequest> {
+    let payload = serde_json::to_vec(&check_in).map_err(BadStoreRequest::InvalidJson)?;
+
+    let mut item = Item::new(ItemType::CheckIn);
+    item.set_payload(ContentType::Json, payload);
+
+    let mut envelope = Envelope::from_request(None, meta);
+    envelope.add_item(item);
+    Ok(envelope)
+}
+
+/// Handler for monitor check-ins via query parameters.
+///
+/// The check-in is validated before it is queued, so that invalid requests are rejected right
+/// away instead of being dropped silently during processing.
+fn store_check_in(
+    meta: RequestMeta,
+    request: HttpRequest<ServiceState>,
+    params: Query<CheckInParams>,
+) -> ResponseFuture<HttpResponse, BadStoreRequest> {
+    let params = params.into_inner();
+    let monitor_slug = request
+        .match_info()
+        .get("monitor_slug")
+        .unwrap_or_default()
+        .to_owned();
+
+    let mut check_in = CheckIn {
+        check_in_id: params.check_in_id.unwrap_or_else(Uuid::new_v4),
+        monitor_slug,
+        status: params.status,
+        environment: params.environment,
+        duration: params.duration,
+    };
+
+    tryf!(check_in
+        .normalize()
+        .map_err(BadStoreRequest::InvalidCheckIn));
+
+    let id = check_in.check_in_id;
+    common::handle_store_like_request(
+        meta,
+        request,
+        move |_, meta| extract_envelope(meta, check_in),
+        move |_| {
+            let id = id.to_simple().to_string();
+            HttpResponse::Accepted().json(CheckInResponse { id })
+        },
+        true,
+    )
+}
+
+pub fn configure_app(app: ServiceApp) -> ServiceApp {
+    common::cors(app)
+        .resource(
+            &common::normpath(r"/api/{project:\d+}/cron/{monitor_slug:[^/]+}/"),
+            |r| {
+                r.name("store-check-in");
+                r.post().with(store_check_in);
+                r.get().with(store_check_in);
+            },
+        )
+        .register()
+}
@@ -0,0 +1,131 @@
+//! Handles OpenTelemetry trace exports via OTLP/HTTP.
+//!
+//! Spans are converted into transaction events, see [`utils::envelopes_from_traces`].
+
+use actix_web::actix::ResponseFuture;
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use futures01::{future, Future};
+
+use relay_log::LogError;
+use relay_statsd::metric;
+
+use crate::body::RequestBody;
+use crate::endpoints::common::{self, BadStoreRequest};
+use crate::envelope::Envelope;
+use crate::extractors::RequestMeta;
+use crate::service::{ServiceApp, ServiceState};
+use crate::statsd::RelayCounters;
+use crate::utils::{
+    self, ExportTraceServiceRequest, OTLP_JSON_CONTENT_TYPE, OTLP_PROTOBUF_CONTENT_TYPE,
+};
+
+fn content_type(request: &HttpRequest<ServiceState>) -> String {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+fn extract_envelopes(
+    request: &HttpRequest<ServiceState>,
+    meta: RequestMeta,
+) -> ResponseFuture<Vec<Box<Envelope>>, BadStoreRequest> {
+    let max_payload_size = request.state().config().max_envelope_size();
+    let content_type = content_type(request);
+
+    let future = RequestBody::new(request, max_payload_size)
+        .map_err(BadStoreRequest::from)
+        .and_then(move |data| {
+            if data.is_empty() {
+                return Err(BadStoreRequest::EmptyBody);
+            }
+
+            let traces = ExportTraceServiceRequest::parse(&data, &content_type)
+                .map_err(BadStoreRequest::InvalidOtlp)?;
+
+            Ok(utils::envelopes_from_traces(traces, &meta))
+        });
+
+    Box::new(future)
+}
+
+/// Creates an empty `ExportTraceServiceResponse` in the encoding of the request.
+fn create_response(is_json: bool) -> HttpResponse {
+    if is_json {
+        HttpResponse::Ok()
+            .content_type(OTLP_JSON_CONTENT_TYPE)
+            .body("{}")
+    } else {
+        HttpResponse::Ok()
+            .content_type(OTLP_PROTOBUF_CONTENT_TYPE)
+            .finish()
+    }
+}
+
+/// Handler for the OTLP/HTTP trace export endpoint.
+///
+/// Every transaction is checked and queued in its own envelope. The request succeeds if at least
+/// one of the transactions was accepted, since exporters retry the entire batch on failure.
+/// Otherwise, the error of the first rejected transaction is returned.
+fn store_traces(
+    meta: RequestMeta,
+    request: HttpRequest<ServiceState>,
+) -> ResponseFuture<HttpResponse, BadStoreRequest> {
+    let state = request.state().clone();
+    let is_json = content_type(&request).starts_with(OTLP_JSON_CONTENT_TYPE);
+
+    let future = extract_envelopes(&request, meta)
+        .and_then(move |envelopes| {
+            let futures = envelopes.into_iter().map(move |envelope| {
+                common::check_and_queue_envelope(&state, envelope).then(Ok::<_, BadStoreRequest>)
+            });
+
+            future::join_all(futures)
+        })
+        .and_then(move |results| {
+            let mut first_error = None;
+            let mut accepted = false;
+
+            for result in results {
+                match result {
+                    Ok(()) => accepted = true,
+                    // This is a control-flow error without a bad status code.
+                    Err(BadStoreRequest::EmptyEnvelope) => (),
+                    Err(error) => {
+                        metric!(counter(RelayCounters::EnvelopeRejected) += 1);
+                        first_error.get_or_insert(error);
+                    }
+                }
+            }
+
+            match first_error {
+                Some(error) if !accepted => Err(error),
+                _ => Ok(create_response(is_json)),
+            }
+        })
+        .or_else(|error: BadStoreRequest| {
+            let response = error.error_response();
+            if response.status().is_server_error() {
+                relay_log::error!("error handling otlp request: {}", LogError(&error));
+            }
+
+            Ok(response)
+        });
+
+    Box::new(future)
+}
+
+pub fn configure_app(app: ServiceApp) -> ServiceApp {
+    common::cors(app)
+        .resource(
+            &common::normpath(r"/api/{project:\d+}/otlp/v1/traces"),
+            |r| {
+                r.name("store-otlp-traces");
+                r.post().with(store_traces);
+            },
+        )
+        .register()
+}
@@ -11,6 +11,7 @@ use relay_common::ProjectKey;
 
 use crate::actors::project::{LimitedProjectState, ProjectState};
 use crate::actors::project_cache::{GetCachedProjectState, GetProjectState, ProjectCache};
+use crate::endpoints::common::has_project_access;
 use crate::extractors::SignedJson;
 use crate::service::ServiceApp;
 use crate::utils::ErrorBoundary;
@@ -159,13 +160,7 @@ fn get_project_configs(
                 Some(project_state) => {
                     // If public key is known (even if rate-limited, which is Some(false)), it has
                     // access to the project config
-                    let has_access = relay.internal
-                        || project_state
-                            .config
-                            .trusted_relays
-                            .contains(&relay.public_key);
-
-                    if has_access {
+                    if has_project_access(&relay, &project_state) {
                         let wrapper = ProjectStateWrapper::new((*project_state).clone(), full);
                         configs.insert(project_key, Some(wrapper));
                     } else {
@@ -0,0 +1,178 @@
+//! Endpoint to explain dynamic sampling decisions for an envelope.
+
+use std::net::IpAddr;
+
+use actix::prelude::*;
+use actix_web::{Error, HttpResponse, ResponseError};
+use bytes::Bytes;
+use futures::{FutureExt, TryFutureExt};
+use serde::Deserialize;
+
+use relay_common::ProjectKey;
+use relay_config::Config;
+use relay_general::protocol::{Event, EventType};
+use relay_general::types::Annotated;
+
+use crate::actors::processor::{EnvelopeProcessor, ExplainSampling};
+use crate::actors::project::ProjectState;
+use crate::actors::project_cache::{GetProjectState, ProjectCache};
+use crate::endpoints::common::has_project_access;
+use crate::envelope::{Envelope, EnvelopeError, ItemType};
+use crate::extractors::{CurrentServiceState, PartialDsn, RequestMeta, SignedJson};
+use crate::service::ServiceApp;
+use crate::utils::{self, ApiErrorResponse};
+
+#[derive(Debug, thiserror::Error)]
+enum BadSamplingRequest {
+    #[error("relay does not have access to the project")]
+    Forbidden,
+
+    #[error("project does not exist")]
+    ProjectNotFound,
+
+    #[error("invalid envelope")]
+    InvalidEnvelope(#[source] EnvelopeError),
+
+    #[error("invalid event payload")]
+    InvalidJson(#[source] serde_json::Error),
+}
+
+impl ResponseError for BadSamplingRequest {
+    fn error_response(&self) -> HttpResponse {
+        let body = ApiErrorResponse::from_error(self);
+
+        match self {
+            Self::Forbidden => HttpResponse::Forbidden().json(&body),
+            Self::ProjectNotFound => HttpResponse::NotFound().json(&body),
+            _ => HttpResponse::BadRequest().json(&body),
+        }
+    }
+}
+
+/// Request payload of the sampling explain endpoint.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExplainSamplingRequest {
+    /// The project key whose sampling configuration applies to the envelope.
+    public_key: ProjectKey,
+    /// The serialized envelope.
+    envelope: String,
+    /// The IP address of the client that sent the envelope.
+    #[serde(default)]
+    client_ip: Option<IpAddr>,
+}
+
+/// Parses the envelope, filling in headers that were omitted by the caller.
+fn parse_envelope(
+    config: &Config,
+    request: &ExplainSamplingRequest,
+    project_state: &ProjectState,
+) -> Result<Box<Envelope>, BadSamplingRequest> {
+    let upstream = config.upstream_descriptor();
+    let dsn = PartialDsn {
+        scheme: upstream.scheme(),
+        public_key: request.public_key,
+        host: upstream.host().to_owned(),
+        port: upstream.port(),
+        path: "".to_owned(),
+        project_id: project_state.project_id,
+    };
+
+    let bytes = Bytes::from(request.envelope.as_bytes());
+    Envelope::parse_request(bytes, RequestMeta::outbound(dsn))
+        .map_err(BadSamplingRequest::InvalidEnvelope)
+}
+
+/// Extracts the event from the envelope in the same way as the processor does before sampling.
+fn extract_event(envelope: &Envelope) -> Result<Option<Event>, BadSamplingRequest> {
+    let item = match envelope
+        .get_item_by(|item| matches!(item.ty(), ItemType::Event | ItemType::Transaction))
+    {
+        Some(item) => item,
+        None => return Ok(None),
+    };
+
+    let mut event = Annotated::<Event>::from_json_bytes(&item.payload())
+        .map_err(BadSamplingRequest::InvalidJson)?
+        .0
+        .unwrap_or_default();
+
+    if item.ty() == &ItemType::Transaction {
+        event.ty.set_value(Some(EventType::Transaction));
+    }
+
+    if event.id.value().is_none() {
+        event.id.set_value(envelope.event_id());
+    }
+
+    Ok(Some(event))
+}
+
+fn explain_sampling(
+    state: CurrentServiceState,
+    body: SignedJson<ExplainSamplingRequest>,
+) -> ResponseFuture<HttpResponse, Error> {
+    let config = state.config();
+
+    let future = async move {
+        let relay = body.relay;
+        let request = body.inner;
+        let project_cache = ProjectCache::from_registry();
+
+        let project_state = project_cache
+            .send(GetProjectState::new(request.public_key))
+            .await
+            .map_err(|_| Error::from(MailboxError::Closed))?;
+
+        if !has_project_access(&relay, &project_state) {
+            return Err(BadSamplingRequest::Forbidden.into());
+        }
+
+        if project_state.invalid() || project_state.project_id.is_none() {
+            return Err(BadSamplingRequest::ProjectNotFound.into());
+        }
+
+        let envelope = parse_envelope(&config, &request, &project_state)?;
+        let event = extract_event(&envelope)?;
+
+        // The sampling project is only considered if this Relay may read its configuration.
+        let sampling_project_state = match utils::get_sampling_key(&envelope) {
+            Some(sampling_key) => {
+                let sampling_state = project_cache
+                    .send(GetProjectState::new(sampling_key))
+                    .await
+                    .map_err(|_| Error::from(MailboxError::Closed))?;
+
+                Some(sampling_state).filter(|state| has_project_access(&relay, state))
+            }
+            None => None,
+        };
+
+        let explanation = EnvelopeProcessor::from_registry()
+            .send(ExplainSampling {
+                envelope,
+                event,
+                client_ip: request.client_ip,
+                project_state,
+                sampling_project_state,
+            })
+            .await
+            .map_err(|_| Error::from(MailboxError::Closed))?;
+
+        Ok(HttpResponse::Ok().json(explanation))
+    };
+
+    Box::new(future.boxed().compat())
+}
+
+/// Registers the sampling explain endpoint.
+///
+/// This endpoint is meant for debugging dynamic sampling configurations. It evaluates the sampling
+/// rules for an envelope without ingesting it. Matching reservoir rules are reported with their
+/// current fill state, but the envelope is not counted against them.
+pub fn configure_app(app: ServiceApp) -> ServiceApp {
+    app.resource("/api/0/relays/sampling/explain/", |r| {
+        r.name("relay-sampling-explain");
+        r.post().with(explain_sampling);
+    })
+}
@@ -0,0 +1,114 @@
+//! Endpoint to compute transaction name rules from the observed transaction names of a project.
+
+use actix::prelude::*;
+use actix_web::{Error, HttpResponse, ResponseError};
+use futures::{FutureExt, TryFutureExt};
+use serde::{Deserialize, Serialize};
+
+use relay_common::ProjectKey;
+use relay_general::store::{TransactionNameClusterer, TransactionNameRule};
+use relay_redis::RedisError;
+
+use crate::actors::project_cache::{GetProjectState, ProjectCache};
+use crate::endpoints::common::has_project_access;
+use crate::extractors::{CurrentServiceState, SignedJson};
+use crate::service::ServiceApp;
+use crate::utils::ApiErrorResponse;
+
+#[derive(Debug, thiserror::Error)]
+enum BadClusterRequest {
+    #[error("relay does not have access to the project")]
+    Forbidden,
+
+    #[error("project does not exist")]
+    ProjectNotFound,
+
+    #[error("transaction name clustering requires redis")]
+    NotConfigured,
+
+    #[error("failed to read transaction names")]
+    Redis(#[source] RedisError),
+}
+
+impl ResponseError for BadClusterRequest {
+    fn error_response(&self) -> HttpResponse {
+        let body = ApiErrorResponse::from_error(self);
+
+        match self {
+            Self::Forbidden => HttpResponse::Forbidden().json(&body),
+            Self::ProjectNotFound => HttpResponse::NotFound().json(&body),
+            Self::NotConfigured => HttpResponse::NotImplemented().json(&body),
+            Self::Redis(_) => HttpResponse::InternalServerError().json(&body),
+        }
+    }
+}
+
+/// Request payload of the transaction name rules endpoint.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetTransactionNameRules {
+    /// A project key of the project whose transaction names are clustered.
+    public_key: ProjectKey,
+    /// The number of distinct segments above which a segment is replaced.
+    #[serde(default)]
+    merge_threshold: Option<usize>,
+}
+
+/// Response of the transaction name rules endpoint.
+#[derive(Debug, Serialize)]
+struct TransactionNameRulesResponse {
+    /// Candidate rules for the project's `txNameRules`, most specific first.
+    rules: Vec<TransactionNameRule>,
+}
+
+fn get_transaction_name_rules(
+    state: CurrentServiceState,
+    body: SignedJson<GetTransactionNameRules>,
+) -> ResponseFuture<HttpResponse, Error> {
+    let store = state.transaction_names();
+
+    let future = async move {
+        let relay = body.relay;
+        let request = body.inner;
+
+        let project_state = ProjectCache::from_registry()
+            .send(GetProjectState::new(request.public_key))
+            .await
+            .map_err(|_| Error::from(MailboxError::Closed))?;
+
+        if !has_project_access(&relay, &project_state) {
+            return Err(BadClusterRequest::Forbidden.into());
+        }
+
+        let project_id = match project_state.project_id {
+            Some(project_id) if !project_state.invalid() => project_id,
+            _ => return Err(BadClusterRequest::ProjectNotFound.into()),
+        };
+
+        let store = store.ok_or(BadClusterRequest::NotConfigured)?;
+
+        let merge_threshold = request
+            .merge_threshold
+            .unwrap_or(TransactionNameClusterer::DEFAULT_MERGE_THRESHOLD);
+
+        let rules = store
+            .get_rules(project_id, merge_threshold)
+            .await
+            .map_err(BadClusterRequest::Redis)?;
+
+        Ok(HttpResponse::Ok().json(TransactionNameRulesResponse { rules }))
+    };
+
+    Box::new(future.boxed().compat())
+}
+
+/// Registers the transaction name rules endpoint.
+///
+/// This endpoint clusters the URL transaction names that processing Relays recorded for a project
+/// and returns candidate transaction name rules for its high-cardinality path segments.
+pub fn configure_app(app: ServiceApp) -> ServiceApp {
+    app.resource("/api/0/relays/transactions/rules/", |r| {
+        r.name("relay-transaction-name-rules");
+        r.post().with(get_transaction_name_rules);
+    })
+}
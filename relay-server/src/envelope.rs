@@ -106,6 +106,12 @@ pub enum ItemType {
     ReplayEvent,
     /// Replay Recording data
     ReplayRecording,
+    /// Monitor check-in encoded as JSON.
+    CheckIn,
+    /// A span extracted from a transaction, encoded as JSON.
+    ///
+    /// This item type is only created by processing Relays and never accepted from clients.
+    Span,
     /// A new item type that is yet unknown by this version of Relay.
     ///
     /// By default, items of this type are forwarded without modification. Processing Relays and
@@ -147,6 +153,8 @@ impl fmt::Display for ItemType {
             Self::Profile => write!(f, "profile"),
             Self::ReplayEvent => write!(f, "replay_event"),
             Self::ReplayRecording => write!(f, "replay_recording"),
+            Self::CheckIn => write!(f, "check_in"),
+            Self::Span => write!(f, "span"),
             Self::Unknown(s) => s.fmt(f),
         }
     }
@@ -173,6 +181,8 @@ impl std::str::FromStr for ItemType {
             "profile" => Self::Profile,
             "replay_event" => Self::ReplayEvent,
             "replay_recording" => Self::ReplayRecording,
+            "check_in" => Self::CheckIn,
+            "span" => Self::Span,
             other => Self::Unknown(other.to_owned()),
         })
     }
@@ -676,7 +686,9 @@ impl Item {
             | ItemType::ClientReport
             | ItemType::ReplayEvent
             | ItemType::ReplayRecording
-            | ItemType::Profile => false,
+            | ItemType::Profile
+            | ItemType::CheckIn
+            | ItemType::Span => false,
 
             // The unknown item type can observe any behavior, most likely there are going to be no
             // item types added that create events.
@@ -705,6 +717,8 @@ impl Item {
             ItemType::ClientReport => false,
             ItemType::ReplayRecording => false,
             ItemType::Profile => true,
+            ItemType::CheckIn => false,
+            ItemType::Span => true,
 
             // Since this Relay cannot interpret the semantics of this item, it does not know
             // whether it requires an event or not. Depending on the strategy, this can cause two
@@ -825,7 +839,6 @@ impl Envelope {
     }
 
     /// Parses an envelope from bytes.
-    #[allow(dead_code)]
     pub fn parse_bytes(bytes: Bytes) -> Result<Box<Self>, EnvelopeError> {
         let (headers, offset) = Self::parse_headers(&bytes)?;
         let items = Self::parse_items(&bytes, offset)?;
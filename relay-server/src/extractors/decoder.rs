@@ -9,6 +9,8 @@ use bytes::Bytes;
 use flate2::write::{GzDecoder, ZlibDecoder};
 use futures01::{Async, Poll, Stream};
 use relay_config::HttpEncoding;
+use zstd::stream::raw::Decoder as ZstdOperation;
+use zstd::stream::zio::Writer as ZstdDecoder;
 
 use crate::extractors::SharedPayload;
 
@@ -85,12 +87,20 @@ fn write_overflowing<W: Write>(write: &mut W, slice: &[u8]) -> io::Result<bool>
     }
 }
 
+/// Creates a copy of an I/O error with the same kind and message.
+fn clone_error(error: &io::Error) -> io::Error {
+    io::Error::new(error.kind(), error.to_string())
+}
+
 /// Internal dispatch for all supported [`HttpEncoding`]s.
 enum DecoderInner {
     Identity(Box<Sink>),
     Br(Box<BrotliDecoder<Sink>>),
     Gzip(Box<GzDecoder<Sink>>),
     Deflate(Box<ZlibDecoder<Sink>>),
+    Zstd(Box<ZstdDecoder<Sink, ZstdOperation<'static>>>),
+    /// The decoder could not be initialized. All operations return this error.
+    Failed(io::Error),
 }
 
 /// Stateful decoder for all supported [`HttpEncoding`]s.
@@ -128,6 +138,10 @@ impl Decoder {
             HttpEncoding::Br => DecoderInner::Br(Box::new(BrotliDecoder::new(sink))),
             HttpEncoding::Gzip => DecoderInner::Gzip(Box::new(GzDecoder::new(sink))),
             HttpEncoding::Deflate => DecoderInner::Deflate(Box::new(ZlibDecoder::new(sink))),
+            HttpEncoding::Zstd => match ZstdOperation::new() {
+                Ok(operation) => DecoderInner::Zstd(Box::new(ZstdDecoder::new(sink, operation))),
+                Err(error) => DecoderInner::Failed(error),
+            },
         };
 
         Self { inner }
@@ -146,11 +160,15 @@ impl Decoder {
             DecoderInner::Br(inner) => write_overflowing(inner, &bytes),
             DecoderInner::Gzip(inner) => write_overflowing(inner, &bytes),
             DecoderInner::Deflate(inner) => write_overflowing(inner, &bytes),
+            DecoderInner::Zstd(inner) => write_overflowing(inner, &bytes),
+            DecoderInner::Failed(error) => Err(clone_error(error)),
         }
     }
 
     /// Finish decoding the output stream and validate checksums, returning the final bytes.
     ///
+    /// Returns an error if the encoded stream is truncated.
+    ///
     /// This may only be called a single time at the end of decoding. Attempts to write data to this
     /// decoder may result in a panic after this function is called.
     pub fn finish(&mut self) -> io::Result<Bytes> {
@@ -165,6 +183,11 @@ impl Decoder {
                 inner.try_finish()?;
                 inner.get_mut().take()
             }
+            DecoderInner::Zstd(inner) => {
+                inner.finish()?;
+                inner.writer_mut().take()
+            }
+            DecoderInner::Failed(error) => return Err(clone_error(error)),
         })
     }
 
@@ -178,6 +201,8 @@ impl Decoder {
             DecoderInner::Br(inner) => inner.get_mut().take(),
             DecoderInner::Gzip(inner) => inner.get_mut().take(),
             DecoderInner::Deflate(inner) => inner.get_mut().take(),
+            DecoderInner::Zstd(inner) => inner.writer_mut().take(),
+            DecoderInner::Failed(_) => Bytes::new(),
         }
     }
 }
@@ -189,6 +214,8 @@ impl fmt::Debug for Decoder {
             DecoderInner::Br(_inner) => f.debug_tuple("Br").finish(),
             DecoderInner::Gzip(inner) => f.debug_tuple("Gzip").field(inner).finish(),
             DecoderInner::Deflate(inner) => f.debug_tuple("Deflate").field(inner).finish(),
+            DecoderInner::Zstd(inner) => f.debug_tuple("Zstd").field(inner.writer()).finish(),
+            DecoderInner::Failed(error) => f.debug_tuple("Failed").field(error).finish(),
         }
     }
 }
@@ -259,4 +286,33 @@ mod tests {
         assert!(!decoder.decode(b"\x1f\x8b\x08\x00\x06\xb4\x8ba\x00\x03\xcbH\xcd\xc9\xc9\xe7\x02\x00 0:6\x06\x00\x00\x00"[..].into()).unwrap());
         assert_eq!(&*decoder.take(), b"hello\n");
     }
+
+    #[test]
+    fn test_decoder_zstd() {
+        let encoded = zstd::encode_all(&b"hello\n"[..], 0).unwrap();
+
+        let mut decoder = Decoder::from_encoding(HttpEncoding::Zstd, 10000);
+        assert!(!decoder.decode(encoded.into()).unwrap());
+        assert_eq!(&*decoder.finish().unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn test_decoder_zstd_overflow() {
+        let encoded = zstd::encode_all(&[0; 10000][..], 0).unwrap();
+
+        let mut decoder = Decoder::from_encoding(HttpEncoding::Zstd, 10);
+        assert!(decoder.decode(encoded.into()).unwrap());
+    }
+
+    #[test]
+    fn test_decoder_zstd_truncated() {
+        let mut encoded = zstd::encode_all(&b"hello\n"[..], 0).unwrap();
+        encoded.truncate(encoded.len() - 2);
+
+        let mut decoder = Decoder::from_encoding(HttpEncoding::Zstd, 10000);
+        assert!(!decoder.decode(encoded.into()).unwrap());
+
+        let error = decoder.finish().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
 }
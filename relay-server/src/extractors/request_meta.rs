@@ -265,6 +265,13 @@ impl<D> RequestMeta<D> {
     pub fn start_time(&self) -> Instant {
         self.start_time
     }
+
+    /// Overrides the time at which the request started.
+    ///
+    /// The start time is not serialized. This restores it for envelopes read back from disk.
+    pub fn set_start_time(&mut self, start_time: Instant) {
+        self.start_time = start_time;
+    }
 }
 
 impl RequestMeta {
@@ -1,5 +1,6 @@
 mod conditional_tagging;
 pub mod sessions;
+pub mod spans;
 pub mod transactions;
 mod utils;
 
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use relay_common::UnixTimestamp;
+use relay_general::protocol::{Event, EventType, Span};
+use relay_general::store::{normalize_sql_query, SPAN_DESCRIPTION_NORMALIZED};
+use relay_general::types::Value;
+use relay_metrics::{
+    AggregatorConfig, DurationUnit, Metric, MetricNamespace, MetricUnit, MetricValue,
+};
+use url::Url;
+
+use crate::metrics_extraction::transactions::{
+    get_span_transaction_name, TransactionMetricsConfig,
+};
+
+const METRIC_NAMESPACE: MetricNamespace = MetricNamespace::Spans;
+
+/// Matches URL path segments that contain digits, such as IDs and hashes.
+static PATH_SEGMENT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[^/]*\d[^/]*$").unwrap());
+
+/// Returns the value of a string field in the span's data.
+fn get_data_str<'a>(span: &'a Span, key: &str) -> Option<&'a str> {
+    match span.data.value()?.get(key)?.value()? {
+        Value::String(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Splits the description of an HTTP span into the method and the URL.
+fn parse_http_description(description: &str) -> Option<(&str, Url)> {
+    let (method, url) = description.split_once(' ')?;
+    let url = Url::parse(url.trim()).ok()?;
+    Some((method, url))
+}
+
+/// Returns the domain of an HTTP span, including the port if it is not the default.
+fn get_domain(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    })
+}
+
+/// Replaces path segments that contain digits and removes the query string and fragment.
+fn normalize_http(method: &str, url: &Url) -> String {
+    let path = url
+        .path()
+        .split('/')
+        .map(|segment| match PATH_SEGMENT_REGEX.is_match(segment) {
+            true => "*",
+            false => segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let domain = get_domain(url).unwrap_or_default();
+    format!("{} {}://{}{}", method, url.scheme(), domain, path)
+}
+
+/// Extracts the tags of a single span.
+///
+/// The description is only tagged for database and HTTP spans, where it can be grouped into a
+/// low-cardinality form. All other descriptions are omitted.
+fn extract_span_tags(
+    span: &Span,
+    shared_tags: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut tags = shared_tags.clone();
+
+    let op = span.op.as_str().unwrap_or_default();
+    if !op.is_empty() {
+        tags.insert("span.op".to_owned(), op.to_owned());
+    }
+
+    let description = span.description.as_str().unwrap_or_default();
+    if op.starts_with("db") {
+        if let Some(system) = get_data_str(span, "db.system") {
+            tags.insert("span.system".to_owned(), system.to_owned());
+        }
+
+        // Light normalization stores the normalized query in the span data. Fall back to
+        // normalizing the raw description if it has not run.
+        let normalized = match get_data_str(span, SPAN_DESCRIPTION_NORMALIZED) {
+            Some(normalized) => Some(normalized.to_owned()),
+            None if !description.is_empty() => Some(normalize_sql_query(description)),
+            None => None,
+        };
+
+        if let Some(normalized) = normalized {
+            tags.insert("span.description".to_owned(), normalized);
+        }
+    } else if op.starts_with("http") {
+        if let Some((method, url)) = parse_http_description(description) {
+            if let Some(domain) = get_domain(&url) {
+                tags.insert("span.domain".to_owned(), domain);
+            }
+
+            tags.insert("span.description".to_owned(), normalize_http(method, &url));
+        }
+    }
+
+    tags
+}
+
+/// Extracts duration metrics from the spans of a transaction event.
+///
+/// For every span with valid timestamps, this extracts the `exclusive_time` and `duration`
+/// distributions in the spans namespace. Metrics are tagged with the span operation, a grouped form
+/// of the description, the HTTP domain, the database system and the transaction name.
+///
+/// Returns `true` if any metrics were extracted.
+pub fn extract_span_metrics(
+    aggregator_config: &AggregatorConfig,
+    config: &TransactionMetricsConfig,
+    event: &Event,
+    metrics: &mut Vec<Metric>, // output parameter
+) -> bool {
+    if event.ty.value() != Some(&EventType::Transaction) {
+        return false;
+    }
+
+    let spans = match event.spans.value() {
+        Some(spans) => spans,
+        None => return false,
+    };
+
+    let timestamp = event
+        .timestamp
+        .value()
+        .and_then(|end| UnixTimestamp::from_datetime(end.into_inner()));
+
+    // Span metrics are bucketed by the end timestamp of the transaction, like transaction metrics.
+    let timestamp = match timestamp {
+        Some(timestamp) if aggregator_config.timestamp_range().contains(&timestamp) => timestamp,
+        _ => return false,
+    };
+
+    let mut shared_tags = BTreeMap::new();
+    if let Some(transaction) = get_span_transaction_name(event, config) {
+        shared_tags.insert("transaction".to_owned(), transaction);
+    }
+
+    let before_len = metrics.len();
+
+    for span in spans.iter().filter_map(|span| span.value()) {
+        let (start, end) = match (span.start_timestamp.value(), span.timestamp.value()) {
+            (Some(&start), Some(&end)) => (start, end),
+            _ => continue,
+        };
+
+        let tags = extract_span_tags(span, &shared_tags);
+
+        if let Some(&exclusive_time) = span.exclusive_time.value() {
+            metrics.push(Metric::new_mri(
+                METRIC_NAMESPACE,
+                "exclusive_time",
+                MetricUnit::Duration(DurationUnit::MilliSecond),
+                MetricValue::Distribution(exclusive_time),
+                timestamp,
+                tags.clone(),
+            ));
+        }
+
+        metrics.push(Metric::new_mri(
+            METRIC_NAMESPACE,
+            "duration",
+            MetricUnit::Duration(DurationUnit::MilliSecond),
+            MetricValue::Distribution(relay_common::chrono_to_positive_millis(end - start)),
+            timestamp,
+            tags,
+        ));
+    }
+
+    metrics.len() > before_len
+}
+
+#[cfg(test)]
+mod tests {
+    use relay_general::types::Annotated;
+
+    use super::*;
+
+    #[test]
+    fn test_normalize_http() {
+        let (method, url) =
+            parse_http_description("GET https://api.example.com:8080/users/123/posts?page=2")
+                .unwrap();
+
+        assert_eq!(method, "GET");
+        assert_eq!(get_domain(&url).as_deref(), Some("api.example.com:8080"));
+        assert_eq!(
+            normalize_http(method, &url),
+            "GET https://api.example.com:8080/users/*/posts"
+        );
+    }
+
+    #[test]
+    fn test_extract_span_metrics() {
+        let json = r#"{
+            "type": "transaction",
+            "transaction": "/api/users",
+            "transaction_info": {"source": "route"},
+            "start_timestamp": "2021-04-26T07:59:01+0100",
+            "timestamp": "2021-04-26T08:00:00+0100",
+            "spans": [
+                {
+                    "description": "SELECT * FROM users WHERE id = 42",
+                    "op": "db.sql.query",
+                    "span_id": "bd429c44b67a3eb4",
+                    "start_timestamp": "2021-04-26T07:59:02+0100",
+                    "timestamp": "2021-04-26T07:59:03+0100",
+                    "trace_id": "ff62a8b040f340bda5d830223def1d81",
+                    "exclusive_time": 700.0,
+                    "data": {"db.system": "postgresql"}
+                },
+                {
+                    "description": "GET https://example.com/users/42",
+                    "op": "http.client",
+                    "span_id": "bd2eb23da2beb459",
+                    "start_timestamp": "2021-04-26T07:59:03+0100",
+                    "timestamp": "2021-04-26T07:59:04+0100",
+                    "trace_id": "ff62a8b040f340bda5d830223def1d81"
+                }
+            ]
+        }"#;
+
+        let event = Annotated::<Event>::from_json(json).unwrap();
+        let config = TransactionMetricsConfig::new();
+        let aggregator_config = AggregatorConfig {
+            max_secs_in_past: u64::MAX,
+            max_secs_in_future: u64::MAX,
+            ..Default::default()
+        };
+
+        let mut metrics = vec![];
+        assert!(extract_span_metrics(
+            &aggregator_config,
+            &config,
+            event.value().unwrap(),
+            &mut metrics
+        ));
+
+        let names = metrics.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "d:spans/exclusive_time@millisecond",
+                "d:spans/duration@millisecond",
+                "d:spans/duration@millisecond",
+            ]
+        );
+
+        let db_tags = &metrics[1].tags;
+        assert_eq!(db_tags["transaction"], "/api/users");
+        assert_eq!(db_tags["span.op"], "db.sql.query");
+        assert_eq!(db_tags["span.system"], "postgresql");
+        assert_eq!(
+            db_tags["span.description"],
+            "SELECT * FROM users WHERE id = %s"
+        );
+        assert_eq!(metrics[1].value, MetricValue::Distribution(1000.0));
+
+        let http_tags = &metrics[2].tags;
+        assert_eq!(http_tags["span.op"], "http.client");
+        assert_eq!(http_tags["span.domain"], "example.com");
+        assert_eq!(
+            http_tags["span.description"],
+            "GET https://example.com/users/*"
+        );
+    }
+}
@@ -255,40 +255,7 @@ fn get_transaction_name(
     event: &Event,
     accept_transaction_names: AcceptTransactionNames,
 ) -> Option<String> {
-    let original_transaction_name = match event.transaction.value() {
-        Some(name) => name,
-        None => {
-            return None;
-        }
-    };
-
-    // In client-based mode, handling of "unknown" sources depends on the SDK name.
-    // In strict mode, treat "unknown" as high cardinality.
-    let treat_unknown_as_low_cardinality = matches!(
-        accept_transaction_names,
-        AcceptTransactionNames::ClientBased
-    ) && !store::is_high_cardinality_sdk(event);
-
-    let source = event.get_transaction_source();
-    let use_original_name = is_low_cardinality(source, treat_unknown_as_low_cardinality);
-
-    let name_used;
-    let name = if use_original_name {
-        name_used = "original";
-        Some(original_transaction_name.clone())
-    } else {
-        // Pick a sentinel based on the transaction source:
-        match source {
-            TransactionSource::Unknown | TransactionSource::Other(_) => {
-                name_used = "none";
-                None
-            }
-            _ => {
-                name_used = "placeholder";
-                Some("<< unparameterized >>".to_owned())
-            }
-        }
-    };
+    let (name, name_used) = choose_transaction_name(event, accept_transaction_names)?;
 
     relay_statsd::metric!(
         counter(RelayCounters::MetricsTransactionNameExtracted) += 1,
@@ -296,7 +263,7 @@ fn get_transaction_name(
             AcceptTransactionNames::ClientBased => "client-based",
             AcceptTransactionNames::Strict => "strict",
         },
-        source = &source.to_string(),
+        source = &event.get_transaction_source().to_string(),
         sdk_name = event
             .client_sdk
             .value()
@@ -309,6 +276,47 @@ fn get_transaction_name(
     name
 }
 
+/// Returns the transaction name to use in metrics along with the strategy used to pick it.
+///
+/// Returns `None` if the event has no transaction name.
+fn choose_transaction_name(
+    event: &Event,
+    accept_transaction_names: AcceptTransactionNames,
+) -> Option<(Option<String>, &'static str)> {
+    let original_transaction_name = event.transaction.value()?;
+
+    // In client-based mode, handling of "unknown" sources depends on the SDK name.
+    // In strict mode, treat "unknown" as high cardinality.
+    let treat_unknown_as_low_cardinality = matches!(
+        accept_transaction_names,
+        AcceptTransactionNames::ClientBased
+    ) && !store::is_high_cardinality_sdk(event);
+
+    let source = event.get_transaction_source();
+    let use_original_name = is_low_cardinality(source, treat_unknown_as_low_cardinality);
+
+    Some(if use_original_name {
+        (Some(original_transaction_name.clone()), "original")
+    } else {
+        // Pick a sentinel based on the transaction source:
+        match source {
+            TransactionSource::Unknown | TransactionSource::Other(_) => (None, "none"),
+            _ => (Some("<< unparameterized >>".to_owned()), "placeholder"),
+        }
+    })
+}
+
+/// Returns the transaction name for metrics extracted from the spans of a transaction.
+///
+/// Unlike [`get_transaction_name`], this does not count the chosen strategy, since the name is
+/// already counted when extracting the transaction's own metrics.
+pub(super) fn get_span_transaction_name(
+    event: &Event,
+    config: &TransactionMetricsConfig,
+) -> Option<String> {
+    choose_transaction_name(event, config.accept_transaction_names)?.0
+}
+
 /// These are the tags that are added to all extracted metrics.
 fn extract_universal_tags(
     event: &Event,
@@ -20,6 +20,7 @@ use crate::actors::outcome_aggregator::OutcomeAggregator;
 use crate::actors::processor::{EnvelopeProcessor, EnvelopeProcessorService};
 use crate::actors::project_cache::{ProjectCache, ProjectCacheService};
 use crate::actors::relays::{RelayCache, RelayCacheService};
+use crate::actors::statsd_listener::StatsdListener;
 #[cfg(feature = "processing")]
 use crate::actors::store::StoreService;
 use crate::actors::test_store::{TestStore, TestStoreService};
@@ -27,7 +28,9 @@ use crate::actors::upstream::UpstreamRelay;
 use crate::middlewares::{
     AddCommonHeaders, ErrorHandlers, Metrics, ReadRequestMiddleware, SentryMiddleware,
 };
-use crate::utils::BufferGuard;
+#[cfg(feature = "processing")]
+use crate::utils::TransactionNameStore;
+use crate::utils::{BufferGuard, EnvelopeSpool};
 use crate::{endpoints, utils};
 
 pub static REGISTRY: OnceBox<Registry> = OnceBox::new();
@@ -65,6 +68,14 @@ pub enum ServerError {
     /// Initializing the Redis cluster client failed.
     #[error("could not initialize redis cluster client")]
     RedisError,
+
+    /// Opening the envelope spool directory failed.
+    #[error("could not open envelope spool")]
+    SpoolError,
+
+    /// Binding the statsd listener sockets failed.
+    #[error("could not bind statsd listener")]
+    StatsdListenerError,
 }
 
 #[derive(Clone)]
@@ -107,6 +118,9 @@ impl fmt::Debug for Registry {
 pub struct ServiceState {
     config: Arc<Config>,
     buffer_guard: Arc<BufferGuard>,
+    envelope_spool: Option<Arc<EnvelopeSpool>>,
+    #[cfg(feature = "processing")]
+    transaction_names: Option<TransactionNameStore>,
     _aggregator_runtime: Arc<tokio::runtime::Runtime>,
     _outcome_runtime: Arc<tokio::runtime::Runtime>,
     _main_runtime: Arc<tokio::runtime::Runtime>,
@@ -143,17 +157,34 @@ impl ServiceState {
 
         let _guard = main_runtime.enter();
 
+        #[cfg(feature = "processing")]
+        let transaction_names = redis_pool.clone().map(TransactionNameStore::new);
+
         let buffer = Arc::new(BufferGuard::new(config.envelope_buffer_size()));
         let processor = EnvelopeProcessorService::new(config.clone(), redis_pool.clone())?.start();
-        #[allow(unused_mut)]
         let mut envelope_manager = EnvelopeManagerService::new(config.clone());
 
+        // Processing Relays produce to Kafka and never spool envelopes to disk.
+        let mut envelope_spool = None;
+        if !config.processing_enabled() {
+            if let Some(spool) =
+                EnvelopeSpool::from_config(&config).context(ServerError::SpoolError)?
+            {
+                let spool = Arc::new(spool);
+                envelope_manager.set_spool(spool.clone(), buffer.clone());
+                envelope_spool = Some(spool);
+            }
+        }
+
         #[cfg(feature = "processing")]
         if config.processing_enabled() {
             let rt = utils::create_runtime("store-rt", 1);
             let _guard = rt.enter();
             let store = StoreService::create(config.clone())?.start();
             envelope_manager.set_store_forwarder(store);
+            if let Some(ref redis_pool) = redis_pool {
+                envelope_manager.set_redis(redis_pool.clone());
+            }
             _store_runtime = Some(rt);
         }
 
@@ -164,6 +195,9 @@ impl ServiceState {
         let project_cache = ProjectCacheService::new(config.clone(), redis_pool).start();
         drop(guard);
 
+        StatsdListener::start(&config, project_cache.clone())
+            .context(ServerError::StatsdListenerError)?;
+
         let health_check = HealthCheckService::new(config.clone()).start();
         let relay_cache = RelayCacheService::new(config.clone()).start();
 
@@ -197,6 +231,9 @@ impl ServiceState {
 
         Ok(ServiceState {
             buffer_guard: buffer,
+            envelope_spool,
+            #[cfg(feature = "processing")]
+            transaction_names,
             config,
             _aggregator_runtime: Arc::new(aggregator_runtime),
             _outcome_runtime: Arc::new(outcome_runtime),
@@ -218,6 +255,21 @@ impl ServiceState {
     pub fn buffer_guard(&self) -> Arc<BufferGuard> {
         self.buffer_guard.clone()
     }
+
+    /// Returns a reference to the on-disk envelope spool, if enabled.
+    ///
+    /// See [`EnvelopeSpool`] for more information.
+    pub fn envelope_spool(&self) -> Option<Arc<EnvelopeSpool>> {
+        self.envelope_spool.clone()
+    }
+
+    /// Returns the store of observed transaction names, if Redis is configured.
+    ///
+    /// See [`TransactionNameStore`] for more information.
+    #[cfg(feature = "processing")]
+    pub fn transaction_names(&self) -> Option<TransactionNameStore> {
+        self.transaction_names.clone()
+    }
 }
 
 /// The actix app type for the relay web service.
@@ -8,6 +8,11 @@ pub enum RelayGauges {
 
     /// The number of items currently in the garbage disposal queue.
     ProjectCacheGarbageQueueSize,
+
+    /// The total size of the on-disk envelope spool in bytes.
+    ///
+    /// This is only reported if `spool.envelopes.path` is configured.
+    EnvelopeSpoolSize,
 }
 
 impl GaugeMetric for RelayGauges {
@@ -15,6 +20,7 @@ impl GaugeMetric for RelayGauges {
         match self {
             RelayGauges::NetworkOutage => "upstream.network_outage",
             RelayGauges::ProjectCacheGarbageQueueSize => "project_cache.garbage.queue_size",
+            RelayGauges::EnvelopeSpoolSize => "buffer.spool.disk_size",
         }
     }
 }
@@ -172,6 +178,12 @@ pub enum RelayTimers {
     /// Time in milliseconds spent running light normalization on an event. Light normalization
     /// happens before envelope filtering and metrics extraction.
     EventProcessingLightNormalization,
+    /// Time in milliseconds spent resolving minified JavaScript frames with source maps from the
+    /// local artifact directory. This happens after light normalization.
+    EventProcessingSourcemaps,
+    /// Time in milliseconds spent deobfuscating Java and Android frames with ProGuard mapping files
+    /// from the local mapping directory. This happens after source map resolution.
+    EventProcessingProguard,
     /// Time in milliseconds spent running event processors on an event for normalization. Event
     /// processing happens before filtering.
     #[cfg(feature = "processing")]
@@ -183,7 +195,6 @@ pub enum RelayTimers {
     /// Not all events reach this point. After an event is rate limited for the first time, the rate
     /// limit is cached. Events coming in after this will be discarded earlier in the request queue
     /// and do not reach the processing queue.
-    #[cfg(feature = "processing")]
     EventProcessingRateLimiting,
     /// Time in milliseconds spent in data scrubbing for the current event. Data scrubbing happens
     /// last before serializing the event back to JSON.
@@ -298,6 +309,9 @@ pub enum RelayTimers {
 
     /// Time in milliseconds spent on converting a transaction event into a metric.
     TransactionMetricsExtraction,
+
+    /// Time in milliseconds spent on extracting span metrics from a transaction event.
+    SpanMetricsExtraction,
 }
 
 impl TimerMetric for RelayTimers {
@@ -307,10 +321,11 @@ impl TimerMetric for RelayTimers {
             RelayTimers::EventProcessingLightNormalization => {
                 "event_processing.light_normalization"
             }
+            RelayTimers::EventProcessingSourcemaps => "event_processing.sourcemaps",
+            RelayTimers::EventProcessingProguard => "event_processing.proguard",
             #[cfg(feature = "processing")]
             RelayTimers::EventProcessingProcess => "event_processing.process",
             RelayTimers::EventProcessingFiltering => "event_processing.filtering",
-            #[cfg(feature = "processing")]
             RelayTimers::EventProcessingRateLimiting => "event_processing.rate_limiting",
             RelayTimers::EventProcessingPii => "event_processing.pii",
             RelayTimers::EventProcessingSerialization => "event_processing.serialization",
@@ -328,6 +343,7 @@ impl TimerMetric for RelayTimers {
             RelayTimers::TimestampDelay => "requests.timestamp_delay",
             RelayTimers::OutcomeAggregatorFlushTime => "outcomes.aggregator.flush_time",
             RelayTimers::TransactionMetricsExtraction => "metrics.extraction.transactions",
+            RelayTimers::SpanMetricsExtraction => "metrics.extraction.spans",
         }
     }
 }
@@ -360,6 +376,18 @@ pub enum RelayCounters {
     ///  - `handling`: Either `"success"` if the envelope was handled correctly, or `"failure"` if
     ///    there was an error or bug.
     EnvelopeRejected,
+    /// Number of envelopes written to the on-disk spool.
+    ///
+    /// This metric is tagged with:
+    ///  - `reason`: `"outage"` if the envelope could not be sent due to a network outage, or
+    ///    `"buffer_full"` if the in-memory envelope buffer was at capacity.
+    EnvelopeSpooled,
+    /// Number of envelopes read from the on-disk spool and sent or requeued.
+    EnvelopeUnspooled,
+    /// Number of spool segments that could not be read and were deleted.
+    ///
+    /// The envelopes in these segments are lost without outcomes, since their contents are unknown.
+    EnvelopeSpoolCorrupted,
     /// Number of outcomes and reasons for rejected Envelopes.
     ///
     /// This metric is tagged with:
@@ -492,6 +520,12 @@ pub enum RelayCounters {
     EvictingStaleProjectCaches,
     /// Number of times that parsing a metrics bucket item from an envelope failed.
     MetricBucketsParsingFailed,
+    /// Number of metric lines received by the statsd listener.
+    ///
+    /// This metric is tagged with:
+    ///  - `outcome`: `"accepted"` for valid metrics, `"invalid"` for lines that could not be
+    ///    parsed, and `"no_project"` for metrics without a valid project key.
+    StatsdListenerLines,
     /// Count extraction of transaction names. Tag with the decision to drop / replace / use original.
     MetricsTransactionNameExtracted,
     /// Number of Events with an OpenTelemetry Context
@@ -501,6 +535,14 @@ pub enum RelayCounters {
     ///  - `sdk`: The name of the Sentry SDK sending the transaction. This tag is only set for
     ///    Sentry's SDKs and defaults to "proprietary".
     OpenTelemetryEvent,
+    /// Number of matches of audited PII rules, which did not modify the event.
+    ///
+    /// Rules are audited if they are listed in `auditRules` of the project's PII config.
+    ///
+    /// This metric is tagged with:
+    ///  - `rule`: The ID of the matching rule.
+    ///  - `value_type`: The types of the matched value, such as `"string,message"`.
+    PiiAuditMatches,
 }
 
 impl CounterMetric for RelayCounters {
@@ -510,6 +552,9 @@ impl CounterMetric for RelayCounters {
             RelayCounters::EventCorrupted => "event.corrupted",
             RelayCounters::EnvelopeAccepted => "event.accepted",
             RelayCounters::EnvelopeRejected => "event.rejected",
+            RelayCounters::EnvelopeSpooled => "buffer.spool.writes",
+            RelayCounters::EnvelopeUnspooled => "buffer.spool.reads",
+            RelayCounters::EnvelopeSpoolCorrupted => "buffer.spool.corrupted",
             RelayCounters::Outcomes => "events.outcomes",
             RelayCounters::ProjectStateGet => "project_state.get",
             RelayCounters::ProjectStateRequest => "project_state.request",
@@ -528,8 +573,10 @@ impl CounterMetric for RelayCounters {
             RelayCounters::ResponsesStatusCodes => "responses.status_codes",
             RelayCounters::EvictingStaleProjectCaches => "project_cache.eviction",
             RelayCounters::MetricBucketsParsingFailed => "metrics.buckets.parsing_failed",
+            RelayCounters::StatsdListenerLines => "metrics.statsd_listener.lines",
             RelayCounters::MetricsTransactionNameExtracted => "metrics.transaction_name",
             RelayCounters::OpenTelemetryEvent => "event.opentelemetry",
+            RelayCounters::PiiAuditMatches => "pii.audit_matches",
         }
     }
 }
@@ -2,9 +2,12 @@
 //!
 use std::net::IpAddr;
 
-use relay_common::{ProjectKey, Uuid};
+use relay_common::{ProjectId, ProjectKey};
 use relay_general::protocol::Event;
-use relay_sampling::{DynamicSamplingContext, RuleId, SamplingConfig, SamplingMode};
+use relay_sampling::{
+    DynamicSamplingContext, ReservoirCounters, ReservoirExplanation, ReservoirRule, RuleId,
+    SamplingConfig, SamplingExplanation, SamplingMatch, SamplingMode,
+};
 
 use crate::actors::project::ProjectState;
 use crate::envelope::{Envelope, ItemType};
@@ -44,19 +47,12 @@ fn check_unsupported_rules(
     Ok(())
 }
 
-#[derive(Clone, Debug, PartialEq)]
-struct SamplingSpec {
-    sample_rate: f64,
-    rule_id: RuleId,
-    seed: Uuid,
-}
-
 fn get_trace_sampling_rule(
     processing_enabled: bool,
     sampling_project_state: Option<&ProjectState>,
     dsc: Option<&DynamicSamplingContext>,
     ip_addr: Option<IpAddr>,
-) -> Result<Option<SamplingSpec>, SamplingResult> {
+) -> Result<Option<SamplingMatch>, SamplingResult> {
     let dsc = or_ok_none!(dsc);
 
     if sampling_project_state.is_none() {
@@ -66,23 +62,15 @@ fn get_trace_sampling_rule(
     let sampling_config = or_ok_none!(&sampling_project_state.config.dynamic_sampling);
     check_unsupported_rules(processing_enabled, sampling_config)?;
 
-    let rule = or_ok_none!(sampling_config.get_matching_trace_rule(dsc, ip_addr));
-    let sample_rate = match sampling_config.mode {
-        SamplingMode::Received => rule.sample_rate,
-        SamplingMode::Total => dsc.adjusted_sample_rate(rule.sample_rate),
-        SamplingMode::Unsupported => {
-            if processing_enabled {
-                relay_log::error!("Found unsupported sampling mode even as processing Relay, keep");
-            }
-            return Err(SamplingResult::Keep);
+    let sampling_match = or_ok_none!(sampling_config.match_trace_rule(dsc, ip_addr));
+    if sampling_match.mode == SamplingMode::Unsupported {
+        if processing_enabled {
+            relay_log::error!("Found unsupported sampling mode even as processing Relay, keep");
         }
-    };
+        return Err(SamplingResult::Keep);
+    }
 
-    Ok(Some(SamplingSpec {
-        sample_rate,
-        rule_id: rule.id,
-        seed: dsc.trace_id,
-    }))
+    Ok(Some(sampling_match))
 }
 
 fn get_event_sampling_rule(
@@ -91,62 +79,196 @@ fn get_event_sampling_rule(
     dsc: Option<&DynamicSamplingContext>,
     event: Option<&Event>,
     ip_addr: Option<IpAddr>,
-) -> Result<Option<SamplingSpec>, SamplingResult> {
+) -> Result<Option<SamplingMatch>, SamplingResult> {
     let event = or_ok_none!(event);
-    let event_id = or_ok_none!(event.id.value());
 
     let sampling_config = or_ok_none!(&project_state.config.dynamic_sampling);
     check_unsupported_rules(processing_enabled, sampling_config)?;
 
-    let rule = or_ok_none!(sampling_config.get_matching_event_rule(event, ip_addr));
-    let sample_rate = match (dsc, sampling_config.mode) {
-        (Some(dsc), SamplingMode::Total) => dsc.adjusted_sample_rate(rule.sample_rate),
-        _ => rule.sample_rate,
-    };
-
-    Ok(Some(SamplingSpec {
-        sample_rate,
-        rule_id: rule.id,
-        seed: event_id.0,
-    }))
+    Ok(sampling_config.match_event_rule(event, dsc, ip_addr))
 }
 
-/// Checks whether an event should be kept or removed by dynamic sampling.
+/// Returns the rule that determines the sampling decision, if any rule matches.
 ///
-/// This runs both trace- and event/transaction/error-based rules at once.
-pub fn should_keep_event(
+/// Returns `Err` with the sampling result if sampling cannot be applied, for instance because of an
+/// unsupported sampling configuration.
+fn get_sampling_match(
     dsc: Option<&DynamicSamplingContext>,
     event: Option<&Event>,
     ip_addr: Option<IpAddr>,
     project_state: &ProjectState,
     sampling_project_state: Option<&ProjectState>,
     processing_enabled: bool,
-) -> SamplingResult {
+) -> Result<Option<SamplingMatch>, SamplingResult> {
     let matching_trace_rule =
-        match get_trace_sampling_rule(processing_enabled, sampling_project_state, dsc, ip_addr) {
-            Ok(spec) => spec,
-            Err(sampling_result) => return sampling_result,
-        };
-
+        get_trace_sampling_rule(processing_enabled, sampling_project_state, dsc, ip_addr)?;
     let matching_event_rule =
-        match get_event_sampling_rule(processing_enabled, project_state, dsc, event, ip_addr) {
-            Ok(spec) => spec,
-            Err(sampling_result) => return sampling_result,
-        };
+        get_event_sampling_rule(processing_enabled, project_state, dsc, event, ip_addr)?;
 
     // NOTE: Event rules take precedence over trace rules. If the event rule has a lower sample rate
     // than the trace rule, this means that traces will be incomplete.
     // We could guarantee consistent traces if trace rules took precedence over event rules,
     // but we need the current behavior to allow health check rules
     // to take precedence over the overall base rate, which is set on the trace.
-    if let Some(spec) = matching_event_rule.or(matching_trace_rule) {
-        let random_number = relay_sampling::pseudo_random_from_uuid(spec.seed);
-        if random_number >= spec.sample_rate {
-            return SamplingResult::Drop(spec.rule_id);
+    Ok(matching_event_rule.or(matching_trace_rule))
+}
+
+/// Returns all reservoir rules that match the event or its trace, along with the owning project.
+///
+/// Event reservoirs of the project come before trace reservoirs of the sampling project. Reservoirs
+/// only apply to projects with a known project ID, since counters are tracked per project.
+fn get_matching_reservoirs<'a>(
+    dsc: Option<&'a DynamicSamplingContext>,
+    event: Option<&'a Event>,
+    ip_addr: Option<IpAddr>,
+    project_state: &'a ProjectState,
+    sampling_project_state: Option<&'a ProjectState>,
+) -> Vec<(ProjectId, &'a ReservoirRule)> {
+    let mut reservoirs = Vec::new();
+
+    if let (Some(event), Some(project_id), Some(config)) = (
+        event,
+        project_state.project_id,
+        &project_state.config.dynamic_sampling,
+    ) {
+        let rules = config.get_matching_event_reservoirs(event, ip_addr);
+        reservoirs.extend(rules.map(|rule| (project_id, rule)));
+    }
+
+    if let (Some(dsc), Some(state)) = (dsc, sampling_project_state) {
+        if let (Some(project_id), Some(config)) = (state.project_id, &state.config.dynamic_sampling)
+        {
+            let rules = config.get_matching_trace_reservoirs(dsc, ip_addr);
+            reservoirs.extend(rules.map(|rule| (project_id, rule)));
+        }
+    }
+
+    reservoirs
+}
+
+/// Returns `true` if the event is kept by the reservoir of a matching reservoir rule.
+///
+/// Event reservoirs of the project take precedence over trace reservoirs of the sampling project.
+/// Only the first matching rule with a reservoir that is not yet filled counts the event.
+///
+/// This runs before sample rates are applied, so the event is counted even if the regular rules
+/// would keep it as well. A reservoir therefore keeps the first `limit` matching events of every
+/// window, not `limit` events in addition to the sampled ones. With Redis configured, every call
+/// with a matching reservoir rule costs a round trip.
+fn fill_reservoir(
+    reservoirs: &ReservoirCounters,
+    dsc: Option<&DynamicSamplingContext>,
+    event: Option<&Event>,
+    ip_addr: Option<IpAddr>,
+    project_state: &ProjectState,
+    sampling_project_state: Option<&ProjectState>,
+) -> bool {
+    get_matching_reservoirs(dsc, event, ip_addr, project_state, sampling_project_state)
+        .into_iter()
+        .any(|(project_id, rule)| reservoirs.try_acquire(project_id, rule))
+}
+
+/// Checks whether an event should be kept or removed by dynamic sampling.
+///
+/// This runs both trace- and event/transaction/error-based rules at once. Before sample rates are
+/// applied, matching reservoir rules keep events until their reservoir is filled for the current
+/// window.
+pub fn should_keep_event(
+    dsc: Option<&DynamicSamplingContext>,
+    event: Option<&Event>,
+    ip_addr: Option<IpAddr>,
+    project_state: &ProjectState,
+    sampling_project_state: Option<&ProjectState>,
+    processing_enabled: bool,
+    reservoirs: &ReservoirCounters,
+) -> SamplingResult {
+    let sampling_match = match get_sampling_match(
+        dsc,
+        event,
+        ip_addr,
+        project_state,
+        sampling_project_state,
+        processing_enabled,
+    ) {
+        Ok(sampling_match) => sampling_match,
+        Err(sampling_result) => return sampling_result,
+    };
+
+    if fill_reservoir(
+        reservoirs,
+        dsc,
+        event,
+        ip_addr,
+        project_state,
+        sampling_project_state,
+    ) {
+        return SamplingResult::Keep;
+    }
+
+    match sampling_match {
+        Some(sampling_match) if !sampling_match.should_keep() => {
+            SamplingResult::Drop(sampling_match.rule_id)
         }
+        _ => SamplingResult::Keep,
+    }
+}
+
+/// Explains how dynamic sampling evaluates an event, without sampling it.
+///
+/// This derives the decision in the same way as [`should_keep_event`], but also records every rule
+/// that was considered along with the field values its condition was checked against. Reservoir
+/// rules are checked with [`ReservoirCounters::peek`], so the event is not counted against them.
+pub fn explain_sampling(
+    dsc: Option<&DynamicSamplingContext>,
+    event: Option<&Event>,
+    ip_addr: Option<IpAddr>,
+    project_state: &ProjectState,
+    sampling_project_state: Option<&ProjectState>,
+    processing_enabled: bool,
+    reservoirs: &ReservoirCounters,
+) -> SamplingExplanation {
+    let sampling_config = project_state.config.dynamic_sampling.as_ref();
+    let trace_config =
+        sampling_project_state.and_then(|state| state.config.dynamic_sampling.as_ref());
+
+    let trace_rules = match (dsc, trace_config) {
+        (Some(dsc), Some(config)) => config.explain_trace_rules(dsc, ip_addr),
+        _ => Vec::new(),
+    };
+
+    let event_rules = match (event, sampling_config) {
+        (Some(event), Some(config)) => config.explain_event_rules(event, ip_addr),
+        _ => Vec::new(),
+    };
+
+    let reservoir_rules =
+        get_matching_reservoirs(dsc, event, ip_addr, project_state, sampling_project_state)
+            .into_iter()
+            .map(|(project_id, rule)| {
+                ReservoirExplanation::new(rule, !reservoirs.peek(project_id, rule))
+            })
+            .collect();
+
+    let mut explanation = SamplingExplanation::new(
+        dsc.and_then(|dsc| dsc.sample_rate),
+        trace_rules,
+        event_rules,
+        reservoir_rules,
+    );
+
+    match get_sampling_match(
+        dsc,
+        event,
+        ip_addr,
+        project_state,
+        sampling_project_state,
+        processing_enabled,
+    ) {
+        Ok(sampling_match) => explanation.decide(sampling_match),
+        Err(_) => explanation.unsupported(),
     }
 
-    SamplingResult::Keep
+    explanation
 }
 
 /// Returns the project key defined in the `trace` header of the envelope.
@@ -172,7 +294,8 @@ mod tests {
     use relay_general::protocol::EventId;
     use relay_general::types::Annotated;
     use relay_sampling::{
-        EqCondition, RuleCondition, RuleId, RuleType, SamplingConfig, SamplingRule,
+        EqCondition, ReservoirRule, RuleCondition, RuleId, RuleType, SamplingConfig,
+        SamplingDecision, SamplingReason, SamplingRule,
     };
 
     use crate::envelope::Item;
@@ -205,6 +328,7 @@ mod tests {
             rules,
             mode,
             next_id: None,
+            reservoir_rules: Vec::new(),
         })
     }
 
@@ -281,6 +405,7 @@ mod tests {
             rules,
             mode,
             next_id: None,
+            reservoir_rules: Vec::new(),
         })
     }
 
@@ -292,6 +417,7 @@ mod tests {
             rules,
             mode: SamplingMode::Received,
             next_id: None,
+            reservoir_rules: Vec::new(),
         });
 
         let some_event = Event {
@@ -310,6 +436,7 @@ mod tests {
             &event_state,
             None,
             processing_enabled,
+            &ReservoirCounters::new(),
         )
     }
 
@@ -391,6 +518,7 @@ mod tests {
             &event_state,
             Some(&trace_state),
             false,
+            &ReservoirCounters::new(),
         );
 
         // if it doesn't match the transaction rule, the transaction shouldn't be dropped
@@ -401,6 +529,7 @@ mod tests {
             &event_state,
             Some(&trace_state),
             false,
+            &ReservoirCounters::new(),
         );
 
         // matching event should return an event rule
@@ -442,20 +571,147 @@ mod tests {
 
         assert_eq!(
             SamplingResult::Drop(RuleId(1)),
-            should_keep_event(None, Some(&event), None, &proj_state, None, true)
+            should_keep_event(
+                None,
+                Some(&event),
+                None,
+                &proj_state,
+                None,
+                true,
+                &ReservoirCounters::new()
+            )
         );
         let proj_state = state_with_rule(Some(1.0), RuleType::Error, SamplingMode::default());
         assert_eq!(
             SamplingResult::Keep,
-            should_keep_event(None, Some(&event), None, &proj_state, None, true)
+            should_keep_event(
+                None,
+                Some(&event),
+                None,
+                &proj_state,
+                None,
+                true,
+                &ReservoirCounters::new()
+            )
         );
         let proj_state = state_with_rule(None, RuleType::Error, SamplingMode::default());
         assert_eq!(
             SamplingResult::Keep,
-            should_keep_event(None, Some(&event), None, &proj_state, None, true)
+            should_keep_event(
+                None,
+                Some(&event),
+                None,
+                &proj_state,
+                None,
+                true,
+                &ReservoirCounters::new()
+            )
+        );
+    }
+
+    #[test]
+    fn test_reservoir_rule() {
+        let mut state = state_with_rule(Some(0.0), RuleType::Transaction, SamplingMode::default());
+        state.project_id = Some("42".parse().unwrap());
+        let config = state.config.dynamic_sampling.as_mut().unwrap();
+        config.reservoir_rules.push(ReservoirRule {
+            condition: RuleCondition::all(),
+            limit: 2,
+            window: 3600,
+            ty: RuleType::Transaction,
+            id: RuleId(2),
+            time_range: Default::default(),
+        });
+
+        let event = Event {
+            id: Annotated::new(EventId::new()),
+            ty: Annotated::new(EventType::Transaction),
+            ..Event::default()
+        };
+
+        // The first two events fill the reservoir, the remaining ones fall through to the
+        // regular rule that drops everything.
+        let reservoirs = ReservoirCounters::new();
+        let results = (0..3)
+            .map(|_| should_keep_event(None, Some(&event), None, &state, None, true, &reservoirs))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            vec![
+                SamplingResult::Keep,
+                SamplingResult::Keep,
+                SamplingResult::Drop(RuleId(1))
+            ]
         );
     }
 
+    #[test]
+    fn test_trace_reservoir_rule() {
+        let state = state_with_rule(Some(1.0), RuleType::Trace, SamplingMode::default());
+        let mut sampling_state =
+            state_with_rule(Some(0.0), RuleType::Trace, SamplingMode::default());
+        sampling_state.project_id = Some("43".parse().unwrap());
+        let config = sampling_state.config.dynamic_sampling.as_mut().unwrap();
+        config.reservoir_rules.push(ReservoirRule {
+            condition: RuleCondition::all(),
+            limit: 1,
+            window: 3600,
+            ty: RuleType::Trace,
+            id: RuleId(2),
+            time_range: Default::default(),
+        });
+
+        let envelope = new_envelope(true, "");
+        let reservoirs = ReservoirCounters::new();
+        let check = || {
+            should_keep_event(
+                envelope.dsc(),
+                None,
+                None,
+                &state,
+                Some(&sampling_state),
+                true,
+                &reservoirs,
+            )
+        };
+
+        assert_eq!(check(), SamplingResult::Keep);
+        assert_eq!(check(), SamplingResult::Drop(RuleId(1)));
+    }
+
+    #[test]
+    fn test_reservoir_counts_sampled_events() {
+        let mut state = state_with_rule(Some(1.0), RuleType::Transaction, SamplingMode::default());
+        state.project_id = Some("42".parse().unwrap());
+        let rule = ReservoirRule {
+            condition: RuleCondition::all(),
+            limit: 2,
+            window: 3600,
+            ty: RuleType::Transaction,
+            id: RuleId(2),
+            time_range: Default::default(),
+        };
+        let config = state.config.dynamic_sampling.as_mut().unwrap();
+        config.reservoir_rules.push(rule.clone());
+
+        let event = Event {
+            id: Annotated::new(EventId::new()),
+            ty: Annotated::new(EventType::Transaction),
+            ..Event::default()
+        };
+
+        // The regular rule keeps every event, but the reservoir still counts the first two.
+        let reservoirs = ReservoirCounters::new();
+        for _ in 0..3 {
+            let result =
+                should_keep_event(None, Some(&event), None, &state, None, true, &reservoirs);
+            assert_eq!(result, SamplingResult::Keep);
+        }
+
+        assert!(!reservoirs.peek(state.project_id.unwrap(), &rule));
+    }
+
     #[test]
     fn test_unsampled_envelope_with_sample_rate() {
         //create an envelope with a event and a transaction
@@ -469,6 +725,7 @@ mod tests {
             &state,
             Some(&sampling_state),
             true,
+            &ReservoirCounters::new(),
         );
         assert_eq!(result, SamplingResult::Drop(RuleId(1)));
     }
@@ -488,6 +745,7 @@ mod tests {
             &state,
             Some(&sampling_state),
             true,
+            &ReservoirCounters::new(),
         );
         assert_eq!(result, SamplingResult::Keep);
         // both the event and the transaction item should have been left in the envelope
@@ -510,6 +768,7 @@ mod tests {
             &state,
             Some(&sampling_state),
             true,
+            &ReservoirCounters::new(),
         );
         assert_eq!(result, SamplingResult::Drop(RuleId(1)));
     }
@@ -575,6 +834,7 @@ mod tests {
             &project_state,
             Some(&project_state),
             true,
+            &ReservoirCounters::new(),
         );
 
         assert_eq!(keep_event, SamplingResult::Keep);
@@ -660,4 +920,106 @@ mod tests {
 
         assert_eq!(spec.unwrap().unwrap().sample_rate, 0.2);
     }
+
+    #[test]
+    fn test_explain_sampling() {
+        let project_state = state_with_rule(Some(0.1), RuleType::Transaction, SamplingMode::Total);
+        let sampling_state = state_with_rule(Some(0.0), RuleType::Trace, SamplingMode::Total);
+        let sampling_context = create_sampling_context(Some(0.5));
+        let event = Event {
+            id: Annotated::new(EventId::new()),
+            ty: Annotated::new(EventType::Transaction),
+            ..Event::default()
+        };
+
+        let explanation = explain_sampling(
+            Some(&sampling_context),
+            Some(&event),
+            None,
+            &project_state,
+            Some(&sampling_state),
+            true,
+            &ReservoirCounters::new(),
+        );
+
+        assert_eq!(explanation.trace_rules.len(), 1);
+        assert_eq!(explanation.event_rules.len(), 1);
+        assert_eq!(explanation.client_sample_rate, Some(0.5));
+        assert_eq!(explanation.applied_rule, Some(RuleId(1)));
+        assert_eq!(explanation.effective_sample_rate, Some(0.2));
+        assert_eq!(explanation.reason, SamplingReason::SampleRate);
+
+        let expected = if explanation.random_number.unwrap() < 0.2 {
+            SamplingDecision::Keep
+        } else {
+            SamplingDecision::Drop
+        };
+        assert_eq!(explanation.decision, expected);
+    }
+
+    #[test]
+    fn test_explain_sampling_unsupported() {
+        let project_state = state_with_rule(Some(0.1), RuleType::Trace, SamplingMode::Unsupported);
+        let sampling_context = create_sampling_context(Some(0.5));
+
+        let explanation = explain_sampling(
+            Some(&sampling_context),
+            None,
+            None,
+            &project_state,
+            Some(&project_state),
+            true,
+            &ReservoirCounters::new(),
+        );
+
+        assert_eq!(explanation.trace_rules.len(), 1);
+        assert_eq!(explanation.applied_rule, None);
+        assert_eq!(explanation.decision, SamplingDecision::Keep);
+        assert_eq!(explanation.reason, SamplingReason::UnsupportedConfig);
+    }
+
+    #[test]
+    fn test_explain_sampling_reservoir() {
+        let mut state = state_with_rule(Some(0.0), RuleType::Transaction, SamplingMode::default());
+        state.project_id = Some("42".parse().unwrap());
+        let config = state.config.dynamic_sampling.as_mut().unwrap();
+        config.reservoir_rules.push(ReservoirRule {
+            condition: RuleCondition::all(),
+            limit: 1,
+            window: 3600,
+            ty: RuleType::Transaction,
+            id: RuleId(2),
+            time_range: Default::default(),
+        });
+
+        let event = Event {
+            id: Annotated::new(EventId::new()),
+            ty: Annotated::new(EventType::Transaction),
+            ..Event::default()
+        };
+
+        let reservoirs = ReservoirCounters::new();
+        let explain =
+            || explain_sampling(None, Some(&event), None, &state, None, true, &reservoirs);
+
+        // Explaining does not count the event, so the reservoir keeps it repeatedly.
+        for _ in 0..2 {
+            let explanation = explain();
+            assert_eq!(explanation.reservoir_rules.len(), 1);
+            assert!(!explanation.reservoir_rules[0].filled);
+            assert_eq!(explanation.applied_rule, Some(RuleId(2)));
+            assert_eq!(explanation.decision, SamplingDecision::Keep);
+            assert_eq!(explanation.reason, SamplingReason::Reservoir);
+        }
+
+        // Once sampling fills the reservoir, the explanation falls through to the regular rule.
+        let result = should_keep_event(None, Some(&event), None, &state, None, true, &reservoirs);
+        assert_eq!(result, SamplingResult::Keep);
+
+        let explanation = explain();
+        assert!(explanation.reservoir_rules[0].filled);
+        assert_eq!(explanation.applied_rule, Some(RuleId(1)));
+        assert_eq!(explanation.decision, SamplingDecision::Drop);
+        assert_eq!(explanation.reason, SamplingReason::SampleRate);
+    }
 }
@@ -87,11 +87,11 @@ impl EnvelopeContext {
         }
     }
 
-    /// Creates a standalone `EnvelopeContext` for testing purposes.
+    /// Creates a standalone `EnvelopeContext` without a queue permit.
     ///
     /// As opposed to [`new`](Self::new), this does not require a queue permit. This makes it
-    /// suitable for unit testing internals of the processing pipeline.
-    #[cfg(test)]
+    /// suitable for envelopes held outside of the processing queue, such as in the envelope spool,
+    /// and for unit testing internals of the processing pipeline.
     pub fn standalone(envelope: &Envelope) -> Self {
         Self::new_internal(envelope, None)
     }
@@ -204,12 +204,24 @@ impl EnvelopeContext {
 
         if self.summary.profile_quantity > 0 {
             self.track_outcome(
-                outcome,
+                outcome.clone(),
                 DataCategory::Profile,
                 self.summary.profile_quantity,
             );
         }
 
+        if self.summary.monitor_quantity > 0 {
+            self.track_outcome(
+                outcome.clone(),
+                DataCategory::Monitor,
+                self.summary.monitor_quantity,
+            );
+        }
+
+        if self.summary.span_quantity > 0 {
+            self.track_outcome(outcome, DataCategory::Span, self.summary.span_quantity);
+        }
+
         self.finish(RelayCounters::EnvelopeRejected, handling);
     }
 
@@ -7,18 +7,25 @@ mod error_boundary;
 mod garbage;
 mod metrics_rate_limits;
 mod multipart;
+mod otlp;
 mod param_parser;
+mod queue;
 mod rate_limits;
 mod request;
 mod semaphore;
 mod sizes;
 mod sleep_handle;
+mod spool;
 mod timer;
 mod tracked_future;
 
 #[cfg(feature = "processing")]
 mod native;
 #[cfg(feature = "processing")]
+mod spans;
+#[cfg(feature = "processing")]
+mod transaction_names;
+#[cfg(feature = "processing")]
 mod unreal;
 
 pub use self::actix::*;
@@ -30,16 +37,23 @@ pub use self::error_boundary::*;
 pub use self::garbage::*;
 pub use self::metrics_rate_limits::*;
 pub use self::multipart::*;
+pub use self::otlp::*;
 pub use self::param_parser::*;
+pub use self::queue::*;
 pub use self::rate_limits::*;
 pub use self::request::*;
 pub use self::semaphore::*;
 pub use self::sizes::*;
 pub use self::sleep_handle::*;
+pub use self::spool::*;
 pub use self::timer::*;
 pub use self::tracked_future::*;
 
 #[cfg(feature = "processing")]
 pub use self::native::*;
 #[cfg(feature = "processing")]
+pub use self::spans::*;
+#[cfg(feature = "processing")]
+pub use self::transaction_names::*;
+#[cfg(feature = "processing")]
 pub use self::unreal::*;
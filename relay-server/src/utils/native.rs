@@ -6,10 +6,15 @@
 use std::collections::BTreeMap;
 
 use chrono::{TimeZone, Utc};
-use minidump::{MinidumpAnnotation, MinidumpCrashpadInfo, MinidumpModuleList, Module};
+use minidump::system_info::{Cpu, Os};
+use minidump::{
+    MinidumpAnnotation, MinidumpCrashpadInfo, MinidumpException, MinidumpModuleList,
+    MinidumpSystemInfo, Module,
+};
 
 use relay_general::protocol::{
-    Context, ContextInner, Contexts, Event, Exception, JsonLenientString, Level, Mechanism, Values,
+    Addr, Context, ContextInner, Contexts, DebugImage, DebugMeta, DeviceContext, Event, Exception,
+    JsonLenientString, Level, Mechanism, NativeDebugImage, OsContext, ThreadId, Values,
 };
 use relay_general::types::{Annotated, Value};
 
@@ -178,6 +183,134 @@ fn write_crashpad_annotations(
     Ok(())
 }
 
+/// Returns the display name of an operating system, as used in the OS context.
+fn os_name(os: Os) -> Option<&'static str> {
+    Some(match os {
+        Os::Windows => "Windows",
+        Os::MacOs => "macOS",
+        Os::Ios => "iOS",
+        Os::Linux => "Linux",
+        Os::Android => "Android",
+        Os::Solaris => "Solaris",
+        _ => return None,
+    })
+}
+
+/// Returns the name of a CPU architecture, as used in the device context and debug images.
+fn cpu_arch(cpu: Cpu) -> Option<&'static str> {
+    Some(match cpu {
+        Cpu::X86 => "x86",
+        Cpu::X86_64 => "x86_64",
+        Cpu::Ppc => "ppc",
+        Cpu::Ppc64 => "ppc64",
+        Cpu::Sparc => "sparc",
+        Cpu::Arm => "arm",
+        Cpu::Arm64 => "arm64",
+        Cpu::Mips => "mips",
+        Cpu::Mips64 => "mips64",
+        _ => return None,
+    })
+}
+
+/// Writes the OS context and the device architecture from the minidump's system info stream.
+///
+/// Existing values in the event take precedence over values from the minidump.
+fn write_system_info(event: &mut Event, system_info: &MinidumpSystemInfo) {
+    let contexts = event.contexts.get_or_insert_with(Contexts::new);
+
+    if let Some(name) = os_name(system_info.os) {
+        let context = contexts.get_or_insert_with(OsContext::default_key(), || {
+            Context::Os(Box::new(OsContext::default()))
+        });
+
+        if let Context::Os(os) = context {
+            os.name.get_or_insert_with(|| name.to_owned());
+
+            // Some platforms, such as Linux, only store the version in the CSD version string.
+            let raw = &system_info.raw;
+            if raw.major_version > 0 || raw.minor_version > 0 || raw.build_number > 0 {
+                os.version.get_or_insert_with(|| {
+                    format!(
+                        "{}.{}.{}",
+                        raw.major_version, raw.minor_version, raw.build_number
+                    )
+                });
+            }
+        }
+    }
+
+    if let Some(arch) = cpu_arch(system_info.cpu) {
+        let context = contexts.get_or_insert_with(DeviceContext::default_key(), || {
+            Context::Device(Box::new(DeviceContext::default()))
+        });
+
+        if let Context::Device(device) = context {
+            device.arch.get_or_insert_with(|| arch.to_owned());
+        }
+    }
+}
+
+/// Writes the crash reason from the minidump's exception stream into the placeholder exception.
+fn write_exception(event: &mut Event, exception: &MinidumpException, os: Os, cpu: Cpu) {
+    let placeholder = event
+        .exceptions
+        .value_mut()
+        .as_mut()
+        .and_then(|values| values.values.value_mut().as_mut())
+        .and_then(|values| values.first_mut())
+        .and_then(|exception| exception.value_mut().as_mut());
+
+    if let Some(placeholder) = placeholder {
+        let reason = exception.get_crash_reason(os, cpu).to_string();
+        placeholder.value = Annotated::new(JsonLenientString(format!("Fatal Error: {}", reason)));
+        placeholder.ty = Annotated::new(reason);
+        placeholder.thread_id = Annotated::new(ThreadId::Int(exception.thread_id.into()));
+    }
+}
+
+/// Writes debug images for all modules in the minidump's module list.
+///
+/// Modules without a debug identifier cannot be symbolicated and are skipped. If the event already
+/// declares debug images, they are kept and the module list is ignored.
+fn write_debug_images(event: &mut Event, module_list: &MinidumpModuleList, os: Os, cpu: Cpu) {
+    let debug_meta = event.debug_meta.get_or_insert_with(DebugMeta::default);
+    let images = debug_meta.images.get_or_insert_with(Vec::new);
+    if !images.is_empty() {
+        return;
+    }
+
+    let arch = cpu_arch(cpu);
+
+    for module in module_list.iter() {
+        let debug_id = match module.debug_identifier() {
+            Some(debug_id) => debug_id,
+            None => continue,
+        };
+
+        let code_id = module.code_identifier();
+
+        let image = Box::new(NativeDebugImage {
+            code_id: Annotated::from((!code_id.is_nil()).then(|| code_id.into())),
+            code_file: Annotated::new(module.code_file().into_owned().into()),
+            debug_id: Annotated::new(debug_id.into()),
+            debug_file: Annotated::from(module.debug_file().map(|f| f.into_owned().into())),
+            arch: Annotated::from(arch.map(str::to_owned)),
+            image_addr: Annotated::new(Addr(module.base_address())),
+            image_size: Annotated::new(module.size()),
+            ..NativeDebugImage::default()
+        });
+
+        let image = match os {
+            Os::Windows => DebugImage::Pe(image),
+            Os::MacOs | Os::Ios => DebugImage::MachO(image),
+            Os::Linux | Os::Android => DebugImage::Elf(image),
+            _ => DebugImage::Symbolic(image),
+        };
+
+        images.push(Annotated::new(image));
+    }
+}
+
 /// Extracts information from the minidump and writes it into the given event.
 ///
 /// This function operates at best-effort. It always attaches the placeholder and returns
@@ -209,6 +342,30 @@ pub fn process_minidump(event: &mut Event, data: &[u8]) {
         // TODO: Consider adding an event error for failed annotation extraction.
         relay_log::debug!("Failed to parse minidump module list: {:?}", err);
     }
+
+    // The system info is required to interpret the exception and module list streams. Without it,
+    // the event keeps the bare placeholder.
+    let system_info = match minidump.get_stream::<MinidumpSystemInfo>() {
+        Ok(system_info) => system_info,
+        Err(err) => {
+            relay_log::debug!("Failed to parse minidump system info: {:?}", err);
+            return;
+        }
+    };
+
+    let os = system_info.os;
+    let cpu = system_info.cpu;
+    write_system_info(event, &system_info);
+
+    match minidump.get_stream::<MinidumpException>() {
+        Ok(exception) => write_exception(event, &exception, os, cpu),
+        Err(err) => relay_log::debug!("Failed to parse minidump exception: {:?}", err),
+    }
+
+    match minidump.get_stream::<MinidumpModuleList>() {
+        Ok(module_list) => write_debug_images(event, &module_list, os, cpu),
+        Err(err) => relay_log::debug!("Failed to parse minidump module list: {:?}", err),
+    }
 }
 
 /// Writes minimal information into the event to indicate it is associated with an Apple Crash
@@ -0,0 +1,966 @@
+//! Conversion of OpenTelemetry traces into transaction events.
+//!
+//! This module contains a minimal subset of the OTLP trace schema, which can be decoded from both
+//! the protobuf and the JSON encoding of `ExportTraceServiceRequest`. Only fields that are required
+//! to construct transaction events are declared. All other fields are skipped during decoding.
+//!
+//! Spans are grouped by their trace and then into segments: Every span whose parent is not part of
+//! the same resource in the request starts a new segment and becomes the root span of a
+//! transaction event. All other spans are added as child spans to the transaction of their closest
+//! ancestor. Spans whose parent chain forms a cycle and root spans that share an id with another
+//! span also become transactions, so that no span is dropped. Each transaction is sent in a
+//! separate envelope, so that it undergoes the regular processing pipeline, including dynamic
+//! sampling, data scrubbing and metrics extraction.
+//!
+//! See <https://github.com/open-telemetry/opentelemetry-proto/tree/main/opentelemetry/proto> for
+//! the full schema.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{de, Deserialize, Deserializer};
+
+use relay_common::SpanStatus;
+use relay_general::protocol::{
+    Context, Contexts, Event, EventId, EventType, OtelContext, Span, SpanId, Timestamp,
+    TraceContext, TraceId, TransactionInfo, TransactionSource,
+};
+use relay_general::types::{Annotated, Array, FromValue, Object, Value};
+
+use crate::envelope::{ContentType, Envelope, Item, ItemType};
+use crate::extractors::RequestMeta;
+
+/// Content type of protobuf-encoded OTLP payloads.
+pub const OTLP_PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Content type of JSON-encoded OTLP payloads.
+pub const OTLP_JSON_CONTENT_TYPE: &str = "application/json";
+
+/// An error returned when decoding an OTLP trace export request.
+#[derive(Debug, thiserror::Error)]
+pub enum OtlpError {
+    /// The payload is not valid protobuf.
+    #[error("invalid protobuf payload")]
+    InvalidProtobuf(#[from] prost::DecodeError),
+
+    /// The payload is not valid JSON or does not match the OTLP JSON schema.
+    #[error("invalid json payload")]
+    InvalidJson(#[from] serde_json::Error),
+
+    /// The content type is neither protobuf nor JSON.
+    #[error("unsupported content type")]
+    UnsupportedContentType,
+}
+
+/// The request body of an OTLP trace export.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExportTraceServiceRequest {
+    /// Spans grouped by the resource that produced them.
+    #[prost(message, repeated, tag = "1")]
+    pub resource_spans: Vec<ResourceSpans>,
+}
+
+impl ExportTraceServiceRequest {
+    /// Decodes a trace export request with the given content type.
+    ///
+    /// Both `application/x-protobuf` and `application/json` are supported. Parameters of the
+    /// content type, such as the charset, are ignored.
+    pub fn parse(data: &[u8], content_type: &str) -> Result<Self, OtlpError> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+
+        if mime.eq_ignore_ascii_case(OTLP_PROTOBUF_CONTENT_TYPE) {
+            Ok(<Self as prost::Message>::decode(data)?)
+        } else if mime.eq_ignore_ascii_case(OTLP_JSON_CONTENT_TYPE) {
+            Ok(serde_json::from_slice(data)?)
+        } else {
+            Err(OtlpError::UnsupportedContentType)
+        }
+    }
+}
+
+/// A collection of spans from a single resource.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ResourceSpans {
+    /// The resource, such as a service, for which the spans were recorded.
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<Resource>,
+
+    /// Spans grouped by the instrumentation scope that produced them.
+    #[prost(message, repeated, tag = "2")]
+    pub scope_spans: Vec<ScopeSpans>,
+}
+
+/// Information about the entity producing telemetry.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Resource {
+    /// Attributes describing the resource, such as `service.name`.
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<KeyValue>,
+}
+
+/// A collection of spans produced by an instrumentation scope.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScopeSpans {
+    /// The spans recorded by this scope.
+    #[prost(message, repeated, tag = "2")]
+    pub spans: Vec<OtelSpan>,
+}
+
+/// A single operation within a trace.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OtelSpan {
+    /// 16 byte identifier of the trace. Hex-encoded in JSON.
+    #[prost(bytes = "vec", tag = "1")]
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub trace_id: Vec<u8>,
+
+    /// 8 byte identifier of the span. Hex-encoded in JSON.
+    #[prost(bytes = "vec", tag = "2")]
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub span_id: Vec<u8>,
+
+    /// Identifier of the parent span, or empty if this is a root span.
+    #[prost(bytes = "vec", tag = "4")]
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub parent_span_id: Vec<u8>,
+
+    /// Description of the operation, such as `GET /users/:id`.
+    #[prost(string, tag = "5")]
+    pub name: String,
+
+    /// The type of the span, see [`SpanKind`].
+    #[prost(enumeration = "SpanKind", tag = "6")]
+    #[serde(deserialize_with = "deserialize_span_kind")]
+    pub kind: i32,
+
+    /// Start time of the span in nanoseconds since the UNIX epoch.
+    #[prost(fixed64, tag = "7")]
+    #[serde(deserialize_with = "deserialize_u64")]
+    pub start_time_unix_nano: u64,
+
+    /// End time of the span in nanoseconds since the UNIX epoch.
+    #[prost(fixed64, tag = "8")]
+    #[serde(deserialize_with = "deserialize_u64")]
+    pub end_time_unix_nano: u64,
+
+    /// Attributes of the span, such as `http.method`.
+    #[prost(message, repeated, tag = "9")]
+    pub attributes: Vec<KeyValue>,
+
+    /// The final status of the span.
+    #[prost(message, optional, tag = "15")]
+    pub status: Option<Status>,
+}
+
+/// The type of a span.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum SpanKind {
+    Unspecified = 0,
+    Internal = 1,
+    Server = 2,
+    Client = 3,
+    Producer = 4,
+    Consumer = 5,
+}
+
+impl SpanKind {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "SPAN_KIND_UNSPECIFIED" => Self::Unspecified,
+            "SPAN_KIND_INTERNAL" => Self::Internal,
+            "SPAN_KIND_SERVER" => Self::Server,
+            "SPAN_KIND_CLIENT" => Self::Client,
+            "SPAN_KIND_PRODUCER" => Self::Producer,
+            "SPAN_KIND_CONSUMER" => Self::Consumer,
+            _ => return None,
+        })
+    }
+}
+
+/// The status of a finished span.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Status {
+    /// Developer-facing error message.
+    #[prost(string, tag = "2")]
+    pub message: String,
+
+    /// The status code: `0` for unset, `1` for ok and `2` for error.
+    #[prost(int32, tag = "3")]
+    #[serde(deserialize_with = "deserialize_status_code")]
+    pub code: i32,
+}
+
+/// Status code indicating that the span failed.
+const STATUS_CODE_ERROR: i32 = 2;
+
+/// A key-value pair used for attributes.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct KeyValue {
+    /// The attribute name.
+    #[prost(string, tag = "1")]
+    pub key: String,
+
+    /// The attribute value.
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<AnyValue>,
+}
+
+/// A dynamically typed attribute value.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(from = "JsonAnyValue")]
+pub struct AnyValue {
+    /// The typed value. `None` represents a missing value.
+    #[prost(oneof = "AnyValueKind", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub value: Option<AnyValueKind>,
+}
+
+/// The types of [`AnyValue`].
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum AnyValueKind {
+    #[prost(string, tag = "1")]
+    StringValue(String),
+    #[prost(bool, tag = "2")]
+    BoolValue(bool),
+    #[prost(int64, tag = "3")]
+    IntValue(i64),
+    #[prost(double, tag = "4")]
+    DoubleValue(f64),
+    #[prost(message, tag = "5")]
+    ArrayValue(ArrayValue),
+    #[prost(message, tag = "6")]
+    KvlistValue(KeyValueList),
+    #[prost(bytes, tag = "7")]
+    BytesValue(Vec<u8>),
+}
+
+/// A list of attribute values.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default)]
+pub struct ArrayValue {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<AnyValue>,
+}
+
+/// A list of nested attributes.
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(default)]
+pub struct KeyValueList {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<KeyValue>,
+}
+
+/// JSON representation of [`AnyValue`], where the oneof is flattened into optional fields.
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct JsonAnyValue {
+    string_value: Option<String>,
+    bool_value: Option<bool>,
+    #[serde(deserialize_with = "deserialize_opt_i64")]
+    int_value: Option<i64>,
+    double_value: Option<f64>,
+    array_value: Option<ArrayValue>,
+    kvlist_value: Option<KeyValueList>,
+    bytes_value: Option<String>,
+}
+
+impl From<JsonAnyValue> for AnyValue {
+    fn from(json: JsonAnyValue) -> Self {
+        let value = if let Some(s) = json.string_value {
+            Some(AnyValueKind::StringValue(s))
+        } else if let Some(b) = json.bool_value {
+            Some(AnyValueKind::BoolValue(b))
+        } else if let Some(i) = json.int_value {
+            Some(AnyValueKind::IntValue(i))
+        } else if let Some(f) = json.double_value {
+            Some(AnyValueKind::DoubleValue(f))
+        } else if let Some(array) = json.array_value {
+            Some(AnyValueKind::ArrayValue(array))
+        } else if let Some(kvlist) = json.kvlist_value {
+            Some(AnyValueKind::KvlistValue(kvlist))
+        } else {
+            // Bytes are base64-encoded in JSON. They are retained as string, since they are
+            // converted to a string value in the event anyway.
+            json.bytes_value.map(AnyValueKind::StringValue)
+        };
+
+        Self { value }
+    }
+}
+
+/// Either a JSON number or a JSON string containing a number.
+///
+/// The OTLP JSON encoding allows 64-bit integers to be encoded as strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString<T> {
+    Number(T),
+    String(String),
+}
+
+fn deserialize_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match NumberOrString::<u64>::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s.parse().map_err(de::Error::custom),
+    }
+}
+
+fn deserialize_opt_i64<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<NumberOrString<i64>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrString::Number(n)) => Ok(Some(n)),
+        Some(NumberOrString::String(s)) => s.parse().map(Some).map_err(de::Error::custom),
+    }
+}
+
+fn deserialize_span_kind<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match NumberOrString::<i32>::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => SpanKind::from_name(&s)
+            .map(|kind| kind as i32)
+            .ok_or_else(|| de::Error::custom("invalid span kind")),
+    }
+}
+
+fn deserialize_status_code<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match NumberOrString::<i32>::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => match s.as_str() {
+            "STATUS_CODE_UNSET" => Ok(0),
+            "STATUS_CODE_OK" => Ok(1),
+            "STATUS_CODE_ERROR" => Ok(STATUS_CODE_ERROR),
+            _ => Err(de::Error::custom("invalid status code")),
+        },
+    }
+}
+
+/// Deserializes trace and span identifiers, which are hex-encoded in OTLP JSON.
+fn deserialize_hex<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(de::Error::custom("invalid hex identifier"));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| de::Error::custom("invalid hex identifier"))
+        })
+        .collect()
+}
+
+/// Formats binary trace and span identifiers as lowercase hex string.
+struct HexId<'a>(&'a [u8]);
+
+impl fmt::Display for HexId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+fn convert_value(value: AnyValue) -> Annotated<Value> {
+    let value = match value.value {
+        Some(AnyValueKind::StringValue(s)) => Value::String(s),
+        Some(AnyValueKind::BoolValue(b)) => Value::Bool(b),
+        Some(AnyValueKind::IntValue(i)) => Value::I64(i),
+        Some(AnyValueKind::DoubleValue(f)) => Value::F64(f),
+        Some(AnyValueKind::ArrayValue(array)) => {
+            Value::Array(array.values.into_iter().map(convert_value).collect())
+        }
+        Some(AnyValueKind::KvlistValue(kvlist)) => Value::Object(convert_attributes(kvlist.values)),
+        Some(AnyValueKind::BytesValue(bytes)) => Value::String(base64::encode(&bytes)),
+        None => return Annotated::empty(),
+    };
+
+    Annotated::new(value)
+}
+
+fn convert_attributes(attributes: Vec<KeyValue>) -> Object<Value> {
+    attributes
+        .into_iter()
+        .map(|kv| {
+            (
+                kv.key,
+                kv.value.map_or_else(Annotated::empty, convert_value),
+            )
+        })
+        .collect()
+}
+
+fn convert_timestamp(nanos: u64) -> Option<Timestamp> {
+    let secs = (nanos / 1_000_000_000) as i64;
+    let subsec_nanos = (nanos % 1_000_000_000) as u32;
+    let datetime: DateTime<Utc> = Utc.timestamp_opt(secs, subsec_nanos).single()?;
+    Some(datetime.into())
+}
+
+fn get_str<'a>(attributes: &'a Object<Value>, key: &str) -> Option<&'a str> {
+    attributes.get(key)?.as_str()
+}
+
+fn get_i64(attributes: &Object<Value>, key: &str) -> Option<i64> {
+    match attributes.get(key)?.value()? {
+        Value::I64(i) => Some(*i),
+        Value::U64(u) => i64::try_from(*u).ok(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Maps an HTTP status code to the closest span status.
+fn status_from_http_code(code: i64) -> SpanStatus {
+    match code {
+        400 => SpanStatus::InvalidArgument,
+        401 => SpanStatus::Unauthenticated,
+        403 => SpanStatus::PermissionDenied,
+        404 => SpanStatus::NotFound,
+        409 => SpanStatus::AlreadyExists,
+        429 => SpanStatus::ResourceExhausted,
+        499 => SpanStatus::Cancelled,
+        501 => SpanStatus::Unimplemented,
+        503 => SpanStatus::Unavailable,
+        504 => SpanStatus::DeadlineExceeded,
+        400..=499 => SpanStatus::InvalidArgument,
+        500..=599 => SpanStatus::InternalError,
+        _ => SpanStatus::Unknown,
+    }
+}
+
+/// Computes the span status from the OpenTelemetry status and semantic attributes.
+///
+/// OpenTelemetry only distinguishes between errors and non-errors. For failed spans, HTTP and gRPC
+/// status codes are used to infer a more specific status.
+fn convert_status(status: Option<&Status>, attributes: &Object<Value>) -> SpanStatus {
+    if status.map_or(true, |status| status.code != STATUS_CODE_ERROR) {
+        return SpanStatus::Ok;
+    }
+
+    if let Some(code) = get_i64(attributes, "http.status_code") {
+        return status_from_http_code(code);
+    }
+
+    if let Some(code) = get_i64(attributes, "rpc.grpc.status_code") {
+        if let Some(status) = SpanStatus::from_value(Annotated::new(Value::I64(code))).into_value()
+        {
+            return status;
+        }
+    }
+
+    SpanStatus::Unknown
+}
+
+/// Infers the span operation from semantic conventions of the span's attributes.
+fn convert_op(kind: SpanKind, attributes: &Object<Value>) -> String {
+    let op = if attributes.contains_key("http.method") {
+        match kind {
+            SpanKind::Client => "http.client",
+            _ => "http.server",
+        }
+    } else if attributes.contains_key("db.system") {
+        "db"
+    } else if attributes.contains_key("rpc.service") {
+        "rpc"
+    } else if attributes.contains_key("messaging.system") {
+        "message"
+    } else if let Some(trigger) = get_str(attributes, "faas.trigger") {
+        return trigger.to_owned();
+    } else {
+        "default"
+    };
+
+    op.to_owned()
+}
+
+/// Infers a human-readable description for a child span.
+fn convert_description(name: String, attributes: &Object<Value>) -> String {
+    if let Some(statement) = get_str(attributes, "db.statement") {
+        return statement.to_owned();
+    }
+
+    if let Some(method) = get_str(attributes, "http.method") {
+        let target = get_str(attributes, "http.url")
+            .or_else(|| get_str(attributes, "http.target"))
+            .or_else(|| get_str(attributes, "http.route"));
+
+        if let Some(target) = target {
+            return format!("{} {}", method, target);
+        }
+    }
+
+    name
+}
+
+/// A span along with the resource-level information needed for conversion.
+struct SpanRef {
+    span: OtelSpan,
+    resource: usize,
+}
+
+impl SpanRef {
+    fn kind(&self) -> SpanKind {
+        SpanKind::from_i32(self.span.kind).unwrap_or(SpanKind::Unspecified)
+    }
+}
+
+fn convert_child_span(span_ref: SpanRef) -> Annotated<Span> {
+    let kind = span_ref.kind();
+    let span = span_ref.span;
+    let data = convert_attributes(span.attributes);
+
+    Annotated::new(Span {
+        timestamp: Annotated::from(convert_timestamp(span.end_time_unix_nano)),
+        start_timestamp: Annotated::from(convert_timestamp(span.start_time_unix_nano)),
+        op: Annotated::new(convert_op(kind, &data)),
+        status: Annotated::new(convert_status(span.status.as_ref(), &data)),
+        description: Annotated::new(convert_description(span.name, &data)),
+        span_id: Annotated::new(SpanId(HexId(&span.span_id).to_string())),
+        parent_span_id: Annotated::new(SpanId(HexId(&span.parent_span_id).to_string())),
+        trace_id: Annotated::new(TraceId(HexId(&span.trace_id).to_string())),
+        data: Annotated::new(data),
+        ..Default::default()
+    })
+}
+
+fn convert_transaction(
+    root: SpanRef,
+    children: Vec<SpanRef>,
+    resource: &Object<Value>,
+) -> Annotated<Event> {
+    let kind = root.kind();
+    let span = root.span;
+    let attributes = convert_attributes(span.attributes);
+
+    let parent_span_id = match span.parent_span_id.as_slice() {
+        [] => Annotated::empty(),
+        id => Annotated::new(SpanId(HexId(id).to_string())),
+    };
+
+    let trace_context = TraceContext {
+        trace_id: Annotated::new(TraceId(HexId(&span.trace_id).to_string())),
+        span_id: Annotated::new(SpanId(HexId(&span.span_id).to_string())),
+        parent_span_id,
+        op: Annotated::new(convert_op(kind, &attributes)),
+        status: Annotated::new(convert_status(span.status.as_ref(), &attributes)),
+        ..Default::default()
+    };
+
+    let source = if attributes.contains_key("http.route") {
+        TransactionSource::Route
+    } else {
+        TransactionSource::Custom
+    };
+
+    let environment = get_str(resource, "deployment.environment").map(str::to_owned);
+    let release = get_str(resource, "service.version").map(|v| v.to_owned().into());
+
+    let otel_context = OtelContext {
+        attributes: Annotated::new(attributes),
+        resource: Annotated::new(resource.clone()),
+        ..Default::default()
+    };
+
+    let mut contexts = Contexts::new();
+    contexts.add(Context::Trace(Box::new(trace_context)));
+    contexts.add(Context::Otel(Box::new(otel_context)));
+
+    let spans: Array<Span> = children.into_iter().map(convert_child_span).collect();
+
+    Annotated::new(Event {
+        id: Annotated::new(EventId::new()),
+        ty: Annotated::new(EventType::Transaction),
+        transaction: Annotated::new(span.name),
+        transaction_info: Annotated::new(TransactionInfo {
+            source: Annotated::new(source),
+            ..Default::default()
+        }),
+        timestamp: Annotated::from(convert_timestamp(span.end_time_unix_nano)),
+        start_timestamp: Annotated::from(convert_timestamp(span.start_time_unix_nano)),
+        environment: Annotated::from(environment),
+        release: Annotated::from(release),
+        contexts: Annotated::new(contexts),
+        spans: Annotated::new(spans),
+        ..Default::default()
+    })
+}
+
+/// The state of resolving the segment root of a span in [`convert_traces`].
+#[derive(Clone, Copy, Debug)]
+enum Resolution {
+    /// The span has not been visited yet.
+    Pending,
+    /// The span is on the parent chain that is currently being walked.
+    Visiting,
+    /// The root of the span's segment, or `None` if its parent chain runs into a cycle.
+    Resolved(Option<usize>),
+}
+
+/// Groups the spans of an export request into transaction events.
+///
+/// See the [module-level documentation](self) for how spans are assigned to transactions.
+pub fn convert_traces(request: ExportTraceServiceRequest) -> Vec<Annotated<Event>> {
+    let mut resources = Vec::new();
+    let mut spans = Vec::new();
+
+    for (index, resource_spans) in request.resource_spans.into_iter().enumerate() {
+        let attributes = resource_spans.resource.unwrap_or_default().attributes;
+        resources.push(convert_attributes(attributes));
+
+        for scope_spans in resource_spans.scope_spans {
+            for span in scope_spans.spans {
+                spans.push(SpanRef {
+                    span,
+                    resource: index,
+                });
+            }
+        }
+    }
+
+    // Index spans by trace and span id to resolve their parents within the same resource. If
+    // several spans share an id, their children are attached to the first one.
+    let mut index: HashMap<(&[u8], &[u8]), usize> = HashMap::new();
+    for (i, s) in spans.iter().enumerate() {
+        let key = (s.span.trace_id.as_slice(), s.span.span_id.as_slice());
+        index.entry(key).or_insert(i);
+    }
+
+    let parent_of = |i: usize| {
+        let s = &spans[i];
+        let key = (s.span.trace_id.as_slice(), s.span.parent_span_id.as_slice());
+        index
+            .get(&key)
+            .copied()
+            .filter(|&parent| spans[parent].resource == s.resource)
+    };
+
+    // Walk up the parent chain of every span to find the root span of its segment. Spans whose
+    // chain runs into a cycle have no root, so they start a segment of their own. Resolved roots
+    // are memoized, so every span is visited once even for long chains.
+    let mut resolved: Vec<Resolution> = vec![Resolution::Pending; spans.len()];
+    let mut chain = Vec::new();
+
+    for i in 0..spans.len() {
+        let mut current = i;
+        let root = loop {
+            match resolved[current] {
+                Resolution::Resolved(root) => break root,
+                Resolution::Visiting => break None,
+                Resolution::Pending => {
+                    resolved[current] = Resolution::Visiting;
+                    chain.push(current);
+                    match parent_of(current) {
+                        Some(parent) => current = parent,
+                        None => break Some(current),
+                    }
+                }
+            }
+        };
+
+        for visited in chain.drain(..) {
+            resolved[visited] = Resolution::Resolved(root);
+        }
+    }
+
+    let segments = resolved
+        .into_iter()
+        .enumerate()
+        .map(|(i, resolution)| match resolution {
+            Resolution::Resolved(Some(root)) => root,
+            _ => i,
+        });
+
+    let mut roots = BTreeMap::new();
+    let mut children: BTreeMap<usize, Vec<SpanRef>> = BTreeMap::new();
+
+    for (i, (span_ref, segment)) in spans.into_iter().zip(segments).enumerate() {
+        if i == segment {
+            roots.insert(i, span_ref);
+        } else {
+            children.entry(segment).or_default().push(span_ref);
+        }
+    }
+
+    roots
+        .into_iter()
+        .map(|(key, root)| {
+            let resource = &resources[root.resource];
+            let children = children.remove(&key).unwrap_or_default();
+            convert_transaction(root, children, resource)
+        })
+        .collect()
+}
+
+/// Creates one envelope per transaction from an OTLP trace export request.
+///
+/// Transactions that cannot be serialized are skipped.
+pub fn envelopes_from_traces(
+    request: ExportTraceServiceRequest,
+    meta: &RequestMeta,
+) -> Vec<Box<Envelope>> {
+    convert_traces(request)
+        .into_iter()
+        .filter_map(|event| {
+            let event_id = event.value().and_then(|e| e.id.value()).copied();
+            let json = match event.to_json() {
+                Ok(json) => json,
+                Err(error) => {
+                    relay_log::error!(
+                        "failed to serialize otel transaction: {}",
+                        relay_log::LogError(&error)
+                    );
+                    return None;
+                }
+            };
+
+            let mut item = Item::new(ItemType::Transaction);
+            item.set_payload(ContentType::Json, json);
+
+            let mut envelope = Envelope::from_request(event_id, meta.clone());
+            envelope.add_item(item);
+            Some(envelope)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue {
+                value: Some(AnyValueKind::StringValue(value.to_owned())),
+            }),
+        }
+    }
+
+    fn span(span_id: u8, parent_span_id: Option<u8>, name: &str) -> OtelSpan {
+        OtelSpan {
+            trace_id: vec![0xab; 16],
+            span_id: vec![span_id; 8],
+            parent_span_id: parent_span_id.map(|id| vec![id; 8]).unwrap_or_default(),
+            name: name.to_owned(),
+            kind: SpanKind::Server as i32,
+            start_time_unix_nano: 1_670_000_000_000_000_000,
+            end_time_unix_nano: 1_670_000_001_500_000_000,
+            attributes: vec![],
+            status: None,
+        }
+    }
+
+    fn request(resources: Vec<Vec<OtelSpan>>) -> ExportTraceServiceRequest {
+        ExportTraceServiceRequest {
+            resource_spans: resources
+                .into_iter()
+                .map(|spans| ResourceSpans {
+                    resource: Some(Resource {
+                        attributes: vec![
+                            string_attribute("service.name", "checkout"),
+                            string_attribute("deployment.environment", "production"),
+                        ],
+                    }),
+                    scope_spans: vec![ScopeSpans { spans }],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_protobuf() {
+        let request = request(vec![vec![span(1, None, "root")]]);
+        let data = request.encode_to_vec();
+
+        let parsed = ExportTraceServiceRequest::parse(&data, "application/x-protobuf").unwrap();
+        assert_eq!(parsed, request);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let json = r#"{
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [{"key": "service.name", "value": {"stringValue": "checkout"}}]
+                },
+                "scopeSpans": [{
+                    "scope": {"name": "manual"},
+                    "spans": [{
+                        "traceId": "5b8efff798038103d269b633813fc60c",
+                        "spanId": "eee19b7ec3c1b174",
+                        "parentSpanId": "",
+                        "name": "GET /users",
+                        "kind": 2,
+                        "startTimeUnixNano": "1544712660000000000",
+                        "endTimeUnixNano": 1544712661000000000,
+                        "attributes": [
+                            {"key": "http.method", "value": {"stringValue": "GET"}},
+                            {"key": "http.status_code", "value": {"intValue": "500"}}
+                        ],
+                        "status": {"code": 2}
+                    }]
+                }]
+            }]
+        }"#;
+
+        let parsed =
+            ExportTraceServiceRequest::parse(json.as_bytes(), "application/json; charset=utf-8")
+                .unwrap();
+
+        let span = &parsed.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(
+            HexId(&span.trace_id).to_string(),
+            "5b8efff798038103d269b633813fc60c"
+        );
+        assert_eq!(span.parent_span_id, Vec::<u8>::new());
+        assert_eq!(span.start_time_unix_nano, 1_544_712_660_000_000_000);
+        assert_eq!(
+            span.attributes[1].value.as_ref().unwrap().value,
+            Some(AnyValueKind::IntValue(500))
+        );
+
+        let events = convert_traces(parsed);
+        assert_eq!(events.len(), 1);
+
+        let event = events[0].value().unwrap();
+        let trace = match event.contexts.value().unwrap().get("trace") {
+            Some(Annotated(Some(context), _)) => match context.0 {
+                Context::Trace(ref trace) => trace.clone(),
+                _ => panic!("expected trace context"),
+            },
+            _ => panic!("missing trace context"),
+        };
+
+        assert_eq!(trace.op.as_str(), Some("http.server"));
+        assert_eq!(trace.status.value(), Some(&SpanStatus::InternalError));
+    }
+
+    #[test]
+    fn test_parse_unsupported_content_type() {
+        let result = ExportTraceServiceRequest::parse(b"", "text/plain");
+        assert!(matches!(result, Err(OtlpError::UnsupportedContentType)));
+    }
+
+    #[test]
+    fn test_convert_segments() {
+        // Trace with a root span, a child and a grandchild in one service, plus a span in a
+        // second service whose parent is the child span of the first service.
+        let request = request(vec![
+            vec![
+                span(1, None, "root"),
+                span(2, Some(1), "child"),
+                span(3, Some(2), "grandchild"),
+            ],
+            vec![span(4, Some(2), "downstream")],
+        ]);
+
+        let events = convert_traces(request);
+        assert_eq!(events.len(), 2);
+
+        let root = events[0].value().unwrap();
+        assert_eq!(root.transaction.as_str(), Some("root"));
+        assert_eq!(root.environment.as_str(), Some("production"));
+        assert_eq!(root.spans.value().unwrap().len(), 2);
+
+        let downstream = events[1].value().unwrap();
+        assert_eq!(downstream.transaction.as_str(), Some("downstream"));
+        assert!(downstream.spans.value().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_convert_orphan_becomes_transaction() {
+        let request = request(vec![vec![span(2, Some(1), "orphan")]]);
+
+        let events = convert_traces(request);
+        assert_eq!(events.len(), 1);
+
+        let event = events[0].value().unwrap();
+        assert_eq!(event.ty.value(), Some(&EventType::Transaction));
+        assert_eq!(event.transaction.as_str(), Some("orphan"));
+    }
+
+    #[test]
+    fn test_convert_cycle_becomes_transactions() {
+        let request = request(vec![vec![
+            span(1, Some(2), "first"),
+            span(2, Some(1), "second"),
+            span(3, Some(3), "self"),
+        ]]);
+
+        let events = convert_traces(request);
+        let names: Vec<_> = events
+            .iter()
+            .map(|event| event.value().unwrap().transaction.as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["first", "second", "self"]);
+    }
+
+    #[test]
+    fn test_convert_long_chain() {
+        let spans = (0..10_000u32)
+            .map(|i| OtelSpan {
+                span_id: i.to_be_bytes().repeat(2),
+                parent_span_id: match i {
+                    0 => vec![],
+                    _ => (i - 1).to_be_bytes().repeat(2),
+                },
+                ..span(0, None, "chain")
+            })
+            .collect();
+
+        let events = convert_traces(request(vec![spans]));
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].value().unwrap().spans.value().unwrap().len(),
+            9_999
+        );
+    }
+
+    #[test]
+    fn test_convert_duplicate_span_ids() {
+        let request = request(vec![vec![
+            span(1, None, "first"),
+            span(1, None, "second"),
+            span(2, Some(1), "child"),
+        ]]);
+
+        let events = convert_traces(request);
+        assert_eq!(events.len(), 2);
+
+        let first = events[0].value().unwrap();
+        assert_eq!(first.transaction.as_str(), Some("first"));
+        assert_eq!(first.spans.value().unwrap().len(), 1);
+
+        let second = events[1].value().unwrap();
+        assert_eq!(second.transaction.as_str(), Some("second"));
+        assert!(second.spans.value().unwrap().is_empty());
+    }
+}
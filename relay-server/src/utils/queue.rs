@@ -0,0 +1,129 @@
+//! Checking and queueing of envelopes for processing.
+
+use relay_config::Config;
+
+use crate::actors::outcome::{DiscardReason, Outcome};
+use crate::actors::processor::{EnvelopeProcessor, ProcessMetrics};
+use crate::actors::project_cache::{
+    CheckEnvelope, CheckedEnvelope, ProjectCache, ValidateEnvelope,
+};
+use crate::envelope::{Envelope, Item, ItemType};
+use crate::utils::{self, BufferError, BufferGuard, EnvelopeContext};
+
+/// An error returned by [`check_envelope`].
+///
+/// In all cases, outcomes for the envelope have already been recorded.
+#[derive(Debug, thiserror::Error)]
+pub enum CheckEnvelopeError {
+    /// The envelope does not contain any items.
+    #[error("empty envelope")]
+    EmptyEnvelope,
+
+    /// The envelope could not be sent to the project cache.
+    #[error("could not schedule event processing")]
+    ScheduleFailed,
+
+    /// The project state rejected the entire envelope.
+    #[error("event submission rejected with_reason: {0:?}")]
+    Rejected(DiscardReason),
+
+    /// The envelope exceeds the configured size limits.
+    #[error("envelope exceeds the size limits")]
+    TooLarge,
+}
+
+/// Checks an envelope against cached project state, rate limits and size limits.
+///
+/// Items violating the rate limits are removed from the envelope. If no items remain, the returned
+/// [`CheckedEnvelope`] does not contain an envelope. Outcomes are recorded for all items that are
+/// removed, as well as for the entire envelope if it is rejected with an error.
+pub async fn check_envelope(
+    config: &Config,
+    envelope: Box<Envelope>,
+    mut envelope_context: EnvelopeContext,
+) -> Result<CheckedEnvelope, CheckEnvelopeError> {
+    if envelope.is_empty() {
+        envelope_context.reject(Outcome::Invalid(DiscardReason::EmptyEnvelope));
+        return Err(CheckEnvelopeError::EmptyEnvelope);
+    }
+
+    let mut checked = ProjectCache::from_registry()
+        .send(CheckEnvelope::new(envelope, envelope_context))
+        .await
+        .map_err(|_| CheckEnvelopeError::ScheduleFailed)?
+        .map_err(CheckEnvelopeError::Rejected)?;
+
+    if let Some((envelope, mut envelope_context)) = checked.envelope.take() {
+        if !utils::check_envelope_size_limits(config, &envelope) {
+            envelope_context.reject(Outcome::Invalid(DiscardReason::TooLarge));
+            return Err(CheckEnvelopeError::TooLarge);
+        }
+
+        checked.envelope = Some((envelope, envelope_context));
+    }
+
+    Ok(checked)
+}
+
+/// Queues an envelope for processing.
+///
+/// Depending on the items in the envelope, there are multiple outcomes:
+///
+/// - Events and event related items, such as attachments, are always queued together. See the
+///   [crate-level documentation](crate) for a full description of how envelopes are
+///   queued and processed.
+/// - Sessions and Session batches are always queued separately. If they occur in the same envelope
+///   as an event, they are split off. Their path is the same as other Envelopes.
+/// - Metrics are directly sent to the [`EnvelopeProcessor`], bypassing the manager's queue and
+///   going straight into metrics aggregation. See [`ProcessMetrics`] for a full description.
+///
+/// Queueing can fail if the queue exceeds `envelope_buffer_size`. In this case, `Err` is
+/// returned and the envelope is not queued.
+pub fn queue_envelope(
+    mut envelope: Box<Envelope>,
+    mut envelope_context: EnvelopeContext,
+    buffer_guard: &BufferGuard,
+) -> Result<(), BufferError> {
+    // Remove metrics from the envelope and queue them directly on the project's `Aggregator`.
+    let mut metric_items = Vec::new();
+    let is_metric = |i: &Item| matches!(i.ty(), ItemType::Metrics | ItemType::MetricBuckets);
+    while let Some(item) = envelope.take_item_by(is_metric) {
+        metric_items.push(item);
+    }
+
+    if !metric_items.is_empty() {
+        relay_log::trace!("sending metrics into processing queue");
+        EnvelopeProcessor::from_registry().send(ProcessMetrics {
+            items: metric_items,
+            project_key: envelope.meta().public_key(),
+            start_time: envelope.meta().start_time(),
+            sent_at: envelope.sent_at(),
+        });
+    }
+
+    // Split the envelope into event-related items and other items. This allows to fast-track:
+    //  1. Envelopes with only session items. They only require rate limiting.
+    //  2. Event envelope processing can bail out if the event is filtered or rate limited,
+    //     since all items depend on this event.
+    if let Some(event_envelope) = envelope.split_by(Item::requires_event) {
+        relay_log::trace!("queueing separate envelope for non-event items");
+
+        // The envelope has been split, so we need to fork the context.
+        let event_context = buffer_guard.enter(&event_envelope)?;
+
+        // Update the old context after successful forking.
+        envelope_context.update(&envelope);
+        ProjectCache::from_registry().send(ValidateEnvelope::new(event_envelope, event_context));
+    }
+
+    if envelope.is_empty() {
+        // The envelope can be empty here if it contained only metrics items which were removed
+        // above. In this case, the envelope was accepted and needs no further queueing.
+        envelope_context.accept();
+    } else {
+        relay_log::trace!("queueing envelope");
+        ProjectCache::from_registry().send(ValidateEnvelope::new(envelope, envelope_context));
+    }
+
+    Ok(())
+}
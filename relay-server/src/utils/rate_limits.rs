@@ -106,6 +106,8 @@ fn infer_event_category(item: &Item) -> Option<DataCategory> {
         ItemType::ReplayEvent => None,
         ItemType::ReplayRecording => None,
         ItemType::ClientReport => None,
+        ItemType::CheckIn => None,
+        ItemType::Span => None,
         ItemType::Unknown(_) => None,
     }
 }
@@ -132,6 +134,12 @@ pub struct EnvelopeSummary {
     /// The number of replays.
     pub replay_quantity: usize,
 
+    /// The number of monitor check-ins.
+    pub monitor_quantity: usize,
+
+    /// The number of spans extracted from transactions.
+    pub span_quantity: usize,
+
     /// Indicates that the envelope contains regular attachments that do not create event payloads.
     pub has_plain_attachments: bool,
 
@@ -173,6 +181,8 @@ impl EnvelopeSummary {
                 ItemType::Profile => summary.profile_quantity += 1,
                 ItemType::ReplayEvent => summary.replay_quantity += 1,
                 ItemType::ReplayRecording => summary.replay_quantity += 1,
+                ItemType::CheckIn => summary.monitor_quantity += 1,
+                ItemType::Span => summary.span_quantity += 1,
                 _ => (),
             }
         }
@@ -255,13 +265,16 @@ pub struct Enforcement {
     profiles: CategoryLimit,
     /// The combined replay item rate limit.
     replays: CategoryLimit,
+    /// The combined check-in item rate limit.
+    check_ins: CategoryLimit,
+    /// The combined span item rate limit.
+    spans: CategoryLimit,
     /// Metrics extraction from a transaction is rate limited.
     event_metrics: CategoryLimit,
 }
 
 impl Enforcement {
     /// Returns `true` if the event should be rate limited.
-    #[cfg(feature = "processing")]
     pub fn event_active(&self) -> bool {
         self.event.is_active()
     }
@@ -276,10 +289,20 @@ impl Enforcement {
             sessions: _, // Do not report outcomes for sessions.
             profiles,
             replays,
+            check_ins,
+            spans,
             event_metrics,
         } = self;
 
-        for limit in [event, attachments, profiles, replays, event_metrics] {
+        for limit in [
+            event,
+            attachments,
+            profiles,
+            replays,
+            check_ins,
+            spans,
+            event_metrics,
+        ] {
             if limit.is_active() {
                 let timestamp = relay_common::instant_to_date_time(envelope.meta().start_time());
                 TrackOutcome::from_registry().send(TrackOutcome {
@@ -333,7 +356,6 @@ where
     /// This ensures that rate limits for the given data category are checked even if there is no
     /// matching item in the envelope. Other items are handled according to the rules as if the
     /// event item were present.
-    #[cfg(feature = "processing")]
     pub fn assume_event(&mut self, category: DataCategory, metrics_extracted: bool) {
         self.event_category = Some((category, metrics_extracted));
     }
@@ -515,6 +537,28 @@ where
             rate_limits.merge(replay_limits);
         }
 
+        if summary.monitor_quantity > 0 {
+            let item_scoping = scoping.item(DataCategory::Monitor);
+            let check_in_limits = (self.check)(item_scoping, summary.monitor_quantity)?;
+            enforcement.check_ins = CategoryLimit::new(
+                DataCategory::Monitor,
+                summary.monitor_quantity,
+                check_in_limits.longest(),
+            );
+            rate_limits.merge(check_in_limits);
+        }
+
+        if summary.span_quantity > 0 {
+            let item_scoping = scoping.item(DataCategory::Span);
+            let span_limits = (self.check)(item_scoping, summary.span_quantity)?;
+            enforcement.spans = CategoryLimit::new(
+                DataCategory::Span,
+                summary.span_quantity,
+                span_limits.longest(),
+            );
+            rate_limits.merge(span_limits);
+        }
+
         Ok((enforcement, rate_limits))
     }
 
@@ -544,6 +588,16 @@ where
             return false;
         }
 
+        // Remove check-ins independently of events
+        if enforcement.check_ins.is_active() && item.ty() == &ItemType::CheckIn {
+            return false;
+        }
+
+        // Remove spans even if the transaction is not rate limited
+        if enforcement.spans.is_active() && item.ty() == &ItemType::Span {
+            return false;
+        }
+
         true
     }
 }
@@ -913,7 +967,55 @@ mod tests {
     }
 
     #[test]
-    #[cfg(feature = "processing")]
+    fn test_enforce_limit_check_ins() {
+        let mut envelope = envelope![CheckIn, CheckIn, Event];
+        let config = ProjectConfig::default();
+
+        let mut mock = MockLimiter::default().deny(DataCategory::Monitor);
+        let (_, limits) = EnvelopeLimiter::new(Some(&config), |s, q| mock.check(s, q))
+            .enforce(&mut envelope, &scoping())
+            .unwrap();
+
+        assert!(limits.is_limited());
+        assert_eq!(envelope.len(), 1);
+        mock.assert_call(DataCategory::Error, Some(1));
+        mock.assert_call(DataCategory::Monitor, Some(2));
+    }
+
+    #[test]
+    fn test_enforce_limit_spans() {
+        let mut envelope = envelope![Span, Span, Transaction];
+        let config = ProjectConfig::default();
+
+        let mut mock = MockLimiter::default().deny(DataCategory::Span);
+        let (_, limits) = EnvelopeLimiter::new(Some(&config), |s, q| mock.check(s, q))
+            .enforce(&mut envelope, &scoping())
+            .unwrap();
+
+        assert!(limits.is_limited());
+        assert_eq!(envelope.len(), 1);
+        mock.assert_call(DataCategory::Transaction, Some(1));
+        mock.assert_call(DataCategory::Span, Some(2));
+    }
+
+    #[test]
+    fn test_enforce_limit_spans_with_transaction() {
+        let mut envelope = envelope![Span, Span, Transaction];
+        let config = ProjectConfig::default();
+
+        let mut mock = MockLimiter::default().deny(DataCategory::Transaction);
+        let (_, limits) = EnvelopeLimiter::new(Some(&config), |s, q| mock.check(s, q))
+            .enforce(&mut envelope, &scoping())
+            .unwrap();
+
+        // Spans depend on their transaction and are removed along with it.
+        assert!(limits.is_limited());
+        assert!(envelope.is_empty());
+        mock.assert_call(DataCategory::Transaction, Some(1));
+        mock.assert_call(DataCategory::Span, Some(2));
+    }
+
+    #[test]
     fn test_enforce_limit_assumed_event() {
         let mut envelope = envelope![];
         let config = ProjectConfig::default();
@@ -931,7 +1033,6 @@ mod tests {
     }
 
     #[test]
-    #[cfg(feature = "processing")]
     fn test_enforce_limit_assumed_attachments() {
         let mut envelope = envelope![Attachment, Attachment];
         let config = ProjectConfig::default();
@@ -0,0 +1,25 @@
+-- Record a transaction name in the set of observed names of a project, unless the set has reached
+-- its maximum size.
+--
+-- ``KEYS`` (1):
+--  * [string] Key of the set of observed transaction names.
+--
+-- ``ARGV``:
+--  * [string] The transaction name to record.
+--  * [number] Maximum number of distinct names in the set.
+--  * [number] Time to live of the set in seconds.
+--
+-- Returns ``1`` if the name was recorded and ``0`` if the set is full.
+local key = KEYS[1]
+local name = ARGV[1]
+local limit = tonumber(ARGV[2])
+local ttl = tonumber(ARGV[3])
+
+if redis.call('SCARD', key) >= limit then
+    return 0
+end
+
+redis.call('SADD', key, name)
+redis.call('EXPIRE', key, ttl)
+
+return 1
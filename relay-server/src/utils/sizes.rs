@@ -42,6 +42,12 @@ pub fn check_envelope_size_limits(config: &Config, envelope: &Envelope) -> bool
             ItemType::Metrics => (),
             ItemType::MetricBuckets => (),
             ItemType::ClientReport => client_reports_size += item.len(),
+            ItemType::CheckIn => {
+                if item.len() > config.max_check_in_size() {
+                    return false;
+                }
+            }
+            ItemType::Span => (),
             ItemType::Profile => {
                 if item.len() > config.max_profile_size() {
                     return false;
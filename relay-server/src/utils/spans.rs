@@ -0,0 +1,191 @@
+use serde::{Serialize, Serializer};
+
+use relay_general::protocol::{
+    Context, ContextInner, Event, EventId, EventType, JsonLenientString, Span, TraceContext,
+};
+use relay_general::types::{Annotated, SerializableAnnotated};
+
+/// A span extracted from a transaction event.
+///
+/// Span records are produced to the spans topic individually. Besides the span payload, they carry
+/// the identifiers of the trace and transaction they belong to. The segment is the root span of the
+/// transaction, which is extracted from the transaction's trace context.
+#[derive(Debug, Serialize)]
+pub struct SpanRecord {
+    /// The ID of the transaction event containing the span.
+    pub event_id: Option<EventId>,
+    /// The ID of the trace the span belongs to.
+    pub trace_id: Option<String>,
+    /// The span ID of the transaction's root span.
+    pub segment_id: Option<String>,
+    /// Whether this span is the root span of the transaction.
+    pub is_segment: bool,
+    /// The span payload, including the tags inherited from the transaction.
+    #[serde(serialize_with = "serialize_span")]
+    pub span: Annotated<Span>,
+}
+
+fn serialize_span<S>(span: &Annotated<Span>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    SerializableAnnotated(span).serialize(serializer)
+}
+
+fn get_trace_context(event: &Event) -> Option<&TraceContext> {
+    let contexts = event.contexts.value()?;
+    let trace = contexts.get("trace").and_then(Annotated::value);
+    if let Some(ContextInner(Context::Trace(trace_context))) = trace {
+        return Some(trace_context.as_ref());
+    }
+
+    None
+}
+
+/// Creates the root span of a transaction from its trace context.
+fn segment_span(event: &Event, trace_context: &TraceContext) -> Span {
+    Span {
+        timestamp: event.timestamp.clone(),
+        start_timestamp: event.start_timestamp.clone(),
+        exclusive_time: trace_context.exclusive_time.clone(),
+        description: event.transaction.clone(),
+        op: trace_context.op.clone(),
+        span_id: trace_context.span_id.clone(),
+        parent_span_id: trace_context.parent_span_id.clone(),
+        trace_id: trace_context.trace_id.clone(),
+        status: trace_context.status.clone(),
+        ..Default::default()
+    }
+}
+
+/// Adds the environment, release and transaction name of the transaction to the span's tags.
+///
+/// Tags that are already set on the span take precedence.
+fn inherit_tags(event: &Event, span: &mut Span) {
+    let inherited = [
+        ("environment", event.environment.as_str()),
+        ("release", event.release.value().map(|r| r.as_str())),
+        ("transaction", event.transaction.as_str()),
+    ];
+
+    let tags = span.tags.get_or_insert_with(Default::default);
+    for (key, value) in inherited {
+        if let Some(value) = value {
+            tags.entry(key.to_owned())
+                .or_insert_with(|| Annotated::new(JsonLenientString::from(value.to_owned())));
+        }
+    }
+}
+
+/// Extracts all spans of a transaction event into individual records.
+///
+/// The first record is the segment, which is the root span of the transaction. It is followed by
+/// all spans from the event payload. Returns an empty list for all other event types.
+pub fn extract_spans(event: &Event) -> Vec<SpanRecord> {
+    if event.ty.value() != Some(&EventType::Transaction) {
+        return Vec::new();
+    }
+
+    let trace_context = get_trace_context(event);
+    let segment_id = trace_context.and_then(|context| context.span_id.value());
+    let trace_id = trace_context.and_then(|context| context.trace_id.value());
+
+    let segment = trace_context.map(|context| segment_span(event, context));
+    let spans = event
+        .spans
+        .value()
+        .into_iter()
+        .flatten()
+        .filter_map(Annotated::value)
+        .cloned();
+
+    segment
+        .into_iter()
+        .chain(spans)
+        .map(|mut span| {
+            inherit_tags(event, &mut span);
+
+            SpanRecord {
+                event_id: event.id.value().copied(),
+                trace_id: span.trace_id.value().or(trace_id).map(|id| id.0.clone()),
+                segment_id: segment_id.map(|id| id.0.clone()),
+                is_segment: segment_id.is_some() && span.span_id.value() == segment_id,
+                span: Annotated::new(span),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+
+    fn transaction() -> Event {
+        let json = r#"{
+            "type": "transaction",
+            "event_id": "52df9022835246eeb317dbd739ccd059",
+            "transaction": "/api/users",
+            "environment": "prod",
+            "release": "1.0",
+            "start_timestamp": 1597976392.6542819,
+            "timestamp": 1597976393.4619668,
+            "contexts": {
+                "trace": {
+                    "trace_id": "ff62a8b040f340bda5d830223def1d81",
+                    "span_id": "bd429c44b67a3eb4",
+                    "op": "http.server"
+                }
+            },
+            "spans": [
+                {
+                    "description": "SELECT * FROM users",
+                    "op": "db",
+                    "parent_span_id": "bd429c44b67a3eb4",
+                    "span_id": "bd2eb23da2beb459",
+                    "start_timestamp": 1597976393.4619668,
+                    "timestamp": 1597976393.4718769,
+                    "trace_id": "ff62a8b040f340bda5d830223def1d81",
+                    "tags": {"environment": "staging"}
+                }
+            ]
+        }"#;
+
+        Annotated::<Event>::from_json(json).unwrap().0.unwrap()
+    }
+
+    #[test]
+    fn test_extract_spans() {
+        let records = extract_spans(&transaction());
+        assert_eq!(records.len(), 2);
+
+        let segment = &records[0];
+        assert!(segment.is_segment);
+        assert_eq!(segment.segment_id.as_deref(), Some("bd429c44b67a3eb4"));
+        assert_eq!(
+            segment.span.value().unwrap().description.as_str(),
+            Some("/api/users")
+        );
+
+        let span = &records[1];
+        assert!(!span.is_segment);
+        assert_eq!(span.segment_id.as_deref(), Some("bd429c44b67a3eb4"));
+        assert_eq!(
+            span.trace_id.as_deref(),
+            Some("ff62a8b040f340bda5d830223def1d81")
+        );
+
+        let tags = span.span.value().unwrap().tags.value().unwrap();
+        assert_eq!(tags["environment"].value().unwrap().as_str(), "staging");
+        assert_eq!(tags["release"].value().unwrap().as_str(), "1.0");
+        assert_eq!(tags["transaction"].value().unwrap().as_str(), "/api/users");
+    }
+
+    #[test]
+    fn test_extract_spans_error() {
+        let mut event = transaction();
+        event.ty.set_value(Some(EventType::Error));
+        assert!(extract_spans(&event).is_empty());
+    }
+}
@@ -0,0 +1,913 @@
+//! Persistent on-disk spool for envelopes.
+//!
+//! During upstream outages, or when the in-memory envelope buffer is at capacity, Relay can write
+//! envelopes to disk instead of dropping them. The spool is a directory of append-only segment
+//! files named after a monotonically increasing sequence number. New envelopes are appended to the
+//! newest segment and the spool is drained from the oldest segment, so envelopes are read back in
+//! the order they were written. Since segments are plain files, the spool survives restarts.
+//!
+//! The spool is bounded by a maximum total size and a maximum age. If a write would exceed the size
+//! limit, the oldest segments are evicted. Envelopes older than the maximum age are discarded when
+//! they are read. Outcomes are emitted for all envelopes that are evicted or discarded.
+//!
+//! Each record in a segment consists of a length-prefixed JSON header with the envelope's scoping,
+//! followed by the length-prefixed envelope in its regular serialization format.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+
+use relay_common::{DataCategory, ProjectId, ProjectKey};
+use relay_config::Config;
+use relay_log::LogError;
+use relay_quotas::Scoping;
+use relay_statsd::metric;
+
+use crate::actors::outcome::{DiscardReason, Outcome, TrackOutcome};
+use crate::envelope::{Envelope, EnvelopeError};
+use crate::statsd::{RelayCounters, RelayGauges};
+use crate::utils::EnvelopeContext;
+
+/// File extension of spool segments.
+const SEGMENT_EXTENSION: &str = "spool";
+
+/// File extension of temporary files used to rewrite partially drained segments.
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Size of the length prefixes in a record.
+const LENGTH_SIZE: usize = std::mem::size_of::<u32>();
+
+/// An error returned when writing to or reading from the [`EnvelopeSpool`].
+#[derive(Debug, thiserror::Error)]
+pub enum SpoolError {
+    /// Accessing the spool directory or a segment failed.
+    #[error("failed to access envelope spool")]
+    Io(#[from] io::Error),
+
+    /// The envelope could not be serialized.
+    #[error("failed to serialize envelope")]
+    Envelope(#[from] EnvelopeError),
+
+    /// The envelope does not fit into the spool, even after evicting all other segments.
+    #[error("envelope spool capacity exceeded")]
+    Full,
+}
+
+/// Metadata stored alongside every envelope in the spool.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RecordHeader {
+    organization_id: u64,
+    project_id: ProjectId,
+    project_key: ProjectKey,
+    key_id: Option<u64>,
+    received_at: DateTime<Utc>,
+    processed: bool,
+}
+
+/// An envelope read back from the [`EnvelopeSpool`].
+///
+/// The envelope is kept in its serialized form until it is parsed with
+/// [`to_envelope`](Self::to_envelope), so that it can be returned to the spool unchanged.
+#[derive(Debug)]
+pub struct SpooledEnvelope {
+    header: RecordHeader,
+    payload: Bytes,
+}
+
+impl SpooledEnvelope {
+    /// Serializes an envelope for the spool.
+    fn new(envelope: &Envelope, scoping: Scoping, processed: bool) -> Result<Self, SpoolError> {
+        Ok(Self {
+            header: RecordHeader {
+                organization_id: scoping.organization_id,
+                project_id: scoping.project_id,
+                project_key: scoping.project_key,
+                key_id: scoping.key_id,
+                received_at: relay_common::instant_to_date_time(envelope.meta().start_time()),
+                processed,
+            },
+            payload: Bytes::from(envelope.to_vec()?),
+        })
+    }
+
+    /// Returns the scoping of the envelope at the time it was spooled.
+    pub fn scoping(&self) -> Scoping {
+        Scoping {
+            organization_id: self.header.organization_id,
+            project_id: self.header.project_id,
+            project_key: self.header.project_key,
+            key_id: self.header.key_id,
+        }
+    }
+
+    /// Returns `true` if the envelope was processed before it was spooled.
+    ///
+    /// Processed envelopes are ready to be sent to the upstream. Envelopes that were spooled
+    /// because the envelope buffer was full still need to go through the processing pipeline.
+    pub fn is_processed(&self) -> bool {
+        self.header.processed
+    }
+
+    /// Parses the envelope and restores the time at which it was originally received.
+    pub fn to_envelope(&self) -> Result<Box<Envelope>, EnvelopeError> {
+        let mut envelope = Envelope::parse_bytes(self.payload.clone())?;
+
+        let age = (Utc::now() - self.header.received_at)
+            .to_std()
+            .unwrap_or_default();
+        let now = Instant::now();
+        let start_time = now.checked_sub(age).unwrap_or(now);
+        envelope.meta_mut().set_start_time(start_time);
+
+        Ok(envelope)
+    }
+
+    /// Returns `true` if the envelope has been in the spool for longer than `max_age`.
+    fn is_expired(&self, max_age: Duration) -> bool {
+        let age = Utc::now() - self.header.received_at;
+        age.to_std().map_or(false, |age| age > max_age)
+    }
+
+    /// Discards the envelope and emits outcomes for all of its items.
+    pub fn reject(self, outcome: Outcome) {
+        match self.to_envelope() {
+            Ok(envelope) => {
+                EnvelopeContext::standalone(&envelope)
+                    .scope(self.scoping())
+                    .reject(outcome);
+            }
+            Err(error) => {
+                relay_log::error!("failed to parse spooled envelope: {}", LogError(&error));
+                self.reject_unparsed(outcome);
+            }
+        }
+    }
+
+    /// Discards an envelope that cannot be parsed and emits a single outcome for it.
+    ///
+    /// Since the items of the envelope are unknown, the outcome is tracked in the default data
+    /// category.
+    pub fn reject_unparsed(self, outcome: Outcome) {
+        TrackOutcome::from_registry().send(TrackOutcome {
+            timestamp: self.header.received_at,
+            scoping: self.scoping(),
+            outcome,
+            event_id: None,
+            remote_addr: None,
+            category: DataCategory::Default,
+            quantity: 1,
+        });
+    }
+
+    /// Serializes this envelope into a spool record.
+    fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let header = serde_json::to_vec(&self.header)?;
+        writer.write_all(&(header.len() as u32).to_be_bytes())?;
+        writer.write_all(&header)?;
+        writer.write_all(&(self.payload.len() as u32).to_be_bytes())?;
+        writer.write_all(&self.payload)
+    }
+
+    /// Returns the size of the serialized spool record.
+    fn record_size(&self) -> usize {
+        // The header is small and serializing it again is cheaper than tracking the size.
+        let header_size = serde_json::to_vec(&self.header).map_or(0, |h| h.len());
+        2 * LENGTH_SIZE + header_size + self.payload.len()
+    }
+}
+
+/// Reads a length-prefixed chunk from the start of `data` and advances it.
+fn read_chunk<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let length = u32::from_be_bytes(data.get(..LENGTH_SIZE)?.try_into().ok()?) as usize;
+    let chunk = data.get(LENGTH_SIZE..LENGTH_SIZE + length)?;
+    *data = &data[LENGTH_SIZE + length..];
+    Some(chunk)
+}
+
+/// Parses all records of a segment file.
+///
+/// If Relay terminated while appending to a segment, the last record may be truncated. Parsing
+/// stops at the first incomplete or invalid record.
+fn parse_records(data: &[u8]) -> VecDeque<SpooledEnvelope> {
+    let mut records = VecDeque::new();
+    let mut remaining = data;
+
+    while !remaining.is_empty() {
+        let record = read_chunk(&mut remaining).and_then(|header| {
+            let header = serde_json::from_slice(header).ok()?;
+            let payload = read_chunk(&mut remaining)?;
+            Some(SpooledEnvelope {
+                header,
+                payload: Bytes::from(payload),
+            })
+        });
+
+        match record {
+            Some(record) => records.push_back(record),
+            None => {
+                relay_log::warn!("skipping truncated record in envelope spool");
+                break;
+            }
+        }
+    }
+
+    records
+}
+
+/// A segment file in the spool directory.
+#[derive(Debug)]
+struct Segment {
+    sequence: u64,
+    size: usize,
+}
+
+/// A batch of envelopes read from the oldest segment of the spool.
+///
+/// The batch must be returned to the spool with [`EnvelopeSpool::commit`] once the envelopes have
+/// been handled. Envelopes that remain in the batch are kept in the spool for the next attempt.
+#[derive(Debug)]
+pub struct SpoolBatch {
+    sequence: u64,
+    /// The spooled envelopes in the order they were written.
+    pub envelopes: VecDeque<SpooledEnvelope>,
+}
+
+#[derive(Debug, Default)]
+struct SpoolState {
+    /// All segments on disk, ordered from oldest to newest.
+    segments: VecDeque<Segment>,
+    /// The open file handle of the newest segment, if it accepts further writes.
+    writer: Option<File>,
+    /// The sequence of the segment that is currently being drained.
+    ///
+    /// This segment is never evicted, since its envelopes are owned by a [`SpoolBatch`].
+    draining: Option<u64>,
+    /// The sequence number of the next segment to create.
+    next_sequence: u64,
+    /// The total size of all segments in bytes.
+    total_size: usize,
+}
+
+/// A persistent, size-bounded FIFO spool of envelopes on disk.
+///
+/// The spool is shared between the endpoints and the envelope manager. All file system access
+/// runs on the blocking threads of the Tokio runtime that opened the spool, so that a slow disk
+/// does not stall request handling or async services.
+///
+/// See the [module-level documentation](self) for more information.
+#[derive(Debug)]
+pub struct EnvelopeSpool {
+    directory: Arc<SpoolDirectory>,
+    runtime: Handle,
+}
+
+impl EnvelopeSpool {
+    /// Opens the spool configured in `spool.envelopes`.
+    ///
+    /// Returns `Ok(None)` if spooling to disk is not enabled. This must be called within a Tokio
+    /// runtime, which runs the blocking file system operations of the spool.
+    pub fn from_config(config: &Config) -> io::Result<Option<Self>> {
+        let path = match config.spool_envelopes_path() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let directory = SpoolDirectory::open(
+            path,
+            config.spool_envelopes_max_disk_size(),
+            config.spool_envelopes_max_segment_size(),
+            config.spool_envelopes_max_age(),
+        )?;
+
+        Ok(Some(Self {
+            directory: Arc::new(directory),
+            runtime: Handle::current(),
+        }))
+    }
+
+    /// Returns `true` if there are no envelopes in the spool.
+    pub async fn is_empty(&self) -> bool {
+        self.run(|directory| directory.is_empty()).await
+    }
+
+    /// Appends an envelope to the spool.
+    ///
+    /// `processed` indicates whether the envelope has already been processed and is ready to be
+    /// sent upstream. If the spool does not have enough capacity, the oldest segments are evicted
+    /// and outcomes are emitted for their envelopes.
+    pub async fn push(
+        &self,
+        envelope: &Envelope,
+        scoping: Scoping,
+        processed: bool,
+    ) -> Result<(), SpoolError> {
+        let record = SpooledEnvelope::new(envelope, scoping, processed)?;
+        self.run(move |directory| directory.push(record)).await
+    }
+
+    /// Reads all envelopes from the oldest segment.
+    ///
+    /// Envelopes that exceeded the maximum age are discarded with an outcome and not returned.
+    /// Segments that cannot be read are deleted, so that they do not block the spool. While the
+    /// batch is outstanding, no further batch can be read. Returns `None` if the spool is empty or
+    /// a batch is already being drained.
+    pub async fn pop_batch(&self) -> Option<SpoolBatch> {
+        self.run(|directory| directory.pop_batch()).await
+    }
+
+    /// Returns a batch obtained from [`pop_batch`](Self::pop_batch) to the spool.
+    ///
+    /// If all envelopes were taken from the batch, its segment is deleted. Otherwise, the segment
+    /// is rewritten with the remaining envelopes, which will be returned by the next batch.
+    pub async fn commit(&self, batch: SpoolBatch) -> Result<(), SpoolError> {
+        self.run(move |directory| directory.commit(batch)).await
+    }
+
+    /// Evicts segments whose last write is older than the maximum age.
+    ///
+    /// Outcomes are emitted for all envelopes in the evicted segments.
+    pub async fn evict_expired(&self) {
+        self.run(|directory| directory.evict_expired()).await
+    }
+
+    /// Runs a blocking operation on the spool directory.
+    async fn run<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&SpoolDirectory) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let directory = Arc::clone(&self.directory);
+        let task = self.runtime.spawn_blocking(move || f(&directory));
+
+        match task.await {
+            Ok(result) => result,
+            Err(error) => std::panic::resume_unwind(error.into_panic()),
+        }
+    }
+}
+
+/// The segment files of an [`EnvelopeSpool`].
+///
+/// All methods access the file system synchronously and block the current thread.
+#[derive(Debug)]
+struct SpoolDirectory {
+    path: PathBuf,
+    max_disk_size: usize,
+    max_segment_size: usize,
+    max_age: Duration,
+    state: Mutex<SpoolState>,
+}
+
+impl SpoolDirectory {
+    /// Opens the spool in the given directory, resuming from existing segments.
+    ///
+    /// The directory is created if it does not exist.
+    fn open(
+        path: impl Into<PathBuf>,
+        max_disk_size: usize,
+        max_segment_size: usize,
+        max_age: Duration,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        fs::create_dir_all(&path)?;
+
+        let mut state = SpoolState::default();
+        let mut segments = Vec::new();
+
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            let file_path = entry.path();
+
+            match file_path.extension().and_then(|e| e.to_str()) {
+                Some(SEGMENT_EXTENSION) => (),
+                // Left over from a rewrite that was interrupted before it completed.
+                Some(TEMP_EXTENSION) => {
+                    fs::remove_file(&file_path)?;
+                    continue;
+                }
+                _ => continue,
+            }
+
+            let sequence = file_path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok());
+
+            if let Some(sequence) = sequence {
+                let size = entry.metadata()?.len() as usize;
+                segments.push(Segment { sequence, size });
+            }
+        }
+
+        segments.sort_by_key(|segment| segment.sequence);
+        state.next_sequence = segments.last().map_or(0, |s| s.sequence + 1);
+        state.total_size = segments.iter().map(|s| s.size).sum();
+        state.segments = segments.into();
+
+        if !state.segments.is_empty() {
+            relay_log::info!(
+                "resuming envelope spool with {} segments ({} bytes)",
+                state.segments.len(),
+                state.total_size
+            );
+        }
+
+        let spool = Self {
+            path,
+            max_disk_size,
+            max_segment_size,
+            max_age,
+            state: Mutex::new(state),
+        };
+
+        spool.report_size(&spool.state.lock());
+        Ok(spool)
+    }
+
+    /// Returns `true` if there are no envelopes in the spool.
+    fn is_empty(&self) -> bool {
+        self.state.lock().segments.is_empty()
+    }
+
+    /// Appends a record to the spool and rejects the envelopes evicted to make room for it.
+    fn push(&self, record: SpooledEnvelope) -> Result<(), SpoolError> {
+        let evicted = self.append(record)?;
+        reject_all(evicted, DiscardReason::SpoolFull);
+        Ok(())
+    }
+
+    /// Appends a record to the spool and returns the envelopes evicted to make room for it.
+    fn append(&self, record: SpooledEnvelope) -> Result<Vec<SpooledEnvelope>, SpoolError> {
+        let mut buf = Vec::with_capacity(record.record_size());
+        record.write_to(&mut buf)?;
+
+        if buf.len() > self.max_disk_size {
+            return Err(SpoolError::Full);
+        }
+
+        let mut state = self.state.lock();
+        let mut evicted = Vec::new();
+        while state.total_size + buf.len() > self.max_disk_size {
+            match self.evict_oldest(&mut state) {
+                Some(envelopes) => evicted.extend(envelopes),
+                None => {
+                    // Nothing else can be evicted, so the envelopes evicted so far are lost, too.
+                    drop(state);
+                    reject_all(evicted, DiscardReason::SpoolFull);
+                    return Err(SpoolError::Full);
+                }
+            }
+        }
+
+        let needs_segment = match state.segments.back() {
+            Some(segment) => {
+                state.writer.is_none()
+                    || state.draining == Some(segment.sequence)
+                    || segment.size + buf.len() > self.max_segment_size
+            }
+            None => true,
+        };
+
+        if needs_segment {
+            let sequence = state.next_sequence;
+            let file = OpenOptions::new()
+                .create_new(true)
+                .append(true)
+                .open(self.segment_path(sequence))?;
+
+            state.next_sequence += 1;
+            state.segments.push_back(Segment { sequence, size: 0 });
+            state.writer = Some(file);
+        }
+
+        if let Some(ref mut writer) = state.writer {
+            writer.write_all(&buf)?;
+        }
+
+        if let Some(segment) = state.segments.back_mut() {
+            segment.size += buf.len();
+        }
+        state.total_size += buf.len();
+
+        self.report_size(&state);
+        Ok(evicted)
+    }
+
+    /// Reads all envelopes from the oldest segment and rejects expired envelopes.
+    fn pop_batch(&self) -> Option<SpoolBatch> {
+        let (batch, expired) = self.take_batch()?;
+        reject_all(expired, DiscardReason::SpoolExpired);
+        Some(batch)
+    }
+
+    /// Reads the oldest readable segment and separates out expired envelopes.
+    fn take_batch(&self) -> Option<(SpoolBatch, Vec<SpooledEnvelope>)> {
+        let mut state = self.state.lock();
+
+        if state.draining.is_some() {
+            return None;
+        }
+
+        loop {
+            let sequence = state.segments.front()?.sequence;
+
+            // Seal the segment so that new envelopes are appended to the next segment.
+            if state.segments.len() == 1 {
+                state.writer = None;
+            }
+
+            let data = match fs::read(self.segment_path(sequence)) {
+                Ok(data) => data,
+                Err(error) => {
+                    relay_log::error!("failed to read spool segment: {}", LogError(&error));
+                    self.remove_corrupted(&mut state);
+                    continue;
+                }
+            };
+
+            let (expired, envelopes): (VecDeque<_>, VecDeque<_>) = parse_records(&data)
+                .into_iter()
+                .partition(|envelope| envelope.is_expired(self.max_age));
+
+            state.draining = Some(sequence);
+            let batch = SpoolBatch {
+                sequence,
+                envelopes,
+            };
+
+            return Some((batch, expired.into()));
+        }
+    }
+
+    /// Deletes the oldest segment after it could not be read.
+    ///
+    /// The envelopes in the segment are lost. Since their contents are unknown, no outcomes can be
+    /// emitted for them, so this is recorded in the `buffer.spool.corrupted` metric instead.
+    fn remove_corrupted(&self, state: &mut SpoolState) {
+        let segment = match state.segments.pop_front() {
+            Some(segment) => segment,
+            None => return,
+        };
+
+        if state.segments.is_empty() {
+            state.writer = None;
+        }
+
+        state.total_size -= segment.size;
+        self.report_size(state);
+        metric!(counter(RelayCounters::EnvelopeSpoolCorrupted) += 1);
+
+        if let Err(error) = fs::remove_file(self.segment_path(segment.sequence)) {
+            relay_log::error!("failed to delete spool segment: {}", LogError(&error));
+        }
+    }
+
+    /// Returns a batch to the spool, deleting or rewriting its segment.
+    fn commit(&self, batch: SpoolBatch) -> Result<(), SpoolError> {
+        let mut state = self.state.lock();
+        debug_assert_eq!(state.draining, Some(batch.sequence));
+        state.draining = None;
+
+        let path = self.segment_path(batch.sequence);
+        let new_size = if batch.envelopes.is_empty() {
+            fs::remove_file(&path)?;
+            0
+        } else {
+            let temp_path = path.with_extension(TEMP_EXTENSION);
+            let mut file = File::create(&temp_path)?;
+            for envelope in &batch.envelopes {
+                envelope.write_to(&mut file)?;
+            }
+            let size = file.metadata()?.len() as usize;
+            fs::rename(&temp_path, &path)?;
+            size
+        };
+
+        if let Some(index) = state
+            .segments
+            .iter()
+            .position(|s| s.sequence == batch.sequence)
+        {
+            let old_size = state.segments[index].size;
+            state.total_size = state.total_size - old_size + new_size;
+
+            if new_size == 0 {
+                state.segments.remove(index);
+            } else {
+                state.segments[index].size = new_size;
+            }
+        }
+
+        self.report_size(&state);
+        Ok(())
+    }
+
+    /// Evicts segments whose last write is older than the maximum age and rejects their envelopes.
+    fn evict_expired(&self) {
+        let expired = self.take_expired();
+        reject_all(expired, DiscardReason::SpoolExpired);
+    }
+
+    /// Removes expired segments and returns their envelopes.
+    ///
+    /// Since segments are written in order, this stops at the first segment that is recent enough.
+    fn take_expired(&self) -> Vec<SpooledEnvelope> {
+        let mut state = self.state.lock();
+        let mut expired = Vec::new();
+
+        loop {
+            let segment = state
+                .segments
+                .iter()
+                .find(|s| state.draining != Some(s.sequence));
+
+            let is_expired = segment.map_or(false, |segment| {
+                fs::metadata(self.segment_path(segment.sequence))
+                    .and_then(|meta| meta.modified())
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .map_or(false, |age| age > self.max_age)
+            });
+
+            if !is_expired {
+                break;
+            }
+
+            expired.extend(self.evict_oldest(&mut state).unwrap_or_default());
+        }
+
+        expired
+    }
+
+    /// Removes the oldest segment that is not being drained and returns its envelopes.
+    ///
+    /// Returns `None` if there is no segment that can be evicted.
+    fn evict_oldest(&self, state: &mut SpoolState) -> Option<VecDeque<SpooledEnvelope>> {
+        let index = state
+            .segments
+            .iter()
+            .position(|s| state.draining != Some(s.sequence))?;
+
+        if index == state.segments.len() - 1 {
+            state.writer = None;
+        }
+
+        let segment = state.segments.remove(index)?;
+        state.total_size -= segment.size;
+        self.report_size(state);
+
+        let path = self.segment_path(segment.sequence);
+        let envelopes = match fs::read(&path) {
+            Ok(data) => parse_records(&data),
+            Err(error) => {
+                relay_log::error!("failed to read spool segment: {}", LogError(&error));
+                VecDeque::new()
+            }
+        };
+
+        if let Err(error) = fs::remove_file(&path) {
+            relay_log::error!("failed to delete spool segment: {}", LogError(&error));
+        }
+
+        relay_log::warn!("evicted {} envelopes from spool", envelopes.len());
+        Some(envelopes)
+    }
+
+    fn segment_path(&self, sequence: u64) -> PathBuf {
+        segment_path(&self.path, sequence)
+    }
+
+    fn report_size(&self, state: &SpoolState) {
+        metric!(gauge(RelayGauges::EnvelopeSpoolSize) = state.total_size as u64);
+    }
+}
+
+fn segment_path(dir: &Path, sequence: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", sequence, SEGMENT_EXTENSION))
+}
+
+/// Discards envelopes removed from the spool and emits outcomes for them.
+fn reject_all(envelopes: impl IntoIterator<Item = SpooledEnvelope>, reason: DiscardReason) {
+    for envelope in envelopes {
+        envelope.reject(Outcome::Invalid(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractors::RequestMeta;
+
+    use super::*;
+
+    fn scoping() -> Scoping {
+        Scoping {
+            organization_id: 1,
+            project_id: ProjectId::new(42),
+            project_key: ProjectKey::parse("e12d836b15bb49d7bbf99e64295d995b").unwrap(),
+            key_id: Some(17),
+        }
+    }
+
+    fn envelope(event_id: &str) -> Box<Envelope> {
+        let dsn = "https://e12d836b15bb49d7bbf99e64295d995b:@sentry.io/42"
+            .parse()
+            .unwrap();
+        let event_id = event_id.parse().unwrap();
+        Envelope::from_request(Some(event_id), RequestMeta::new(dsn))
+    }
+
+    fn record(event_id: &str) -> SpooledEnvelope {
+        SpooledEnvelope::new(&envelope(event_id), scoping(), true).unwrap()
+    }
+
+    fn drain(spool: &SpoolDirectory) -> Vec<String> {
+        let mut event_ids = Vec::new();
+        while let Some(mut batch) = spool.pop_batch() {
+            while let Some(spooled) = batch.envelopes.pop_front() {
+                let envelope = spooled.to_envelope().unwrap();
+                event_ids.push(envelope.event_id().unwrap().to_string());
+            }
+            spool.commit(batch).unwrap();
+        }
+        event_ids
+    }
+
+    const EVENT_1: &str = "9ec79c33ec9942ab8353589fcb2e04dc";
+    const EVENT_2: &str = "4e8ed9e2e8a04f7a81ae79b10d1d3d28";
+    const EVENT_3: &str = "b2e8e0e1bdb24cc2b3e8d8ecf2c2c6a4";
+
+    #[test]
+    fn test_spool_fifo() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool =
+            SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::from_secs(60)).unwrap();
+
+        assert!(spool.is_empty());
+        for event_id in [EVENT_1, EVENT_2, EVENT_3] {
+            spool.push(record(event_id)).unwrap();
+        }
+        assert!(!spool.is_empty());
+
+        assert_eq!(drain(&spool), [EVENT_1, EVENT_2, EVENT_3]);
+        assert!(spool.is_empty());
+        assert_eq!(spool.state.lock().total_size, 0);
+    }
+
+    #[test]
+    fn test_spool_restore_scoping() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool =
+            SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::from_secs(60)).unwrap();
+
+        let record = SpooledEnvelope::new(&envelope(EVENT_1), scoping(), false).unwrap();
+        spool.push(record).unwrap();
+
+        let batch = spool.pop_batch().unwrap();
+        let spooled = &batch.envelopes[0];
+        assert_eq!(spooled.scoping(), scoping());
+        assert!(!spooled.is_processed());
+    }
+
+    #[test]
+    fn test_spool_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let spool = SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::from_secs(60))
+                .unwrap();
+            spool.push(record(EVENT_1)).unwrap();
+            spool.push(record(EVENT_2)).unwrap();
+        }
+
+        let spool =
+            SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::from_secs(60)).unwrap();
+        spool.push(record(EVENT_3)).unwrap();
+
+        assert_eq!(drain(&spool), [EVENT_1, EVENT_2, EVENT_3]);
+    }
+
+    #[test]
+    fn test_spool_partial_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool =
+            SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::from_secs(60)).unwrap();
+
+        spool.push(record(EVENT_1)).unwrap();
+        spool.push(record(EVENT_2)).unwrap();
+
+        let mut batch = spool.pop_batch().unwrap();
+        batch.envelopes.pop_front();
+
+        // New envelopes go to a new segment while the batch is being drained.
+        spool.push(record(EVENT_3)).unwrap();
+        spool.commit(batch).unwrap();
+
+        assert_eq!(drain(&spool), [EVENT_2, EVENT_3]);
+    }
+
+    #[test]
+    fn test_spool_evicts_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let record_size = {
+            let spool = SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::from_secs(60))
+                .unwrap();
+            spool.push(record(EVENT_1)).unwrap();
+            let size = spool.state.lock().total_size;
+            drain(&spool);
+            size
+        };
+
+        // Every segment holds a single envelope, and the spool holds two segments.
+        let spool = SpoolDirectory::open(
+            dir.path(),
+            record_size * 5 / 2,
+            record_size * 3 / 2,
+            Duration::from_secs(60),
+        )
+        .unwrap();
+
+        let evicted = spool.append(record(EVENT_1)).unwrap();
+        assert!(evicted.is_empty());
+        let evicted = spool.append(record(EVENT_2)).unwrap();
+        assert!(evicted.is_empty());
+
+        let evicted = spool.append(record(EVENT_3)).unwrap();
+        assert_eq!(evicted.len(), 1);
+        let event_id = evicted[0].to_envelope().unwrap().event_id().unwrap();
+        assert_eq!(event_id.to_string(), EVENT_1);
+
+        assert_eq!(drain(&spool), [EVENT_2, EVENT_3]);
+    }
+
+    #[test]
+    fn test_spool_too_large() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = SpoolDirectory::open(dir.path(), 10, 10, Duration::from_secs(60)).unwrap();
+
+        let result = spool.append(record(EVENT_1));
+        assert!(matches!(result, Err(SpoolError::Full)));
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn test_spool_discards_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::ZERO).unwrap();
+
+        spool.append(record(EVENT_1)).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+
+        let (batch, expired) = spool.take_batch().unwrap();
+        assert!(batch.envelopes.is_empty());
+        assert_eq!(expired.len(), 1);
+
+        spool.commit(batch).unwrap();
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn test_spool_skips_unreadable_segment() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A directory in place of the oldest segment cannot be read as a file.
+        fs::create_dir(segment_path(dir.path(), 0)).unwrap();
+
+        let spool =
+            SpoolDirectory::open(dir.path(), 1 << 20, 1 << 20, Duration::from_secs(60)).unwrap();
+        spool.push(record(EVENT_1)).unwrap();
+
+        assert_eq!(drain(&spool), [EVENT_1]);
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn test_parse_truncated_record() {
+        let record = SpooledEnvelope {
+            header: RecordHeader {
+                organization_id: 1,
+                project_id: ProjectId::new(42),
+                project_key: scoping().project_key,
+                key_id: None,
+                received_at: Utc::now(),
+                processed: true,
+            },
+            payload: Bytes::from_static(b"{}\n"),
+        };
+
+        let mut data = Vec::new();
+        record.write_to(&mut data).unwrap();
+        record.write_to(&mut data).unwrap();
+        data.truncate(data.len() - 1);
+
+        assert_eq!(parse_records(&data).len(), 1);
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use relay_common::ProjectId;
+use relay_general::store::{TransactionNameClusterer, TransactionNameRule};
+use relay_redis::redis::Script;
+use relay_redis::{RedisError, RedisPool};
+use tokio::runtime::Handle;
+
+/// Maximum number of distinct transaction names stored per project.
+///
+/// Once a project reaches this limit, further names are not recorded until the set expires. This
+/// bounds memory in Redis for projects that send many distinct, unparameterized names.
+const MAX_TRANSACTION_NAMES: usize = 2000;
+
+/// Time in seconds after which the observed names of a project expire, unless names are added.
+const TRANSACTION_NAMES_TTL: usize = 7 * 24 * 3600;
+
+/// Number of days until rules produced from the observed names expire.
+const RULE_LIFETIME_DAYS: i64 = 90;
+
+/// Stores observed URL transaction names per project in Redis for transaction name clustering.
+///
+/// The names are fed into a [`TransactionNameClusterer`] to produce candidate
+/// [`TransactionNameRule`]s, which can be added to the `txNameRules` of the project config.
+#[derive(Clone, Debug)]
+pub struct TransactionNameStore {
+    redis: RedisPool,
+    script: Arc<Script>,
+    runtime: Handle,
+}
+
+impl TransactionNameStore {
+    /// Creates a store that keeps transaction names in the given Redis instance.
+    ///
+    /// This must be called within a Tokio runtime, which runs the blocking Redis calls of
+    /// [`get_rules`](Self::get_rules).
+    pub fn new(redis: RedisPool) -> Self {
+        Self {
+            redis,
+            script: Arc::new(Script::new(include_str!("record_transaction_name.lua"))),
+            runtime: Handle::current(),
+        }
+    }
+
+    fn key(project_id: ProjectId) -> String {
+        format!("txnames:{{{}}}", project_id)
+    }
+
+    /// Records a transaction name observed for the project.
+    ///
+    /// The size check and the update run atomically in a single script, so that concurrent
+    /// Relays cannot exceed [`MAX_TRANSACTION_NAMES`].
+    pub fn record(&self, project_id: ProjectId, name: &str) -> Result<(), RedisError> {
+        let mut client = self.redis.client()?;
+
+        self.script
+            .prepare_invoke()
+            .key(Self::key(project_id))
+            .arg(name)
+            .arg(MAX_TRANSACTION_NAMES)
+            .arg(TRANSACTION_NAMES_TTL)
+            .invoke::<()>(&mut client.connection())
+            .map_err(RedisError::Redis)?;
+
+        Ok(())
+    }
+
+    /// Computes candidate rules from all names observed for the project.
+    ///
+    /// Segments with more than `merge_threshold` distinct values at the same position are replaced
+    /// by the returned rules. Reading the names blocks on Redis, so this runs on a blocking thread.
+    pub async fn get_rules(
+        &self,
+        project_id: ProjectId,
+        merge_threshold: usize,
+    ) -> Result<Vec<TransactionNameRule>, RedisError> {
+        let store = self.clone();
+        let task = self
+            .runtime
+            .spawn_blocking(move || store.compute_rules(project_id, merge_threshold));
+
+        match task.await {
+            Ok(result) => result,
+            Err(error) => std::panic::resume_unwind(error.into_panic()),
+        }
+    }
+
+    fn compute_rules(
+        &self,
+        project_id: ProjectId,
+        merge_threshold: usize,
+    ) -> Result<Vec<TransactionNameRule>, RedisError> {
+        let names: Vec<String> = relay_redis::redis::cmd("SMEMBERS")
+            .arg(Self::key(project_id))
+            .query(&mut self.redis.client()?.connection())
+            .map_err(RedisError::Redis)?;
+
+        let mut clusterer = TransactionNameClusterer::new(merge_threshold);
+        for name in &names {
+            clusterer.add_name(name);
+        }
+
+        Ok(clusterer.into_rules(Utc::now() + Duration::days(RULE_LIFETIME_DAYS)))
+    }
+}
@@ -23,9 +23,15 @@
 //! ```no_run
 //! # use std::collections::BTreeMap;
 //!
-//! relay_statsd::init("myprefix", "localhost:8125", BTreeMap::new(), true, 1.0);
+//! relay_statsd::init("myprefix", Some("localhost:8125"), BTreeMap::new(), true, 1.0, None);
 //! ```
 //!
+//! ## Prometheus
+//!
+//! In addition to statsd, all metrics can be recorded into an in-process [`PrometheusRegistry`]
+//! by passing histogram buckets to [`init`]. The registry is available through
+//! [`prometheus_registry`] and renders metrics in the Prometheus text exposition format.
+//!
 //! ## Macro Usage
 //!
 //! The recommended way to record metrics is by using the [`metric!`] macro. See the trait docs
@@ -57,18 +63,25 @@
 //!
 //! [Metric Types]: https://github.com/statsd/statsd/blob/master/docs/metric_types.md
 use std::collections::BTreeMap;
+use std::io;
 use std::net::{ToSocketAddrs, UdpSocket};
 use std::ops::{Deref, DerefMut};
+use std::panic::RefUnwindSafe;
 use std::sync::Arc;
 
 use cadence::{
-    BufferedUdpMetricSink, Metric, MetricBuilder, QueuingMetricSink, StatsdClient, UdpMetricSink,
+    BufferedUdpMetricSink, Metric, MetricBuilder, MetricSink, QueuingMetricSink, StatsdClient,
+    UdpMetricSink,
 };
 use parking_lot::RwLock;
 use rand::distributions::{Distribution, Uniform};
 
 use relay_log::LogError;
 
+mod prometheus;
+
+pub use self::prometheus::*;
+
 /// Maximum number of metric events that can be queued before we start dropping them
 const METRICS_MAX_QUEUE_SIZE: usize = 100_000;
 
@@ -140,6 +153,9 @@ impl MetricsClient {
 
 static METRICS_CLIENT: RwLock<Option<Arc<MetricsClient>>> = RwLock::new(None);
 
+/// The global Prometheus registry, if enabled.
+static PROMETHEUS_REGISTRY: RwLock<Option<Arc<PrometheusRegistry>>> = RwLock::new(None);
+
 thread_local! {
     static CURRENT_CLIENT: std::cell::RefCell<Option<Arc<MetricsClient>>>  = METRICS_CLIENT.read().clone().into();
     static RNG_UNIFORM_DISTRIBUTION: Uniform<f32> = Uniform::new(0.0, 1.0);
@@ -184,21 +200,57 @@ pub fn with_capturing_test_client(f: impl FnOnce()) -> Vec<String> {
 /// Disable the client again.
 pub fn disable() {
     *METRICS_CLIENT.write() = None;
+    *PROMETHEUS_REGISTRY.write() = None;
+}
+
+/// Returns the global Prometheus registry if it was enabled in [`init`].
+pub fn prometheus_registry() -> Option<Arc<PrometheusRegistry>> {
+    PROMETHEUS_REGISTRY.read().clone()
+}
+
+/// A sink that is safe to share across threads, as required by [`StatsdClient`].
+type BoxedSink = Box<dyn MetricSink + Send + Sync + RefUnwindSafe>;
+
+/// A sink that forwards every metric to all of its inner sinks.
+struct MultiSink {
+    sinks: Vec<BoxedSink>,
 }
 
-/// Tell the metrics system to report to statsd.
+impl MetricSink for MultiSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut result = Ok(metric.len());
+        for sink in &self.sinks {
+            if let Err(error) = sink.emit(metric) {
+                result = Err(error);
+            }
+        }
+        result
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut result = Ok(());
+        for sink in &self.sinks {
+            if let Err(error) = sink.flush() {
+                result = Err(error);
+            }
+        }
+        result
+    }
+}
+
+/// Tell the metrics system to report to statsd and, optionally, to a Prometheus registry.
+///
+/// Metrics are sent to statsd if `host` is set. If `prometheus_buckets` is set, metrics are
+/// additionally recorded in a global [`PrometheusRegistry`] that uses the given buckets for
+/// timers. See [`prometheus_registry`].
 pub fn init<A: ToSocketAddrs>(
     prefix: &str,
-    host: A,
+    host: Option<A>,
     default_tags: BTreeMap<String, String>,
     buffering: bool,
     sample_rate: f32,
+    prometheus_buckets: Option<Vec<f64>>,
 ) {
-    let addrs: Vec<_> = host.to_socket_addrs().unwrap().collect();
-    if !addrs.is_empty() {
-        relay_log::info!("reporting metrics to statsd at {}", addrs[0]);
-    }
-
     // Normalize sample_rate
     let sample_rate = sample_rate.clamp(0., 1.);
     relay_log::debug!(
@@ -211,22 +263,46 @@ pub fn init<A: ToSocketAddrs>(
         }
     );
 
-    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
-    socket.set_nonblocking(true).unwrap();
+    let mut sinks: Vec<BoxedSink> = Vec::new();
+
+    if let Some(host) = host {
+        let addrs: Vec<_> = host.to_socket_addrs().unwrap().collect();
+        if !addrs.is_empty() {
+            relay_log::info!("reporting metrics to statsd at {}", addrs[0]);
+        }
+
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+
+        if buffering {
+            sinks.push(Box::new(
+                BufferedUdpMetricSink::from(&addrs[..], socket).unwrap(),
+            ));
+        } else {
+            sinks.push(Box::new(UdpMetricSink::from(&addrs[..], socket).unwrap()));
+        }
+    }
+
+    let registry = prometheus_buckets.map(|buckets| Arc::new(PrometheusRegistry::new(buckets)));
+    if let Some(ref registry) = registry {
+        relay_log::info!("recording metrics for prometheus");
+        sinks.push(Box::new(PrometheusSink::new(registry.clone())));
+    }
 
+    // Buffering moves all sinks to a background thread, including the Prometheus registry.
+    let sink = MultiSink { sinks };
     let statsd_client = if buffering {
-        let udp_sink = BufferedUdpMetricSink::from(host, socket).unwrap();
-        let queuing_sink = QueuingMetricSink::with_capacity(udp_sink, METRICS_MAX_QUEUE_SIZE);
+        let queuing_sink = QueuingMetricSink::with_capacity(sink, METRICS_MAX_QUEUE_SIZE);
         StatsdClient::from_sink(prefix, queuing_sink)
     } else {
-        let simple_sink = UdpMetricSink::from(host, socket).unwrap();
-        StatsdClient::from_sink(prefix, simple_sink)
+        StatsdClient::from_sink(prefix, sink)
     };
     relay_log::debug!(
         "metrics buffering is {}",
         if buffering { "enabled" } else { "disabled" }
     );
 
+    *PROMETHEUS_REGISTRY.write() = registry;
     set_client(MetricsClient {
         statsd_client,
         default_tags,
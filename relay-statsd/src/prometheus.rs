@@ -0,0 +1,448 @@
+//! An in-process registry that exposes metrics in the Prometheus text format.
+//!
+//! The registry is fed by a [`MetricSink`] that receives the same statsd lines that are sent to
+//! the statsd server. Statsd metric types are mapped to Prometheus types as follows:
+//!
+//!  - Counters (`c`) and meters (`m`) are exposed as `counter`.
+//!  - Gauges (`g`) are exposed as `gauge`.
+//!  - Timers (`ms`) are exposed as `histogram` with configurable buckets in milliseconds.
+//!  - Histograms (`h`) and distributions (`d`) are exposed as `summary` without quantiles.
+//!  - Sets (`s`) cannot be represented in Prometheus and are skipped.
+//!
+//! Metric names and tag keys are sanitized to match the Prometheus data model. All characters that
+//! are not allowed, such as the dots in `sentry.relay.event.accepted`, are replaced with `_`.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use cadence::MetricSink;
+
+/// Sorted pairs of label names and values identifying a time series.
+type Labels = Vec<(String, String)>;
+
+/// The Prometheus type of a metric family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+}
+
+impl MetricType {
+    fn from_statsd(ty: &str) -> Option<Self> {
+        match ty {
+            "c" | "m" => Some(Self::Counter),
+            "g" => Some(Self::Gauge),
+            "ms" => Some(Self::Histogram),
+            "h" | "d" => Some(Self::Summary),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Histogram => "histogram",
+            Self::Summary => "summary",
+        }
+    }
+}
+
+/// The current value of a single time series.
+#[derive(Debug)]
+enum SeriesValue {
+    /// The value of a counter or a gauge.
+    Single(f64),
+    /// The cumulative state of a histogram or summary.
+    ///
+    /// `buckets` holds the non-cumulative count per bucket and is empty for summaries.
+    Distribution {
+        buckets: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+/// All time series of a metric with the same name.
+#[derive(Debug)]
+struct Family {
+    ty: MetricType,
+    series: BTreeMap<Labels, SeriesValue>,
+}
+
+/// A single statsd line parsed into its components.
+#[derive(Debug, PartialEq)]
+struct StatsdLine<'a> {
+    name: &'a str,
+    value: f64,
+    ty: &'a str,
+    tags: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> StatsdLine<'a> {
+    /// Parses a line in the DogStatsD format, for example `name:1|c|#tag:value`.
+    ///
+    /// Counter values are scaled by the inverse of their sample rate (`@`), so that the registry
+    /// reports the estimated total rather than the sampled count.
+    fn parse(line: &'a str) -> Option<Self> {
+        let (name, rest) = line.split_once(':')?;
+        let mut components = rest.split('|');
+        let mut value: f64 = components.next()?.parse().ok()?;
+        let ty = components.next()?;
+
+        let mut tags = Vec::new();
+        for component in components {
+            if let Some(tag_string) = component.strip_prefix('#') {
+                for tag in tag_string.split(',') {
+                    tags.push(tag.split_once(':').unwrap_or((tag, "")));
+                }
+            } else if let Some(rate_string) = component.strip_prefix('@') {
+                let rate: f64 = rate_string.parse().ok()?;
+                if ty == "c" && rate > 0.0 && rate < 1.0 {
+                    value /= rate;
+                }
+            }
+        }
+
+        Some(Self {
+            name,
+            value,
+            ty,
+            tags,
+        })
+    }
+}
+
+/// Replaces all characters that are not valid in Prometheus metric and label names with `_`.
+fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+
+    sanitized
+}
+
+/// Formats label values, escaping backslashes, double quotes and line feeds.
+struct LabelValue<'a>(&'a str);
+
+impl fmt::Display for LabelValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats sample values, using the Prometheus notation for infinity.
+struct SampleValue(f64);
+
+impl fmt::Display for SampleValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == f64::INFINITY {
+            f.write_str("+Inf")
+        } else if self.0 == f64::NEG_INFINITY {
+            f.write_str("-Inf")
+        } else if self.0.is_nan() {
+            f.write_str("NaN")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// Writes a single sample line, appending an optional extra label such as `le`.
+fn write_sample(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    labels: &Labels,
+    extra: Option<(&str, &str)>,
+    value: f64,
+) {
+    out.push_str(name);
+    out.push_str(suffix);
+
+    let mut labels = labels
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .chain(extra)
+        .peekable();
+
+    if labels.peek().is_some() {
+        out.push('{');
+        for (index, (key, value)) in labels.enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            write!(out, "{}=\"{}\"", key, LabelValue(value)).ok();
+        }
+        out.push('}');
+    }
+
+    writeln!(out, " {}", SampleValue(value)).ok();
+}
+
+/// An in-process registry of metrics that renders the Prometheus text exposition format.
+///
+/// Use [`render`](Self::render) to obtain the current state of all metrics. Metrics are recorded
+/// through the metrics client set up by [`init`](crate::init) and are never reset.
+#[derive(Debug)]
+pub struct PrometheusRegistry {
+    buckets: Vec<f64>,
+    families: Mutex<BTreeMap<String, Family>>,
+}
+
+impl PrometheusRegistry {
+    /// Creates a new registry with the given upper bounds of histogram buckets for timers.
+    ///
+    /// Buckets are sorted and deduplicated. An implicit `+Inf` bucket is always added.
+    pub fn new(mut buckets: Vec<f64>) -> Self {
+        buckets.retain(|bound| bound.is_finite());
+        buckets.sort_by(|a, b| a.total_cmp(b));
+        buckets.dedup();
+
+        Self {
+            buckets,
+            families: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records a single statsd line.
+    ///
+    /// Lines that cannot be parsed and metrics with a type that conflicts with a previously
+    /// recorded metric of the same name are skipped.
+    fn record(&self, line: &str) {
+        let line = match StatsdLine::parse(line) {
+            Some(line) => line,
+            None => return,
+        };
+
+        let ty = match MetricType::from_statsd(line.ty) {
+            Some(ty) => ty,
+            None => return,
+        };
+
+        let mut labels: Labels = line
+            .tags
+            .iter()
+            .map(|(key, value)| (sanitize_name(key), (*value).to_owned()))
+            .collect();
+        labels.sort();
+        labels.dedup_by(|a, b| a.0 == b.0);
+
+        let mut families = self.families.lock().unwrap_or_else(PoisonError::into_inner);
+        let family = families
+            .entry(sanitize_name(line.name))
+            .or_insert_with(|| Family {
+                ty,
+                series: BTreeMap::new(),
+            });
+
+        if family.ty != ty {
+            return;
+        }
+
+        let series = family.series.entry(labels).or_insert_with(|| match ty {
+            MetricType::Counter | MetricType::Gauge => SeriesValue::Single(0.0),
+            MetricType::Histogram => SeriesValue::Distribution {
+                buckets: vec![0; self.buckets.len()],
+                sum: 0.0,
+                count: 0,
+            },
+            MetricType::Summary => SeriesValue::Distribution {
+                buckets: Vec::new(),
+                sum: 0.0,
+                count: 0,
+            },
+        });
+
+        match series {
+            SeriesValue::Single(current) if ty == MetricType::Counter => *current += line.value,
+            SeriesValue::Single(current) => *current = line.value,
+            SeriesValue::Distribution {
+                buckets,
+                sum,
+                count,
+            } => {
+                let index = self.buckets.iter().position(|bound| line.value <= *bound);
+                if let Some(bucket) = index.and_then(|index| buckets.get_mut(index)) {
+                    *bucket += 1;
+                }
+                *sum += line.value;
+                *count += 1;
+            }
+        }
+    }
+
+    /// Renders all recorded metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut out = String::new();
+
+        for (name, family) in families.iter() {
+            writeln!(out, "# TYPE {} {}", name, family.ty.name()).ok();
+
+            for (labels, value) in &family.series {
+                match value {
+                    SeriesValue::Single(value) => {
+                        write_sample(&mut out, name, "", labels, None, *value);
+                    }
+                    SeriesValue::Distribution {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        if family.ty == MetricType::Histogram {
+                            let mut cumulative = 0;
+                            for (bound, bucket) in self.buckets.iter().zip(buckets) {
+                                cumulative += bucket;
+                                let le = SampleValue(*bound).to_string();
+                                let extra = Some(("le", le.as_str()));
+                                write_sample(
+                                    &mut out,
+                                    name,
+                                    "_bucket",
+                                    labels,
+                                    extra,
+                                    cumulative as f64,
+                                );
+                            }
+
+                            let extra = Some(("le", "+Inf"));
+                            write_sample(&mut out, name, "_bucket", labels, extra, *count as f64);
+                        }
+
+                        write_sample(&mut out, name, "_sum", labels, None, *sum);
+                        write_sample(&mut out, name, "_count", labels, None, *count as f64);
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// A [`MetricSink`] that records metrics into a [`PrometheusRegistry`].
+#[derive(Debug)]
+pub(crate) struct PrometheusSink {
+    registry: Arc<PrometheusRegistry>,
+}
+
+impl PrometheusSink {
+    pub fn new(registry: Arc<PrometheusRegistry>) -> Self {
+        Self { registry }
+    }
+}
+
+impl MetricSink for PrometheusSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.registry.record(metric);
+        Ok(metric.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_statsd_line() {
+        assert_eq!(
+            StatsdLine::parse("sentry.relay.foo:42|c|@0.5|#a:b,c"),
+            Some(StatsdLine {
+                name: "sentry.relay.foo",
+                value: 84.0,
+                ty: "c",
+                tags: vec![("a", "b"), ("c", "")],
+            })
+        );
+
+        // Sample rates only apply to counters.
+        assert_eq!(
+            StatsdLine::parse("sentry.relay.foo:42|g|@0.5"),
+            Some(StatsdLine {
+                name: "sentry.relay.foo",
+                value: 42.0,
+                ty: "g",
+                tags: vec![],
+            })
+        );
+
+        assert_eq!(StatsdLine::parse("foo"), None);
+        assert_eq!(StatsdLine::parse("foo:bar|c"), None);
+    }
+
+    #[test]
+    fn test_render_counters_and_gauges() {
+        let registry = PrometheusRegistry::new(Vec::new());
+        registry.record("sentry.relay.requests:1|c|#route:store");
+        registry.record("sentry.relay.requests:2|c|#route:store");
+        registry.record("sentry.relay.requests:1|c|#route:envelope");
+        registry.record("sentry.relay.buffer:10|g");
+        registry.record("sentry.relay.buffer:7|g");
+        registry.record("sentry.relay.unique:foo|s");
+
+        assert_eq!(
+            registry.render(),
+            "\
+# TYPE sentry_relay_buffer gauge
+sentry_relay_buffer 7
+# TYPE sentry_relay_requests counter
+sentry_relay_requests{route=\"envelope\"} 1
+sentry_relay_requests{route=\"store\"} 3
+"
+        );
+    }
+
+    #[test]
+    fn test_render_histograms() {
+        let registry = PrometheusRegistry::new(vec![100.0, 10.0]);
+        registry.record("timer:5|ms|#a:\"quoted\"");
+        registry.record("timer:50|ms|#a:\"quoted\"");
+        registry.record("timer:500|ms|#a:\"quoted\"");
+        registry.record("size:3|h");
+
+        assert_eq!(
+            registry.render(),
+            "\
+# TYPE size summary
+size_sum 3
+size_count 1
+# TYPE timer histogram
+timer_bucket{a=\"\\\"quoted\\\"\",le=\"10\"} 1
+timer_bucket{a=\"\\\"quoted\\\"\",le=\"100\"} 2
+timer_bucket{a=\"\\\"quoted\\\"\",le=\"+Inf\"} 3
+timer_sum{a=\"\\\"quoted\\\"\"} 555
+timer_count{a=\"\\\"quoted\\\"\"} 3
+"
+        );
+    }
+
+    #[test]
+    fn test_type_conflict() {
+        let registry = PrometheusRegistry::new(Vec::new());
+        registry.record("foo:1|c");
+        registry.record("foo:5|g");
+
+        assert_eq!(registry.render(), "# TYPE foo counter\nfoo 1\n");
+    }
+}
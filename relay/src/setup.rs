@@ -51,7 +51,7 @@ pub fn dump_credentials(config: &Config) {
 /// Initialize the metric system.
 pub fn init_metrics(config: &Config) -> Result<()> {
     let addrs = config.statsd_addrs()?;
-    if addrs.is_empty() {
+    if addrs.is_empty() && !config.metrics_prometheus() {
         return Ok(());
     }
 
@@ -63,10 +63,13 @@ pub fn init_metrics(config: &Config) -> Result<()> {
     }
     relay_statsd::init(
         config.metrics_prefix(),
-        &addrs[..],
+        Some(&addrs[..]).filter(|addrs| !addrs.is_empty()),
         default_tags,
         config.metrics_buffering(),
         config.metrics_sample_rate(),
+        config
+            .metrics_prometheus()
+            .then(|| config.metrics_prometheus_buckets().to_vec()),
     );
 
     Ok(())
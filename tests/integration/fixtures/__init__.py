@@ -203,6 +203,13 @@ class SentryLike(object):
 
         self.send_envelope(project_id, envelope)
 
+    def send_check_in(self, project_id, payload, item_headers=None):
+        envelope = Envelope()
+        envelope.add_item(
+            Item(payload=PayloadRef(json=payload), type="check_in", headers=item_headers)
+        )
+        self.send_envelope(project_id, envelope)
+
     def send_session_aggregates(self, project_id, payload):
         envelope = Envelope()
         envelope.add_item(Item(payload=PayloadRef(json=payload), type="sessions"))
@@ -3,6 +3,7 @@ import json
 
 import pytest
 from sentry_sdk.envelope import Envelope, Item, PayloadRef
+from sentry_relay import SecretKey
 import queue
 
 
@@ -232,6 +233,49 @@ def test_it_keeps_transactions(mini_sentry, relay):
         mini_sentry.captured_outcomes.get(timeout=2)
 
 
+def test_reservoir_keeps_transactions(mini_sentry, relay):
+    """
+    Tests that a reservoir rule keeps a minimum number of transactions before regular sampling
+    rules drop the remaining ones
+    """
+    project_id = 42
+    relay = relay(mini_sentry, _outcomes_enabled_config())
+
+    config = mini_sentry.add_basic_project_config(project_id)
+    public_key = config["publicKeys"][0]["publicKey"]
+    rules = _add_sampling_config(config, sample_rate=0, rule_type="trace")
+    config["config"]["dynamicSampling"]["reservoirRules"] = [
+        {
+            "condition": {"op": "and", "inner": []},
+            "limit": 2,
+            "window": 3600,
+            "type": "trace",
+            "id": len(rules) + 1,
+        }
+    ]
+
+    for _ in range(3):
+        envelope = Envelope()
+        transaction, trace_id, event_id = _create_transaction_item()
+        envelope.add_transaction(transaction)
+        _add_trace_info(envelope, trace_id=trace_id, public_key=public_key)
+        relay.send_envelope(project_id, envelope)
+
+    # the first two transactions fill the reservoir
+    for _ in range(2):
+        evt = mini_sentry.captured_events.get(timeout=1).get_transaction_event()
+        assert evt is not None
+
+    # the third transaction falls through to the sampling rule and is dropped
+    with pytest.raises(queue.Empty):
+        mini_sentry.captured_events.get(timeout=1)
+
+    outcomes = mini_sentry.captured_outcomes.get(timeout=2)
+    outcome = outcomes["outcomes"][0]
+    assert outcome.get("outcome") == 1  # filtered
+    assert outcome.get("reason") == f"Sampled:{rules[0]['id']}"
+
+
 def _create_event_envelope(
     public_key, client_sample_rate=None, trace_id=None, event_id=None, transaction=None
 ):
@@ -559,3 +603,52 @@ def test_relay_chain(
         envelope.get_event()
     else:
         envelope.get_transaction_event()
+
+
+def test_explain_sampling(mini_sentry, relay):
+    """
+    Tests that the sampling explain endpoint reports the evaluated rules and the decision without
+    ingesting the envelope
+    """
+    project_id = 42
+    relay = relay(mini_sentry, wait_health_check=True)
+    config = mini_sentry.add_basic_project_config(project_id)
+    public_key = config["publicKeys"][0]["publicKey"]
+    _add_sampling_config(config, sample_rate=0, rule_type="transaction", releases=["1.0"])
+    _add_sampling_config(config, sample_rate=1, rule_type="trace")
+
+    envelope, trace_id, event_id = _create_transaction_envelope(public_key)
+
+    body = {
+        "publicKey": public_key,
+        "envelope": envelope.serialize().decode("utf-8"),
+    }
+    packed, signature = SecretKey.parse(relay.secret_key).pack(body)
+
+    response = relay.post(
+        "/api/0/relays/sampling/explain/",
+        data=packed,
+        headers={
+            "X-Sentry-Relay-Id": relay.relay_id,
+            "X-Sentry-Relay-Signature": signature,
+        },
+    )
+
+    assert response.ok
+    explanation = response.json()
+
+    event_rules = explanation["eventRules"]
+    assert [rule["id"] for rule in event_rules] == [1]
+    assert not event_rules[0]["matched"]
+
+    trace_rules = explanation["traceRules"]
+    assert [rule["id"] for rule in trace_rules] == [2]
+    assert trace_rules[0]["matched"]
+
+    assert explanation["appliedRule"] == 2
+    assert explanation["effectiveSampleRate"] == 1.0
+    assert explanation["decision"] == "keep"
+
+    # the envelope is only explained, not ingested
+    with pytest.raises(queue.Empty):
+        mini_sentry.captured_events.get(timeout=1)
@@ -11,10 +11,44 @@ import pytest
     [
         ({"errorMessages": {"patterns": ["Panic: originalCreateNotification"]}}, True),
         ({"errorMessages": {"patterns": ["Warning"]}}, False),
+        (
+            {
+                "generic": [
+                    {
+                        "id": "other-platform",
+                        "isEnabled": True,
+                        "condition": {
+                            "op": "eq",
+                            "name": "event.platform",
+                            "value": "other",
+                        },
+                    }
+                ]
+            },
+            True,
+        ),
+        (
+            {
+                "generic": [
+                    {
+                        "id": "python-platform",
+                        "isEnabled": True,
+                        "condition": {
+                            "op": "eq",
+                            "name": "event.platform",
+                            "value": "python",
+                        },
+                    }
+                ]
+            },
+            False,
+        ),
     ],
     ids=[
         "error messages filtered",
         "error messages not filtered",
+        "generic filtered",
+        "generic not filtered",
     ],
 )
 def test_filters_are_applied(
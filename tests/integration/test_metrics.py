@@ -1,6 +1,7 @@
 from datetime import datetime, timedelta, timezone
 import json
 import signal
+import socket
 
 import pytest
 import requests
@@ -74,6 +75,63 @@ def test_metrics(mini_sentry, relay):
     ]
 
 
+def test_metrics_cardinality_limit(mini_sentry, relay):
+    options = {
+        "aggregator": {
+            **TEST_CONFIG["aggregator"],
+            "cardinality_limiter": {"limit": 2},
+        }
+    }
+    relay = relay(mini_sentry, options=options)
+
+    project_id = 42
+    mini_sentry.add_basic_project_config(project_id)
+
+    timestamp = int(datetime.now(tz=timezone.utc).timestamp())
+    metrics_payload = "\n".join(
+        f"transactions/foo:1|c|#user:{user}" for user in ["a", "b", "c"]
+    )
+    relay.send_metrics(project_id, metrics_payload, timestamp)
+
+    envelope = mini_sentry.captured_events.get(timeout=3)
+    received_metrics = json.loads(envelope.items[0].get_bytes().decode())
+    assert len(received_metrics) == 2
+
+    # Known buckets are still accepted after the limit has been reached.
+    user = received_metrics[0]["tags"]["user"]
+    relay.send_metrics(project_id, f"transactions/foo:1|c|#user:{user}", timestamp)
+    envelope = mini_sentry.captured_events.get(timeout=3)
+    received_metrics = json.loads(envelope.items[0].get_bytes().decode())
+    assert len(received_metrics) == 1
+
+
+def test_metrics_statsd_listener(mini_sentry, relay, random_port):
+    project_id = 42
+    mini_sentry.add_basic_project_config(project_id)
+    public_key = mini_sentry.get_dsn_public_key(project_id)
+
+    port = random_port()
+    options = dict(TEST_CONFIG)
+    options["statsd_listener"] = {"udp": "127.0.0.1:%s" % port}
+    relay = relay(mini_sentry, options=options)
+
+    packet = "page.views:1|c|#sentry_key:%s,route:home\npage.views:2|c" % public_key
+    with socket.socket(socket.AF_INET, socket.SOCK_DGRAM) as sock:
+        sock.sendto(packet.encode(), ("127.0.0.1", port))
+
+    envelope = mini_sentry.captured_events.get(timeout=3)
+    assert len(envelope.items) == 1
+
+    metrics_item = envelope.items[0]
+    assert metrics_item.type == "metric_buckets"
+
+    received_metrics = json.loads(metrics_item.get_bytes().decode())
+    assert len(received_metrics) == 1
+    assert received_metrics[0]["name"] == "c:custom/page.views@none"
+    assert received_metrics[0]["value"] == 1.0
+    assert received_metrics[0]["tags"] == {"route": "home"}
+
+
 def test_metrics_backdated(mini_sentry, relay):
     relay = relay(mini_sentry, options=TEST_CONFIG)
 
@@ -675,6 +733,69 @@ def test_transaction_metrics(
     }
 
 
+def test_span_metrics(mini_sentry, relay_with_processing, metrics_consumer):
+    metrics_consumer = metrics_consumer()
+    relay = relay_with_processing(options=TEST_CONFIG)
+
+    project_id = 42
+    mini_sentry.add_full_project_config(project_id)
+    config = mini_sentry.project_configs[project_id]["config"]
+    config["transactionMetrics"] = {"version": 1}
+    config["features"] = ["projects:span-metrics-extraction"]
+
+    timestamp = datetime.now(tz=timezone.utc)
+    transaction = generate_transaction_item()
+    transaction["timestamp"] = timestamp.timestamp()
+    transaction["start_timestamp"] = timestamp.timestamp() - 1
+    transaction["spans"] = [
+        {
+            "description": "SELECT * FROM users WHERE id = 42",
+            "op": "db.sql.query",
+            "parent_span_id": "fa90fdead5f74052",
+            "span_id": "bd429c44b67a3eb4",
+            "start_timestamp": timestamp.timestamp() - 0.5,
+            "timestamp": timestamp.timestamp(),
+            "trace_id": "4c79f60c11214eb38604f4ae0781bfb2",
+            "data": {"db.system": "postgresql"},
+        }
+    ]
+
+    relay.send_transaction(project_id, transaction)
+
+    metrics = {
+        metric["name"]: metric
+        for metric in metrics_consumer.get_metrics(timeout=2)
+        if metric["name"].startswith("d:spans/")
+    }
+
+    duration = metrics["d:spans/duration@millisecond"]
+    assert duration["value"] == [500.0]
+    assert duration["tags"] == {
+        "transaction": "/organizations/:orgId/performance/:eventSlug/",
+        "span.op": "db.sql.query",
+        "span.system": "postgresql",
+        "span.description": "SELECT * FROM users WHERE id = %s",
+    }
+
+
+def test_span_metrics_disabled(mini_sentry, relay_with_processing, metrics_consumer):
+    metrics_consumer = metrics_consumer()
+    relay = relay_with_processing(options=TEST_CONFIG)
+
+    project_id = 42
+    mini_sentry.add_full_project_config(project_id)
+    config = mini_sentry.project_configs[project_id]["config"]
+    config["transactionMetrics"] = {"version": 1}
+
+    transaction = generate_transaction_item()
+    transaction["timestamp"] = datetime.now(tz=timezone.utc).isoformat()
+    relay.send_transaction(project_id, transaction)
+
+    names = [metric["name"] for metric in metrics_consumer.get_metrics(timeout=2)]
+    assert names
+    assert not any(name.startswith("d:spans/") for name in names)
+
+
 @pytest.mark.parametrize(
     "send_extracted_header,expect_extracted_header,expect_metrics_extraction",
     [(False, True, True), (True, True, False)],
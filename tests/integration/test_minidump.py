@@ -417,6 +417,16 @@ def test_minidump_with_processing(
 
     # Check information extracted from the minidump
     assert event["timestamp"] == 1574692481.0  # 11/25/2019 @ 2:34pm (UTC)
+    assert event["contexts"]["os"]["name"] == "Linux"
+    assert event["contexts"]["device"]["arch"] == "x86_64"
+
+    exception = event["exception"]["values"][0]
+    assert exception["type"].startswith("SIGILL")
+    assert exception["thread_id"] == 22385
+
+    images = event["debug_meta"]["images"]
+    assert images
+    assert all(image["type"] == "elf" for image in images)
 
     assert list(message["attachments"]) == [
         {
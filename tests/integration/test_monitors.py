@@ -0,0 +1,106 @@
+import queue
+
+import pytest
+
+
+def generate_check_in(slug):
+    return {
+        "check_in_id": "a460c25ff2554577b920fcfacae4e5eb",
+        "monitor_slug": slug,
+        "status": "in_progress",
+        "duration": 21.0,
+    }
+
+
+def test_monitors_with_processing(
+    mini_sentry, relay_with_processing, monitors_consumer
+):
+    project_id = 42
+    relay = relay_with_processing()
+    mini_sentry.add_basic_project_config(project_id)
+    monitors_consumer = monitors_consumer()
+
+    check_in = generate_check_in("My-Monitor")
+    relay.send_check_in(project_id, check_in)
+
+    check_in, message = monitors_consumer.get_check_in()
+    assert message["start_time"] is not None
+    assert message["project_id"] == 42
+    assert check_in == {
+        "check_in_id": "a460c25ff2554577b920fcfacae4e5eb",
+        "monitor_slug": "my-monitor",
+        "status": "in_progress",
+        "duration": 21.0,
+    }
+
+
+def test_monitors_invalid_with_processing(
+    mini_sentry, relay_with_processing, monitors_consumer, outcomes_consumer
+):
+    project_id = 42
+    relay = relay_with_processing()
+    mini_sentry.add_basic_project_config(project_id)
+    monitors_consumer = monitors_consumer()
+    outcomes_consumer = outcomes_consumer(timeout=2)
+
+    check_in = generate_check_in("")
+    relay.send_check_in(project_id, check_in)
+
+    monitors_consumer.assert_empty()
+
+    outcome = outcomes_consumer.get_outcome()
+    assert outcome["outcome"] == 3  # invalid
+    assert outcome["reason"] == "invalid_check_in"
+    assert outcome["category"] == 10  # monitor
+
+
+def test_monitors_endpoint(mini_sentry, relay_with_processing, monitors_consumer):
+    project_id = 42
+    relay = relay_with_processing()
+    mini_sentry.add_basic_project_config(project_id)
+    monitors_consumer = monitors_consumer()
+
+    response = relay.get(
+        "/api/%s/cron/my-monitor/?sentry_key=%s&status=ok&environment=production"
+        % (project_id, relay.get_dsn_public_key(project_id))
+    )
+    assert response.status_code == 202
+    check_in_id = response.json()["id"]
+
+    check_in, _ = monitors_consumer.get_check_in()
+    assert check_in == {
+        "check_in_id": check_in_id,
+        "monitor_slug": "my-monitor",
+        "status": "ok",
+        "environment": "production",
+    }
+
+
+def test_monitors_endpoint_invalid(mini_sentry, relay):
+    project_id = 42
+    relay = relay(mini_sentry)
+    mini_sentry.add_basic_project_config(project_id)
+
+    response = relay.post(
+        "/api/%s/cron/my-monitor/?sentry_key=%s&status=ok&duration=-1"
+        % (project_id, relay.get_dsn_public_key(project_id))
+    )
+    assert response.status_code == 400
+
+
+def test_monitors_without_processing(mini_sentry, relay):
+    project_id = 42
+    relay = relay(mini_sentry)
+    mini_sentry.add_basic_project_config(project_id)
+
+    relay.send_check_in(project_id, generate_check_in("My-Monitor"))
+
+    envelope = mini_sentry.captured_events.get(timeout=1)
+    check_in = envelope.items[0].payload.json
+    assert check_in["monitor_slug"] == "my-monitor"
+
+    # Invalid check-ins are dropped before they are forwarded.
+    relay.send_check_in(project_id, generate_check_in(""))
+
+    with pytest.raises(queue.Empty):
+        mini_sentry.captured_events.get(timeout=1)
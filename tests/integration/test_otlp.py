@@ -0,0 +1,123 @@
+import time
+
+TRACE_ID = "0af7651916cd43dd8448eb211c80319c"
+
+
+def make_otlp_payload():
+    start = int(time.time() * 1e9)
+
+    return {
+        "resourceSpans": [
+            {
+                "resource": {
+                    "attributes": [
+                        {
+                            "key": "service.name",
+                            "value": {"stringValue": "checkout"},
+                        },
+                        {
+                            "key": "deployment.environment",
+                            "value": {"stringValue": "production"},
+                        },
+                    ]
+                },
+                "scopeSpans": [
+                    {
+                        "spans": [
+                            {
+                                "traceId": TRACE_ID,
+                                "spanId": "b7ad6b7169203331",
+                                "name": "GET /checkout",
+                                "kind": 2,
+                                "startTimeUnixNano": str(start),
+                                "endTimeUnixNano": str(start + 1_000_000_000),
+                                "attributes": [
+                                    {
+                                        "key": "http.method",
+                                        "value": {"stringValue": "GET"},
+                                    },
+                                    {
+                                        "key": "http.route",
+                                        "value": {"stringValue": "/checkout"},
+                                    },
+                                    {
+                                        "key": "http.status_code",
+                                        "value": {"intValue": "200"},
+                                    },
+                                ],
+                            },
+                            {
+                                "traceId": TRACE_ID,
+                                "spanId": "b7ad6b7169203332",
+                                "parentSpanId": "b7ad6b7169203331",
+                                "name": "SELECT orders",
+                                "kind": 3,
+                                "startTimeUnixNano": str(start + 100_000_000),
+                                "endTimeUnixNano": str(start + 200_000_000),
+                                "attributes": [
+                                    {
+                                        "key": "db.system",
+                                        "value": {"stringValue": "postgresql"},
+                                    },
+                                ],
+                            },
+                        ]
+                    }
+                ],
+            }
+        ]
+    }
+
+
+def send_otlp_traces(relay, project_id, payload):
+    return relay.post(
+        "/api/%s/otlp/v1/traces" % project_id,
+        headers={"X-Sentry-Auth": relay.get_auth_header(project_id)},
+        json=payload,
+    )
+
+
+def test_otlp_traces(mini_sentry, relay):
+    project_id = 42
+    mini_sentry.add_full_project_config(project_id)
+    relay = relay(mini_sentry)
+
+    response = send_otlp_traces(relay, project_id, make_otlp_payload())
+    assert response.ok
+    assert response.json() == {}
+
+    envelope = mini_sentry.captured_events.get(timeout=5)
+    event = envelope.get_transaction_event()
+
+    assert event["transaction"] == "GET /checkout"
+    assert event["transaction_info"]["source"] == "route"
+    assert event["environment"] == "production"
+    assert event["contexts"]["trace"]["trace_id"] == TRACE_ID
+    assert event["contexts"]["trace"]["span_id"] == "b7ad6b7169203331"
+    assert event["contexts"]["trace"]["op"] == "http.server"
+    assert event["contexts"]["trace"]["status"] == "ok"
+
+    (span,) = event["spans"]
+    assert span["span_id"] == "b7ad6b7169203332"
+    assert span["parent_span_id"] == "b7ad6b7169203331"
+    assert span["op"] == "db"
+    assert span["description"] == "SELECT orders"
+
+    assert mini_sentry.captured_events.empty()
+
+
+def test_otlp_traces_invalid_payload(mini_sentry, relay):
+    project_id = 42
+    mini_sentry.add_full_project_config(project_id)
+    relay = relay(mini_sentry)
+
+    response = relay.post(
+        "/api/%s/otlp/v1/traces" % project_id,
+        headers={
+            "X-Sentry-Auth": relay.get_auth_header(project_id),
+            "Content-Type": "application/x-protobuf",
+        },
+        data=b"\xff\xff\xff",
+    )
+    assert response.status_code == 400
+    assert mini_sentry.captured_events.empty()
@@ -0,0 +1,49 @@
+"""
+Test the Prometheus metrics endpoint
+"""
+
+import time
+
+
+def get_metrics(relay, name):
+    """Polls the metrics endpoint until the given metric family is exposed."""
+    for _ in range(20):
+        response = relay.get("/metrics")
+        assert response.status_code == 200
+        if "# TYPE %s " % name in response.text:
+            return response
+        time.sleep(0.1)
+
+    raise AssertionError("metric %s not exposed" % name)
+
+
+def test_metrics_disabled(mini_sentry, relay):
+    relay = relay(mini_sentry)
+    response = relay.get("/metrics")
+    assert response.status_code == 404
+
+
+def test_metrics_endpoint(mini_sentry, relay):
+    options = {"metrics": {"prometheus": True, "prometheus_buckets": [10.0, 1000.0]}}
+    relay = relay(mini_sentry, options=options)
+
+    response = relay.get("/api/relay/healthcheck/live/")
+    assert response.status_code == 200
+
+    response = get_metrics(relay, "sentry_relay_requests_duration")
+    assert response.headers["content-type"].startswith("text/plain; version=0.0.4")
+
+    lines = response.text.splitlines()
+    assert "# TYPE sentry_relay_requests counter" in lines
+    assert "# TYPE sentry_relay_requests_duration histogram" in lines
+
+    route = 'route="internal-healthcheck-live"'
+    assert any(
+        line.startswith("sentry_relay_requests_duration_bucket{")
+        and route in line
+        and 'le="+Inf"' in line
+        for line in lines
+    )
+    assert any(
+        line.startswith("sentry_relay_requests{") and route in line for line in lines
+    )
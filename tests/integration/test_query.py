@@ -257,3 +257,46 @@ def test_processing_redis_query_compressed(
 
     event, v = events_consumer.get_event()
     assert event["logentry"] == {"formatted": "Hello, World!"}
+
+
+def test_persisted_project_config(mini_sentry, relay, tmpdir):
+    """
+    Project configs persisted on disk are used after a restart, even if the upstream is not able
+    to serve project configs.
+    """
+    project_id = 42
+    mini_sentry.add_basic_project_config(project_id)
+    public_key = mini_sentry.get_dsn_public_key(project_id)
+
+    cache_dir = tmpdir.mkdir("project_cache")
+    options = {
+        "cache": {
+            "project_persist_path": str(cache_dir),
+            "project_grace_period": 60,
+        }
+    }
+
+    relay_1 = relay(mini_sentry, options=options)
+    relay_1.send_event(project_id)
+    mini_sentry.captured_events.get(timeout=2)
+
+    persisted = cache_dir.join("%s.json" % public_key)
+    for _ in range(20):
+        if persisted.check():
+            break
+        time.sleep(0.1)
+    assert json.loads(persisted.read())["state"]["projectId"] == project_id
+    relay_1.shutdown()
+
+    @mini_sentry.app.endpoint("get_project_config")
+    def get_project_config():
+        return "", 500
+
+    try:
+        relay_2 = relay(mini_sentry, options=options)
+        relay_2.send_event(project_id)
+
+        event = mini_sentry.captured_events.get(timeout=2).get_event()
+        assert event["logentry"] == {"formatted": "Hello, World!"}
+    finally:
+        mini_sentry.test_failures.clear()
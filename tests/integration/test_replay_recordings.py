@@ -1,5 +1,8 @@
+import json
 import time
+import zlib
 
+import pytest
 from sentry_sdk.envelope import Envelope, Item, PayloadRef
 
 
@@ -92,7 +95,10 @@ def test_chunked_replay_recordings_processing(
     assert replay_recording["received"]
     assert type(replay_recording["received"]) == int
 
-    outcomes_consumer.assert_empty()
+    # The payload is not a valid recording. It is reported, but still accepted.
+    outcome = outcomes_consumer.get_outcome()
+    assert outcome["outcome"] == 3  # invalid
+    assert outcome["reason"] == "invalid_replay_recording"
 
 
 def test_nonchunked_replay_recordings_processing(
@@ -132,4 +138,72 @@ def test_nonchunked_replay_recordings_processing(
     assert replay_recording["payload"] == b"test"
     assert replay_recording["type"] == "replay_recording_not_chunked"
 
-    outcomes_consumer.assert_empty()
+    # The payload is not a valid recording. It is reported, but still accepted.
+    outcome = outcomes_consumer.get_outcome()
+    assert outcome["outcome"] == 3  # invalid
+    assert outcome["reason"] == "invalid_replay_recording"
+
+
+@pytest.mark.parametrize("organization_id", [1, 42])
+def test_replay_recordings_pii_config(
+    mini_sentry, relay_with_processing, replay_recordings_consumer, organization_id
+):
+    project_id = 42
+    replay_id = "515539018c9b4260a6f999572f1661ee"
+    relay = relay_with_processing()
+    mini_sentry.add_full_project_config(
+        project_id,
+        extra={
+            "organizationId": organization_id,
+            "config": {
+                "features": ["organizations:session-replay"],
+                "piiConfig": {
+                    "rules": {
+                        "ticket": {
+                            "type": "pattern",
+                            "pattern": r"TICKET-\d+",
+                            "redaction": {"method": "replace", "text": "[ticket]"},
+                        }
+                    },
+                    "applications": {
+                        "text": ["ticket"],
+                        "attributes.title": ["ticket"],
+                    },
+                },
+            }
+        },
+    )
+    replay_recordings_consumer = replay_recordings_consumer()
+
+    events = [
+        {
+            "type": 2,
+            "timestamp": 1,
+            "data": {
+                "node": {
+                    "type": 2,
+                    "id": 1,
+                    "tagName": "h1",
+                    "attributes": {"title": "TICKET-1"},
+                    "childNodes": [
+                        {"type": 3, "id": 2, "textContent": "see TICKET-2"}
+                    ],
+                },
+                "initialOffset": {"top": 0, "left": 0},
+            },
+        }
+    ]
+    payload = b'{"segment_id":0}\n' + zlib.compress(json.dumps(events).encode())
+
+    envelope = Envelope(headers=[["event_id", replay_id]])
+    envelope.add_item(Item(payload=PayloadRef(bytes=payload), type="replay_recording"))
+
+    relay.send_envelope(project_id, envelope)
+
+    replay_recording = replay_recordings_consumer.get_not_chunked_replay()
+    header, body = replay_recording["payload"].split(b"\n", 1)
+    assert header == b'{"segment_id":0}'
+
+    node = json.loads(zlib.decompress(body))[0]["data"]["node"]
+    assert node["attributes"]["title"] == "[ticket]"
+    assert node["childNodes"][0]["textContent"] == "see [ticket]"
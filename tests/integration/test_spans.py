@@ -0,0 +1,83 @@
+from datetime import datetime, timedelta, timezone
+
+
+def _transaction_payload():
+    now = datetime.now(tz=timezone.utc)
+    return {
+        "event_id": "d2132d31b39445f1938d7e21b6bf0ec4",
+        "type": "transaction",
+        "transaction": "/api/users/",
+        "transaction_info": {"source": "route"},
+        "environment": "production",
+        "release": "app@1.0.0",
+        "start_timestamp": (now - timedelta(seconds=2)).timestamp(),
+        "timestamp": now.timestamp(),
+        "contexts": {
+            "trace": {
+                "trace_id": "ff62a8b040f340bda5d830223def1d81",
+                "span_id": "fa90fdead5f74052",
+                "op": "http.server",
+                "type": "trace",
+            }
+        },
+        "spans": [
+            {
+                "description": "SELECT * FROM users WHERE email = 'jane@example.com'",
+                "op": "db",
+                "parent_span_id": "fa90fdead5f74052",
+                "span_id": "bd429c44b67a3eb4",
+                "start_timestamp": (now - timedelta(seconds=1)).timestamp(),
+                "timestamp": now.timestamp(),
+                "trace_id": "ff62a8b040f340bda5d830223def1d81",
+                "data": {"password": "hunter2"},
+            }
+        ],
+    }
+
+
+def test_span_extraction(mini_sentry, relay_with_processing, spans_consumer):
+    relay = relay_with_processing()
+    project_id = 42
+    mini_sentry.add_full_project_config(
+        project_id,
+        extra={"config": {"features": ["organizations:standalone-span-ingestion"]}},
+    )
+    spans_consumer = spans_consumer(timeout=5)
+
+    relay.send_transaction(project_id, _transaction_payload())
+
+    messages = [spans_consumer.get_span() for _ in range(2)]
+    spans_consumer.assert_empty()
+
+    records = {message["span"]["span"]["span_id"]: message for message in messages}
+    segment = records["fa90fdead5f74052"]
+    child = records["bd429c44b67a3eb4"]
+
+    assert segment["project_id"] == project_id
+    assert segment["span"]["is_segment"] is True
+    assert segment["span"]["span"]["description"] == "/api/users/"
+
+    record = child["span"]
+    assert record["event_id"] == "d2132d31b39445f1938d7e21b6bf0ec4"
+    assert record["trace_id"] == "ff62a8b040f340bda5d830223def1d81"
+    assert record["segment_id"] == "fa90fdead5f74052"
+    assert record["is_segment"] is False
+    assert record["span"]["tags"] == {
+        "environment": "production",
+        "release": "app@1.0.0",
+        "transaction": "/api/users/",
+    }
+
+    # Spans are scrubbed individually with the project's data scrubbing settings.
+    assert record["span"]["data"]["password"] == "[Filtered]"
+
+
+def test_span_extraction_disabled(mini_sentry, relay_with_processing, spans_consumer):
+    relay = relay_with_processing()
+    project_id = 42
+    mini_sentry.add_full_project_config(project_id)
+    spans_consumer = spans_consumer(timeout=2)
+
+    relay.send_transaction(project_id, _transaction_payload())
+
+    spans_consumer.assert_empty()
@@ -183,6 +183,45 @@ def test_store_static_config(mini_sentry, relay):
         )
 
 
+def test_store_static_config_quotas(mini_sentry, relay):
+    project_id = 42
+    project_config = mini_sentry.add_full_project_config(project_id)
+    project_config["config"]["quotas"] = [
+        {
+            "id": "test_static_rate_limiting",
+            "categories": ["error"],
+            "limit": 1,
+            "window": 3600,
+            "reasonCode": "get_lost",
+        }
+    ]
+
+    def configure_static_project(dir):
+        os.remove(dir.join("credentials.json"))
+        os.makedirs(dir.join("projects"))
+        dir.join("projects").join("{}.json".format(project_id)).write(
+            json.dumps(project_config)
+        )
+
+    relay_options = {"relay": {"mode": "static"}}
+    relay = relay(mini_sentry, options=relay_options, prepare=configure_static_project)
+
+    relay.send_event(project_id, {"message": "regular"})
+    event = mini_sentry.captured_events.get(timeout=1).get_event()
+    assert event["logentry"] == {"formatted": "regular"}
+
+    # this one will not get a 429 but still get rate limited (silently) because
+    # of our caching
+    relay.send_event(project_id, {"message": "some_message"})
+    sleep(0.5)
+    assert mini_sentry.captured_events.empty()
+
+    with pytest.raises(HTTPError) as excinfo:
+        relay.send_event(project_id, {"message": "rate_limited"})
+    headers = excinfo.value.response.headers
+    assert headers["x-sentry-rate-limits"].endswith(":error:organization:get_lost")
+
+
 def test_store_proxy_config(mini_sentry, relay):
     from time import sleep
 
@@ -1095,6 +1134,67 @@ def test_buffer_events_during_outage(relay, mini_sentry):
     assert event["logentry"] == {"formatted": "123"}
 
 
+def test_spool_events_during_outage(relay, mini_sentry, tmp_path):
+    """
+    Tests that events are spooled to disk during network outages and sent once the upstream is
+    reachable again.
+    """
+
+    original_store_event = mini_sentry.app.view_functions["store_event"]
+    is_network_error = True
+
+    def network_error_endpoint(*args, **kwargs):
+        if is_network_error:
+            # simulate a network error
+            raise socket.timeout()
+        else:
+            # normal processing
+            return original_store_event(*args, **kwargs)
+
+    mini_sentry.app.view_functions["store_event"] = network_error_endpoint
+    original_is_live = mini_sentry.app.view_functions["is_live"]
+    evt = threading.Event()
+
+    def is_live():
+        evt.set()  # mark is_live was called
+        return original_is_live()
+
+    mini_sentry.app.view_functions["is_live"] = is_live
+
+    spool_dir = tmp_path / "spool"
+    relay_options = {
+        "http": {
+            "max_retry_interval": 1,
+            "auth_interval": 1000,
+            "outage_grace_period": 1,
+        },
+        "spool": {"envelopes": {"path": str(spool_dir), "drain_interval": 100}},
+    }
+    relay = relay(mini_sentry, relay_options)
+    project_id = 42
+    mini_sentry.add_basic_project_config(project_id)
+
+    # the first event fails and puts relay into outage mode
+    relay.send_event(project_id, {"message": "first"})
+    assert evt.wait(5)
+
+    # the second event is written to the spool since the upstream is unreachable
+    relay.send_event(project_id, {"message": "second"})
+    for _ in range(50):
+        if any(spool_dir.glob("*.spool")):
+            break
+        sleep(0.1)
+    assert any(spool_dir.glob("*.spool"))
+
+    is_network_error = False
+
+    messages = {
+        mini_sentry.captured_events.get(timeout=5).get_event()["logentry"]["formatted"]
+        for _ in range(2)
+    }
+    assert messages == {"first", "second"}
+
+
 def test_store_invalid_gzip(mini_sentry, relay_chain):
     relay = relay_chain(min_relay_version="21.6.0")
     project_id = 42
@@ -1154,3 +1254,135 @@ def test_invalid_project_id(mini_sentry, relay):
 
     relay.send_event(99, headers=headers)
     pytest.raises(queue.Empty, lambda: mini_sentry.captured_events.get(timeout=1))
+
+
+def test_sourcemaps(mini_sentry, relay, tmp_path):
+    """
+    Tests that minified JavaScript frames are resolved with source maps from the local artifact
+    directory, and that missing source maps are reported as event errors.
+    """
+    source = "function add(first, second) {\n  return first + second;\n}\n\nfunction fail() {\n  throw new Error(add(1, 2));\n}\n"
+    minified = "function add(a,b){return a+b}function fail(){throw new Error(add(1,2))}\n//# sourceMappingURL=app.min.js.map\n"
+
+    artifacts = tmp_path / "1.0" / "static"
+    artifacts.mkdir(parents=True)
+    (artifacts / "app.min.js").write_text(minified)
+    (artifacts / "app.min.js.map").write_text(
+        json.dumps(
+            {
+                "version": 3,
+                "file": "app.min.js",
+                "sources": ["../src/app.js"],
+                "sourcesContent": [source],
+                "names": [],
+                "mappings": "AAAA,6CAKE",
+            }
+        )
+    )
+
+    relay = relay(mini_sentry, {"sourcemaps": {"path": str(tmp_path)}})
+    mini_sentry.add_basic_project_config(42)
+
+    frames = [
+        {
+            "abs_path": "https://example.com/static/vendor.min.js",
+            "lineno": 1,
+            "colno": 10,
+        },
+        {
+            "abs_path": "https://example.com/static/app.min.js",
+            "function": "fail",
+            "lineno": 1,
+            "colno": 46,
+        },
+    ]
+
+    relay.send_event(
+        42,
+        {
+            "platform": "javascript",
+            "release": "1.0",
+            "exception": {
+                "values": [{"type": "Error", "stacktrace": {"frames": frames}}]
+            },
+        },
+    )
+
+    event = mini_sentry.captured_events.get(timeout=1).get_event()
+    frames = event["exception"]["values"][0]["stacktrace"]["frames"]
+
+    assert frames[0]["lineno"] == 1
+    assert frames[1]["abs_path"] == "https://example.com/src/app.js"
+    assert frames[1]["lineno"] == 6
+    assert frames[1]["colno"] == 3
+    assert frames[1]["context_line"] == "  throw new Error(add(1, 2));"
+
+    assert {
+        "type": "js_no_source",
+        "url": "https://example.com/static/vendor.min.js",
+    } in event["errors"]
+
+
+def test_proguard(mini_sentry, relay, tmp_path):
+    """
+    Tests that obfuscated Java frames and exceptions are deobfuscated with ProGuard mapping files
+    from the local mapping directory, and that missing mappings are reported as event errors.
+    """
+    mapping_uuid = "3c6d4e5f-2b1a-4c8d-9e0f-1a2b3c4d5e6f"
+    missing_uuid = "8f2a5b7c-4d3e-4f1a-9b8c-7d6e5f4a3b2c"
+
+    (tmp_path / f"{mapping_uuid}.txt").write_text(
+        "io.sentry.sample.MainActivity -> io.sentry.sample.a:\n"
+        "    1:1:void bar():54:54 -> a\n"
+        "    1:1:void onClickHandler(android.view.View):40 -> a\n"
+        "io.sentry.sample.BrokenException -> io.sentry.sample.b:\n"
+    )
+
+    relay = relay(mini_sentry, {"proguard": {"path": str(tmp_path)}})
+    mini_sentry.add_basic_project_config(42)
+
+    relay.send_event(
+        42,
+        {
+            "platform": "java",
+            "debug_meta": {
+                "images": [
+                    {"type": "proguard", "uuid": mapping_uuid},
+                    {"type": "proguard", "uuid": missing_uuid},
+                ]
+            },
+            "exception": {
+                "values": [
+                    {
+                        "type": "b",
+                        "module": "io.sentry.sample",
+                        "stacktrace": {
+                            "frames": [
+                                {
+                                    "module": "io.sentry.sample.a",
+                                    "function": "a",
+                                    "lineno": 1,
+                                }
+                            ]
+                        },
+                    }
+                ]
+            },
+        },
+    )
+
+    event = mini_sentry.captured_events.get(timeout=1).get_event()
+    exception = event["exception"]["values"][0]
+    assert exception["type"] == "BrokenException"
+
+    frames = exception["stacktrace"]["frames"]
+    assert [(f["function"], f["lineno"]) for f in frames] == [
+        ("onClickHandler", 40),
+        ("bar", 54),
+    ]
+    assert frames[0]["module"] == "io.sentry.sample.MainActivity"
+
+    assert {
+        "type": "proguard_missing_mapping",
+        "mapping_uuid": missing_uuid,
+    } in event["errors"]
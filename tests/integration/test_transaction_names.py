@@ -0,0 +1,81 @@
+import random
+import uuid
+from datetime import datetime, timedelta, timezone
+
+from sentry_relay import SecretKey
+
+
+def _transaction_payload(name):
+    now = datetime.now(tz=timezone.utc)
+    return {
+        "event_id": uuid.uuid4().hex,
+        "type": "transaction",
+        "transaction": name,
+        "transaction_info": {"source": "url"},
+        "start_timestamp": (now - timedelta(seconds=1)).timestamp(),
+        "timestamp": now.timestamp(),
+        "contexts": {
+            "trace": {
+                "trace_id": uuid.uuid4().hex,
+                "span_id": "fa90fdead5f74052",
+                "type": "trace",
+            }
+        },
+        "spans": [],
+    }
+
+
+def _get_rules(relay, public_key, merge_threshold):
+    body = {"publicKey": public_key, "mergeThreshold": merge_threshold}
+    packed, signature = SecretKey.parse(relay.secret_key).pack(body)
+
+    response = relay.post(
+        "/api/0/relays/transactions/rules/",
+        data=packed,
+        headers={
+            "X-Sentry-Relay-Id": relay.relay_id,
+            "X-Sentry-Relay-Signature": signature,
+        },
+    )
+
+    assert response.ok, response.text
+    return response.json()["rules"]
+
+
+def test_transaction_name_rules(
+    mini_sentry, relay_with_processing, transactions_consumer
+):
+    # Observed names are kept in Redis across tests, so use a fresh project.
+    project_id = random.randint(100_000, 1_000_000)
+    relay = relay_with_processing()
+    transactions_consumer = transactions_consumer()
+
+    config = mini_sentry.add_basic_project_config(project_id)
+    config["config"]["features"] = ["organizations:transaction-name-clusterer"]
+    public_key = config["publicKeys"][0]["publicKey"]
+
+    for name in ["/users/1/", "/users/2/", "/users/3/", "/about/"]:
+        relay.send_transaction(project_id, _transaction_payload(name))
+        transactions_consumer.get_event()
+
+    rules = _get_rules(relay, public_key, merge_threshold=2)
+    assert [rule["pattern"] for rule in rules] == ["/users/*/**"]
+    assert rules[0]["scope"] == {"source": "url"}
+    assert rules[0]["redaction"] == {"method": "replace", "substitution": "*"}
+
+
+def test_transaction_name_rules_disabled(
+    mini_sentry, relay_with_processing, transactions_consumer
+):
+    project_id = random.randint(100_000, 1_000_000)
+    relay = relay_with_processing()
+    transactions_consumer = transactions_consumer()
+
+    config = mini_sentry.add_basic_project_config(project_id)
+    public_key = config["publicKeys"][0]["publicKey"]
+
+    for name in ["/users/1/", "/users/2/", "/users/3/"]:
+        relay.send_transaction(project_id, _transaction_payload(name))
+        transactions_consumer.get_event()
+
+    assert _get_rules(relay, public_key, merge_threshold=2) == []
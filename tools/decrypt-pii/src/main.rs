@@ -0,0 +1,157 @@
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/getsentry/relay/master/artwork/relay-icon.png",
+    html_favicon_url = "https://raw.githubusercontent.com/getsentry/relay/master/artwork/relay-icon.png"
+)]
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+use relay_general::pii::{decrypt_tokens, encryption_public_key};
+
+/// Decrypt values that were scrubbed with the `encrypt` PII redaction.
+///
+/// The keys file is a JSON object that maps key IDs to hex-encoded X25519 secret keys:
+///
+///     {"2023": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"}
+///
+/// Secret keys must never be added to the PII config. Use `--public-keys` to print the public keys
+/// for `vars.encryptionKeys` instead.
+///
+/// Include the keys of previous rotations to decrypt older tokens. If the input is JSON, such as an
+/// event payload, tokens are decrypted in all string values. Otherwise, the input is treated as
+/// plain text.
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment, setting = AppSettings::ColoredHelp)]
+struct Cli {
+    /// Path to a JSON file with secret keys by key ID.
+    #[structopt(short, long, value_name = "PATH")]
+    keys: PathBuf,
+
+    /// Print the public keys for `vars.encryptionKeys` instead of decrypting.
+    #[structopt(long)]
+    public_keys: bool,
+
+    /// Path to the input file. Defaults to stdin.
+    input: Option<PathBuf>,
+
+    /// Pretty print JSON output.
+    #[structopt(long)]
+    pretty: bool,
+}
+
+impl Cli {
+    fn load_keys(&self) -> Result<BTreeMap<String, String>> {
+        let json = fs::read_to_string(&self.keys).with_context(|| "failed to read keys")?;
+        let keys = serde_json::from_str(&json).with_context(|| "failed to parse keys")?;
+        Ok(keys)
+    }
+
+    fn load_input(&self) -> Result<String> {
+        let mut input = String::new();
+
+        match self.input {
+            Some(ref path) => {
+                input = fs::read_to_string(path).with_context(|| "failed to read input")?;
+            }
+            None => {
+                io::stdin()
+                    .read_to_string(&mut input)
+                    .with_context(|| "failed to read from stdin")?;
+            }
+        }
+
+        Ok(input)
+    }
+
+    fn print_public_keys(&self, keys: &BTreeMap<String, String>) -> Result<()> {
+        let mut public_keys = BTreeMap::new();
+        for (id, secret_key) in keys {
+            let public_key = encryption_public_key(secret_key)
+                .with_context(|| format!("invalid secret key {:?}", id))?;
+            public_keys.insert(id, public_key);
+        }
+
+        println!("{}", serde_json::to_string_pretty(&public_keys)?);
+        Ok(())
+    }
+
+    pub fn run(self) -> Result<()> {
+        let keys = self.load_keys()?;
+        if self.public_keys {
+            return self.print_public_keys(&keys);
+        }
+
+        let input = self.load_input()?;
+
+        match serde_json::from_str::<Value>(&input) {
+            Ok(mut value) => {
+                decrypt_value(&mut value, &keys)?;
+                let output = if self.pretty {
+                    serde_json::to_string_pretty(&value)?
+                } else {
+                    serde_json::to_string(&value)?
+                };
+                println!("{}", output);
+            }
+            Err(_) => {
+                let output = decrypt_tokens(&input, &keys).with_context(|| "failed to decrypt")?;
+                print!("{}", output);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Decrypts tokens in all strings of a JSON value.
+///
+/// Object keys are not decrypted, since PII scrubbing does not apply to them.
+fn decrypt_value(value: &mut Value, keys: &BTreeMap<String, String>) -> Result<()> {
+    match value {
+        Value::String(string) => {
+            let decrypted = decrypt_tokens(string, keys).with_context(|| "failed to decrypt")?;
+            *string = decrypted.into_owned();
+        }
+        Value::Array(array) => {
+            for item in array {
+                decrypt_value(item, keys)?;
+            }
+        }
+        Value::Object(object) => {
+            for item in object.values_mut() {
+                decrypt_value(item, keys)?;
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => (),
+    }
+
+    Ok(())
+}
+
+fn print_error(error: &anyhow::Error) {
+    eprintln!("Error: {}", error);
+
+    let mut cause = error.source();
+    while let Some(ref e) = cause {
+        eprintln!("  caused by: {}", e);
+        cause = e.source();
+    }
+}
+
+#[paw::main]
+fn main(cli: Cli) {
+    match cli.run() {
+        Ok(()) => (),
+        Err(error) => {
+            print_error(&error);
+            std::process::exit(1);
+        }
+    }
+}
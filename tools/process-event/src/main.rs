@@ -14,6 +14,9 @@ use relay_general::store::{
     light_normalize_event, LightNormalizationConfig, StoreConfig, StoreProcessor,
 };
 use relay_general::types::Annotated;
+use relay_sampling::{
+    DynamicSamplingContext, ReservoirExplanation, SamplingConfig, SamplingExplanation,
+};
 
 use anyhow::{format_err, Context, Result};
 use structopt::clap::AppSettings;
@@ -23,6 +26,12 @@ use structopt::StructOpt;
 ///
 /// This command takes a JSON event payload on stdin and write the processed event payload to
 /// stdout. Optionally, an additional PII config can be supplied.
+///
+/// With a dynamic sampling config, the command instead writes an explanation of how the sampling
+/// rules were evaluated on the processed event and an optional dynamic sampling context.
+///
+/// With `--pii-dry-run`, the command writes the redactions that the PII config would make instead
+/// of applying them.
 #[derive(Debug, StructOpt)]
 #[structopt(verbatim_doc_comment, setting = AppSettings::ColoredHelp)]
 struct Cli {
@@ -30,10 +39,22 @@ struct Cli {
     #[structopt(short = "c", long)]
     pii_config: Option<PathBuf>,
 
+    /// Report what the PII config would scrub instead of scrubbing the event.
+    #[structopt(long, requires = "pii-config")]
+    pii_dry_run: bool,
+
     /// Path to an event payload JSON file (defaults to stdin).
     #[structopt(short, long)]
     event: Option<PathBuf>,
 
+    /// Path to a dynamic sampling config JSON file to explain.
+    #[structopt(long)]
+    sampling_config: Option<PathBuf>,
+
+    /// Path to a dynamic sampling context JSON file, used with `--sampling-config`.
+    #[structopt(long, requires = "sampling-config")]
+    dsc: Option<PathBuf>,
+
     /// Apply full store normalization.
     #[structopt(long)]
     store: bool,
@@ -59,6 +80,99 @@ impl Cli {
         Ok(Some(config))
     }
 
+    fn load_sampling_config(&self) -> Result<Option<SamplingConfig>> {
+        let path = match self.sampling_config {
+            Some(ref path) => path,
+            None => return Ok(None),
+        };
+
+        let json = fs::read_to_string(path).with_context(|| "failed to read sampling config")?;
+        let config =
+            serde_json::from_str(&json).with_context(|| "failed to parse sampling config")?;
+        Ok(Some(config))
+    }
+
+    fn load_dsc(&self) -> Result<Option<DynamicSamplingContext>> {
+        let path = match self.dsc {
+            Some(ref path) => path,
+            None => return Ok(None),
+        };
+
+        let json = fs::read_to_string(path).with_context(|| "failed to read sampling context")?;
+        let dsc =
+            serde_json::from_str(&json).with_context(|| "failed to parse sampling context")?;
+        Ok(Some(dsc))
+    }
+
+    fn explain_sampling(&self, config: &SamplingConfig, event: &Annotated<Event>) -> Result<()> {
+        let dsc = self.load_dsc()?;
+
+        let (trace_rules, trace_match, trace_reservoirs): (_, _, Vec<_>) = match dsc {
+            Some(ref dsc) => (
+                config.explain_trace_rules(dsc, None),
+                config.match_trace_rule(dsc, None),
+                config.get_matching_trace_reservoirs(dsc, None).collect(),
+            ),
+            None => (Vec::new(), None, Vec::new()),
+        };
+
+        let (event_rules, event_match, event_reservoirs): (_, _, Vec<_>) = match event.value() {
+            Some(event) => (
+                config.explain_event_rules(event, None),
+                config.match_event_rule(event, dsc.as_ref(), None),
+                config.get_matching_event_reservoirs(event, None).collect(),
+            ),
+            None => (Vec::new(), None, Vec::new()),
+        };
+
+        // Reservoirs are not counted across runs, so only reservoirs without a limit are filled.
+        let reservoir_rules = event_reservoirs
+            .into_iter()
+            .chain(trace_reservoirs)
+            .map(|rule| ReservoirExplanation::new(rule, rule.limit == 0))
+            .collect();
+
+        let mut explanation = SamplingExplanation::new(
+            dsc.as_ref().and_then(|dsc| dsc.sample_rate),
+            trace_rules,
+            event_rules,
+            reservoir_rules,
+        );
+
+        // Event rules take precedence over trace rules, like in Relay.
+        explanation.decide(event_match.or(trace_match));
+
+        if self.debug {
+            println!("{:#?}", explanation);
+        } else if self.pretty {
+            println!("{}", serde_json::to_string_pretty(&explanation)?);
+        } else {
+            println!("{}", serde_json::to_string(&explanation)?);
+        }
+
+        Ok(())
+    }
+
+    fn audit_pii(&self, config: &PiiConfig, event: &mut Annotated<Event>) -> Result<()> {
+        let mut processor = PiiProcessor::dry_run(config.compiled());
+        process_value(event, &mut processor, ProcessingState::root())
+            .map_err(|e| format_err!("{}", e))?;
+
+        let diff = serde_json::json!({
+            "matches": processor.audit_matches(),
+        });
+
+        if self.debug {
+            println!("{:#?}", diff);
+        } else if self.pretty {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else {
+            println!("{}", serde_json::to_string(&diff)?);
+        }
+
+        Ok(())
+    }
+
     fn load_event(&self) -> Result<Annotated<Event>> {
         let json = match self.event {
             Some(ref path) => fs::read_to_string(path).with_context(|| "failed to read event")?,
@@ -79,6 +193,10 @@ impl Cli {
         let mut event = self.load_event()?;
 
         if let Some(pii_config) = self.load_pii_config()? {
+            if self.pii_dry_run {
+                return self.audit_pii(&pii_config, &mut event);
+            }
+
             let mut processor = PiiProcessor::new(pii_config.compiled());
             process_value(&mut event, &mut processor, ProcessingState::root())
                 .map_err(|e| format_err!("{}", e))?;
@@ -93,6 +211,10 @@ impl Cli {
                 .with_context(|| "failed to store process event")?;
         }
 
+        if let Some(sampling_config) = self.load_sampling_config()? {
+            return self.explain_sampling(&sampling_config, &event);
+        }
+
         if self.debug {
             println!("{:#?}", event);
         } else if self.pretty {